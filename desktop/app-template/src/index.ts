@@ -0,0 +1,14 @@
+import { StandardServer } from "@episensor/app-framework";
+
+// The desktop shell passes the port in PORT
+const server = new StandardServer({
+  appName: "{{APP_TITLE}}",
+  appVersion: "{{APP_VERSION}}",
+  port: Number(process.env.PORT) || {{API_PORT}},
+  onInitialize: async (app) => {
+    app.get("/api/health", (_req, res) => res.json({ status: "ok" }));
+  },
+});
+
+await server.initialize();
+await server.start();
@@ -0,0 +1,8 @@
+// The shell's commands and events are typed in ./bindings.ts, which it writes
+// when it runs in dev mode:
+//
+//   import { commands } from "./bindings";
+//   const logs = await commands.getLogs();
+
+const app = document.querySelector<HTMLDivElement>("#app")!;
+app.textContent = "{{APP_TITLE}}";
@@ -0,0 +1,20 @@
+import { defineConfig } from "vite";
+
+// In development the API server runs separately; see docs/PORTS.md
+export default defineConfig({
+  server: {
+    port: {{DEV_PORT}},
+    strictPort: true,
+    proxy: {
+      "/api": {
+        target: "http://localhost:{{API_PORT}}",
+        changeOrigin: true,
+      },
+      "/socket.io": {
+        target: "ws://localhost:{{API_PORT}}",
+        ws: true,
+        changeOrigin: true,
+      },
+    },
+  },
+});
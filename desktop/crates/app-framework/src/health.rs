@@ -0,0 +1,163 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How the shell decides the backend is up: each candidate port's health endpoint
+// is probed until one answers. The request itself is left to the caller, which
+// knows the backend's TLS certificate and auth token.
+
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    path: String,
+    ports: Vec<u16>,
+    interval: Duration,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            path: "/api/health".to_string(),
+            ports: vec![8080, 7500, 5000, 3000],
+            interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl HealthCheck {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    // Ports the backend may listen on, probed in order
+    pub fn ports(mut self, ports: &[u16]) -> Self {
+        self.ports = ports.to_vec();
+        self
+    }
+
+    // Delay between rounds of probes while waiting for the backend
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn url(&self, scheme: &str, port: u16) -> String {
+        format!("{}://127.0.0.1:{}{}", scheme, port, self.path)
+    }
+
+    // The first port whose health endpoint `is_healthy` accepts
+    pub fn probe(&self, scheme: &str, is_healthy: impl Fn(&str) -> bool) -> Option<u16> {
+        self.ports
+            .iter()
+            .copied()
+            .find(|port| is_healthy(&self.url(scheme, *port)))
+    }
+
+    // Probe until a port is healthy or the timeout passes
+    pub fn wait(
+        &self,
+        scheme: &str,
+        timeout: Duration,
+        is_healthy: impl Fn(&str) -> bool,
+    ) -> Option<u16> {
+        self.wait_unless(scheme, timeout, is_healthy, || false)
+    }
+
+    // As `wait`, but stops early once `give_up` returns true
+    pub(crate) fn wait_unless(
+        &self,
+        scheme: &str,
+        timeout: Duration,
+        is_healthy: impl Fn(&str) -> bool,
+        give_up: impl Fn() -> bool,
+    ) -> Option<u16> {
+        let start_time = Instant::now();
+
+        loop {
+            if let Some(port) = self.probe(scheme, &is_healthy) {
+                return Some(port);
+            }
+
+            if give_up() || start_time.elapsed() > timeout {
+                return None;
+            }
+
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[test]
+    fn builds_loopback_urls() {
+        let check = HealthCheck::new("/health");
+        assert_eq!(check.url("https", 8443), "https://127.0.0.1:8443/health");
+    }
+
+    #[test]
+    fn probes_ports_in_order() {
+        let check = HealthCheck::default().ports(&[9001, 9002, 9003]);
+        let probed = RefCell::new(Vec::new());
+        let port = check.probe("http", |url| {
+            probed.borrow_mut().push(url.to_string());
+            url.contains(":9002/") || url.contains(":9003/")
+        });
+        assert_eq!(port, Some(9002));
+        assert_eq!(
+            probed.into_inner(),
+            vec![
+                "http://127.0.0.1:9001/api/health",
+                "http://127.0.0.1:9002/api/health",
+            ]
+        );
+    }
+
+    #[test]
+    fn waits_until_a_port_is_healthy() {
+        let check = HealthCheck::default()
+            .ports(&[9001])
+            .interval(Duration::from_millis(1));
+        let attempts = Cell::new(0);
+        let port = check.wait("http", Duration::from_secs(5), |_| {
+            attempts.set(attempts.get() + 1);
+            attempts.get() == 3
+        });
+        assert_eq!(port, Some(9001));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_timeout() {
+        let check = HealthCheck::default()
+            .ports(&[9001])
+            .interval(Duration::from_millis(1));
+        assert_eq!(
+            check.wait("http", Duration::from_millis(20), |_| false),
+            None
+        );
+    }
+
+    #[test]
+    fn stops_waiting_when_told_to() {
+        let check = HealthCheck::default()
+            .ports(&[9001])
+            .interval(Duration::from_millis(1));
+        let attempts = Cell::new(0);
+        let port = check.wait_unless(
+            "http",
+            Duration::from_secs(60),
+            |_| {
+                attempts.set(attempts.get() + 1);
+                false
+            },
+            || attempts.get() >= 2,
+        );
+        assert_eq!(port, None);
+        assert_eq!(attempts.get(), 2);
+    }
+}
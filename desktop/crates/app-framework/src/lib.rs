@@ -0,0 +1,22 @@
+// Backend sidecar management shared by EpiSensor desktop shells: finding the
+// sidecar binary, launching it sandboxed, capturing its output, waiting for its
+// health check and stopping it. Shells build an `AppShell` once and keep it in
+// their state:
+//
+//     let shell = AppShell::builder()
+//         .sidecar(Sidecar::new("server").search_dir(resources.join("server")))
+//         .health_check(HealthCheck::new("/api/health").ports(&[8080, 3000]))
+//         .logging(Logging::to_file(log_dir.join("backend.log")))
+//         .build();
+
+mod health;
+mod logging;
+mod sandbox;
+mod shell;
+mod sidecar;
+
+pub use health::HealthCheck;
+pub use logging::Logging;
+pub use sandbox::{SandboxOptions, SandboxStatus};
+pub use shell::{AppShell, AppShellBuilder, StartError};
+pub use sidecar::{Launch, Sidecar};
@@ -0,0 +1,151 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Where the backend's stdout and stderr go. By default the backend shares the
+// shell's, which is lost when the shell has no console (a release build on
+// Windows, a service). With a log file, each line is also appended to the file,
+// which is rotated to `<file>.1` when it grows past its size limit.
+
+#[derive(Debug, Clone)]
+pub struct Logging {
+    file: Option<PathBuf>,
+    max_bytes: u64,
+    echo: bool,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            file: None,
+            max_bytes: 10 * 1024 * 1024,
+            echo: true,
+        }
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_bytes: u64) -> Self {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path);
+        if let Err(e) = &file {
+            eprintln!("Failed to open backend log {:?}: {}", path, e);
+        }
+        let written = fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        Self {
+            path,
+            file: file.ok(),
+            written,
+            max_bytes,
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.written >= self.max_bytes {
+            self.file = None;
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            let _ = fs::rename(&self.path, rotated);
+            self.file = File::create(&self.path).ok();
+            self.written = 0;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            if writeln!(file, "{}", line).is_ok() {
+                self.written += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+impl Logging {
+    // Keep the backend's output in a file as well as the shell's output
+    pub fn to_file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    // Size the file may reach before it is rotated
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    // Whether the backend's output is also written to the shell's
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    pub(crate) fn configure(&self, command: &mut Command) {
+        if self.file.is_some() || !self.echo {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+    }
+
+    // Copy the spawned backend's output, line by line, until it exits
+    pub(crate) fn attach(&self, child: &mut Child) {
+        let log = self
+            .file
+            .clone()
+            .map(|path| Arc::new(Mutex::new(LogFile::open(path, self.max_bytes))));
+
+        if let Some(stdout) = child.stdout.take() {
+            self.forward(stdout, log.clone(), false);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.forward(stderr, log, true);
+        }
+    }
+
+    fn forward(
+        &self,
+        output: impl Read + Send + 'static,
+        log: Option<Arc<Mutex<LogFile>>>,
+        is_stderr: bool,
+    ) {
+        let echo = self.echo;
+        thread::spawn(move || {
+            // Lines are read as bytes, so output that isn't UTF-8 doesn't stop the
+            // pipe from being drained
+            let mut reader = BufReader::new(output);
+            let mut buffer = Vec::new();
+            loop {
+                buffer.clear();
+                match reader.read_until(b'\n', &mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                let line = String::from_utf8_lossy(&buffer);
+                let line = line.trim_end_matches(['\r', '\n']);
+                if echo {
+                    if is_stderr {
+                        eprintln!("{}", line);
+                    } else {
+                        println!("{}", line);
+                    }
+                }
+                if let Some(log) = &log {
+                    log.lock().unwrap().write_line(line);
+                }
+            }
+        });
+    }
+}
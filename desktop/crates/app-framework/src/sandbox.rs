@@ -0,0 +1,380 @@
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::process::{Child, Command};
+
+use serde::Serialize;
+
+// Least-privilege launch of the backend. On Windows the backend is put in a job
+// object that is killed when the shell exits, however it exits. It is created
+// suspended and only resumed once it is in the job, so nothing it starts can
+// escape. On Unix it runs in its own process group, which is killed as a whole
+// when the backend is stopped, and any file descriptors the shell has open are
+// closed at exec. The environment can be reduced to what Node needs plus the
+// variables the app names.
+
+// Variables passed through to the backend when the environment is restricted
+const BASE_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "LANG",
+    "LC_ALL",
+    "LC_CTYPE",
+    "TZ",
+    "TMPDIR",
+    "SystemRoot",
+    "SystemDrive",
+    "windir",
+    "ComSpec",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "ProgramData",
+    "NUMBER_OF_PROCESSORS",
+    "PROCESSOR_ARCHITECTURE",
+    // Corporate networks: the proxy to reach the internet through, and the CA
+    // that signs the certificates it presents
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+    "NODE_EXTRA_CA_CERTS",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct SandboxOptions {
+    // Only pass the backend the variables Node needs, plus `inherit_env`
+    pub restrict_env: bool,
+    pub inherit_env: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Default)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SandboxStatus {
+    // The backend is killed by the OS when the shell exits (Windows job object)
+    pub kill_on_exit: bool,
+    // The backend runs in its own process group, stopped as a whole (Unix)
+    pub process_group: bool,
+    // File descriptors other than stdio are not inherited (Unix)
+    pub handles_closed: bool,
+    pub restricted_env: bool,
+    pub working_dir: String,
+}
+
+// Job object the backend is assigned to on Windows. The shell holds the only
+// handle, so the OS kills the backend when the shell exits, even if it crashes.
+pub(crate) struct Job {
+    #[cfg(target_os = "windows")]
+    handle: Option<windows::Win32::Foundation::HANDLE>,
+}
+
+// The handle is only used to assign processes, which is thread-safe
+#[cfg(target_os = "windows")]
+unsafe impl Send for Job {}
+#[cfg(target_os = "windows")]
+unsafe impl Sync for Job {}
+
+#[cfg(target_os = "windows")]
+impl Job {
+    pub(crate) fn new() -> Self {
+        use windows::core::PCWSTR;
+        use windows::Win32::Foundation::CloseHandle;
+        use windows::Win32::System::JobObjects::{
+            CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+
+        let handle = unsafe { CreateJobObjectW(None, PCWSTR::null()) }
+            .and_then(|job| {
+                let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let result = unsafe {
+                    SetInformationJobObject(
+                        job,
+                        JobObjectExtendedLimitInformation,
+                        &info as *const _ as *const std::ffi::c_void,
+                        std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                    )
+                };
+                if result.is_err() {
+                    let _ = unsafe { CloseHandle(job) };
+                }
+                result.map(|_| job)
+            })
+            .map_err(|e| eprintln!("Failed to create job object for the backend: {}", e))
+            .ok();
+
+        Self { handle }
+    }
+
+    pub(crate) fn assign(&self, child: &Child) -> bool {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::System::JobObjects::AssignProcessToJobObject;
+
+        let Some(job) = self.handle else {
+            return false;
+        };
+        unsafe { AssignProcessToJobObject(job, HANDLE(child.as_raw_handle())) }
+            .map_err(|e| eprintln!("Failed to assign the backend to its job object: {}", e))
+            .is_ok()
+    }
+}
+
+// Resume a process created suspended. Its only thread is its main thread, which
+// std doesn't give a handle to, so it is found among the system's threads.
+#[cfg(target_os = "windows")]
+fn resume(child: &Child) -> std::io::Result<()> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+    use windows::Win32::System::Threading::{OpenThread, ResumeThread, THREAD_SUSPEND_RESUME};
+
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) }?;
+    let mut entry = THREADENTRY32 {
+        dwSize: std::mem::size_of::<THREADENTRY32>() as u32,
+        ..Default::default()
+    };
+    let mut resumed = false;
+    let mut found = unsafe { Thread32First(snapshot, &mut entry) }.is_ok();
+    while found {
+        if entry.th32OwnerProcessID == child.id() {
+            if let Ok(thread) =
+                unsafe { OpenThread(THREAD_SUSPEND_RESUME, false, entry.th32ThreadID) }
+            {
+                resumed |= unsafe { ResumeThread(thread) } != u32::MAX;
+                let _ = unsafe { CloseHandle(thread) };
+            }
+        }
+        found = unsafe { Thread32Next(snapshot, &mut entry) }.is_ok();
+    }
+    let _ = unsafe { CloseHandle(snapshot) };
+
+    if resumed {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("Failed to resume the backend"))
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let Some(job) = self.handle.take() {
+            let _ = unsafe { windows::Win32::Foundation::CloseHandle(job) };
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Job {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+
+    pub(crate) fn assign(&self, _child: &Child) -> bool {
+        false
+    }
+}
+
+// Mark every descriptor above stdio close-on-exec. This runs between fork and
+// exec, so it only makes async-signal-safe calls.
+#[cfg(unix)]
+fn close_inherited_fds() {
+    #[cfg(target_os = "linux")]
+    {
+        const CLOSE_RANGE_CLOEXEC: libc::c_uint = 1 << 2;
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_close_range,
+                3 as libc::c_uint,
+                libc::c_uint::MAX,
+                CLOSE_RANGE_CLOEXEC,
+            )
+        };
+        if result == 0 {
+            return;
+        }
+    }
+
+    let max = match unsafe { libc::sysconf(libc::_SC_OPEN_MAX) } {
+        max if max > 3 && max <= 65536 => max as libc::c_int,
+        _ => 65536,
+    };
+    for fd in 3..max {
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+}
+
+// Windows variable names are case-insensitive, so `Path` counts as `PATH`
+fn is_named(name: &OsStr, allowed: &str) -> bool {
+    if cfg!(windows) {
+        name.eq_ignore_ascii_case(allowed)
+    } else {
+        name == allowed
+    }
+}
+
+// The variables of `env`, the shell's own, that the backend gets when its
+// environment is restricted
+fn restricted_env(
+    env: impl IntoIterator<Item = (OsString, OsString)>,
+    inherit_env: &[String],
+) -> Vec<(OsString, OsString)> {
+    env.into_iter()
+        .filter(|(name, _)| {
+            BASE_ENV
+                .iter()
+                .copied()
+                .chain(inherit_env.iter().map(String::as_str))
+                .any(|allowed| is_named(name, allowed))
+        })
+        .collect()
+}
+
+// Apply the launch restrictions to the backend command before it is spawned.
+// Variables the shell sets itself are added afterwards.
+pub(crate) fn configure(
+    command: &mut Command,
+    options: &SandboxOptions,
+    working_dir: &Path,
+) -> SandboxStatus {
+    command.current_dir(working_dir);
+
+    if options.restrict_env {
+        command
+            .env_clear()
+            .envs(restricted_env(std::env::vars_os(), &options.inherit_env));
+    }
+
+    // Resumed by `attach` once it is in the job object
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        use windows::Win32::System::Threading::CREATE_SUSPENDED;
+
+        command.creation_flags(CREATE_SUSPENDED.0);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        command.process_group(0);
+        unsafe {
+            command.pre_exec(|| {
+                close_inherited_fds();
+                Ok(())
+            });
+        }
+    }
+
+    SandboxStatus {
+        kill_on_exit: false,
+        process_group: cfg!(unix),
+        handles_closed: cfg!(unix),
+        restricted_env: options.restrict_env,
+        working_dir: working_dir.to_string_lossy().into_owned(),
+    }
+}
+
+// Finish sandboxing the backend once it has been spawned, and let it run. If it
+// can't be, the caller must kill it.
+pub(crate) fn attach(job: &Job, child: &Child, status: &mut SandboxStatus) -> std::io::Result<()> {
+    status.kill_on_exit = job.assign(child);
+
+    #[cfg(target_os = "windows")]
+    resume(child)?;
+    Ok(())
+}
+
+// Kill the backend along with anything it started
+pub(crate) fn kill(child: &mut Child) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        // The backend leads its own process group, whose id is its pid
+        if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == 0 {
+            return Ok(());
+        }
+    }
+
+    child.kill()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        vars.iter()
+            .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+            .collect()
+    }
+
+    #[test]
+    fn passes_only_base_and_named_variables_when_restricted() {
+        let shell_env = env(&[
+            ("PATH", "/usr/bin"),
+            ("NODE_EXTRA_CA_CERTS", "/etc/ssl/corporate.pem"),
+            ("https_proxy", "http://proxy:3128"),
+            ("APP_LICENSE", "licensed"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+        ]);
+
+        let passed = restricted_env(shell_env, &["APP_LICENSE".to_string()]);
+        assert_eq!(
+            passed,
+            env(&[
+                ("PATH", "/usr/bin"),
+                ("NODE_EXTRA_CA_CERTS", "/etc/ssl/corporate.pem"),
+                ("https_proxy", "http://proxy:3128"),
+                ("APP_LICENSE", "licensed"),
+            ])
+        );
+    }
+
+    #[test]
+    fn matches_names_case_insensitively_only_on_windows() {
+        let passed = restricted_env(env(&[("Path", "C:\\Windows")]), &[]);
+        assert_eq!(passed.len(), usize::from(cfg!(windows)));
+    }
+
+    #[test]
+    fn clears_the_environment_when_restricted() {
+        let mut command = Command::new("node");
+        let options = SandboxOptions {
+            restrict_env: true,
+            inherit_env: Vec::new(),
+        };
+        let status = configure(&mut command, &options, Path::new("/srv/app"));
+
+        assert!(command
+            .get_envs()
+            .all(|(name, _)| BASE_ENV.iter().any(|allowed| is_named(name, allowed))));
+        assert!(status.restricted_env);
+    }
+
+    #[test]
+    fn leaves_the_environment_alone_by_default() {
+        let mut command = Command::new("node");
+        let status = configure(
+            &mut command,
+            &SandboxOptions::default(),
+            Path::new("/srv/app"),
+        );
+
+        assert_eq!(command.get_envs().count(), 0);
+        assert!(!status.restricted_env);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/srv/app")));
+        assert_eq!(status.working_dir, Path::new("/srv/app").to_string_lossy());
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::health::HealthCheck;
+use crate::logging::Logging;
+use crate::sandbox::{self, Job, SandboxOptions, SandboxStatus};
+use crate::sidecar::{Launch, Sidecar};
+
+// The backend process a shell owns, or another sidecar it runs alongside. It
+// finds the sidecar, launches it sandboxed with its output sent where `logging`
+// says, waits for it to report healthy and stops it along with anything it
+// started. What the shell adds to the launch
+// (environment, integrity checks) is left to the caller of `start`.
+pub struct AppShell {
+    sidecar: Sidecar,
+    health_check: HealthCheck,
+    logging: Logging,
+    process: Mutex<Option<Child>>,
+    job: Job,
+    sandbox: Mutex<Option<SandboxStatus>>,
+}
+
+// Why `AppShell::start` didn't start the backend
+#[derive(Debug)]
+pub enum StartError {
+    // No sidecar, script or build was found for this platform
+    NotFound,
+    // `prepare` refused the launch
+    Refused(String),
+    // The process couldn't be spawned
+    Spawn(io::Error),
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartError::NotFound => write!(f, "No backend found for this platform"),
+            StartError::Refused(message) => write!(f, "{}", message),
+            StartError::Spawn(e) => write!(f, "Failed to start backend server: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StartError {}
+
+pub struct AppShellBuilder {
+    sidecar: Sidecar,
+    health_check: HealthCheck,
+    logging: Logging,
+}
+
+impl AppShellBuilder {
+    pub fn sidecar(mut self, sidecar: Sidecar) -> Self {
+        self.sidecar = sidecar;
+        self
+    }
+
+    pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = health_check;
+        self
+    }
+
+    pub fn logging(mut self, logging: Logging) -> Self {
+        self.logging = logging;
+        self
+    }
+
+    pub fn build(self) -> AppShell {
+        AppShell {
+            sidecar: self.sidecar,
+            health_check: self.health_check,
+            logging: self.logging,
+            process: Mutex::new(None),
+            job: Job::new(),
+            sandbox: Mutex::new(None),
+        }
+    }
+}
+
+impl AppShell {
+    pub fn builder() -> AppShellBuilder {
+        AppShellBuilder {
+            sidecar: Sidecar::new("server"),
+            health_check: HealthCheck::default(),
+            logging: Logging::default(),
+        }
+    }
+
+    pub fn sidecar(&self) -> &Sidecar {
+        &self.sidecar
+    }
+
+    pub fn health_check(&self) -> &HealthCheck {
+        &self.health_check
+    }
+
+    // Start the backend unless it is already running. `prepare` gets the sidecar
+    // that was found and its command once the sandbox is applied, to refuse the
+    // launch or add to the environment. Returns whether a process was started.
+    pub fn start(
+        &self,
+        options: &SandboxOptions,
+        working_dir: &Path,
+        prepare: impl FnOnce(&Launch, &mut Command) -> Result<(), String>,
+    ) -> Result<bool, StartError> {
+        let mut process = self.process.lock().unwrap();
+        if let Some(child) = process.as_mut() {
+            if let Ok(None) = child.try_wait() {
+                return Ok(false);
+            }
+        }
+
+        let launch = self
+            .sidecar
+            .resolve()
+            .ok_or(StartError::NotFound)?;
+        let mut command = launch.command();
+        let mut status = sandbox::configure(&mut command, options, working_dir);
+        self.logging.configure(&mut command);
+        prepare(&launch, &mut command).map_err(StartError::Refused)?;
+
+        let mut child = command.spawn().map_err(StartError::Spawn)?;
+        if let Err(e) = sandbox::attach(&self.job, &child, &mut status) {
+            let _ = sandbox::kill(&mut child);
+            let _ = child.wait();
+            return Err(StartError::Spawn(e));
+        }
+        self.logging.attach(&mut child);
+        *self.sandbox.lock().unwrap() = Some(status);
+        *process = Some(child);
+        Ok(true)
+    }
+
+    // Wait for the backend to report healthy, returning the port it answered on.
+    // Gives up early if the backend exits, e.g. on a syntax error.
+    pub fn wait_for_health(
+        &self,
+        scheme: &str,
+        timeout: Duration,
+        is_healthy: impl Fn(&str) -> bool,
+    ) -> Option<u16> {
+        self.health_check
+            .wait_unless(scheme, timeout, is_healthy, || self.has_exited())
+    }
+
+    // Whether the backend was started and has since exited
+    pub fn has_exited(&self) -> bool {
+        self.exit_status().is_some()
+    }
+
+    // How the backend exited, if it was started and has since exited
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.process
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|child| child.try_wait().ok().flatten())
+    }
+
+    // Kill the backend along with anything it started
+    pub fn stop(&self) {
+        if let Some(mut child) = self.process.lock().unwrap().take() {
+            if let Err(e) = sandbox::kill(&mut child) {
+                eprintln!("Failed to stop backend server: {}", e);
+            }
+            let _ = child.wait();
+        }
+    }
+
+    // How the backend was sandboxed when it was last started
+    pub fn sandbox_status(&self) -> Option<SandboxStatus> {
+        self.sandbox.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// A process an app ships: a sidecar binary built by `build-sidecar`, looked up
+// in each search directory in turn, or failing that a binary at a fixed path, the
+// plain Node backend used by development builds, or a script run with another
+// interpreter such as Python. The backend is one; apps can run others alongside
+// it, e.g. a Python analytics worker.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Launch {
+    Sidecar(PathBuf),
+    Node(PathBuf),
+    Script { interpreter: String, script: PathBuf },
+}
+
+impl Launch {
+    pub fn command(&self) -> Command {
+        match self {
+            Launch::Sidecar(binary) => Command::new(binary),
+            Launch::Node(script) => {
+                let mut command = Command::new("node");
+                command.arg(script);
+                command
+            }
+            Launch::Script {
+                interpreter,
+                script,
+            } => {
+                let mut command = Command::new(interpreter);
+                command.arg(script);
+                command
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sidecar {
+    name: String,
+    search_dirs: Vec<PathBuf>,
+    program: Option<PathBuf>,
+    node_script: Option<PathBuf>,
+    build_dir: Option<PathBuf>,
+    script: Option<(String, PathBuf)>,
+}
+
+// Target suffixes of the binaries `build-sidecar` produces for the current
+// platform. The first is the name delta updates are installed under;
+// `linux-arm64` is pkg's own name for ARM Linux builds, used as-is by Pi gateway
+// builds.
+fn target_suffixes() -> &'static [&'static str] {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("macos", "aarch64") => &["aarch64-apple-darwin"],
+        ("macos", "x86_64") => &["x86_64-apple-darwin"],
+        ("windows", "x86_64") => &["x86_64-pc-windows-msvc.exe", "x86_64-pc-windows-gnu.exe"],
+        ("linux", "x86_64") => &["x86_64-unknown-linux-gnu"],
+        ("linux", "aarch64") => &["aarch64-unknown-linux-gnu", "linux-arm64"],
+        _ => &[],
+    }
+}
+
+impl Sidecar {
+    // `name` is the binary's name without the target suffix, e.g. `server`
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            search_dirs: Vec::new(),
+            program: None,
+            node_script: None,
+            build_dir: None,
+            script: None,
+        }
+    }
+
+    // Directories searched for the binary, in order of preference
+    pub fn search_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.search_dirs.push(dir.into());
+        self
+    }
+
+    // Binary used as-is when none is found in the search directories, for
+    // programs not built by `build-sidecar`
+    pub fn program(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.program = Some(binary.into());
+        self
+    }
+
+    // Script run with Node when no binary is found
+    pub fn node_script(mut self, script: impl Into<PathBuf>) -> Self {
+        self.node_script = Some(script.into());
+        self
+    }
+
+    // Project directory the backend is built in with `npm run build` when the
+    // Node script doesn't exist yet
+    pub fn build_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.build_dir = Some(dir.into());
+        self
+    }
+
+    // Script run with another interpreter, e.g. `python3`, when no binary is
+    // found. Unlike a Node script it is never built, so it must exist.
+    pub fn script(mut self, interpreter: impl Into<String>, script: impl Into<PathBuf>) -> Self {
+        self.script = Some((interpreter.into(), script.into()));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Binary names for the current platform, preferred first
+    pub fn binary_names(&self) -> Vec<String> {
+        target_suffixes()
+            .iter()
+            .map(|suffix| format!("{}-{}", self.name, suffix))
+            .collect()
+    }
+
+    pub fn resolve(&self) -> Option<Launch> {
+        for dir in &self.search_dirs {
+            for name in self.binary_names() {
+                let binary = dir.join(name);
+                if binary.exists() {
+                    return Some(Launch::Sidecar(binary));
+                }
+            }
+        }
+
+        if let Some(binary) = self.program.as_ref().filter(|binary| binary.exists()) {
+            return Some(Launch::Sidecar(binary.clone()));
+        }
+
+        if let Some(script) = self.node_script.clone() {
+            if !script.exists() {
+                println!("Backend not found at: {:?}", script);
+                if let Some(dir) = &self.build_dir {
+                    build_backend(dir);
+                }
+            }
+            return Some(Launch::Node(script));
+        }
+
+        let (interpreter, script) = self.script.clone()?;
+        script.exists().then_some(Launch::Script {
+            interpreter,
+            script,
+        })
+    }
+}
+
+fn build_backend(project_dir: &Path) {
+    println!("Building backend...");
+    let build_output = Command::new("npm")
+        .args(["run", "build"])
+        .current_dir(project_dir)
+        .output();
+
+    match build_output {
+        Ok(output) => {
+            if !output.status.success() {
+                eprintln!(
+                    "Backend build failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            } else {
+                println!("Backend built successfully");
+            }
+        }
+        Err(e) => eprintln!("Failed to build backend: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn prefers_binaries_in_earlier_search_dirs() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let (first, second) = (root.join("first"), root.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+
+        let sidecar = Sidecar::new("server")
+            .search_dir(&first)
+            .search_dir(&second)
+            .node_script(root.join("index.js"));
+        let Some(name) = sidecar.binary_names().into_iter().next() else {
+            return;
+        };
+        fs::write(second.join(&name), "").unwrap();
+        assert_eq!(sidecar.resolve(), Some(Launch::Sidecar(second.join(&name))));
+
+        fs::write(first.join(&name), "").unwrap();
+        assert_eq!(sidecar.resolve(), Some(Launch::Sidecar(first.join(&name))));
+    }
+
+    #[test]
+    fn ignores_binaries_for_other_platforms() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::write(root.join("server"), "").unwrap();
+        fs::write(root.join("server-riscv64gc-unknown-linux-gnu"), "").unwrap();
+
+        let sidecar = Sidecar::new("server").search_dir(root);
+        assert_eq!(sidecar.resolve(), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_program_then_the_node_script() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let program = root.join("server");
+        let script = root.join("index.js");
+
+        let sidecar = Sidecar::new("server")
+            .search_dir(root.join("missing"))
+            .program(&program)
+            .node_script(&script);
+        assert_eq!(sidecar.resolve(), Some(Launch::Node(script.clone())));
+
+        fs::write(&program, "").unwrap();
+        assert_eq!(sidecar.resolve(), Some(Launch::Sidecar(program)));
+    }
+
+    #[test]
+    fn only_runs_scripts_that_exist() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let script = root.join("worker.py");
+        let sidecar = Sidecar::new("worker").script("python3", &script);
+        assert_eq!(sidecar.resolve(), None);
+
+        fs::write(&script, "").unwrap();
+        assert_eq!(
+            sidecar.resolve(),
+            Some(Launch::Script {
+                interpreter: "python3".to_string(),
+                script: script.clone(),
+            })
+        );
+        let command = sidecar.resolve().unwrap().command();
+        assert_eq!(command.get_program(), "python3");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![script.as_os_str()]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+use std::process;
+
+mod template;
+
+use template::NewApp;
+
+// `cargo episensor-app new <name>`: stamps out a new desktop app from the
+// framework's templates, with its name, identifier, ports and icon filled in
+// everywhere they appear. Cargo runs `cargo-episensor-app episensor-app ...`, so
+// the subcommand's own name is skipped; the binary can also be run directly.
+const USAGE: &str = "Usage: cargo episensor-app new <name> [options]
+
+Options:
+  --dir <path>              Where to create the app (default: ./<name>)
+  --title <title>           Product name and window title (default: from <name>)
+  --description <text>      One-line description of the app
+  --identifier <id>         Bundle identifier (default: com.episensor.<name>)
+  --port <port>             Backend API port (default: 8080)
+  --web-port <port>         Vite dev server port (default: 5173)
+  --icon <png>              Square PNG app icon (default: the EpiSensor icon)
+  --update-endpoint <url>   Updater endpoint; the updater is off without one
+  --update-pubkey <key>     Updater public key
+  --sidecar-update-endpoint <url>
+                            Sidecar-only update manifest
+  --framework <path>        Framework checkout or installed package to take the
+                            templates from (default: the one this was built from)";
+
+fn parse_port(flag: &str, value: &str) -> Result<u16, String> {
+    match value.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(port),
+        _ => Err(format!("{} must be a port between 1 and 65535", flag)),
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<NewApp, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("episensor-app") {
+        args.next();
+    }
+    match args.next().as_deref() {
+        Some("new") => {}
+        Some(command) => return Err(format!("Unknown command: {}", command)),
+        None => return Err("Missing command".to_string()),
+    }
+
+    let mut name = None;
+    let mut app = NewApp::default();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if name.replace(arg).is_some() {
+                return Err("Only one app name may be given".to_string());
+            }
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--dir" => app.dir = Some(PathBuf::from(value)),
+            "--title" => app.title = Some(value),
+            "--description" => app.description = value,
+            "--identifier" => app.identifier = Some(value),
+            "--port" => app.api_port = parse_port(&arg, &value)?,
+            "--web-port" => app.web_port = parse_port(&arg, &value)?,
+            "--icon" => app.icon = Some(PathBuf::from(value)),
+            "--update-endpoint" => app.update_endpoint = Some(value),
+            "--update-pubkey" => app.update_pubkey = value,
+            "--sidecar-update-endpoint" => app.sidecar_update_endpoint = Some(value),
+            "--framework" => app.framework = Some(PathBuf::from(value)),
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    app.name = name.ok_or_else(|| "Missing app name".to_string())?;
+    Ok(app)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let app = match parse_args(args) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match template::generate(&app) {
+        Ok(dir) => {
+            println!("Created {} in {}", app.name, dir.display());
+            println!("\nNext steps:");
+            println!("  cd {}", dir.display());
+            println!("  npm install");
+            println!("  npm run tauri:icon");
+            println!("  npm run tauri:dev");
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_name_and_options() {
+        let app = parse_args(args(&[
+            "episensor-app",
+            "new",
+            "my-app",
+            "--port",
+            "9000",
+            "--title",
+            "My App",
+            "--dir",
+            "apps/my-app",
+        ]))
+        .unwrap();
+        assert_eq!(app.name, "my-app");
+        assert_eq!(app.api_port, 9000);
+        assert_eq!(app.web_port, 5173);
+        assert_eq!(app.title.as_deref(), Some("My App"));
+        assert_eq!(app.dir, Some(PathBuf::from("apps/my-app")));
+    }
+
+    #[test]
+    fn runs_without_the_cargo_subcommand_name() {
+        let app = parse_args(args(&["new", "my-app"])).unwrap();
+        assert_eq!(app.name, "my-app");
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["create", "my-app"])).is_err());
+        assert!(parse_args(args(&["new"])).is_err());
+        assert!(parse_args(args(&["new", "one", "two"])).is_err());
+        assert!(parse_args(args(&["new", "my-app", "--port"])).is_err());
+        assert!(parse_args(args(&["new", "my-app", "--colour", "red"])).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_ports() {
+        for port in ["0", "65536", "-1", "http"] {
+            assert!(parse_args(args(&["new", "my-app", "--port", port])).is_err());
+            assert!(parse_args(args(&["new", "my-app", "--web-port", port])).is_err());
+        }
+    }
+}
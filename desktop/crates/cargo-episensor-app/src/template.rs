@@ -0,0 +1,506 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+// A new app is assembled from the framework's templates, read from the framework
+// when it runs: the app skeleton in `desktop/app-template`, the shell in
+// `desktop/rust-templates` (as `src-tauri`) and its `tauri.conf.json` from
+// `desktop/tauri/template.json`. `{{PLACEHOLDER}}`s are filled in as the files are
+// copied, and any left over afterwards fail the run rather than ship.
+const APP_VERSION: &str = "0.1.0";
+const COMPANY: &str = "EpiSensor";
+const CATEGORY: &str = "DeveloperTool";
+// Where the shell finds the framework crate once the app's packages are installed
+const FRAMEWORK_CRATE_PATH: &str =
+    "../node_modules/@episensor/app-framework/desktop/crates/app-framework";
+// npm leaves `.gitignore` files out of published packages
+const RENAMED: &[(&str, &str)] = &[("gitignore", ".gitignore")];
+
+pub struct NewApp {
+    pub name: String,
+    pub dir: Option<PathBuf>,
+    pub title: Option<String>,
+    pub description: String,
+    pub identifier: Option<String>,
+    pub api_port: u16,
+    pub web_port: u16,
+    pub icon: Option<PathBuf>,
+    pub update_endpoint: Option<String>,
+    pub update_pubkey: String,
+    pub sidecar_update_endpoint: Option<String>,
+    pub framework: Option<PathBuf>,
+}
+
+impl Default for NewApp {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            dir: None,
+            title: None,
+            description: "Desktop application".to_string(),
+            identifier: None,
+            api_port: 8080,
+            web_port: 5173,
+            icon: None,
+            update_endpoint: None,
+            update_pubkey: String::new(),
+            sidecar_update_endpoint: None,
+            framework: None,
+        }
+    }
+}
+
+impl NewApp {
+    // `my-app` becomes `My App`
+    fn title(&self) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => self
+                .name
+                .split('-')
+                .filter(|word| !word.is_empty())
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first.to_uppercase().chain(chars).collect(),
+                        None => String::new(),
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+
+    fn identifier(&self) -> String {
+        match &self.identifier {
+            Some(identifier) => identifier.clone(),
+            None => format!(
+                "com.episensor.{}",
+                self.name
+                    .chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .collect::<String>()
+            ),
+        }
+    }
+}
+
+// The name is used for the npm package and the Rust crate, so it has to suit both
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && !name.ends_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(format!(
+            "Invalid app name {:?}: use lowercase letters, digits and dashes, starting with a letter",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn validate_identifier(identifier: &str) -> Result<(), String> {
+    let segments: Vec<&str> = identifier.split('.').collect();
+    let valid = segments.len() >= 2
+        && segments.iter().all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_alphabetic())
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        return Err(format!(
+            "Invalid identifier {:?}: use reverse domain notation, e.g. com.episensor.myapp",
+            identifier
+        ));
+    }
+    Ok(())
+}
+
+// Text is substituted into JSON, TOML and TypeScript strings as it is
+fn validate_text(option: &str, text: &str) -> Result<(), String> {
+    if text
+        .chars()
+        .any(|c| c == '"' || c == '\\' || c.is_control())
+    {
+        return Err(format!(
+            "{} must not contain quotes, backslashes or control characters",
+            option
+        ));
+    }
+    Ok(())
+}
+
+fn validate(app: &NewApp) -> Result<(), String> {
+    validate_name(&app.name)?;
+    validate_identifier(&app.identifier())?;
+    validate_text("--title", &app.title())?;
+    validate_text("--description", &app.description)?;
+    if app.api_port == app.web_port {
+        return Err("--port and --web-port must differ".to_string());
+    }
+    if let Some(icon) = &app.icon {
+        if icon.extension().and_then(|ext| ext.to_str()) != Some("png") || !icon.is_file() {
+            return Err(format!(
+                "Icon must be an existing PNG file: {}",
+                icon.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn framework_root(app: &NewApp) -> Result<PathBuf, String> {
+    let root = match &app.framework {
+        Some(root) => root.clone(),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("../../.."),
+    };
+    if !root.join("desktop/rust-templates").is_dir() {
+        return Err(format!(
+            "No framework templates found in {}, pass --framework",
+            root.display()
+        ));
+    }
+    Ok(root)
+}
+
+fn framework_version(root: &Path) -> Result<String, String> {
+    let path = root.join("package.json");
+    let package: Value = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    package["version"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("No version in {}", path.display()))
+}
+
+// Days since the epoch to the proleptic Gregorian year
+fn current_year() -> i64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or_default() as i64;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    year_of_era + era * 400 + i64::from(month >= 10)
+}
+
+fn substitutions(app: &NewApp, framework_version: String) -> Vec<(&'static str, String)> {
+    let short_description: String = app.description.chars().take(100).collect();
+    vec![
+        ("APP_NAME", app.name.clone()),
+        ("APP_TITLE", app.title()),
+        ("APP_VERSION", APP_VERSION.to_string()),
+        ("APP_DESCRIPTION", app.description.clone()),
+        ("APP_IDENTIFIER", app.identifier()),
+        ("API_PORT", app.api_port.to_string()),
+        ("DEV_PORT", app.web_port.to_string()),
+        ("FRAMEWORK_VERSION", framework_version),
+        ("FRAMEWORK_CRATE_PATH", FRAMEWORK_CRATE_PATH.to_string()),
+        ("YEAR", current_year().to_string()),
+        ("COMPANY", COMPANY.to_string()),
+        ("CATEGORY", CATEGORY.to_string()),
+        ("SHORT_DESCRIPTION", short_description),
+        ("LONG_DESCRIPTION", app.description.clone()),
+        (
+            "UPDATE_ENDPOINT",
+            app.update_endpoint.clone().unwrap_or_default(),
+        ),
+        ("UPDATE_PUBKEY", app.update_pubkey.clone()),
+        (
+            "SIDECAR_UPDATE_ENDPOINT",
+            app.sidecar_update_endpoint.clone().unwrap_or_default(),
+        ),
+    ]
+}
+
+fn substitute(contents: &str, substitutions: &[(&str, String)]) -> String {
+    substitutions
+        .iter()
+        .fold(contents.to_string(), |contents, (placeholder, value)| {
+            contents.replace(&format!("{{{{{}}}}}", placeholder), value)
+        })
+}
+
+fn copy_templates(from: &Path, to: &Path, substitutions: &[(&str, String)]) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    let entries =
+        fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let renamed = RENAMED
+            .iter()
+            .find(|(template, _)| *template == name)
+            .map_or(name.as_str(), |(_, renamed)| renamed);
+        let source = entry.path();
+        let target = to.join(renamed);
+
+        if source.is_dir() {
+            copy_templates(&source, &target, substitutions)?;
+            continue;
+        }
+        let contents = fs::read_to_string(&source)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        fs::write(&target, substitute(&contents, substitutions))
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    }
+    Ok(())
+}
+
+// Values that aren't strings, or that switch a plugin off when they're missing,
+// are set on the parsed config
+fn write_tauri_config(
+    root: &Path,
+    src_tauri: &Path,
+    app: &NewApp,
+    substitutions: &[(&str, String)],
+) -> Result<(), String> {
+    let path = root.join("desktop/tauri/template.json");
+    let template = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut config: Value = serde_json::from_str(&substitute(&template, substitutions))
+        .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+
+    let plugins = &mut config["plugins"];
+    plugins["backend"]["port"] = json!(app.api_port);
+    if app.update_endpoint.is_none() {
+        plugins["updater"]["active"] = json!(false);
+        plugins["updater"]["endpoints"] = json!([]);
+    }
+    if app.sidecar_update_endpoint.is_none() {
+        if let Some(plugins) = plugins.as_object_mut() {
+            plugins.remove("sidecarUpdater");
+        }
+    }
+
+    let target = src_tauri.join("tauri.conf.json");
+    let contents = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    fs::write(&target, contents + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+}
+
+// `npm run tauri:icon` makes the other sizes from it
+fn copy_icon(root: &Path, src_tauri: &Path, app: &NewApp) -> Result<(), String> {
+    let icon = match &app.icon {
+        Some(icon) => icon.clone(),
+        None => root.join("desktop/icons/icon.png"),
+    };
+    let icons = src_tauri.join("icons");
+    fs::create_dir_all(&icons).map_err(|e| e.to_string())?;
+    fs::copy(&icon, icons.join("icon.png"))
+        .map(|_| ())
+        .map_err(|e| format!("Failed to copy icon {}: {}", icon.display(), e))
+}
+
+// `{{PLACEHOLDER}}`s left in text files, with the file each is in. Lowercase
+// ones, such as the updater's `{{channel}}`, are filled in at runtime.
+fn find_placeholders(dir: &Path, found: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_placeholders(&path, found);
+            continue;
+        }
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        for (start, _) in contents.match_indices("{{") {
+            let rest = &contents[start + 2..];
+            let Some(end) = rest.find("}}") else {
+                continue;
+            };
+            let placeholder = &rest[..end];
+            if !placeholder.is_empty()
+                && placeholder
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c == '_')
+            {
+                found.push((placeholder.to_string(), path.clone()));
+            }
+        }
+    }
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+// The app is generated beside its directory and only moved into place once it is
+// complete, so a failed run doesn't leave a half-made app behind
+fn staging_dir(dir: &Path) -> Result<PathBuf, String> {
+    // Made absolute so that `--dir .` has a name to stage under
+    let dir = std::path::absolute(dir).map_err(|e| e.to_string())?;
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(dir.with_file_name(format!(".{}.partial", name)))
+}
+
+fn generate_into(app: &NewApp, root: &Path, dir: &Path, shown_as: &Path) -> Result<(), String> {
+    let substitutions = substitutions(app, framework_version(root)?);
+    let src_tauri = dir.join("src-tauri");
+    copy_templates(&root.join("desktop/app-template"), dir, &substitutions)?;
+    copy_templates(
+        &root.join("desktop/rust-templates"),
+        &src_tauri,
+        &substitutions,
+    )?;
+    write_tauri_config(root, &src_tauri, app, &substitutions)?;
+    copy_icon(root, &src_tauri, app)?;
+
+    let mut leftover = Vec::new();
+    find_placeholders(dir, &mut leftover);
+    if !leftover.is_empty() {
+        let leftover: Vec<String> = leftover
+            .into_iter()
+            .map(|(placeholder, path)| {
+                let path = path
+                    .strip_prefix(dir)
+                    .map_or(path.clone(), |relative| shown_as.join(relative));
+                format!("{{{{{}}}}} in {}", placeholder, path.display())
+            })
+            .collect();
+        return Err(format!(
+            "Templates have placeholders this version doesn't fill in:\n  {}",
+            leftover.join("\n  ")
+        ));
+    }
+    Ok(())
+}
+
+// Generate the app, returning the directory it was created in
+pub fn generate(app: &NewApp) -> Result<PathBuf, String> {
+    validate(app)?;
+    let root = framework_root(app)?;
+    let dir = app.dir.clone().unwrap_or_else(|| PathBuf::from(&app.name));
+    if dir.exists() && !is_empty_dir(&dir) {
+        return Err(format!("{} already exists and is not empty", dir.display()));
+    }
+
+    // Left over from a run that was killed
+    let staging = staging_dir(&dir)?;
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to remove {}: {}", staging.display(), e))?;
+    }
+    let result = generate_into(app, &root, &staging, &dir).and_then(|()| {
+        // Windows won't rename over a directory, even an empty one
+        if dir.exists() {
+            fs::remove_dir(&dir)
+                .map_err(|e| format!("Failed to replace {}: {}", dir.display(), e))?;
+        }
+        fs::rename(&staging, &dir)
+            .map_err(|e| format!("Failed to move the app to {}: {}", dir.display(), e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    result.map(|()| dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_unfilled_placeholders() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("package.json"), r#"{"name": "{{APP_NAME}}"}"#).unwrap();
+        fs::write(
+            dir.join("src/config.ts"),
+            "const url = '{{UPDATE_ENDPOINT}}/{{channel}}';\nconst x = {{}};\nconst y = '{{OPEN",
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "Nothing to fill in").unwrap();
+
+        let mut found = Vec::new();
+        find_placeholders(dir, &mut found);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("APP_NAME".to_string(), dir.join("package.json")),
+                ("UPDATE_ENDPOINT".to_string(), dir.join("src/config.ts")),
+            ]
+        );
+    }
+
+    #[test]
+    fn substitutes_every_occurrence() {
+        let substitutions = vec![("APP_NAME", "my-app".to_string())];
+        assert_eq!(
+            substitute("{{APP_NAME}} and {{APP_NAME}} {{channel}}", &substitutions),
+            "my-app and my-app {{channel}}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        assert!(validate_name("my-app2").is_ok());
+        for name in [
+            "", "My-App", "2app", "my-app-", "my_app", "my app", "../app",
+        ] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_text_that_would_break_out_of_a_string() {
+        assert!(validate_text("--title", "My App").is_ok());
+        for text in ["My \"App\"", "My\\App", "My\nApp"] {
+            assert!(validate_text("--title", text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn stages_beside_the_target() {
+        let staging = staging_dir(Path::new("apps/my-app")).unwrap();
+        assert_eq!(staging.file_name().unwrap(), ".my-app.partial");
+        assert!(staging.ends_with("apps/.my-app.partial"));
+    }
+
+    #[test]
+    fn leaves_nothing_behind_when_generation_fails() {
+        let temp = tempfile::tempdir().unwrap();
+        let framework = temp.path();
+        fs::create_dir_all(framework.join("desktop/rust-templates")).unwrap();
+        fs::create_dir_all(framework.join("desktop/app-template")).unwrap();
+        fs::write(framework.join("package.json"), r#"{"version": "1.0.0"}"#).unwrap();
+        fs::write(
+            framework.join("desktop/app-template/README.md"),
+            "{{UNKNOWN}}",
+        )
+        .unwrap();
+        let target = framework.join("my-app");
+
+        let app = NewApp {
+            name: "my-app".to_string(),
+            dir: Some(target.clone()),
+            framework: Some(framework.to_path_buf()),
+            ..NewApp::default()
+        };
+        assert!(generate(&app).is_err());
+        assert!(!target.exists());
+        assert!(!staging_dir(&target).unwrap().exists());
+    }
+}
@@ -0,0 +1,5 @@
+const COMMANDS: &[&str] = &["status", "restart", "request"];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS).build();
+}
@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use episensor_app_framework::{AppShell, Launch, SandboxOptions, SandboxStatus};
+
+// Header carrying the plugin's token on every request to the backend, which
+// receives the token through `DESKTOP_AUTH_TOKEN` and rejects requests without it.
+// Same as the shell template's, so backends built on the framework accept either.
+pub const TOKEN_HEADER: &str = "X-Desktop-Token";
+pub const TOKEN_ENV: &str = "DESKTOP_AUTH_TOKEN";
+
+// The backend run by the plugin, managed as app state
+pub struct Backend {
+    shell: AppShell,
+    token: String,
+    port: Mutex<Option<u16>>,
+    env: Vec<(String, String)>,
+    sandbox: SandboxOptions,
+    working_dir: PathBuf,
+    startup_timeout: Duration,
+    client: reqwest::Client,
+}
+
+// Random per-launch token, hex encoded
+fn generate_token() -> String {
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The path must start with `/`, or it could continue the authority instead: with
+// `@evil.example/x` the request would go to evil.example, token and all
+fn backend_url(port: u16, path: &str) -> Result<String, String> {
+    if !path.starts_with('/') {
+        return Err(format!("Backend path must start with '/': {}", path));
+    }
+    Ok(format!("http://127.0.0.1:{}{}", port, path))
+}
+
+impl Backend {
+    pub(crate) fn new(
+        shell: AppShell,
+        env: Vec<(String, String)>,
+        sandbox: SandboxOptions,
+        working_dir: PathBuf,
+        startup_timeout: Duration,
+    ) -> Self {
+        Self {
+            shell,
+            token: generate_token(),
+            port: Mutex::new(None),
+            env,
+            sandbox,
+            working_dir,
+            startup_timeout,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        *self.port.lock().unwrap()
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    // URL of a backend path, once the backend is up
+    pub fn url(&self, path: &str) -> Result<String, String> {
+        let port = self.port().ok_or("Backend server is not running")?;
+        backend_url(port, path)
+    }
+
+    pub fn sandbox_status(&self) -> Option<SandboxStatus> {
+        self.shell.sandbox_status()
+    }
+
+    pub fn has_exited(&self) -> bool {
+        self.shell.has_exited()
+    }
+
+    pub(crate) fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    // Start the backend (if it is not already running) and block until it is
+    // healthy
+    pub fn start(&self) -> Result<u16, String> {
+        self.shell
+            .start(&self.sandbox, &self.working_dir, |launch, command| {
+                match launch {
+                    Launch::Sidecar(binary) => println!("Starting backend sidecar: {:?}", binary),
+                    Launch::Node(script) => println!("Starting backend server: {:?}", script),
+                    Launch::Script {
+                        interpreter,
+                        script,
+                    } => println!("Starting backend server: {} {:?}", interpreter, script),
+                }
+                command
+                    .env("NODE_ENV", "production")
+                    .env("DESKTOP", "true")
+                    .env("HOST", "127.0.0.1")
+                    .env(TOKEN_ENV, &self.token)
+                    .envs(self.env.iter().map(|(name, value)| (name, value)));
+                Ok(())
+            })
+            .map_err(|e| e.to_string())?;
+
+        let client = reqwest::blocking::Client::new();
+        let port = self
+            .shell
+            .wait_for_health("http", self.startup_timeout, |url| {
+                client
+                    .get(url)
+                    .header(TOKEN_HEADER, &self.token)
+                    .timeout(Duration::from_secs(1))
+                    .send()
+                    .map(|response| response.status().is_success())
+                    .unwrap_or(false)
+            })
+            .ok_or("Backend failed to start within timeout")?;
+
+        *self.port.lock().unwrap() = Some(port);
+        println!("Backend server is ready on port {}!", port);
+        Ok(port)
+    }
+
+    pub fn stop(&self) {
+        self.shell.stop();
+        *self.port.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_urls_for_absolute_paths() {
+        assert_eq!(
+            backend_url(3000, "/api/health").unwrap(),
+            "http://127.0.0.1:3000/api/health"
+        );
+        assert_eq!(
+            backend_url(3000, "//evil.example/x").unwrap(),
+            "http://127.0.0.1:3000//evil.example/x"
+        );
+    }
+
+    #[test]
+    fn rejects_paths_that_would_change_the_host() {
+        for path in ["", "@evil.example/x", ".evil.example/x", ":80@evil.example", "api/health"] {
+            assert!(backend_url(3000, path).is_err(), "{:?}", path);
+        }
+    }
+}
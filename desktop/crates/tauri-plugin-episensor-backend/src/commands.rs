@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use episensor_app_framework::SandboxStatus;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use serde::Serialize;
+use tauri::{AppHandle, Runtime};
+
+use crate::backend::TOKEN_HEADER;
+use crate::BackendExt;
+
+#[derive(Serialize)]
+pub struct BackendStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub sandbox: Option<SandboxStatus>,
+}
+
+#[derive(Serialize)]
+pub struct BackendResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    // Parsed JSON when the backend returns JSON, the raw text otherwise
+    pub body: serde_json::Value,
+}
+
+// Headers supplied by the frontend, minus the auth token which only the plugin sets
+fn request_headers(headers: HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case(TOKEN_HEADER) {
+            continue;
+        }
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+        let value = HeaderValue::from_str(&value).map_err(|e| e.to_string())?;
+        header_map.insert(name, value);
+    }
+    Ok(header_map)
+}
+
+// Command to report whether the backend is up, and how it was sandboxed
+#[tauri::command]
+pub fn status<R: Runtime>(app: AppHandle<R>) -> BackendStatus {
+    let backend = app.backend();
+    BackendStatus {
+        running: backend.port().is_some() && !backend.has_exited(),
+        port: backend.port(),
+        sandbox: backend.sandbox_status(),
+    }
+}
+
+// Command to restart the backend, returning the port it came back on
+#[tauri::command]
+pub async fn restart<R: Runtime>(app: AppHandle<R>) -> Result<u16, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let backend = app.backend();
+        backend.stop();
+        backend.start()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to forward a request to the backend and return its response
+#[tauri::command]
+pub async fn request<R: Runtime>(
+    app: AppHandle<R>,
+    method: String,
+    path: String,
+    headers: Option<HashMap<String, String>>,
+    body: Option<serde_json::Value>,
+) -> Result<BackendResponse, String> {
+    let backend = app.backend();
+    let method = Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|e| e.to_string())?;
+    let url = backend.url(&path)?;
+
+    let mut request = backend
+        .client()
+        .request(method, url)
+        .header(TOKEN_HEADER, backend.token())
+        .headers(request_headers(headers.unwrap_or_default())?);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+
+    let status = response.status().as_u16();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.contains("json"))
+        .unwrap_or(false);
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.to_string(), value.to_string()))
+        })
+        .collect();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    let body = if is_json {
+        serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text))
+    } else {
+        serde_json::Value::String(text)
+    };
+
+    Ok(BackendResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_token_header_from_the_frontend() {
+        let headers = request_headers(HashMap::from([
+            ("x-desktop-token".to_string(), "forged".to_string()),
+            ("Accept".to_string(), "application/json".to_string()),
+        ]))
+        .unwrap();
+        assert!(headers.get(TOKEN_HEADER).is_none());
+        assert_eq!(headers["accept"], "application/json");
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert!(request_headers(HashMap::from([("bad header".to_string(), "x".to_string())])).is_err());
+        assert!(request_headers(HashMap::from([("x-ok".to_string(), "a\nb".to_string())])).is_err());
+    }
+}
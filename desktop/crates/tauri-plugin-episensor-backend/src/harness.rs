@@ -0,0 +1,301 @@
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+use tauri::{App, AppHandle, Listener, Manager, WebviewWindowBuilder};
+use tempfile::TempDir;
+
+use crate::backend::TOKEN_HEADER;
+use crate::commands::{self, BackendResponse, BackendStatus};
+use crate::{BackendExt, Builder, HealthCheck, Logging, Sidecar};
+
+// Test harness for CI, behind the `test-harness` feature: runs the plugin in an
+// app on Tauri's mock runtime, so no display server is needed, with a mock
+// sidecar in place of the real backend. Tests drive the plugin's commands and
+// assert on the events it emits:
+//
+//     let shell = TestShell::builder().start()?;
+//     assert!(shell.status().running);
+//     shell.crash_backend();
+//     assert!(shell.wait_for_event("backend-exited", TIMEOUT).is_some());
+//     assert!(shell.wait_for_event("backend-ready", TIMEOUT).is_some());
+//     shell.shutdown()?;
+//
+// The built-in mock sidecar is a Node script, so `node` must be on the PATH. It
+// answers `/api/health`, exits with status 1 on `POST /__mock/exit` and echoes
+// any other request back as `{ method, path, body }`. A custom mock passed to
+// `mock_sidecar` gets its port in `PORT` and should do the same.
+
+// Events the plugin emits, recorded in the order they arrive
+pub const EVENTS: &[&str] = &["backend-ready", "backend-failed", "backend-exited"];
+
+const MOCK_SIDECAR: &str = r#"const http = require('http');
+
+const token = process.env.DESKTOP_AUTH_TOKEN;
+http
+  .createServer((req, res) => {
+    if (req.headers['x-desktop-token'] !== token) {
+      res.writeHead(401);
+      res.end();
+      return;
+    }
+
+    let body = '';
+    req.on('data', (chunk) => (body += chunk));
+    req.on('end', () => {
+      if (req.url === '/__mock/exit') {
+        res.writeHead(200);
+        res.end(() => process.exit(1));
+        return;
+      }
+
+      const response =
+        req.url === '/api/health'
+          ? { status: 'ok' }
+          : { method: req.method, path: req.url, body: body ? JSON.parse(body) : null };
+      res.writeHead(200, { 'Content-Type': 'application/json' });
+      res.end(JSON.stringify(response));
+    });
+  })
+  .listen(Number(process.env.PORT), '127.0.0.1');
+"#;
+
+type Events = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+pub struct TestShellBuilder {
+    plugin: Builder,
+    mock_sidecar: Option<PathBuf>,
+    timeout: Duration,
+}
+
+// The plugin in a mock app, with its backend running
+pub struct TestShell {
+    app: Option<App<MockRuntime>>,
+    events: Events,
+    port: u16,
+    // Removed once the backend has been stopped, as the fields drop after `drop`
+    _dir: TempDir,
+}
+
+fn free_port() -> Result<u16, String> {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|e| format!("Failed to find a free port: {}", e))
+}
+
+impl TestShellBuilder {
+    // The app's own plugin configuration. The sidecar, health check, logging and
+    // supervision are replaced by the harness's.
+    pub fn plugin(mut self, plugin: Builder) -> Self {
+        self.plugin = plugin;
+        self
+    }
+
+    // Script run with Node in place of the built-in mock sidecar
+    pub fn mock_sidecar(mut self, script: impl Into<PathBuf>) -> Self {
+        self.mock_sidecar = Some(script.into());
+        self
+    }
+
+    // How long the backend gets to start
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Build the app and wait for the backend to be ready
+    pub fn start(self) -> Result<TestShell, String> {
+        let temp = tempfile::Builder::new()
+            .prefix("episensor-harness-")
+            .tempdir()
+            .map_err(|e| e.to_string())?;
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("data")).map_err(|e| e.to_string())?;
+        let script = match self.mock_sidecar {
+            Some(script) => script,
+            None => {
+                let script = dir.join("mock-sidecar.cjs");
+                fs::write(&script, MOCK_SIDECAR).map_err(|e| e.to_string())?;
+                script
+            }
+        };
+        let port = free_port()?;
+
+        // Registered before the backend plugin, so its first events aren't missed
+        let events = Events::default();
+        let recorded = events.clone();
+        let recorder = tauri::plugin::Builder::<MockRuntime>::new("episensor-harness")
+            .setup(move |app, _api| {
+                for name in EVENTS {
+                    let recorded = recorded.clone();
+                    app.listen_any(*name, move |event| {
+                        let payload = serde_json::from_str(event.payload())
+                            .unwrap_or(serde_json::Value::Null);
+                        recorded.lock().unwrap().push((name.to_string(), payload));
+                    });
+                }
+                Ok(())
+            })
+            .build();
+
+        let plugin = self
+            .plugin
+            .sidecar(Sidecar::new("mock-sidecar").node_script(script))
+            .health_check(HealthCheck::new("/api/health").ports(&[port]))
+            .logging(Logging::to_file(dir.join("backend.log")))
+            .env("PORT", port.to_string())
+            .env("DATA_DIR", dir.join("data").to_string_lossy())
+            .startup_timeout(self.timeout)
+            .restart_on_exit(true)
+            .supervise_interval(Duration::from_millis(100));
+
+        let app = mock_builder()
+            .plugin(recorder)
+            .plugin(plugin.build())
+            .build(mock_context(noop_assets()))
+            .map_err(|e| e.to_string())?;
+        // Closing the only window is how the mock runtime exits
+        WebviewWindowBuilder::new(&app, "main", Default::default())
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let shell = TestShell {
+            app: Some(app),
+            events,
+            port,
+            _dir: temp,
+        };
+        let deadline = Instant::now() + self.timeout + Duration::from_secs(1);
+        loop {
+            if shell.take_event("backend-ready").is_some() {
+                return Ok(shell);
+            }
+            if let Some(failed) = shell.take_event("backend-failed") {
+                return Err(format!("Backend failed to start: {}", failed["error"]));
+            }
+            if Instant::now() > deadline {
+                return Err("Backend did not report ready or failed".to_string());
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl TestShell {
+    pub fn builder() -> TestShellBuilder {
+        TestShellBuilder {
+            plugin: Builder::default(),
+            mock_sidecar: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn app(&self) -> &App<MockRuntime> {
+        self.app
+            .as_ref()
+            .expect("the app is only taken by shutdown")
+    }
+
+    fn handle(&self) -> AppHandle<MockRuntime> {
+        self.app().handle().clone()
+    }
+
+    // Port the mock sidecar listens on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn status(&self) -> BackendStatus {
+        commands::status(self.handle())
+    }
+
+    pub fn restart(&self) -> Result<u16, String> {
+        tauri::async_runtime::block_on(commands::restart(self.handle()))
+    }
+
+    pub fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<BackendResponse, String> {
+        tauri::async_runtime::block_on(commands::request(
+            self.handle(),
+            method.to_string(),
+            path.to_string(),
+            None,
+            body,
+        ))
+    }
+
+    // Names of the events recorded and not yet taken, oldest first
+    pub fn events(&self) -> Vec<String> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn take_event(&self, name: &str) -> Option<serde_json::Value> {
+        let mut events = self.events.lock().unwrap();
+        let index = events.iter().position(|(event, _)| event == name)?;
+        Some(events.remove(index).1)
+    }
+
+    // Take the oldest `name` event, waiting for one to arrive. Returns its payload.
+    pub fn wait_for_event(&self, name: &str, timeout: Duration) -> Option<serde_json::Value> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(payload) = self.take_event(name) {
+                return Some(payload);
+            }
+            if Instant::now() > deadline {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    // Make the mock sidecar exit with an error, as if it had crashed
+    pub fn crash_backend(&self) {
+        let backend = self.app().backend();
+        let _ = reqwest::blocking::Client::new()
+            .post(format!("http://127.0.0.1:{}/__mock/exit", self.port))
+            .header(TOKEN_HEADER, backend.token())
+            .timeout(Duration::from_secs(1))
+            .send();
+    }
+
+    // Close the app as a user would and check the backend was stopped with it
+    pub fn shutdown(mut self) -> Result<(), String> {
+        let app = self.app.take().expect("the app is only taken by shutdown");
+        let handle = app.handle().clone();
+        if let Some(window) = app.get_webview_window("main") {
+            window.close().map_err(|e| e.to_string())?;
+        }
+        app.run(|_, _| {});
+
+        if handle.backend().port().is_some() {
+            return Err("Backend was not stopped when the app exited".to_string());
+        }
+        if TcpStream::connect(("127.0.0.1", self.port)).is_ok() {
+            return Err("Backend is still listening after the app exited".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestShell {
+    fn drop(&mut self) {
+        if let Some(app) = &self.app {
+            app.backend().stop();
+        }
+    }
+}
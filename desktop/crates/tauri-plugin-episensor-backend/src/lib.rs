@@ -0,0 +1,223 @@
+// Tauri plugin running an EpiSensor app's Node backend, for Tauri apps that don't
+// use the generated desktop shell. One line starts the backend with the app,
+// supervises it and stops it on exit:
+//
+//     tauri::Builder::default()
+//         .plugin(tauri_plugin_episensor_backend::init())
+//
+// `Builder` overrides the defaults: the sidecar is found in the `server`
+// resource directory (or `backend/index.js` is run with Node), its output goes to
+// `backend.log` in the app's log directory and its data to the app's data
+// directory. The frontend is told `backend-ready` with the port once the backend
+// is healthy, `backend-failed` if it never gets there and `backend-exited` before
+// it is restarted. It reaches the backend through the `request` command, which
+// adds the per-launch token, with `status` and `restart` alongside, e.g.
+// `invoke('plugin:episensor-backend|request', { method: 'GET', path: '/api/devices' })`.
+// The capability needs `episensor-backend:default`.
+
+use std::thread;
+use std::time::Duration;
+
+use episensor_app_framework::AppShell;
+use serde::Serialize;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Emitter, Manager, RunEvent, Runtime};
+
+mod backend;
+mod commands;
+#[cfg(feature = "test-harness")]
+pub mod harness;
+
+pub use backend::{Backend, TOKEN_ENV, TOKEN_HEADER};
+pub use commands::{BackendResponse, BackendStatus};
+pub use episensor_app_framework::{HealthCheck, Logging, SandboxOptions, SandboxStatus, Sidecar};
+
+#[derive(Serialize, Clone)]
+struct BackendReady {
+    port: u16,
+}
+
+#[derive(Serialize, Clone)]
+struct BackendFailed {
+    error: String,
+}
+
+// Access to the backend from anything holding the app, e.g. `app.backend().url("/api/health")`
+pub trait BackendExt<R: Runtime> {
+    fn backend(&self) -> &Backend;
+}
+
+impl<R: Runtime, T: Manager<R>> BackendExt<R> for T {
+    fn backend(&self) -> &Backend {
+        self.state::<Backend>().inner()
+    }
+}
+
+pub struct Builder {
+    sidecar: Option<Sidecar>,
+    health_check: HealthCheck,
+    logging: Option<Logging>,
+    sandbox: SandboxOptions,
+    env: Vec<(String, String)>,
+    startup_timeout: Duration,
+    restart_on_exit: bool,
+    supervise_interval: Duration,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            sidecar: None,
+            health_check: HealthCheck::default(),
+            logging: None,
+            sandbox: SandboxOptions {
+                restrict_env: true,
+                ..SandboxOptions::default()
+            },
+            env: Vec::new(),
+            startup_timeout: Duration::from_secs(30),
+            restart_on_exit: true,
+            supervise_interval: Duration::from_secs(15),
+        }
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sidecar(mut self, sidecar: Sidecar) -> Self {
+        self.sidecar = Some(sidecar);
+        self
+    }
+
+    pub fn health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = health_check;
+        self
+    }
+
+    pub fn logging(mut self, logging: Logging) -> Self {
+        self.logging = Some(logging);
+        self
+    }
+
+    pub fn sandbox(mut self, sandbox: SandboxOptions) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    // Extra environment for the backend, e.g. `PORT`. Overrides `DATA_DIR`.
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    pub fn restart_on_exit(mut self, restart: bool) -> Self {
+        self.restart_on_exit = restart;
+        self
+    }
+
+    // How often the backend is checked for having exited
+    pub fn supervise_interval(mut self, interval: Duration) -> Self {
+        self.supervise_interval = interval;
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        tauri::plugin::Builder::new("episensor-backend")
+            .invoke_handler(tauri::generate_handler![
+                commands::status,
+                commands::restart,
+                commands::request
+            ])
+            .setup(move |app, _api| {
+                let resource_dir = app.path().resource_dir()?;
+                let data_dir = app.path().app_data_dir()?;
+                let log_dir = app.path().app_log_dir()?;
+                std::fs::create_dir_all(&data_dir)?;
+                std::fs::create_dir_all(&log_dir)?;
+
+                let sidecar = self.sidecar.unwrap_or_else(|| {
+                    Sidecar::new("server")
+                        .search_dir(resource_dir.join("server"))
+                        .node_script(resource_dir.join("backend").join("index.js"))
+                });
+                let logging = self
+                    .logging
+                    .unwrap_or_else(|| Logging::to_file(log_dir.join("backend.log")));
+                let shell = AppShell::builder()
+                    .sidecar(sidecar)
+                    .health_check(self.health_check)
+                    .logging(logging)
+                    .build();
+
+                let mut env = vec![(
+                    "DATA_DIR".to_string(),
+                    data_dir.to_string_lossy().into_owned(),
+                )];
+                env.extend(self.env);
+
+                app.manage(Backend::new(
+                    shell,
+                    env,
+                    self.sandbox,
+                    data_dir,
+                    self.startup_timeout,
+                ));
+                let supervise = self.restart_on_exit.then_some(self.supervise_interval);
+                run(app.clone(), supervise);
+                Ok(())
+            })
+            .on_event(|app, event| {
+                if let RunEvent::Exit = event {
+                    app.backend().stop();
+                }
+            })
+            .build()
+    }
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::default().build()
+}
+
+fn start<R: Runtime>(app: &AppHandle<R>) {
+    match app.backend().start() {
+        Ok(port) => {
+            let _ = app.emit("backend-ready", BackendReady { port });
+        }
+        Err(error) => {
+            eprintln!("Failed to start backend: {}", error);
+            let _ = app.emit("backend-failed", BackendFailed { error });
+        }
+    }
+}
+
+// Start the backend off the main thread, then check it every `supervise`
+// interval and restart it if it has exited
+fn run<R: Runtime>(app: AppHandle<R>, supervise: Option<Duration>) {
+    thread::spawn(move || {
+        start(&app);
+        let Some(interval) = supervise else {
+            return;
+        };
+
+        loop {
+            thread::sleep(interval);
+
+            let backend = app.backend();
+            if backend.has_exited() {
+                eprintln!("Backend exited, restarting");
+                let _ = app.emit("backend-exited", ());
+                backend.stop();
+                start(&app);
+            }
+        }
+    });
+}
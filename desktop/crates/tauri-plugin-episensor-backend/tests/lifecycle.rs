@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use tauri_plugin_episensor_backend::harness::TestShell;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn starts_restarts_after_a_crash_and_stops_on_exit() {
+    let shell = TestShell::builder().start().unwrap();
+    let status = shell.status();
+    assert!(status.running);
+    assert_eq!(status.port, Some(shell.port()));
+
+    let response = shell
+        .request(
+            "POST",
+            "/api/devices",
+            Some(serde_json::json!({ "name": "meter" })),
+        )
+        .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["path"], "/api/devices");
+    assert_eq!(response.body["body"]["name"], "meter");
+
+    shell.crash_backend();
+    assert!(shell.wait_for_event("backend-exited", TIMEOUT).is_some());
+    let ready = shell.wait_for_event("backend-ready", TIMEOUT).unwrap();
+    assert_eq!(ready["port"], shell.port());
+
+    assert_eq!(shell.restart().unwrap(), shell.port());
+    shell.shutdown().unwrap();
+}
+
+#[test]
+fn refuses_request_paths_that_could_leave_the_backend() {
+    let shell = TestShell::builder().start().unwrap();
+
+    for path in ["@evil.example/x", "evil.example/x", ""] {
+        let Err(error) = shell.request("GET", path, None) else {
+            panic!("{:?} reached the backend", path);
+        };
+        assert!(error.contains("must start with '/'"), "{}", error);
+    }
+    assert_eq!(shell.request("GET", "/api/devices", None).unwrap().status, 200);
+
+    shell.shutdown().unwrap();
+}
@@ -1,3 +1,18 @@
+use std::process::Command;
+
 fn main() {
+    // Build metadata reported by `get_version_info`
+    let git_commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_COMMIT={}", git_commit);
+
+    let build_date = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    println!("cargo:rustc-env=BUILD_DATE={}", build_date);
+
     tauri_build::build()
 }
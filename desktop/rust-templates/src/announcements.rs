@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+use tokio::net::UdpSocket;
+
+use crate::events;
+
+// Meters announce themselves with UDP broadcasts, which the sandboxed webview
+// can't receive. The shell listens on the requested ports and emits every
+// announcement it can parse as `device-announced`.
+const MAX_PACKET_SIZE: usize = 2048;
+
+#[derive(Default)]
+pub struct AnnouncementState {
+    listeners: Mutex<HashMap<u16, JoinHandle<()>>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct DeviceAnnouncement {
+    pub address: String,
+    pub port: u16,
+    // Fields from the packet, which is either a JSON object or `key=value` lines
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+fn parse_announcement(packet: &[u8]) -> Option<serde_json::Map<String, serde_json::Value>> {
+    let text = std::str::from_utf8(packet).ok()?.trim();
+
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::from_str(text) {
+        return Some(fields);
+    }
+
+    let fields: serde_json::Map<_, _> = text
+        .split(['\n', ';'])
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.trim().to_string(),
+                serde_json::Value::String(value.trim().to_string()),
+            )
+        })
+        .filter(|(key, _)| !key.is_empty())
+        .collect();
+
+    (!fields.is_empty()).then_some(fields)
+}
+
+// Shared ports such as 5353 are usually already bound by the OS, so the socket
+// is opened with address reuse
+fn bind(port: u16) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)).into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+// Command to start emitting `device-announced` for broadcasts received on a port
+#[tauri::command]
+#[specta::specta]
+pub async fn listen_device_announcements(
+    app: AppHandle,
+    state: State<'_, AnnouncementState>,
+    port: u16,
+) -> Result<(), String> {
+    // Held until the listener is registered, so two calls can't both bind
+    let mut listeners = state.listeners.lock().unwrap();
+    if listeners.contains_key(&port) {
+        return Ok(());
+    }
+
+    let socket = bind(port).map_err(|e| format!("Failed to listen on UDP port {}: {}", port, e))?;
+    println!("Listening for device announcements on UDP port {}", port);
+
+    let handle = tauri::async_runtime::spawn(async move {
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        loop {
+            let (length, sender) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    // Forgotten, so listening on the port can be started again
+                    eprintln!("Device announcement listener on port {} failed: {}", port, e);
+                    app.state::<AnnouncementState>()
+                        .listeners
+                        .lock()
+                        .unwrap()
+                        .remove(&port);
+                    return;
+                }
+            };
+
+            if let Some(fields) = parse_announcement(&buffer[..length]) {
+                let _ = events::DEVICE_ANNOUNCED.emit(
+                    &app,
+                    &DeviceAnnouncement {
+                        address: sender.ip().to_string(),
+                        port,
+                        fields,
+                    },
+                );
+            }
+        }
+    });
+
+    listeners.insert(port, handle);
+    Ok(())
+}
+
+// Command to stop listening for announcements on a port
+#[tauri::command]
+#[specta::specta]
+pub fn stop_device_announcements(state: State<AnnouncementState>, port: u16) {
+    if let Some(handle) = state.listeners.lock().unwrap().remove(&port) {
+        handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_json_announcements() {
+        let fields = parse_announcement(br#" {"serial": "ABC123", "port": 502} "#).unwrap();
+        assert_eq!(fields["serial"], "ABC123");
+        assert_eq!(fields["port"], 502);
+    }
+
+    #[test]
+    fn parses_key_value_announcements() {
+        let fields =
+            parse_announcement(b"serial=ABC123\nmodel = EM-1 ;firmware=1.2=beta\n=x").unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields["serial"], "ABC123");
+        assert_eq!(fields["model"], "EM-1");
+        assert_eq!(fields["firmware"], "1.2=beta");
+    }
+
+    #[test]
+    fn ignores_packets_without_fields() {
+        assert!(parse_announcement(b"").is_none());
+        assert!(parse_announcement(b"hello").is_none());
+        assert!(parse_announcement(b"[1, 2, 3]").is_none());
+        assert!(parse_announcement(&[0xff, 0xfe, b'=', b'x']).is_none());
+    }
+}
@@ -0,0 +1,14 @@
+use rand::RngCore;
+
+// Header carrying the shell's token on every request to the backend. The backend
+// receives the token through `DESKTOP_AUTH_TOKEN` and rejects requests without it,
+// so other processes on localhost can't use its API.
+pub const TOKEN_HEADER: &str = "X-Desktop-Token";
+pub const TOKEN_ENV: &str = "DESKTOP_AUTH_TOKEN";
+
+// Random per-launch token, hex encoded
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
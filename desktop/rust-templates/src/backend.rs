@@ -0,0 +1,349 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::auth;
+use crate::server::ServerState;
+use crate::tls::{self, TlsIdentity};
+
+// Timeouts, retries and circuit breaking for every request the shell makes to
+// the backend, configured under `backendClient` in `desktop.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BackendClientConfig {
+    pub timeout_ms: u64,
+    pub connect_timeout_ms: u64,
+    // Extra attempts after the first one fails
+    pub retries: u32,
+    // Base delay for exponential backoff between attempts (with jitter)
+    pub retry_delay_ms: u64,
+    // Consecutive failed requests before calls fail fast without reaching the backend
+    pub circuit_failure_threshold: u32,
+    // How long the circuit stays open before a request is let through again
+    pub circuit_reset_ms: u64,
+}
+
+impl Default for BackendClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            connect_timeout_ms: 2_000,
+            retries: 2,
+            retry_delay_ms: 200,
+            circuit_failure_threshold: 5,
+            circuit_reset_ms: 10_000,
+        }
+    }
+}
+
+// Closed while `opened_at` is None. Once open, requests fail fast until the reset
+// delay has passed; the circuit is then half-open, and a single request is let
+// through as a probe. It closes the circuit if it succeeds and reopens it if
+// not, while other requests keep failing fast.
+#[derive(Default)]
+struct Circuit {
+    failures: u32,
+    opened_at: Option<Instant>,
+    // When the probe was let through. A probe that never reports back, such as
+    // one whose caller went away, is given up on after another reset delay.
+    probe_started: Option<Instant>,
+}
+
+impl Circuit {
+    fn admit(&mut self, now: Instant, reset: Duration) -> bool {
+        let Some(opened_at) = self.opened_at else {
+            return true;
+        };
+        let probing = self
+            .probe_started
+            .is_some_and(|started| now.duration_since(started) < reset);
+        if probing || now.duration_since(opened_at) < reset {
+            return false;
+        }
+        self.probe_started = Some(now);
+        true
+    }
+
+    fn succeed(&mut self) {
+        *self = Circuit::default();
+    }
+
+    // Returns whether this failure opened the circuit
+    fn fail(&mut self, now: Instant, threshold: u32) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        self.probe_started = None;
+        if self.failures < threshold {
+            return false;
+        }
+        let opened = self.opened_at.is_none();
+        self.opened_at = Some(now);
+        opened
+    }
+}
+
+pub struct BackendClient {
+    http: reqwest::Client,
+    // No overall timeout, for long-lived responses such as event streams
+    streaming: reqwest::Client,
+    blocking: reqwest::blocking::Client,
+    config: BackendClientConfig,
+    circuit: Mutex<Circuit>,
+}
+
+impl BackendClient {
+    pub fn new(config: BackendClientConfig, identity: Option<&TlsIdentity>) -> Self {
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let connect_timeout = Duration::from_millis(config.connect_timeout_ms);
+
+        Self {
+            http: tls::client_builder(identity)
+                .timeout(timeout)
+                .connect_timeout(connect_timeout)
+                .build()
+                .unwrap_or_default(),
+            streaming: tls::client_builder(identity)
+                .connect_timeout(connect_timeout)
+                .build()
+                .unwrap_or_default(),
+            blocking: tls::blocking_client_builder(identity)
+                .timeout(timeout)
+                .connect_timeout(connect_timeout)
+                .build()
+                .unwrap_or_default(),
+            config,
+            circuit: Mutex::new(Circuit::default()),
+        }
+    }
+
+    // Client for health checks, which do their own polling and must not be
+    // short-circuited (they are how the shell finds out the backend is back)
+    pub fn blocking(&self) -> &reqwest::blocking::Client {
+        &self.blocking
+    }
+
+    fn check_circuit(&self) -> Result<(), String> {
+        let reset = Duration::from_millis(self.config.circuit_reset_ms);
+        if self.circuit.lock().unwrap().admit(Instant::now(), reset) {
+            Ok(())
+        } else {
+            Err("Backend is unavailable, not retrying yet".to_string())
+        }
+    }
+
+    // Close the circuit. Also called after the backend is (re)started so requests
+    // aren't refused while the circuit would otherwise still be open.
+    pub fn reset(&self) {
+        self.circuit.lock().unwrap().succeed();
+    }
+
+    fn record_failure(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.fail(Instant::now(), self.config.circuit_failure_threshold) {
+            eprintln!("Backend circuit opened after {} failed requests", circuit.failures);
+        }
+    }
+}
+
+// Exponential backoff with jitter, so retries from several commands don't line up
+fn retry_delay(base_ms: u64, attempt: u32) -> Duration {
+    let delay = base_ms.saturating_mul(1 << attempt.min(10));
+    Duration::from_millis(rand::thread_rng().gen_range(delay / 2..=delay))
+}
+
+// Requests that can be sent twice without side effects. Others are only retried
+// when the connection failed, so the backend can't have seen them.
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// URL for a backend API path on the port the backend was found listening on.
+// Paths must be absolute so they can't be used to reach another host.
+pub fn backend_url(state: &ServerState, path: &str) -> Result<String, String> {
+    if !path.starts_with('/') {
+        return Err(format!("Backend path must start with '/': {}", path));
+    }
+
+    let port = state.port().ok_or("Backend server is not running")?;
+    Ok(format!("{}://127.0.0.1:{}{}", state.scheme(), port, path))
+}
+
+// Request to the backend with the shell's auth token attached
+pub fn request(state: &ServerState, method: Method, path: &str) -> Result<reqwest::RequestBuilder, String> {
+    let url = backend_url(state, path)?;
+    Ok(state
+        .client
+        .http
+        .request(method, url)
+        .header(auth::TOKEN_HEADER, &state.token))
+}
+
+// Request for a long-lived response, which is never retried or timed out as a
+// whole; callers are expected to handle reconnecting themselves
+pub fn stream_request(state: &ServerState, method: Method, path: &str) -> Result<reqwest::RequestBuilder, String> {
+    let url = backend_url(state, path)?;
+    Ok(state
+        .client
+        .streaming
+        .request(method, url)
+        .header(auth::TOKEN_HEADER, &state.token))
+}
+
+// Send a request built with `request`, applying the retry and circuit breaker policy
+pub async fn send(state: &ServerState, request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+    let client = &state.client;
+    client.check_circuit()?;
+
+    let (http, request) = request.build_split();
+    let mut request = request.map_err(|e| e.to_string())?;
+    let idempotent = is_idempotent(request.method());
+    let mut attempt = 0;
+
+    loop {
+        // Streaming bodies can't be cloned, so those requests get a single attempt
+        let next = if attempt < client.config.retries {
+            request.try_clone()
+        } else {
+            None
+        };
+
+        match http.execute(request).await {
+            // A backend answering 502, 503 or 504 is as good as down
+            Ok(response) => {
+                let unavailable = is_retryable_status(response.status());
+                match next {
+                    Some(next) if unavailable && idempotent => request = next,
+                    _ => {
+                        if unavailable {
+                            client.record_failure();
+                        } else {
+                            client.reset();
+                        }
+                        return Ok(response);
+                    }
+                }
+            }
+            Err(e) => {
+                let retry = e.is_connect() || (idempotent && e.is_timeout());
+                match next {
+                    Some(next) if retry => request = next,
+                    _ => {
+                        client.record_failure();
+                        return Err(e.to_string());
+                    }
+                }
+            }
+        }
+
+        tokio::time::sleep(retry_delay(client.config.retry_delay_ms, attempt)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESET: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        for attempt in 0..4 {
+            let delay = retry_delay(200, attempt).as_millis() as u64;
+            let max = 200 << attempt;
+            assert!((max / 2..=max).contains(&delay), "{} ms", delay);
+        }
+        let capped = retry_delay(200, 50).as_millis() as u64;
+        assert!(capped <= 200 << 10);
+        assert_eq!(retry_delay(0, 3), Duration::ZERO);
+        assert!(retry_delay(u64::MAX, 10) > Duration::ZERO);
+    }
+
+    #[test]
+    fn retries_only_requests_that_are_safe_to_repeat() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+
+    #[test]
+    fn treats_gateway_errors_as_unavailable() {
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::GATEWAY_TIMEOUT));
+        assert!(!is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::OK));
+    }
+
+    #[test]
+    fn opens_after_the_failure_threshold() {
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+        assert!(!circuit.fail(now, 3));
+        assert!(!circuit.fail(now, 3));
+        assert!(circuit.admit(now, RESET));
+        assert!(circuit.fail(now, 3));
+        assert!(!circuit.admit(now, RESET));
+        assert!(!circuit.admit(now + RESET / 2, RESET));
+    }
+
+    #[test]
+    fn lets_a_single_probe_through_once_half_open() {
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+        circuit.fail(now, 1);
+
+        let later = now + RESET;
+        assert!(circuit.admit(later, RESET));
+        assert!(!circuit.admit(later, RESET));
+        assert!(!circuit.admit(later + RESET / 2, RESET));
+    }
+
+    #[test]
+    fn closes_when_the_probe_succeeds() {
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+        circuit.fail(now, 1);
+        assert!(circuit.admit(now + RESET, RESET));
+        circuit.succeed();
+        assert!(circuit.admit(now + RESET, RESET));
+        assert!(circuit.admit(now + RESET, RESET));
+        assert!(!circuit.fail(now + RESET, 2));
+    }
+
+    #[test]
+    fn reopens_when_the_probe_fails() {
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+        circuit.fail(now, 1);
+        let probe = now + RESET;
+        assert!(circuit.admit(probe, RESET));
+        assert!(!circuit.fail(probe, 1));
+        assert!(!circuit.admit(probe + RESET / 2, RESET));
+        assert!(circuit.admit(probe + RESET, RESET));
+    }
+
+    #[test]
+    fn gives_up_on_a_probe_that_never_reports_back() {
+        let now = Instant::now();
+        let mut circuit = Circuit::default();
+        circuit.fail(now, 1);
+        assert!(circuit.admit(now + RESET, RESET));
+        assert!(circuit.admit(now + RESET * 2, RESET));
+    }
+}
@@ -0,0 +1,518 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::config::ConfigState;
+use crate::data_dir;
+use crate::events;
+use crate::paths::AppPaths;
+use crate::server::{self, ServerState};
+use crate::workspace;
+
+// Backups of the backend data directory. Each backup is a zip of the directory
+// under `data/`, plus a `manifest.json` with the SHA-256 of every file so a
+// restore can be verified before anything is overwritten. The backend is stopped
+// while a backup is taken or restored so the data is consistent. Backups can also
+// be taken daily, keeping the newest `retention` of them.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const SCHEDULE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+const MANIFEST: &str = "manifest.json";
+const DATA_PREFIX: &str = "data/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BackupConfig {
+    // Defaults to `backups` in the app data directory
+    pub directory: Option<String>,
+    pub schedule_enabled: bool,
+    // Older backups are deleted once there are more than this
+    pub retention: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            schedule_enabled: false,
+            retention: 7,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BackupState {
+    busy: AtomicBool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    app_version: String,
+    created_at: u64,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BackupInfo {
+    // File name of the backup, used to restore it
+    pub id: String,
+    pub path: String,
+    pub app_version: String,
+    // Milliseconds since the Unix epoch
+    pub created_at: u64,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BackupFailed {
+    pub error: String,
+    pub scheduled: bool,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// Each workspace other than the default one keeps its backups apart, so one
+// site's backup can't be restored over another's
+pub fn backups_dir(app: &AppHandle) -> PathBuf {
+    let config = app.state::<ConfigState>().get();
+    let dir = config
+        .backup
+        .directory
+        .map(PathBuf::from)
+        .unwrap_or_else(|| app.state::<AppPaths>().backups_dir());
+    match config.workspaces.active.as_str() {
+        workspace::DEFAULT_ID => dir,
+        active => dir.join("workspaces").join(active),
+    }
+}
+
+// Copy a reader into a writer, returning the SHA-256 of what was copied
+fn copy_hashed(reader: &mut impl Read, writer: &mut impl Write) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let length = reader.read(&mut buffer)?;
+        if length == 0 {
+            return Ok(format!("{:x}", hasher.finalize()));
+        }
+        hasher.update(&buffer[..length]);
+        writer.write_all(&buffer[..length])?;
+    }
+}
+
+// Every file under a directory, relative to it with `/` separators
+fn list_files(root: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(root.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else {
+                let parts: Vec<_> = path
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect();
+                files.push(parts.join("/"));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn read_manifest(archive: &mut ZipArchive<fs::File>) -> Result<Manifest, String> {
+    let entry = archive
+        .by_name(MANIFEST)
+        .map_err(|_| "Backup is missing its manifest".to_string())?;
+    serde_json::from_reader(entry).map_err(|e| format!("Invalid backup manifest: {}", e))
+}
+
+fn backup_info(path: &Path) -> Result<BackupInfo, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let size_bytes = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Invalid backup: {}", e))?;
+    let manifest = read_manifest(&mut archive)?;
+
+    Ok(BackupInfo {
+        id: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().into_owned(),
+        app_version: manifest.app_version,
+        created_at: manifest.created_at,
+        size_bytes,
+    })
+}
+
+fn list(app: &AppHandle) -> Vec<BackupInfo> {
+    let entries = match fs::read_dir(backups_dir(app)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut backups: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == "zip").unwrap_or(false))
+        .filter_map(|path| backup_info(&path).ok())
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    backups
+}
+
+fn write_archive(app: &AppHandle, data_dir: &Path, target: &Path) -> Result<(), String> {
+    let files = if data_dir.exists() {
+        list_files(data_dir).map_err(|e| format!("Failed to read {}: {}", data_dir.display(), e))?
+    } else {
+        Vec::new()
+    };
+
+    let file = fs::File::create(target).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let mut manifest = Manifest {
+        app_version: app.package_info().version.to_string(),
+        created_at: now_ms(),
+        files: Vec::with_capacity(files.len()),
+    };
+
+    for path in files {
+        let mut source = fs::File::open(data_dir.join(&path))
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let size = source.metadata().map_err(|e| e.to_string())?.len();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size >= u32::MAX as u64);
+
+        zip.start_file(format!("{}{}", DATA_PREFIX, path), options)
+            .map_err(|e| e.to_string())?;
+        let sha256 = copy_hashed(&mut source, &mut zip)
+            .map_err(|e| format!("Failed to back up {}: {}", path, e))?;
+        manifest.files.push(ManifestFile { path, size, sha256 });
+    }
+
+    zip.start_file(MANIFEST, SimpleFileOptions::default())
+        .map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(&mut zip, &manifest).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Delete the oldest backups beyond the configured retention
+fn prune(app: &AppHandle) {
+    let retention = app.state::<ConfigState>().get().backup.retention.max(1);
+    for backup in list(app).into_iter().skip(retention) {
+        println!("Removing old backup {}", backup.id);
+        if let Err(e) = fs::remove_file(&backup.path) {
+            eprintln!("Failed to remove backup {}: {}", backup.id, e);
+        }
+    }
+}
+
+// Run `f` with the backend stopped, restarting it afterwards whatever happens
+fn with_backend_stopped<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&Path) -> Result<T, String>,
+) -> Result<T, String> {
+    let state = app.state::<ServerState>();
+    let paths = app.state::<AppPaths>();
+    server::ensure_owned(&state)?;
+
+    if app.state::<BackupState>().busy.swap(true, Ordering::SeqCst) {
+        return Err("A backup or restore is already in progress".to_string());
+    }
+    if data_dir::is_migrating(app) {
+        app.state::<BackupState>()
+            .busy
+            .store(false, Ordering::SeqCst);
+        return Err("The data directory is being moved".to_string());
+    }
+
+    server::stop_backend_server(&state);
+    let result = f(&data_dir::current_dir(&paths, &state));
+    if let Err(e) = server::start_backend_server(&paths, &state) {
+        eprintln!("Failed to restart backend: {}", e);
+    }
+
+    app.state::<BackupState>()
+        .busy
+        .store(false, Ordering::SeqCst);
+    result
+}
+
+fn create(app: &AppHandle) -> Result<BackupInfo, String> {
+    let dir = backups_dir(app);
+    fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+
+    let target = dir.join(format!("backup-{}.zip", now_ms()));
+    let partial = target.with_extension("zip.part");
+
+    with_backend_stopped(app, |data_dir| {
+        println!("Backing up {:?} to {:?}", data_dir, target);
+        write_archive(app, data_dir, &partial)
+    })
+    .and_then(|_| fs::rename(&partial, &target).map_err(|e| e.to_string()))
+    .inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })?;
+
+    prune(app);
+    let info = backup_info(&target)?;
+    let _ = events::BACKUP_CREATED.emit(app, &info);
+    Ok(info)
+}
+
+// Check every file in the backup against the manifest before anything is touched
+fn verify(archive: &mut ZipArchive<fs::File>, manifest: &Manifest) -> Result<(), String> {
+    for file in &manifest.files {
+        let mut entry = archive
+            .by_name(&format!("{}{}", DATA_PREFIX, file.path))
+            .map_err(|_| format!("Backup is missing {}", file.path))?;
+        let sha256 = copy_hashed(&mut entry, &mut io::sink()).map_err(|e| e.to_string())?;
+        if sha256 != file.sha256 {
+            return Err(format!(
+                "Backup is corrupt: {} failed verification",
+                file.path
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn extract(
+    archive: &mut ZipArchive<fs::File>,
+    manifest: &Manifest,
+    target: &Path,
+) -> Result<(), String> {
+    fs::create_dir_all(target).map_err(|e| e.to_string())?;
+
+    for file in &manifest.files {
+        let mut entry = archive
+            .by_name(&format!("{}{}", DATA_PREFIX, file.path))
+            .map_err(|e| e.to_string())?;
+        // Reject entries that would land outside the data directory
+        let relative = entry
+            .enclosed_name()
+            .and_then(|name| name.strip_prefix(DATA_PREFIX).ok().map(Path::to_path_buf))
+            .ok_or_else(|| format!("Backup contains an invalid path: {}", file.path))?;
+
+        let destination = target.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut output = fs::File::create(&destination).map_err(|e| e.to_string())?;
+        io::copy(&mut entry, &mut output)
+            .map_err(|e| format!("Failed to restore {}: {}", file.path, e))?;
+    }
+    Ok(())
+}
+
+fn restore(app: &AppHandle, id: &str) -> Result<BackupInfo, String> {
+    if id.contains(['/', '\\']) || id.starts_with('.') {
+        return Err("Invalid backup id".to_string());
+    }
+    let path = backups_dir(app).join(id);
+    let info = backup_info(&path)?;
+
+    let file = fs::File::open(&path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Invalid backup: {}", e))?;
+    let manifest = read_manifest(&mut archive)?;
+    verify(&mut archive, &manifest)?;
+
+    with_backend_stopped(app, |data_dir| {
+        println!("Restoring {} into {:?}", id, data_dir);
+
+        // Keep the current data until the restore has been written in full
+        let mut previous = data_dir.as_os_str().to_owned();
+        previous.push(".before-restore");
+        let previous = PathBuf::from(previous);
+        let _ = fs::remove_dir_all(&previous);
+        if data_dir.exists() {
+            fs::rename(data_dir, &previous)
+                .map_err(|e| format!("Failed to set aside current data: {}", e))?;
+        }
+
+        if let Err(e) = extract(&mut archive, &manifest, data_dir) {
+            let _ = fs::remove_dir_all(data_dir);
+            if previous.exists() {
+                let _ = fs::rename(&previous, data_dir);
+            }
+            return Err(e);
+        }
+
+        let _ = fs::remove_dir_all(&previous);
+        Ok(())
+    })?;
+
+    let _ = events::BACKUP_RESTORED.emit(app, &info);
+    Ok(info)
+}
+
+// Scheduled backups are taken when the newest backup is more than a day old, so
+// a machine that is off overnight still gets one shortly after starting
+pub fn start_scheduler(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(SCHEDULE_CHECK_INTERVAL);
+
+        if !app.state::<ConfigState>().get().backup.schedule_enabled {
+            continue;
+        }
+
+        let due = list(&app)
+            .first()
+            .map(|newest| {
+                now_ms().saturating_sub(newest.created_at) >= SCHEDULE_PERIOD.as_millis() as u64
+            })
+            .unwrap_or(true);
+        if !due {
+            continue;
+        }
+
+        println!("Taking scheduled backup");
+        if let Err(error) = create(&app) {
+            eprintln!("Scheduled backup failed: {}", error);
+            let _ = events::BACKUP_FAILED.emit(
+                &app,
+                &BackupFailed {
+                    error,
+                    scheduled: true,
+                },
+            );
+        }
+    });
+}
+
+pub fn is_busy(app: &AppHandle) -> bool {
+    app.state::<BackupState>().busy.load(Ordering::SeqCst)
+}
+
+// Command to back up the backend data directory now
+#[tauri::command]
+#[specta::specta]
+pub async fn create_backup(app: AppHandle) -> Result<BackupInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        create(&app).inspect_err(|error| {
+            let _ = events::BACKUP_FAILED.emit(
+                &app,
+                &BackupFailed {
+                    error: error.clone(),
+                    scheduled: false,
+                },
+            );
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to list backups, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn list_backups(app: AppHandle) -> Result<Vec<BackupInfo>, String> {
+    tauri::async_runtime::spawn_blocking(move || list(&app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to replace the backend data with a backup, after verifying it
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_backup(app: AppHandle, id: String) -> Result<BackupInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || restore(&app, &id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A backup holding `entries` as (path under `data/`, contents), with a
+    // manifest that matches them
+    fn archive(dir: &Path, entries: &[(&str, &str)]) -> (ZipArchive<fs::File>, Manifest) {
+        let path = dir.join("backup.zip");
+        let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+        let mut files = Vec::new();
+        for (name, contents) in entries {
+            zip.start_file(
+                format!("{}{}", DATA_PREFIX, name),
+                SimpleFileOptions::default(),
+            )
+            .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+            files.push(ManifestFile {
+                path: name.to_string(),
+                size: contents.len() as u64,
+                sha256: format!("{:x}", Sha256::digest(contents.as_bytes())),
+            });
+        }
+        zip.finish().unwrap();
+
+        let manifest = Manifest {
+            app_version: "1.0.0".to_string(),
+            created_at: 0,
+            files,
+        };
+        (
+            ZipArchive::new(fs::File::open(&path).unwrap()).unwrap(),
+            manifest,
+        )
+    }
+
+    #[test]
+    fn restores_verified_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (mut archive, manifest) = archive(dir, &[("db/app.sqlite", "data")]);
+        verify(&mut archive, &manifest).unwrap();
+        extract(&mut archive, &manifest, &dir.join("target")).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("target/db/app.sqlite")).unwrap(),
+            "data"
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (mut archive, mut manifest) = archive(dir, &[("config.json", "{}")]);
+        manifest.files[0].sha256 = format!("{:x}", Sha256::digest(b"other"));
+        assert!(verify(&mut archive, &manifest).is_err());
+
+        manifest.files[0].path = "missing.json".to_string();
+        assert!(verify(&mut archive, &manifest).is_err());
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_data_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (mut archive, manifest) = archive(dir, &[("../../escaped.txt", "x")]);
+        assert!(extract(&mut archive, &manifest, &dir.join("a/target")).is_err());
+        assert!(!dir.join("escaped.txt").exists());
+    }
+}
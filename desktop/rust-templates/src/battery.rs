@@ -0,0 +1,189 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::events;
+use crate::ws_bridge;
+
+// Battery and power source, so field engineers surveying on a laptop are warned
+// before it dies mid-capture and the backend can poll less often on battery.
+// Changes are emitted as `power-status-changed` and sent to the backend over the
+// WebSocket bridge as `power-status`; `battery-low` is emitted once each time the
+// charge drops to the threshold while on battery.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const LOW_BATTERY_PERCENT: u8 = 15;
+
+#[derive(Default)]
+pub struct BatteryState {
+    status: Mutex<Option<PowerStatus>>,
+}
+
+// Fields are null where the platform can't tell
+#[derive(Serialize, Clone, Default, PartialEq, specta::Type)]
+pub struct PowerStatus {
+    pub has_battery: bool,
+    pub battery_percent: Option<u8>,
+    pub charging: Option<bool>,
+    pub on_ac: Option<bool>,
+}
+
+impl PowerStatus {
+    fn is_low(&self) -> bool {
+        self.on_ac != Some(true)
+            && self
+                .battery_percent
+                .is_some_and(|percent| percent <= LOW_BATTERY_PERCENT)
+    }
+}
+
+// Linux reports power supplies under sysfs, one directory per battery or adapter
+#[cfg(target_os = "linux")]
+fn read_status() -> PowerStatus {
+    use std::fs;
+
+    let mut status = PowerStatus::default();
+    let entries = match fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return status,
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let read = |name: &str| {
+            fs::read_to_string(path.join(name))
+                .map(|value| value.trim().to_string())
+                .ok()
+        };
+
+        match read("type").as_deref() {
+            Some("Battery") if read("scope").as_deref() != Some("Device") => {
+                status.has_battery = true;
+                status.battery_percent = read("capacity").and_then(|value| value.parse().ok());
+                status.charging = read("status").map(|value| value == "Charging");
+            }
+            Some("Mains") | Some("USB") => {
+                let online = read("online").as_deref() == Some("1");
+                status.on_ac = Some(status.on_ac.unwrap_or(false) || online);
+            }
+            _ => {}
+        }
+    }
+
+    status
+}
+
+#[cfg(target_os = "windows")]
+fn read_status() -> PowerStatus {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut raw = SYSTEM_POWER_STATUS::default();
+    if unsafe { GetSystemPowerStatus(&mut raw) }.is_err() {
+        return PowerStatus::default();
+    }
+
+    // 255 means unknown for every field; battery flag 128 means no battery
+    let has_battery = raw.BatteryFlag != 128 && raw.BatteryFlag != 255;
+    PowerStatus {
+        has_battery,
+        battery_percent: (has_battery && raw.BatteryLifePercent <= 100)
+            .then_some(raw.BatteryLifePercent),
+        charging: has_battery.then_some(raw.BatteryFlag & 8 != 0),
+        on_ac: match raw.ACLineStatus {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        },
+    }
+}
+
+// macOS has no simple API outside IOKit, so parse `pmset -g batt`:
+//   Now drawing from 'AC Power'
+//    -InternalBattery-0 (id=1234)	85%; charging; 1:02 remaining present: true
+#[cfg(target_os = "macos")]
+fn read_status() -> PowerStatus {
+    use std::process::Command;
+
+    let output = match Command::new("pmset").args(["-g", "batt"]).output() {
+        Ok(output) if output.status.success() => output,
+        _ => return PowerStatus::default(),
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    let mut status = PowerStatus {
+        on_ac: text.lines().next().map(|line| line.contains("'AC Power'")),
+        ..PowerStatus::default()
+    };
+
+    if let Some(line) = text.lines().find(|line| line.contains("InternalBattery")) {
+        let fields: Vec<&str> = line
+            .rsplit('\t')
+            .next()
+            .unwrap_or_default()
+            .split(';')
+            .map(str::trim)
+            .collect();
+
+        status.has_battery = true;
+        status.battery_percent = fields
+            .first()
+            .and_then(|field| field.trim_end_matches('%').parse().ok());
+        status.charging = fields
+            .get(1)
+            .map(|state| *state == "charging" || *state == "finishing charge");
+    }
+
+    status
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn read_status() -> PowerStatus {
+    PowerStatus::default()
+}
+
+// Tell the backend the current status. Also called when the WebSocket bridge
+// (re)connects, so the backend never misses a change made while it was away.
+pub fn sync_backend(app: &AppHandle) {
+    let status = app.state::<BatteryState>().status.lock().unwrap().clone();
+    if let Some(status) = status {
+        let payload = serde_json::to_value(&status).unwrap_or_default();
+        let _ = ws_bridge::send_event(&app.state(), "power-status", payload);
+    }
+}
+
+pub fn start_monitor(app: AppHandle) {
+    thread::spawn(move || loop {
+        let status = read_status();
+
+        let previous = app
+            .state::<BatteryState>()
+            .status
+            .lock()
+            .unwrap()
+            .replace(status.clone());
+        if previous.as_ref() != Some(&status) {
+            let _ = events::POWER_STATUS_CHANGED.emit(&app, &status);
+            sync_backend(&app);
+
+            let was_low = previous.is_some_and(|previous| previous.is_low());
+            if status.is_low() && !was_low {
+                eprintln!(
+                    "Battery low: {}%",
+                    status.battery_percent.unwrap_or_default()
+                );
+                let _ = events::BATTERY_LOW.emit(&app, &status);
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+// Command to read the current battery and power source status
+#[tauri::command]
+#[specta::specta]
+pub fn get_power_status() -> PowerStatus {
+    read_status()
+}
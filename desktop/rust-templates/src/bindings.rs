@@ -0,0 +1,167 @@
+use specta_typescript::{BigIntExportBehavior, Typescript};
+use tauri_specta::{collect_commands, Builder, ErrorHandlingMode};
+
+use crate::announcements;
+use crate::backup;
+use crate::battery;
+use crate::ble;
+use crate::certificates;
+use crate::clipboard;
+use crate::connectivity;
+use crate::data_dir;
+use crate::deep_link;
+use crate::dev::{self, DevConfig};
+use crate::discovery;
+use crate::disk_space;
+use crate::downloads;
+use crate::error::ShellError;
+use crate::events::{self, ShellEvents};
+use crate::export;
+use crate::fs_watch;
+use crate::integrity;
+use crate::lan;
+use crate::locale;
+use crate::modbus;
+use crate::offline_update;
+use crate::open_file;
+use crate::permissions;
+use crate::proxy;
+use crate::serial;
+use crate::server;
+use crate::service;
+use crate::session_lock;
+use crate::sidecars;
+use crate::sse::{self, SseEvent};
+use crate::storage;
+use crate::system_info;
+use crate::telemetry;
+use crate::time_sync;
+use crate::updater;
+use crate::usb;
+use crate::version;
+use crate::workspace;
+use crate::ws_bridge;
+
+// TypeScript bindings for the shell's commands, generated with tauri-specta from
+// their Rust signatures so the frontend's calls can't drift from them. Commands
+// are registered here instead of with `generate_handler!`, so none can be left
+// out of the bindings. Dev mode writes them into the project at startup
+// (`dev.bindings`, `web/src/bindings.ts` by default), to be committed with the
+// frontend. Commands throw their error (a string, or a `ShellError`) as `invoke`
+// does, and 64-bit integers are typed as `number`.
+
+pub fn builder() -> Builder {
+    Builder::new()
+        .commands(collect_commands![
+            crate::get_logs,
+            crate::clear_logs,
+            updater::get_update_channel,
+            updater::set_update_channel,
+            updater::check_app_update,
+            updater::install_app_update,
+            updater::check_sidecar_update,
+            updater::install_sidecar_update,
+            offline_update::install_update_from_file,
+            version::get_version_info,
+            proxy::backend_request,
+            ws_bridge::ws_send,
+            ws_bridge::ws_status,
+            lan::get_lan_exposure,
+            lan::set_lan_exposure,
+            connectivity::get_network_status,
+            discovery::discover_gateways,
+            discovery::discover_gateways_stream,
+            sse::subscribe_sse,
+            sse::unsubscribe_sse,
+            announcements::listen_device_announcements,
+            announcements::stop_device_announcements,
+            serial::list_serial_ports,
+            serial::open_serial,
+            serial::write_serial,
+            serial::close_serial,
+            usb::list_usb_devices,
+            ble::ble_scan_start,
+            ble::ble_scan_stop,
+            ble::ble_connect,
+            ble::ble_disconnect,
+            ble::ble_read,
+            ble::ble_write,
+            modbus::modbus_read_registers,
+            modbus::modbus_write_register,
+            fs_watch::watch_path,
+            fs_watch::unwatch_path,
+            time_sync::check_time_sync,
+            battery::get_power_status,
+            system_info::get_system_info,
+            storage::cache_put,
+            storage::cache_get,
+            storage::cache_query,
+            storage::cache_delete,
+            export::export_data,
+            data_dir::get_data_dir,
+            data_dir::set_data_dir,
+            backup::create_backup,
+            backup::list_backups,
+            backup::restore_backup,
+            downloads::start_download,
+            downloads::pause_download,
+            downloads::resume_download,
+            downloads::cancel_download,
+            disk_space::get_disk_usage,
+            clipboard::copy_to_clipboard,
+            clipboard::copy_diagnostics_summary,
+            certificates::import_certificate,
+            certificates::list_certificates,
+            certificates::select_certificate,
+            certificates::remove_certificate,
+            integrity::get_sidecar_integrity,
+            permissions::request_path_access,
+            permissions::list_granted_paths,
+            permissions::revoke_path,
+            session_lock::report_activity,
+            session_lock::lock_session,
+            session_lock::unlock_session,
+            session_lock::get_session_lock,
+            session_lock::set_session_lock,
+            session_lock::set_lock_pin,
+            telemetry::get_telemetry_status,
+            telemetry::set_telemetry_enabled,
+            locale::get_locale,
+            locale::set_locale,
+            server::get_backend_status,
+            server::get_sandbox_status,
+            sidecars::list_sidecars,
+            sidecars::start_sidecar,
+            sidecars::stop_sidecar,
+            sidecars::restart_sidecar,
+            service::install_service,
+            service::uninstall_service,
+            service::service_status,
+            workspace::list_workspaces,
+            workspace::create_workspace,
+            workspace::rename_workspace,
+            workspace::delete_workspace,
+            workspace::switch_workspace,
+            deep_link::take_pending_deep_links,
+            open_file::take_pending_open_files,
+            events::list_event_schema
+        ])
+        .typ::<ShellEvents>()
+        .typ::<SseEvent>()
+        // Thrown rather than returned, so not otherwise exported
+        .typ::<ShellError>()
+        .error_handling(ErrorHandlingMode::Throw)
+}
+
+// Write the bindings into the project, where the frontend imports them from
+pub fn export(builder: &Builder, config: &DevConfig) {
+    let Some(bindings) = &config.bindings else {
+        return;
+    };
+    let path = dev::project_dir(config).join(bindings);
+    let language = Typescript::default().bigint(BigIntExportBehavior::Number);
+    match builder.export(language, &path) {
+        Ok(()) => println!("Wrote TypeScript bindings to {:?}", path),
+        Err(e) => eprintln!("Failed to write TypeScript bindings to {:?}: {}", path, e),
+    }
+}
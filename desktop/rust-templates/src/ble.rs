@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use btleplug::api::{
+    Central, CentralEvent, Characteristic, Manager as _, Peripheral as _, ScanFilter, WriteType,
+};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager as _, State};
+use uuid::Uuid;
+
+use crate::config::ConfigState;
+use crate::events;
+
+// Bluetooth LE for provisioning wireless sensors. Scanning emits `ble-advertisement`
+// for every device seen; reads and writes are limited to characteristics of the
+// service UUIDs listed under `ble.serviceUuids` in `desktop.json`.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BleConfig {
+    // Also used as the scan filter; an empty list scans for every device
+    pub service_uuids: Vec<String>,
+}
+
+impl BleConfig {
+    fn services(&self) -> Vec<Uuid> {
+        self.service_uuids
+            .iter()
+            .filter_map(|uuid| Uuid::parse_str(uuid).ok())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct BleState {
+    adapter: tokio::sync::Mutex<Option<Adapter>>,
+    scan: Mutex<Option<JoinHandle<()>>>,
+    // Peripherals seen while scanning, by id
+    peripherals: Mutex<HashMap<String, Peripheral>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BleAdvertisement {
+    pub id: String,
+    pub address: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+    pub services: Vec<String>,
+    pub manufacturer_data: HashMap<u16, Vec<u8>>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct BleCharacteristic {
+    pub service_uuid: String,
+    pub uuid: String,
+}
+
+// The first Bluetooth adapter, looked up once
+async fn adapter(state: &BleState) -> Result<Adapter, String> {
+    let mut adapter = state.adapter.lock().await;
+    if let Some(adapter) = adapter.as_ref() {
+        return Ok(adapter.clone());
+    }
+
+    let manager = Manager::new().await.map_err(|e| e.to_string())?;
+    let found = manager
+        .adapters()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or("No Bluetooth adapter found")?;
+    *adapter = Some(found.clone());
+    Ok(found)
+}
+
+fn peripheral(state: &BleState, id: &str) -> Result<Peripheral, String> {
+    state
+        .peripherals
+        .lock()
+        .unwrap()
+        .get(id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown Bluetooth device {}, scan for it first", id))
+}
+
+// Find a characteristic the configuration allows access to
+fn characteristic(
+    app: &AppHandle,
+    peripheral: &Peripheral,
+    service_uuid: &str,
+    uuid: &str,
+) -> Result<Characteristic, String> {
+    let service_uuid = Uuid::parse_str(service_uuid).map_err(|e| e.to_string())?;
+    let uuid = Uuid::parse_str(uuid).map_err(|e| e.to_string())?;
+
+    if !app
+        .state::<ConfigState>()
+        .get()
+        .ble
+        .services()
+        .contains(&service_uuid)
+    {
+        return Err(format!("Service {} is not allowed", service_uuid));
+    }
+
+    peripheral
+        .characteristics()
+        .into_iter()
+        .find(|characteristic| {
+            characteristic.service_uuid == service_uuid && characteristic.uuid == uuid
+        })
+        .ok_or_else(|| format!("Characteristic {} not found", uuid))
+}
+
+async fn advertisement(peripheral: &Peripheral) -> Option<BleAdvertisement> {
+    let properties = peripheral.properties().await.ok()??;
+    Some(BleAdvertisement {
+        id: peripheral.id().to_string(),
+        address: properties.address.to_string(),
+        name: properties.local_name,
+        rssi: properties.rssi,
+        services: properties.services.iter().map(Uuid::to_string).collect(),
+        manufacturer_data: properties.manufacturer_data,
+    })
+}
+
+// Command to start scanning, emitting `ble-advertisement` for each device seen
+#[tauri::command]
+#[specta::specta]
+pub async fn ble_scan_start(app: AppHandle, state: State<'_, BleState>) -> Result<(), String> {
+    let adapter = adapter(&state).await?;
+    let mut events = adapter.events().await.map_err(|e| e.to_string())?;
+    let filter = ScanFilter {
+        services: app.state::<ConfigState>().get().ble.services(),
+    };
+    adapter
+        .start_scan(filter)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let scan_app = app.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        while let Some(event) = events.next().await {
+            let id = match event {
+                CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id) => id,
+                _ => continue,
+            };
+            let Ok(peripheral) = adapter.peripheral(&id).await else {
+                continue;
+            };
+
+            if let Some(advertisement) = advertisement(&peripheral).await {
+                scan_app
+                    .state::<BleState>()
+                    .peripherals
+                    .lock()
+                    .unwrap()
+                    .insert(advertisement.id.clone(), peripheral);
+                let _ = events::BLE_ADVERTISEMENT.emit(&scan_app, &advertisement);
+            }
+        }
+    });
+
+    if let Some(previous) = state.scan.lock().unwrap().replace(handle) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+// Command to stop scanning
+#[tauri::command]
+#[specta::specta]
+pub async fn ble_scan_stop(state: State<'_, BleState>) -> Result<(), String> {
+    if let Some(handle) = state.scan.lock().unwrap().take() {
+        handle.abort();
+    }
+    adapter(&state)
+        .await?
+        .stop_scan()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to connect to a scanned device, returning the characteristics that can
+// be read and written
+#[tauri::command]
+#[specta::specta]
+pub async fn ble_connect(
+    app: AppHandle,
+    state: State<'_, BleState>,
+    id: String,
+) -> Result<Vec<BleCharacteristic>, String> {
+    let peripheral = peripheral(&state, &id)?;
+    if !peripheral.is_connected().await.map_err(|e| e.to_string())? {
+        peripheral.connect().await.map_err(|e| e.to_string())?;
+    }
+    peripheral
+        .discover_services()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let services = app.state::<ConfigState>().get().ble.services();
+    Ok(peripheral
+        .characteristics()
+        .into_iter()
+        .filter(|characteristic| services.contains(&characteristic.service_uuid))
+        .map(|characteristic| BleCharacteristic {
+            service_uuid: characteristic.service_uuid.to_string(),
+            uuid: characteristic.uuid.to_string(),
+        })
+        .collect())
+}
+
+// Command to disconnect from a device
+#[tauri::command]
+#[specta::specta]
+pub async fn ble_disconnect(state: State<'_, BleState>, id: String) -> Result<(), String> {
+    peripheral(&state, &id)?
+        .disconnect()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to read a characteristic of a connected device
+#[tauri::command]
+#[specta::specta]
+pub async fn ble_read(
+    app: AppHandle,
+    state: State<'_, BleState>,
+    id: String,
+    service_uuid: String,
+    characteristic_uuid: String,
+) -> Result<Vec<u8>, String> {
+    let peripheral = peripheral(&state, &id)?;
+    let characteristic = characteristic(&app, &peripheral, &service_uuid, &characteristic_uuid)?;
+    peripheral
+        .read(&characteristic)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to write a characteristic of a connected device
+#[tauri::command]
+#[specta::specta]
+pub async fn ble_write(
+    app: AppHandle,
+    state: State<'_, BleState>,
+    id: String,
+    service_uuid: String,
+    characteristic_uuid: String,
+    data: Vec<u8>,
+    with_response: Option<bool>,
+) -> Result<(), String> {
+    let peripheral = peripheral(&state, &id)?;
+    let characteristic = characteristic(&app, &peripheral, &service_uuid, &characteristic_uuid)?;
+    let write_type = if with_response.unwrap_or(true) {
+        WriteType::WithResponse
+    } else {
+        WriteType::WithoutResponse
+    };
+
+    peripheral
+        .write(&characteristic, &data, write_type)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,579 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use x509_parser::pem::Pem;
+
+use crate::config::ConfigState;
+use crate::events;
+use crate::locale;
+use crate::paths::AppPaths;
+use crate::permissions::{self, Access};
+
+// Client certificates for mutual TLS with EpiSensor cloud services and gateways.
+// The certificate chain is stored in `<data dir>/client-certs`; the private key is
+// stored next to it encrypted with a key held in the OS keystore (Keychain,
+// Credential Manager or the Secret Service), as some keystores can't hold a full
+// RSA key. Certificates are selected per host, and every request the shell makes
+// to another machine goes through `client_for`, which presents the selected one.
+// In portable mode the key is kept in the portable config directory instead, so
+// nothing is left in the machine's keystore.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+// Length of a certificate id, see `new_id`
+const ID_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ClientCertificateConfig {
+    // Certificate id per host: an exact host, a wildcard such as `*.episensor.com`,
+    // or `*` for every host without a more specific entry
+    pub hosts: BTreeMap<String, String>,
+    pub expiry_warning_days: i64,
+}
+
+impl Default for ClientCertificateConfig {
+    fn default() -> Self {
+        Self {
+            hosts: BTreeMap::new(),
+            expiry_warning_days: 30,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CertificateState {
+    // HTTP clients by certificate id, with "" for no certificate
+    clients: Mutex<HashMap<String, reqwest::Client>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct CertificateInfo {
+    pub id: String,
+    pub subject: String,
+    pub issuer: String,
+    // Milliseconds since the Unix epoch
+    pub not_before: i64,
+    pub not_after: i64,
+    // Negative once the certificate has expired
+    pub days_left: i64,
+    // Hosts the certificate is selected for
+    pub hosts: Vec<String>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct CertificateExpiring {
+    pub id: String,
+    pub subject: String,
+    pub not_after: i64,
+    pub days_left: i64,
+    pub expired: bool,
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+// Ids are the first 16 hex digits of the certificate's SHA-256 fingerprint
+fn new_id(leaf: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(leaf))[..ID_LENGTH].to_string()
+}
+
+// Ids end up in file names and keystore entries, so anything but the form
+// `new_id` produces is refused
+fn check_id(id: &str) -> Result<(), String> {
+    if id.len() == ID_LENGTH && id.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f')) {
+        Ok(())
+    } else {
+        Err(format!("Unknown certificate: {}", id))
+    }
+}
+
+fn keystore_entry(app: &AppHandle, id: &str) -> Result<keyring::Entry, String> {
+    check_id(id)?;
+    let service = format!("{}.client-certificates", app.config().identifier);
+    keyring::Entry::new(&service, id).map_err(|e| e.to_string())
+}
+
+fn portable_key_path(paths: &AppPaths, id: &str) -> Result<PathBuf, String> {
+    check_id(id)?;
+    Ok(paths.portable_keystore_dir().join(id))
+}
+
+fn load_wrapping_key(app: &AppHandle, id: &str) -> Result<String, String> {
+    let paths = app.state::<AppPaths>();
+    if paths.portable {
+        return fs::read_to_string(portable_key_path(&paths, id)?).map_err(|e| e.to_string());
+    }
+    keystore_entry(app, id)?
+        .get_password()
+        .map_err(|e| e.to_string())
+}
+
+fn store_wrapping_key(app: &AppHandle, id: &str, key: &str) -> Result<(), String> {
+    let paths = app.state::<AppPaths>();
+    if paths.portable {
+        fs::create_dir_all(paths.portable_keystore_dir()).map_err(|e| e.to_string())?;
+        return fs::write(portable_key_path(&paths, id)?, key).map_err(|e| e.to_string());
+    }
+    keystore_entry(app, id)?
+        .set_password(key)
+        .map_err(|e| e.to_string())
+}
+
+fn delete_wrapping_key(app: &AppHandle, id: &str) -> Result<(), String> {
+    let paths = app.state::<AppPaths>();
+    if paths.portable {
+        return fs::remove_file(portable_key_path(&paths, id)?).map_err(|e| e.to_string());
+    }
+    keystore_entry(app, id)?
+        .delete_credential()
+        .map_err(|e| e.to_string())
+}
+
+fn cert_path(paths: &AppPaths, id: &str) -> Result<PathBuf, String> {
+    check_id(id)?;
+    Ok(paths.client_certs_dir().join(format!("{}.pem", id)))
+}
+
+fn key_path(paths: &AppPaths, id: &str) -> Result<PathBuf, String> {
+    check_id(id)?;
+    Ok(paths.client_certs_dir().join(format!("{}.key", id)))
+}
+
+// PEM blocks in a file, with their labels
+fn pem_blocks(contents: &[u8]) -> Result<Vec<(String, Pem)>, String> {
+    Pem::iter_from_buffer(contents)
+        .map(|pem| {
+            pem.map(|pem| (pem.label.clone(), pem))
+                .map_err(|e| format!("Invalid PEM file: {}", e))
+        })
+        .collect()
+}
+
+fn encode_pem(label: &str, contents: &[u8]) -> String {
+    let body = base64::engine::general_purpose::STANDARD.encode(contents);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in body.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+fn describe(
+    paths: &AppPaths,
+    config: &ClientCertificateConfig,
+    id: &str,
+) -> Result<CertificateInfo, String> {
+    let contents = fs::read(cert_path(paths, id)?).map_err(|e| e.to_string())?;
+    let (_, leaf) = pem_blocks(&contents)?
+        .into_iter()
+        .find(|(label, _)| label == "CERTIFICATE")
+        .ok_or("Certificate file has no certificate")?;
+    let certificate = leaf.parse_x509().map_err(|e| e.to_string())?;
+    let validity = certificate.validity();
+
+    let not_after = validity.not_after.timestamp() * 1000;
+    Ok(CertificateInfo {
+        id: id.to_string(),
+        subject: certificate.subject().to_string(),
+        issuer: certificate.issuer().to_string(),
+        not_before: validity.not_before.timestamp() * 1000,
+        not_after,
+        days_left: (not_after - now_ms()).div_euclid(24 * 60 * 60 * 1000),
+        hosts: config
+            .hosts
+            .iter()
+            .filter(|(_, selected)| selected.as_str() == id)
+            .map(|(host, _)| host.clone())
+            .collect(),
+    })
+}
+
+fn list(app: &AppHandle) -> Vec<CertificateInfo> {
+    let paths = app.state::<AppPaths>();
+    let config = app.state::<ConfigState>().get().client_certificates;
+    let entries = match fs::read_dir(paths.client_certs_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut certificates: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == "pem").unwrap_or(false))
+        .filter_map(|path| path.file_stem().map(|id| id.to_string_lossy().into_owned()))
+        .filter_map(|id| describe(&paths, &config, &id).ok())
+        .collect();
+    certificates.sort_by(|a, b| a.subject.cmp(&b.subject));
+    certificates
+}
+
+fn encrypt_key(key_pem: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let rng = SystemRandom::new();
+    let mut wrapping_key = vec![0u8; 32];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut wrapping_key)
+        .map_err(|_| "Failed to generate key")?;
+    rng.fill(&mut nonce).map_err(|_| "Failed to generate key")?;
+
+    let key = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, &wrapping_key).map_err(|_| "Failed to generate key")?,
+    );
+    let mut sealed = key_pem.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut sealed,
+    )
+    .map_err(|_| "Failed to encrypt private key")?;
+
+    let mut stored = nonce.to_vec();
+    stored.extend_from_slice(&sealed);
+    Ok((wrapping_key, stored))
+}
+
+fn decrypt_key(app: &AppHandle, id: &str) -> Result<String, String> {
+    let wrapping_key = load_wrapping_key(app, id)
+        .map_err(|e| format!("Private key for {} is not in the keystore: {}", id, e))?;
+    let wrapping_key = base64::engine::general_purpose::STANDARD
+        .decode(wrapping_key)
+        .map_err(|e| e.to_string())?;
+
+    let stored = fs::read(key_path(&app.state::<AppPaths>(), id)?).map_err(|e| e.to_string())?;
+    if stored.len() < NONCE_LEN {
+        return Err(format!("Private key for {} is corrupt", id));
+    }
+    let (nonce, sealed) = stored.split_at(NONCE_LEN);
+
+    let key = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, &wrapping_key).map_err(|_| "Invalid keystore entry")?,
+    );
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce")?;
+    let mut sealed = sealed.to_vec();
+    let key_pem = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| format!("Private key for {} could not be decrypted", id))?;
+    String::from_utf8(key_pem.to_vec()).map_err(|e| e.to_string())
+}
+
+fn import(
+    app: &AppHandle,
+    cert_file: &Path,
+    key_file: Option<&Path>,
+) -> Result<CertificateInfo, String> {
+    let mut blocks = pem_blocks(&fs::read(cert_file).map_err(|e| e.to_string())?)?;
+    if let Some(key_file) = key_file {
+        blocks.extend(pem_blocks(&fs::read(key_file).map_err(|e| e.to_string())?)?);
+    }
+
+    if blocks
+        .iter()
+        .any(|(label, _)| label == "ENCRYPTED PRIVATE KEY")
+    {
+        return Err("Encrypted private keys aren't supported, decrypt the key first".to_string());
+    }
+    let key_pem = blocks
+        .iter()
+        .find(|(label, _)| label.ends_with("PRIVATE KEY"))
+        .map(|(label, pem)| encode_pem(label, &pem.contents))
+        .ok_or("No private key found, select the key file as well")?;
+    let chain: Vec<_> = blocks
+        .iter()
+        .filter(|(label, _)| label == "CERTIFICATE")
+        .collect();
+    let (_, leaf) = chain.first().ok_or("No certificate found")?;
+    leaf.parse_x509()
+        .map_err(|e| format!("Invalid certificate: {}", e))?;
+
+    // Check the pair is usable before storing anything
+    let chain_pem: String = chain
+        .iter()
+        .map(|(label, pem)| encode_pem(label, &pem.contents))
+        .collect();
+    reqwest::Identity::from_pem(format!("{}{}", key_pem, chain_pem).as_bytes())
+        .map_err(|e| format!("Certificate and key don't form a usable identity: {}", e))?;
+
+    let id = new_id(&leaf.contents);
+
+    let paths = app.state::<AppPaths>();
+    fs::create_dir_all(paths.client_certs_dir()).map_err(|e| e.to_string())?;
+    let (wrapping_key, sealed_key) = encrypt_key(&key_pem)?;
+    store_wrapping_key(
+        app,
+        &id,
+        &base64::engine::general_purpose::STANDARD.encode(wrapping_key),
+    )
+    .map_err(|e| format!("Failed to store the key in the keystore: {}", e))?;
+    fs::write(key_path(&paths, &id)?, sealed_key).map_err(|e| e.to_string())?;
+    fs::write(cert_path(&paths, &id)?, chain_pem).map_err(|e| e.to_string())?;
+
+    app.state::<CertificateState>()
+        .clients
+        .lock()
+        .unwrap()
+        .remove(&id);
+    println!("Imported client certificate {}", id);
+    describe(
+        &paths,
+        &app.state::<ConfigState>().get().client_certificates,
+        &id,
+    )
+}
+
+// Certificate selected for a host: exact match, then the most specific wildcard,
+// then `*`
+fn selected_for(config: &ClientCertificateConfig, host: &str) -> Option<String> {
+    if let Some(id) = config.hosts.get(host) {
+        return Some(id.clone());
+    }
+
+    config
+        .hosts
+        .iter()
+        .filter_map(|(pattern, id)| {
+            let suffix = pattern.strip_prefix("*.")?;
+            host.ends_with(&format!(".{}", suffix))
+                .then_some((suffix.len(), id))
+        })
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, id)| id.clone())
+        .or_else(|| config.hosts.get("*").cloned())
+}
+
+// HTTP client for a request to another machine, presenting the client
+// certificate selected for the URL's host, if any
+pub fn client_for(app: &AppHandle, url: &str) -> Result<reqwest::Client, String> {
+    let host = reqwest::Url::parse(url)
+        .map_err(|e| format!("Invalid URL: {}", e))?
+        .host_str()
+        .unwrap_or_default()
+        .to_string();
+    let id = selected_for(&app.state::<ConfigState>().get().client_certificates, &host)
+        .unwrap_or_default();
+
+    let state = app.state::<CertificateState>();
+    if let Some(client) = state.clients.lock().unwrap().get(&id) {
+        return Ok(client.clone());
+    }
+
+    let mut builder = reqwest::Client::builder().connect_timeout(CONNECT_TIMEOUT);
+    if !id.is_empty() {
+        let chain_pem = fs::read_to_string(cert_path(&app.state::<AppPaths>(), &id)?)
+            .map_err(|e| format!("Client certificate {} is missing: {}", id, e))?;
+        let key_pem = decrypt_key(app, &id)?;
+        let identity = reqwest::Identity::from_pem(format!("{}{}", key_pem, chain_pem).as_bytes())
+            .map_err(|e| e.to_string())?;
+        builder = builder.identity(identity);
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+
+    state.clients.lock().unwrap().insert(id, client.clone());
+    Ok(client)
+}
+
+// Warn about certificates expiring within `expiryWarningDays`, once a day
+pub fn start_monitor(app: AppHandle) {
+    thread::spawn(move || loop {
+        let warning_days = app
+            .state::<ConfigState>()
+            .get()
+            .client_certificates
+            .expiry_warning_days;
+
+        for certificate in list(&app) {
+            if certificate.days_left > warning_days {
+                continue;
+            }
+
+            let expired = certificate.not_after <= now_ms();
+            eprintln!(
+                "Client certificate {} ({}) {}",
+                certificate.id,
+                certificate.subject,
+                if expired {
+                    "has expired"
+                } else {
+                    "expires soon"
+                }
+            );
+            let _ = events::CERTIFICATE_EXPIRING.emit(
+                &app,
+                &CertificateExpiring {
+                    id: certificate.id,
+                    subject: certificate.subject,
+                    not_after: certificate.not_after,
+                    days_left: certificate.days_left,
+                    expired,
+                },
+            );
+        }
+
+        thread::sleep(EXPIRY_CHECK_INTERVAL);
+    });
+}
+
+// Command to import a client certificate from PEM files. The private key can be
+// in the certificate file or a separate one. Without a path the user is asked to
+// pick the file with a dialog; a path given by the caller needs the user's
+// permission.
+#[tauri::command]
+#[specta::specta]
+pub async fn import_certificate(
+    app: AppHandle,
+    cert_path: Option<String>,
+    key_path: Option<String>,
+) -> Result<CertificateInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let cert_file = match cert_path {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                permissions::ensure_access(&app, &path, Access::Read)?;
+                path
+            }
+            None => app
+                .dialog()
+                .file()
+                .set_title(locale::text(&app, "dialog.certificate.title", &[]))
+                .add_filter(
+                    locale::text(&app, "dialog.certificate.filter", &[]),
+                    &["pem", "crt", "cer"],
+                )
+                .blocking_pick_file()
+                .ok_or("No certificate selected")?
+                .into_path()
+                .map_err(|e| e.to_string())?,
+        };
+        let key_file = key_path.map(PathBuf::from);
+        if let Some(key_file) = &key_file {
+            permissions::ensure_access(&app, key_file, Access::Read)?;
+        }
+        import(&app, &cert_file, key_file.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to list imported client certificates
+#[tauri::command]
+#[specta::specta]
+pub fn list_certificates(app: AppHandle) -> Vec<CertificateInfo> {
+    list(&app)
+}
+
+// Command to select the certificate presented to a host, or clear the selection
+// without an id
+#[tauri::command]
+#[specta::specta]
+pub fn select_certificate(
+    app: AppHandle,
+    config: State<ConfigState>,
+    state: State<CertificateState>,
+    host: String,
+    id: Option<String>,
+) -> Result<(), String> {
+    if let Some(id) = &id {
+        if !cert_path(&app.state::<AppPaths>(), id)?.exists() {
+            return Err(format!("Unknown certificate: {}", id));
+        }
+    }
+
+    config.update(|config| match id {
+        Some(id) => {
+            config.client_certificates.hosts.insert(host, id);
+        }
+        None => {
+            config.client_certificates.hosts.remove(&host);
+        }
+    })?;
+    state.clients.lock().unwrap().clear();
+    Ok(())
+}
+
+// Command to delete a client certificate, its key and any selections of it
+#[tauri::command]
+#[specta::specta]
+pub fn remove_certificate(
+    app: AppHandle,
+    config: State<ConfigState>,
+    state: State<CertificateState>,
+    id: String,
+) -> Result<(), String> {
+    let paths = app.state::<AppPaths>();
+    if !cert_path(&paths, &id)?.exists() {
+        return Err(format!("Unknown certificate: {}", id));
+    }
+
+    config.update(|config| {
+        config
+            .client_certificates
+            .hosts
+            .retain(|_, selected| *selected != id)
+    })?;
+    state.clients.lock().unwrap().clear();
+
+    if let Err(e) = delete_wrapping_key(&app, &id) {
+        eprintln!("Failed to remove key for {} from the keystore: {}", id, e);
+    }
+    let _ = fs::remove_file(key_path(&paths, &id)?);
+    fs::remove_file(cert_path(&paths, &id)?).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_16_hex_digits() {
+        let id = new_id(b"certificate");
+        assert_eq!(id.len(), ID_LENGTH);
+        assert!(check_id(&id).is_ok());
+    }
+
+    #[test]
+    fn rejects_ids_that_are_not_from_new_id() {
+        for id in [
+            "",
+            "0123456789abcde",
+            "0123456789abcdef0",
+            "0123456789ABCDEF",
+            "../../desktop.js",
+            "0123456789abcde/",
+            "0123456789abcdeg",
+        ] {
+            assert!(check_id(id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn selects_the_most_specific_host() {
+        let config = ClientCertificateConfig {
+            hosts: BTreeMap::from([
+                ("*".to_string(), "default".to_string()),
+                ("*.example.com".to_string(), "example".to_string()),
+                ("*.site.example.com".to_string(), "site".to_string()),
+                ("meter.example.com".to_string(), "meter".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(selected_for(&config, "meter.example.com").as_deref(), Some("meter"));
+        assert_eq!(selected_for(&config, "a.site.example.com").as_deref(), Some("site"));
+        assert_eq!(selected_for(&config, "other.example.com").as_deref(), Some("example"));
+        assert_eq!(selected_for(&config, "example.com").as_deref(), Some("default"));
+        assert_eq!(selected_for(&config, "evilexample.com").as_deref(), Some("default"));
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use base64::Engine;
+use reqwest::Method;
+use serde::Deserialize;
+use sysinfo::System;
+use tauri::image::Image;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::backend;
+use crate::server::ServerState;
+use crate::version;
+
+// Clipboard writes that the webview's own clipboard API handles poorly: tables
+// are copied as TSV with an HTML alternative so they paste into Excel as cells,
+// and chart images are copied as bitmaps rather than data URLs.
+const DIAGNOSTICS_ERROR_LIMIT: usize = 20;
+
+#[derive(Deserialize, specta::Type)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ClipboardContent {
+    Text {
+        text: String,
+    },
+    Table {
+        #[serde(default)]
+        headers: Vec<String>,
+        rows: Vec<Vec<serde_json::Value>>,
+    },
+    // PNG as base64 or a `data:image/png;base64,` URL, e.g. from
+    // `canvas.toDataURL()`
+    Image {
+        png: String,
+    },
+}
+
+fn cell_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Tabs and line breaks would split the cell
+fn tsv_cell(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ")
+}
+
+fn table_tsv(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    if !headers.is_empty() {
+        lines.push(
+            headers
+                .iter()
+                .map(|header| tsv_cell(header))
+                .collect::<Vec<_>>()
+                .join("\t"),
+        );
+    }
+    for row in rows {
+        lines.push(
+            row.iter()
+                .map(|cell| tsv_cell(cell))
+                .collect::<Vec<_>>()
+                .join("\t"),
+        );
+    }
+    lines.join("\n")
+}
+
+fn table_html(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut html = String::from("<table>");
+    if !headers.is_empty() {
+        html.push_str("<tr>");
+        for header in headers {
+            let _ = write!(html, "<th>{}</th>", escape_html(header));
+        }
+        html.push_str("</tr>");
+    }
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            let _ = write!(html, "<td>{}</td>", escape_html(cell));
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn decode_png(png: &str) -> Result<Vec<u8>, String> {
+    let data = png.split_once("base64,").map_or(png, |(_, data)| data);
+    base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid image data: {}", e))
+}
+
+// Recent errors from the backend log, oldest first
+async fn recent_errors(state: &ServerState) -> Result<Vec<String>, String> {
+    let path = format!(
+        "/api/logs/entries?limit={}&level=error",
+        DIAGNOSTICS_ERROR_LIMIT
+    );
+    let request = backend::request(state, Method::GET, &path)?.timeout(Duration::from_secs(3));
+    let response = backend::send(state, request).await?;
+    if !response.status().is_success() {
+        return Err(format!("backend responded with {}", response.status()));
+    }
+
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    let entries = body
+        .get("logs")
+        .and_then(|logs| logs.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let field = |entry: &serde_json::Value, name: &str| {
+        entry
+            .get(name)
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    Ok(entries
+        .iter()
+        .rev()
+        .take(DIAGNOSTICS_ERROR_LIMIT)
+        .rev()
+        .map(|entry| {
+            let source = field(entry, "source");
+            if source.is_empty() {
+                format!("{} {}", field(entry, "timestamp"), field(entry, "message"))
+            } else {
+                format!(
+                    "{} [{}] {}",
+                    field(entry, "timestamp"),
+                    source,
+                    field(entry, "message")
+                )
+            }
+        })
+        .collect())
+}
+
+async fn diagnostics_summary(app: &AppHandle) -> String {
+    let info = version::version_info(app).await;
+    let mut summary = String::new();
+
+    let _ = writeln!(summary, "{} {}", app.package_info().name, info.app_version);
+    let _ = writeln!(
+        summary,
+        "OS: {} ({})",
+        System::long_os_version().unwrap_or_else(|| "unknown".to_string()),
+        info.platform
+    );
+    let _ = writeln!(
+        summary,
+        "Backend: {} (sidecar {})",
+        info.backend_version.as_deref().unwrap_or("unknown"),
+        info.sidecar_version
+    );
+    let _ = writeln!(
+        summary,
+        "Framework: {}, Tauri: {}",
+        info.framework_version, info.tauri_version
+    );
+    let _ = writeln!(summary, "Build: {} ({})", info.git_commit, info.build_date);
+
+    summary.push('\n');
+    match recent_errors(&app.state::<ServerState>()).await {
+        Ok(errors) if errors.is_empty() => summary.push_str("No recent errors\n"),
+        Ok(errors) => {
+            let _ = writeln!(summary, "Recent errors ({}):", errors.len());
+            for error in errors {
+                let _ = writeln!(summary, "{}", error);
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(summary, "Recent errors unavailable: {}", e);
+        }
+    }
+
+    summary
+}
+
+// Command to copy text, a table or a PNG image to the clipboard
+#[tauri::command]
+#[specta::specta]
+pub fn copy_to_clipboard(app: AppHandle, content: ClipboardContent) -> Result<(), String> {
+    let clipboard = app.clipboard();
+    match content {
+        ClipboardContent::Text { text } => clipboard.write_text(text),
+        ClipboardContent::Table { headers, rows } => {
+            let rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(cell_text).collect())
+                .collect();
+            clipboard.write_html(
+                table_html(&headers, &rows),
+                Some(table_tsv(&headers, &rows)),
+            )
+        }
+        ClipboardContent::Image { png } => {
+            let image = Image::from_bytes(&decode_png(&png)?).map_err(|e| e.to_string())?;
+            clipboard.write_image(&image)
+        }
+    }
+    .map_err(|e| e.to_string())
+}
+
+// Command to copy version details and recent backend errors in a format ready to
+// paste into a support request. Returns the copied text.
+#[tauri::command]
+#[specta::specta]
+pub async fn copy_diagnostics_summary(app: AppHandle) -> Result<String, String> {
+    let summary = diagnostics_summary(&app).await;
+    app.clipboard()
+        .write_text(summary.clone())
+        .map_err(|e| e.to_string())?;
+    Ok(summary)
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::BackendClientConfig;
+use crate::backup::BackupConfig;
+use crate::ble::BleConfig;
+use crate::certificates::ClientCertificateConfig;
+use crate::connectivity::ConnectivityConfig;
+use crate::dev::DevConfig;
+use crate::disk_space::DiskSpaceConfig;
+use crate::fs_watch::FileWatchConfig;
+use crate::import::ImportConfig;
+use crate::lan::LanExposureConfig;
+use crate::mock_backend::MockBackendConfig;
+use crate::paths::AppPaths;
+use crate::permissions::PathGrant;
+use crate::server::BackendProcessConfig;
+use crate::session_lock::SessionLockConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::time_sync::TimeSyncConfig;
+use crate::workspace::WorkspaceConfig;
+
+// Settings owned by the desktop shell (as opposed to the backend's own config),
+// persisted as JSON in the app config directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ShellConfig {
+    pub update_channel: UpdateChannel,
+    // Consecutive failed health checks after a sidecar update before rolling back
+    pub rollback_after_failures: u32,
+    pub lan_exposure: LanExposureConfig,
+    pub connectivity: ConnectivityConfig,
+    pub backend_client: BackendClientConfig,
+    pub backend_process: BackendProcessConfig,
+    pub ble: BleConfig,
+    pub file_watch: FileWatchConfig,
+    pub time_sync: TimeSyncConfig,
+    pub import: ImportConfig,
+    pub backup: BackupConfig,
+    pub disk_space: DiskSpaceConfig,
+    pub client_certificates: ClientCertificateConfig,
+    // Paths outside the app data directory the user has allowed access to
+    pub granted_paths: Vec<PathGrant>,
+    pub session_lock: SessionLockConfig,
+    pub workspaces: WorkspaceConfig,
+    pub telemetry: TelemetryConfig,
+    // Language of the shell's menus and dialogs; unset follows the system
+    pub locale: Option<String>,
+    pub dev: DevConfig,
+    pub mock_backend: MockBackendConfig,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            update_channel: UpdateChannel::default(),
+            rollback_after_failures: 3,
+            lan_exposure: LanExposureConfig::default(),
+            connectivity: ConnectivityConfig::default(),
+            backend_client: BackendClientConfig::default(),
+            backend_process: BackendProcessConfig::default(),
+            ble: BleConfig::default(),
+            file_watch: FileWatchConfig::default(),
+            time_sync: TimeSyncConfig::default(),
+            import: ImportConfig::default(),
+            backup: BackupConfig::default(),
+            disk_space: DiskSpaceConfig::default(),
+            client_certificates: ClientCertificateConfig::default(),
+            granted_paths: Vec::new(),
+            session_lock: SessionLockConfig::default(),
+            workspaces: WorkspaceConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            locale: None,
+            dev: DevConfig::default(),
+            mock_backend: MockBackendConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl UpdateChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+            UpdateChannel::Nightly => "nightly",
+        }
+    }
+}
+
+pub struct ConfigState {
+    path: PathBuf,
+    config: Mutex<ShellConfig>,
+}
+
+impl ConfigState {
+    pub fn load(paths: &AppPaths) -> Self {
+        let path = paths.config_dir.join("desktop.json");
+
+        let config = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Invalid desktop config at {:?}, using defaults: {}", path, e);
+                ShellConfig::default()
+            }),
+            Err(_) => ShellConfig::default(),
+        };
+
+        Self {
+            path,
+            config: Mutex::new(config),
+        }
+    }
+
+    pub fn get(&self) -> ShellConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    // Apply a change and write the config back to disk. The change is made to a
+    // copy, which only replaces the config once it is saved, so a failed write
+    // leaves both as they were.
+    pub fn update<F: FnOnce(&mut ShellConfig)>(&self, f: F) -> Result<ShellConfig, String> {
+        let mut config = self.config.lock().unwrap();
+        let mut updated = config.clone();
+        f(&mut updated);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let contents = serde_json::to_string_pretty(&updated).map_err(|e| e.to_string())?;
+        fs::write(&self.path, contents)
+            .map_err(|e| format!("Failed to save desktop config: {}", e))?;
+
+        *config = updated.clone();
+        Ok(updated)
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::certificates;
+use crate::config::ConfigState;
+use crate::events;
+use crate::updater;
+use crate::ws_bridge;
+
+// Watches whether the network (or the app's cloud service) is reachable, so the
+// frontend and backend can switch to offline buffering before requests start
+// failing. Changes are emitted as `network-status` to the frontend and sent to the
+// backend over the WebSocket bridge.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConnectivityConfig {
+    // Any HTTP response from this URL counts as online. When empty, the app's own
+    // update server is probed instead, and without one the network isn't probed.
+    pub probe_url: String,
+    pub interval_secs: u64,
+}
+
+impl Default for ConnectivityConfig {
+    fn default() -> Self {
+        Self {
+            probe_url: String::new(),
+            interval_secs: 15,
+        }
+    }
+}
+
+// Unknown until the first probe completes, and for good if there is nothing to probe
+#[derive(Default)]
+pub struct NetworkState {
+    online: Mutex<Option<bool>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct NetworkStatus {
+    pub online: Option<bool>,
+}
+
+impl NetworkState {
+    fn status(&self) -> NetworkStatus {
+        NetworkStatus {
+            online: self.online(),
+        }
+    }
+
+    pub fn online(&self) -> Option<bool> {
+        *self.online.lock().unwrap()
+    }
+}
+
+async fn probe(app: &AppHandle, url: &str) -> bool {
+    let client = match certificates::client_for(app, url) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Cannot probe {}: {}", url, e);
+            return false;
+        }
+    };
+    client.head(url).timeout(PROBE_TIMEOUT).send().await.is_ok()
+}
+
+// Tell the backend the current status. Also called when the WebSocket bridge
+// (re)connects, so the backend never misses a change made while it was away.
+pub fn sync_backend(app: &AppHandle) {
+    let status = app.state::<NetworkState>().status();
+    if status.online.is_some() {
+        let payload = serde_json::to_value(&status).unwrap_or_default();
+        let _ = ws_bridge::send_event(&app.state(), "network-status", payload);
+    }
+}
+
+// Probe on the configured interval for the lifetime of the app. The config is
+// re-read every time so a new probe URL takes effect without a restart.
+pub fn start_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = app.state::<ConfigState>().get().connectivity;
+            let url = match config.probe_url.as_str() {
+                "" => updater::update_origin(&app),
+                url => Some(url.to_string()),
+            };
+
+            if let Some(url) = url {
+                let online = probe(&app, &url).await;
+                let state = app.state::<NetworkState>();
+                let previous = state.online.lock().unwrap().replace(online);
+                if previous != Some(online) {
+                    println!("Network is {}", if online { "online" } else { "offline" });
+                    let _ = events::NETWORK_STATUS.emit(&app, &state.status());
+                    sync_backend(&app);
+                }
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.interval_secs.max(1))).await;
+        }
+    });
+}
+
+// Command to get the last known network status (`online` is null before the first probe)
+#[tauri::command]
+#[specta::specta]
+pub fn get_network_status(state: State<NetworkState>) -> NetworkStatus {
+    state.status()
+}
@@ -0,0 +1,330 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::backup;
+use crate::config::ConfigState;
+use crate::events;
+use crate::paths::AppPaths;
+use crate::permissions::{self, Access};
+use crate::server::{self, ServerState};
+use crate::workspace;
+
+// Where the backend keeps its data, passed to it as DATA_DIR. Customers often need
+// it off a small system drive, so it can be moved: the backend is stopped, the
+// data is copied and verified, the old copy is removed and the backend restarts
+// against the new location. Progress is emitted as `data-migration-progress`.
+
+#[derive(Default)]
+pub struct DataDirState {
+    migrating: AtomicBool,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct DataDirInfo {
+    pub path: String,
+    pub is_default: bool,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct MigrationProgress {
+    // "stopping", "copying", "verifying", "restarting" or "done"
+    pub stage: &'static str,
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+}
+
+// In portable mode the backend's data goes with the rest of the portable data,
+// since the resource directory may be read-only or shared (e.g. an AppImage)
+pub fn default_dir(paths: &AppPaths) -> PathBuf {
+    if paths.portable {
+        return paths.data_dir.join("backend");
+    }
+    paths.resource_dir.join("data")
+}
+
+// The directory the backend is currently using
+pub fn current_dir(paths: &AppPaths, state: &ServerState) -> PathBuf {
+    state
+        .process_config()
+        .data_dir
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_dir(paths))
+}
+
+pub fn is_migrating(app: &AppHandle) -> bool {
+    app.state::<DataDirState>().migrating.load(Ordering::SeqCst)
+}
+
+// Mark the data directory as changing, so backups and other moves wait for it
+pub fn begin_change(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<DataDirState>();
+    if state.migrating.swap(true, Ordering::SeqCst) {
+        return Err("The data directory is already being changed".to_string());
+    }
+    if backup::is_busy(app) {
+        state.migrating.store(false, Ordering::SeqCst);
+        return Err("A backup or restore is in progress".to_string());
+    }
+    Ok(())
+}
+
+pub fn end_change(app: &AppHandle) {
+    app.state::<DataDirState>()
+        .migrating
+        .store(false, Ordering::SeqCst);
+}
+
+fn info(app: &AppHandle) -> DataDirInfo {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+    let is_default =
+        state.process_config().data_dir.map(PathBuf::from) == workspace::default_data_dir(app);
+    DataDirInfo {
+        path: current_dir(&paths, &state).to_string_lossy().into_owned(),
+        is_default,
+    }
+}
+
+// Every file under a directory, relative to it
+fn list_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(root.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn file_hash(path: &Path) -> std::io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let length = file.read(&mut buffer)?;
+        if length == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buffer[..length]);
+    }
+}
+
+fn copy_data(app: &AppHandle, from: &Path, to: &Path) -> Result<(), String> {
+    let files = if from.exists() {
+        list_files(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?
+    } else {
+        Vec::new()
+    };
+    let total_bytes = files
+        .iter()
+        .filter_map(|file| fs::metadata(from.join(file)).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+    let progress = |stage, copied_bytes| {
+        let _ = events::DATA_MIGRATION_PROGRESS.emit(
+            app,
+            &MigrationProgress {
+                stage,
+                copied_bytes,
+                total_bytes,
+            },
+        );
+    };
+
+    let mut copied_bytes = 0;
+    for file in &files {
+        let target = to.join(file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        copied_bytes += fs::copy(from.join(file), &target)
+            .map_err(|e| format!("Failed to copy {}: {}", file.display(), e))?;
+        progress("copying", copied_bytes);
+    }
+
+    progress("verifying", copied_bytes);
+    for file in &files {
+        let original = file_hash(&from.join(file)).map_err(|e| e.to_string())?;
+        let copy = file_hash(&to.join(file)).map_err(|e| e.to_string())?;
+        if original != copy {
+            return Err(format!("Verification failed for {}", file.display()));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_target(from: &Path, to: &Path) -> Result<(), String> {
+    if !to.is_absolute() {
+        return Err("The data directory must be an absolute path".to_string());
+    }
+    if to.starts_with(from) || from.starts_with(to) {
+        return Err(
+            "The new data directory can't contain, or be inside, the current one".to_string(),
+        );
+    }
+
+    fs::create_dir_all(to).map_err(|e| format!("Cannot create {}: {}", to.display(), e))?;
+    let is_empty = fs::read_dir(to)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false);
+    if !is_empty {
+        return Err(format!("{} is not empty", to.display()));
+    }
+
+    // Catch read-only locations before the backend is stopped
+    let probe = to.join(".write-test");
+    fs::write(&probe, b"").map_err(|e| format!("Cannot write to {}: {}", to.display(), e))?;
+    let _ = fs::remove_file(probe);
+
+    Ok(())
+}
+
+fn migrate(app: &AppHandle, to: Option<PathBuf>) -> Result<DataDirInfo, String> {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+    server::ensure_owned(&state)?;
+
+    // Without a path, the data goes back to the workspace's default location
+    let to = to.or_else(|| workspace::default_data_dir(app));
+    let from = current_dir(&paths, &state);
+    let target = to.clone().unwrap_or_else(|| default_dir(&paths));
+    if target == from {
+        return Ok(info(app));
+    }
+    validate_target(&from, &target)?;
+
+    let progress = |stage| {
+        let _ = events::DATA_MIGRATION_PROGRESS.emit(
+            app,
+            &MigrationProgress {
+                stage,
+                copied_bytes: 0,
+                total_bytes: 0,
+            },
+        );
+    };
+
+    println!("Moving backend data from {:?} to {:?}", from, target);
+    progress("stopping");
+    server::stop_backend_server(&state);
+
+    let previous = state.process_config().data_dir;
+    let data_dir = to.map(|dir| dir.to_string_lossy().into_owned());
+    let config = app.state::<ConfigState>();
+    let result = copy_data(app, &from, &target)
+        .and_then(|()| {
+            config.update(|config| config.backend_process.data_dir = data_dir.clone())
+        })
+        .and_then(|_| {
+            state.set_data_dir(data_dir.clone());
+            progress("restarting");
+            server::start_backend_server(&paths, &state).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        // Leave everything as it was: the backend back on the old directory, and
+        // the config pointing at it
+        eprintln!("Failed to move backend data, restoring {:?}: {}", from, e);
+        server::stop_backend_server(&state);
+        state.set_data_dir(previous.clone());
+        if let Err(config_error) =
+            config.update(|config| config.backend_process.data_dir = previous.clone())
+        {
+            eprintln!("Failed to restore the data directory setting: {}", config_error);
+        }
+        let _ = fs::remove_dir_all(&target);
+        progress("restarting");
+        if let Err(restart_error) = server::start_backend_server(&paths, &state) {
+            eprintln!("Failed to restart backend: {}", restart_error);
+        }
+        return Err(e);
+    }
+
+    // Only removed once the backend is running from the verified copy
+    if let Err(e) = fs::remove_dir_all(&from) {
+        eprintln!("Failed to remove old data directory {:?}: {}", from, e);
+    }
+    progress("done");
+
+    Ok(info(app))
+}
+
+// Command to get the backend's data directory
+#[tauri::command]
+#[specta::specta]
+pub fn get_data_dir(app: AppHandle) -> DataDirInfo {
+    info(&app)
+}
+
+// Command to move the backend's data to a new directory, which must be empty and
+// needs the user's permission. Without a path the data moves back to the default
+// location.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_data_dir(app: AppHandle, path: Option<String>) -> Result<DataDirInfo, String> {
+    begin_change(&app)?;
+
+    let migrate_app = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let path = path.map(PathBuf::from);
+        if let Some(path) = &path {
+            permissions::ensure_access(&migrate_app, path, Access::Write)?;
+        }
+        migrate(&migrate_app, path)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+
+    end_change(&app);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_an_empty_directory_elsewhere() {
+        let temp = tempfile::tempdir().unwrap();
+        let to = temp.path().join("to");
+        assert!(validate_target(&temp.path().join("from"), &to).is_ok());
+        assert!(to.is_dir());
+    }
+
+    #[test]
+    fn rejects_relative_and_nested_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let from = temp.path().join("from");
+        assert!(validate_target(&from, Path::new("relative/data")).is_err());
+        assert!(validate_target(&from, &from.join("inner")).is_err());
+        assert!(validate_target(&from.join("inner"), &from).is_err());
+    }
+
+    #[test]
+    fn rejects_a_directory_with_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let to = temp.path().join("to");
+        fs::create_dir_all(&to).unwrap();
+        fs::write(to.join("existing.db"), b"").unwrap();
+        assert!(validate_target(&temp.path().join("from"), &to).is_err());
+    }
+}
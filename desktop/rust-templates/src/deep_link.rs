@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::events;
+use crate::session_lock;
+
+// Links such as `episensor://devices/ABC123?tab=config` from emails and the cloud
+// portal. The OS hands them to the app, or to the running instance when it is
+// already open, and each is emitted as `deep-link` with its route and query
+// parameters. A link that launched the app arrives before the frontend is
+// listening, so links are also kept until `take_pending_deep_links` is first
+// called. Links come from outside the app, so the frontend must treat the route
+// and parameters as untrusted input.
+pub const SCHEME: &str = "episensor";
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct DeepLink {
+    pub url: String,
+    // Host and path, e.g. `/devices/ABC123`
+    pub route: String,
+    pub params: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct DeepLinkState {
+    pending: Mutex<Vec<DeepLink>>,
+    collected: AtomicBool,
+}
+
+fn parse(url: &Url) -> Option<DeepLink> {
+    if url.scheme() != SCHEME {
+        return None;
+    }
+
+    let path = url.path().trim_end_matches('/');
+    let route = match url.host_str() {
+        Some(host) => format!("/{}{}", host, path),
+        None => format!("/{}", path.trim_start_matches('/')),
+    };
+
+    Some(DeepLink {
+        url: url.to_string(),
+        route,
+        params: url.query_pairs().into_owned().collect(),
+    })
+}
+
+fn open(app: &AppHandle, urls: Vec<Url>) {
+    if session_lock::is_locked(app) {
+        eprintln!("Dropped {} deep link(s) while the session is locked", urls.len());
+        return;
+    }
+
+    let state = app.state::<DeepLinkState>();
+    for link in urls.iter().filter_map(parse) {
+        println!("Opening deep link {}", link.route);
+        if !state.collected.load(Ordering::SeqCst) {
+            state.pending.lock().unwrap().push(link.clone());
+        }
+        let _ = events::DEEP_LINK.emit(app, &link);
+    }
+}
+
+// Bring the running instance forward when the app is launched again. Links in
+// the new instance's arguments are forwarded by the deep link plugin.
+pub fn focus_window(app: &AppHandle) {
+    if let Some(window) = app.webview_windows().values().next() {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    // Installers register the scheme; AppImages and development builds have to
+    // register it themselves
+    #[cfg(any(target_os = "linux", all(debug_assertions, target_os = "windows")))]
+    if let Err(e) = app.deep_link().register_all() {
+        eprintln!("Failed to register the {} URL scheme: {}", SCHEME, e);
+    }
+
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => open(app, urls),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to read the launch deep link: {}", e),
+    }
+
+    let handle = app.clone();
+    app.deep_link()
+        .on_open_url(move |event| open(&handle, event.urls()));
+}
+
+// Command to collect the links that arrived before the frontend was listening
+#[tauri::command]
+#[specta::specta]
+pub fn take_pending_deep_links(state: State<DeepLinkState>) -> Vec<DeepLink> {
+    let mut pending = state.pending.lock().unwrap();
+    state.collected.store(true, Ordering::SeqCst);
+    std::mem::take(&mut *pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(url: &str) -> Option<DeepLink> {
+        parse(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn parses_route_and_params() {
+        let link = parse_str("episensor://devices/ABC123/?tab=config&name=Meter%201").unwrap();
+        assert_eq!(link.route, "/devices/ABC123");
+        assert_eq!(link.params.get("tab").map(String::as_str), Some("config"));
+        assert_eq!(link.params.get("name").map(String::as_str), Some("Meter 1"));
+    }
+
+    #[test]
+    fn parses_links_without_a_host() {
+        assert_eq!(parse_str("episensor:/settings").unwrap().route, "/settings");
+        assert_eq!(parse_str("episensor:settings").unwrap().route, "/settings");
+        assert_eq!(
+            parse_str("episensor://dashboard").unwrap().route,
+            "/dashboard"
+        );
+    }
+
+    #[test]
+    fn rejects_other_schemes() {
+        assert!(parse_str("https://devices/ABC123").is_none());
+        assert!(parse_str("file:///etc/passwd").is_none());
+        assert!(parse_str("javascript:alert(1)").is_none());
+    }
+
+    #[test]
+    fn resolves_dot_segments_in_routes() {
+        assert_eq!(
+            parse_str("episensor://devices/../../settings")
+                .unwrap()
+                .route,
+            "/devices/settings"
+        );
+    }
+}
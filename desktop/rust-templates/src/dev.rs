@@ -0,0 +1,189 @@
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use serde::{Deserialize, Serialize};
+use tauri::utils::config::FrontendDist;
+use tauri::{AppHandle, Manager, Url};
+
+use crate::backup;
+use crate::data_dir;
+use crate::events;
+use crate::paths::AppPaths;
+use crate::server::{self, ServerState};
+
+// Dev mode, in debug builds or builds with the `dev-mode` feature, never chosen at
+// run time, so a shipped build can't be switched to it: the backend runs
+// from its TypeScript sources instead of the sidecar and is restarted when they
+// change, like nodemon, emitting `backend-restarted`. The webview loads the Vite
+// dev server, so the frontend hot reloads even in a build that embeds it. The
+// health check is relaxed, as a backend compiled on the fly is slow to come up
+// and may not check the shell's token yet.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DevConfig {
+    // Unset uses the directory above `src-tauri`
+    pub project_dir: Option<String>,
+    // Relative to the project directory
+    pub entry: String,
+    // Directories whose changes restart the backend, relative to the project
+    pub watch: Vec<String>,
+    // Passed to Node as `--import`, to run TypeScript
+    pub loader: String,
+    // Unset uses `build.devUrl` from `tauri.conf.json`
+    pub dev_server_url: Option<String>,
+    // Where the TypeScript bindings are written, relative to the project; unset
+    // doesn't write them
+    pub bindings: Option<String>,
+}
+
+impl Default for DevConfig {
+    fn default() -> Self {
+        Self {
+            project_dir: None,
+            entry: "src/index.ts".to_string(),
+            watch: vec!["src".to_string()],
+            loader: "tsx".to_string(),
+            dev_server_url: None,
+            bindings: Some("web/src/bindings.ts".to_string()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DevState {
+    watcher: Mutex<Option<Debouncer<RecommendedWatcher, RecommendedCache>>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BackendRestarted {
+    port: u16,
+}
+
+pub fn requested() -> bool {
+    cfg!(any(debug_assertions, feature = "dev-mode"))
+}
+
+pub fn project_dir(config: &DevConfig) -> PathBuf {
+    match &config.project_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    }
+}
+
+fn is_listening(url: &Url) -> bool {
+    url.socket_addrs(|| None)
+        .unwrap_or_default()
+        .iter()
+        .any(|addr| TcpStream::connect_timeout(addr, Duration::from_millis(500)).is_ok())
+}
+
+// Load the frontend from the dev server instead of the embedded build. Tauri
+// treats a URL `frontendDist` as the app's own origin, so the dev server keeps
+// access to the shell's commands. Runs before the app is built; `tauri dev`
+// already uses the dev server, and the embedded build is kept if it isn't running.
+pub fn use_dev_server(config: &mut tauri::Config, dev: &DevConfig) {
+    if tauri::is_dev() {
+        return;
+    }
+
+    let url = match &dev.dev_server_url {
+        Some(url) => match Url::parse(url) {
+            Ok(url) => url,
+            Err(e) => {
+                eprintln!("Invalid devServerUrl {}: {}", url, e);
+                return;
+            }
+        },
+        None => match &config.build.dev_url {
+            Some(url) => url.clone(),
+            None => return,
+        },
+    };
+
+    if !is_listening(&url) {
+        eprintln!(
+            "Dev server is not running at {}, using the built frontend",
+            url
+        );
+        return;
+    }
+    println!("Loading the frontend from {}", url);
+    config.build.frontend_dist = Some(FrontendDist::Url(url));
+}
+
+fn restart_backend(app: &AppHandle) {
+    if backup::is_busy(app) || data_dir::is_migrating(app) {
+        return;
+    }
+
+    println!("Backend sources changed, restarting");
+    let state = app.state::<ServerState>();
+    server::stop_backend_server(&state);
+    match server::start_backend_server(&app.state::<AppPaths>(), &state) {
+        Ok(port) => {
+            let _ = events::BACKEND_RESTARTED.emit(app, &BackendRestarted { port });
+        }
+        // Like nodemon, wait for the next change to try again
+        Err(e) => eprintln!("Failed to restart backend: {}", e),
+    }
+}
+
+// Restart the backend when its sources change, unless it belongs to a headless
+// shell
+pub fn watch_backend(app: &AppHandle) {
+    let state = app.state::<ServerState>();
+    let Some(config) = state.dev_config() else {
+        return;
+    };
+    if server::ensure_owned(&state).is_err() {
+        return;
+    }
+
+    let handle = app.clone();
+    let debouncer = new_debouncer(
+        DEBOUNCE,
+        None,
+        move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                if events
+                    .iter()
+                    .any(|event| !matches!(event.kind, EventKind::Access(_)))
+                {
+                    restart_backend(&handle);
+                }
+            }
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("Error watching backend sources: {}", error);
+                }
+            }
+        },
+    );
+    let mut debouncer = match debouncer {
+        Ok(debouncer) => debouncer,
+        Err(e) => {
+            eprintln!("Failed to watch backend sources: {}", e);
+            return;
+        }
+    };
+
+    let project_dir = project_dir(config);
+    for dir in &config.watch {
+        let dir = project_dir.join(dir);
+        match debouncer.watch(&dir, RecursiveMode::Recursive) {
+            Ok(()) => println!("Watching {:?} for backend changes", dir),
+            Err(e) => eprintln!("Cannot watch {:?}: {}", dir, e),
+        }
+    }
+    *app.state::<DevState>().watcher.lock().unwrap() = Some(debouncer);
+}
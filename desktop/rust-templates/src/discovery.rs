@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::events;
+
+// mDNS browsing for EpiSensor gateways, so commissioning screens can list the
+// gateways on the network instead of asking users for IP addresses. Gateways
+// advertise their model and firmware version in TXT records.
+const GATEWAY_SERVICE_TYPES: [&str; 2] = ["_episensor._tcp.local.", "_episensor-gw._tcp.local."];
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+// Longer timeouts are cut to this, which also keeps the deadline from overflowing
+const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct Gateway {
+    pub name: String,
+    pub hostname: String,
+    pub ip: String,
+    pub port: u16,
+    pub model: Option<String>,
+    pub firmware: Option<String>,
+}
+
+impl Gateway {
+    fn from_service(info: &ServiceInfo) -> Option<Self> {
+        // Prefer IPv4, which is what users see on the gateway's own display
+        let ip = info
+            .get_addresses()
+            .iter()
+            .min_by_key(|ip| !ip.is_ipv4())?
+            .to_string();
+        let name = info
+            .get_fullname()
+            .strip_suffix(info.get_type())
+            .unwrap_or(info.get_fullname())
+            .trim_end_matches('.')
+            .to_string();
+        let property = |key: &str| {
+            info.get_property_val_str(key)
+                .map(|value| value.to_string())
+        };
+
+        Some(Self {
+            name,
+            hostname: info.get_hostname().trim_end_matches('.').to_string(),
+            ip,
+            port: info.get_port(),
+            model: property("model"),
+            firmware: property("firmware"),
+        })
+    }
+}
+
+// Browse the given service types until the timeout passes, calling `on_found`
+// once for every gateway resolved
+fn browse<F: FnMut(Gateway)>(
+    service_types: &[String],
+    timeout: Duration,
+    mut on_found: F,
+) -> Result<(), String> {
+    let daemon = ServiceDaemon::new().map_err(|e| e.to_string())?;
+    let receivers = service_types
+        .iter()
+        .map(|service_type| daemon.browse(service_type).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut seen = HashSet::new();
+
+    while Instant::now() < deadline {
+        for receiver in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    if seen.insert(info.get_fullname().to_string()) {
+                        if let Some(gateway) = Gateway::from_service(&info) {
+                            on_found(gateway);
+                        }
+                    }
+                }
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    let _ = daemon.shutdown();
+    Ok(())
+}
+
+fn gateway_service_types(service_types: Option<Vec<String>>) -> Vec<String> {
+    service_types.unwrap_or_else(|| {
+        GATEWAY_SERVICE_TYPES
+            .iter()
+            .map(|service_type| service_type.to_string())
+            .collect()
+    })
+}
+
+fn browse_timeout(timeout_ms: Option<u64>) -> Duration {
+    timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .min(MAX_TIMEOUT)
+}
+
+// Command to find gateways on the LAN, returning everything found within the timeout
+#[tauri::command]
+#[specta::specta]
+pub async fn discover_gateways(
+    timeout_ms: Option<u64>,
+    service_types: Option<Vec<String>>,
+) -> Result<Vec<Gateway>, String> {
+    let service_types = gateway_service_types(service_types);
+    let timeout = browse_timeout(timeout_ms);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut gateways = Vec::new();
+        browse(&service_types, timeout, |gateway| gateways.push(gateway))?;
+        Ok(gateways)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to find gateways on the LAN, emitting `gateway-discovered` as each one
+// resolves and `gateway-discovery-finished` once the timeout passes
+#[tauri::command]
+#[specta::specta]
+pub fn discover_gateways_stream(
+    app: AppHandle,
+    timeout_ms: Option<u64>,
+    service_types: Option<Vec<String>>,
+) {
+    let service_types = gateway_service_types(service_types);
+    let timeout = browse_timeout(timeout_ms);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = browse(&service_types, timeout, |gateway| {
+            let _ = events::GATEWAY_DISCOVERED.emit(&app, &gateway);
+        });
+        if let Err(e) = &result {
+            eprintln!("Gateway discovery failed: {}", e);
+        }
+        let _ = events::GATEWAY_DISCOVERY_FINISHED.emit(&app, &result.err());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_the_timeout() {
+        assert_eq!(browse_timeout(None), DEFAULT_TIMEOUT);
+        assert_eq!(browse_timeout(Some(5000)), Duration::from_secs(5));
+        assert_eq!(browse_timeout(Some(u64::MAX)), MAX_TIMEOUT);
+    }
+
+    #[test]
+    fn defaults_to_the_gateway_service_types() {
+        assert_eq!(gateway_service_types(None), GATEWAY_SERVICE_TYPES.to_vec());
+        assert_eq!(
+            gateway_service_types(Some(vec!["_http._tcp.local.".to_string()])),
+            vec!["_http._tcp.local."]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::Disks;
+use tauri::{AppHandle, Manager};
+
+use crate::backup;
+use crate::config::ConfigState;
+use crate::data_dir;
+use crate::events;
+use crate::paths::AppPaths;
+use crate::server::ServerState;
+
+// Free space on the volumes holding the backend's data, logs and backups, so a
+// long-running install warns before it fills the disk rather than failing
+// silently. `disk-space-low` is emitted each time a volume drops to a worse
+// level; it can fire again once the volume has recovered.
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DiskSpaceConfig {
+    // Percent of the volume that must stay free
+    pub warning_free_percent: f64,
+    pub critical_free_percent: f64,
+}
+
+impl Default for DiskSpaceConfig {
+    fn default() -> Self {
+        Self {
+            warning_free_percent: 10.0,
+            critical_free_percent: 5.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DiskSpaceState {
+    // Last level reported per mount point
+    levels: Mutex<HashMap<String, SpaceLevel>>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum SpaceLevel {
+    #[default]
+    Ok,
+    Warning,
+    Critical,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct VolumeStatus {
+    pub mount_point: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    pub free_percent: f64,
+    pub level: SpaceLevel,
+    // Which of "data", "logs" and "backups" are on this volume
+    pub used_for: Vec<&'static str>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct DiskUsage {
+    // Backend data, not counting logs
+    pub data_bytes: u64,
+    pub logs_bytes: u64,
+    pub backups_bytes: u64,
+    pub volumes: Vec<VolumeStatus>,
+}
+
+// The logs are the backend's own, in its data directory, and the shell's, which
+// include the backend's captured output
+fn monitored_dirs(app: &AppHandle) -> Vec<(&'static str, PathBuf)> {
+    let paths = app.state::<AppPaths>();
+    let data = data_dir::current_dir(&paths, &app.state::<ServerState>());
+    let mut dirs = vec![("logs", data.join("logs"))];
+    if !paths.log_dir.starts_with(&data) {
+        dirs.push(("logs", paths.log_dir.clone()));
+    }
+    dirs.push(("data", data));
+    dirs.push(("backups", backup::backups_dir(app)));
+    dirs
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+// The nearest existing ancestor, so a directory that hasn't been created yet is
+// still placed on the right volume. Not canonicalized, as Windows would return a
+// `\\?\` path that no mount point is a prefix of.
+fn existing_path(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.exists())
+}
+
+fn level(config: &DiskSpaceConfig, free_percent: f64) -> SpaceLevel {
+    if free_percent <= config.critical_free_percent {
+        SpaceLevel::Critical
+    } else if free_percent <= config.warning_free_percent {
+        SpaceLevel::Warning
+    } else {
+        SpaceLevel::Ok
+    }
+}
+
+fn volumes(app: &AppHandle) -> Vec<VolumeStatus> {
+    let config = app.state::<ConfigState>().get().disk_space;
+    let disks = Disks::new_with_refreshed_list();
+    let mut volumes: Vec<VolumeStatus> = Vec::new();
+
+    for (name, dir) in monitored_dirs(app) {
+        let Some(path) = existing_path(&dir) else {
+            continue;
+        };
+        // The most specific mount point containing the directory
+        let Some(disk) = disks
+            .list()
+            .iter()
+            .filter(|disk| path.starts_with(disk.mount_point()))
+            .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        else {
+            continue;
+        };
+
+        let mount_point = disk.mount_point().to_string_lossy().into_owned();
+        if let Some(volume) = volumes
+            .iter_mut()
+            .find(|volume| volume.mount_point == mount_point)
+        {
+            if !volume.used_for.contains(&name) {
+                volume.used_for.push(name);
+            }
+            continue;
+        }
+
+        let free_percent = if disk.total_space() > 0 {
+            disk.available_space() as f64 * 100.0 / disk.total_space() as f64
+        } else {
+            100.0
+        };
+        volumes.push(VolumeStatus {
+            mount_point,
+            total_bytes: disk.total_space(),
+            available_bytes: disk.available_space(),
+            free_percent,
+            level: level(&config, free_percent),
+            used_for: vec![name],
+        });
+    }
+
+    volumes
+}
+
+pub fn start_monitor(app: AppHandle) {
+    thread::spawn(move || loop {
+        for volume in volumes(&app) {
+            let state = app.state::<DiskSpaceState>();
+            let previous = state
+                .levels
+                .lock()
+                .unwrap()
+                .insert(volume.mount_point.clone(), volume.level)
+                .unwrap_or_default();
+
+            if volume.level > previous {
+                eprintln!(
+                    "Disk space low on {}: {:.1}% free",
+                    volume.mount_point, volume.free_percent
+                );
+                let _ = events::DISK_SPACE_LOW.emit(&app, &volume);
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+// Command to break down disk usage by logs, data and backups, along with free
+// space on the volumes holding them. Sizing large directories can take a while,
+// so it runs off the main thread.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_disk_usage(app: AppHandle) -> Result<DiskUsage, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dirs = monitored_dirs(&app);
+        let sizes: Vec<(&str, &Path, u64)> = dirs
+            .iter()
+            .map(|(name, dir)| (*name, dir.as_path(), dir_size(dir)))
+            .collect();
+        let size = |name: &str| -> u64 {
+            sizes
+                .iter()
+                .filter(|(dir_name, _, _)| *dir_name == name)
+                .map(|(_, _, bytes)| bytes)
+                .sum()
+        };
+        let data = dirs
+            .iter()
+            .find(|(name, _)| *name == "data")
+            .map(|(_, dir)| dir.as_path());
+        // Logs inside the data directory are counted as logs only
+        let logs_in_data: u64 = sizes
+            .iter()
+            .filter(|(name, dir, _)| {
+                *name == "logs" && data.is_some_and(|data| dir.starts_with(data))
+            })
+            .map(|(_, _, bytes)| bytes)
+            .sum();
+
+        DiskUsage {
+            data_bytes: size("data").saturating_sub(logs_in_data),
+            logs_bytes: size("logs"),
+            backups_bytes: size("backups"),
+            volumes: volumes(&app),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::certificates;
+use crate::events;
+use crate::paths::AppPaths;
+use crate::permissions::{self, Access};
+
+// Downloads of large files such as firmware images and datasets, which the
+// webview handles badly. Data is written to `<destination>.part` and resumed with
+// HTTP range requests after a pause, a dropped connection or a restart of the
+// download, so a 100MB+ file is never fetched twice. The ETag or Last-Modified of
+// the file is kept in `<destination>.part.validator` and sent as `If-Range`, so
+// a file that changed on the server is fetched again whole rather than spliced
+// onto the old part; a part without one is not resumed. The finished file is checked
+// against an optional SHA-256 before it is moved into place. Only network errors,
+// stalls and 5xx responses are retried; a 4xx response ends the download. Progress is emitted
+// as `download-progress`, then `download-completed` or `download-failed`.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+// A download with no data for this long is treated as a dropped connection
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_RETRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+struct Job {
+    url: String,
+    destination: PathBuf,
+    partial: PathBuf,
+    // Where `validator` is kept, so a part left by an earlier run can be resumed
+    validator_path: PathBuf,
+    sha256: Option<String>,
+    // ETag or Last-Modified of the first response, so a resumed download fails
+    // over to a full one if the file changed on the server
+    validator: Mutex<Option<String>>,
+}
+
+struct Download {
+    job: Arc<Job>,
+    // None while paused or after a failure
+    task: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+pub struct DownloadState {
+    next_id: AtomicU64,
+    downloads: Mutex<HashMap<String, Download>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct DownloadProgress {
+    pub id: String,
+    pub received_bytes: u64,
+    // Null when the server doesn't send a length
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct DownloadCompleted {
+    pub id: String,
+    pub path: String,
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct DownloadFailed {
+    pub id: String,
+    pub error: String,
+    // Whether `resume_download` can pick up where it left off
+    pub resumable: bool,
+}
+
+fn file_name_from_url(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back().map(str::to_string))
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "download".to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+// Total size from `Content-Range: bytes 100-199/1000`
+fn range_total(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+// What to send as `If-Range`. A weak ETag can't be, so Last-Modified is used
+// instead.
+fn validator(headers: &HeaderMap) -> Option<String> {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+}
+
+// The validator of the partial file, from an earlier attempt or an earlier run
+async fn stored_validator(job: &Job) -> Option<String> {
+    if let Some(validator) = job.validator.lock().unwrap().clone() {
+        return Some(validator);
+    }
+    let validator = tokio::fs::read_to_string(&job.validator_path).await.ok()?;
+    let validator = validator.trim().to_string();
+    (!validator.is_empty()).then(|| {
+        *job.validator.lock().unwrap() = Some(validator.clone());
+        validator
+    })
+}
+
+async fn discard_partial(job: &Job) {
+    let _ = tokio::fs::remove_file(&job.partial).await;
+    let _ = tokio::fs::remove_file(&job.validator_path).await;
+    *job.validator.lock().unwrap() = None;
+}
+
+// Why a fetch failed, which decides whether it is retried and resumable
+#[derive(Debug)]
+enum FetchError {
+    // Dropped connections, stalls and 5xx responses, retried straight away
+    Network(String),
+    // 4xx and other error responses, which won't go away by asking again
+    Rejected(String),
+    // Such as a failed write, left for the user to resume once fixed
+    Local(String),
+}
+
+impl FetchError {
+    fn status(status: StatusCode) -> Self {
+        let message = format!("Server responded with {}", status);
+        if status.is_server_error() {
+            FetchError::Network(message)
+        } else {
+            FetchError::Rejected(message)
+        }
+    }
+}
+
+// Fetch whatever is missing from the partial file
+async fn fetch(app: &AppHandle, id: &str, job: &Job) -> Result<(), FetchError> {
+    let client = certificates::client_for(app, &job.url).map_err(FetchError::Local)?;
+    let mut offset = tokio::fs::metadata(&job.partial)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    let mut request = client.get(&job.url);
+    if offset > 0 {
+        match stored_validator(job).await {
+            Some(validator) => {
+                request = request
+                    .header(RANGE, format!("bytes={}-", offset))
+                    .header(IF_RANGE, validator);
+            }
+            // Without a validator there's no telling whether the part is of the
+            // same file
+            None => {
+                discard_partial(job).await;
+                offset = 0;
+            }
+        }
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| FetchError::Network(e.to_string()))?;
+    let status = response.status();
+
+    // `Content-Range: bytes */<length>`: nothing left to fetch if the part is
+    // the whole file, otherwise it isn't part of this file and the retry fetches
+    // it again whole
+    if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+        if range_total(response.headers()) == Some(offset) {
+            return Ok(());
+        }
+        discard_partial(job).await;
+        return Err(FetchError::Network(
+            "The partial download doesn't match the file on the server".to_string(),
+        ));
+    }
+    if !status.is_success() {
+        return Err(FetchError::status(status));
+    }
+
+    // A 200 means the server ignored the range, or the file changed
+    let resuming = status == StatusCode::PARTIAL_CONTENT;
+    let mut received_bytes = if resuming { offset } else { 0 };
+    let total_bytes = if resuming {
+        range_total(response.headers())
+    } else {
+        response.content_length()
+    };
+    if !resuming {
+        let validator = validator(response.headers());
+        match &validator {
+            Some(validator) => tokio::fs::write(&job.validator_path, validator)
+                .await
+                .map_err(|e| {
+                    FetchError::Local(format!(
+                        "Failed to write {}: {}",
+                        job.validator_path.display(),
+                        e
+                    ))
+                })?,
+            None => {
+                let _ = tokio::fs::remove_file(&job.validator_path).await;
+            }
+        }
+        *job.validator.lock().unwrap() = validator;
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resuming)
+        .truncate(!resuming)
+        .open(&job.partial)
+        .await
+        .map_err(|e| {
+            FetchError::Local(format!("Failed to write {}: {}", job.partial.display(), e))
+        })?;
+
+    let mut last_progress = Instant::now();
+    loop {
+        let chunk = tokio::time::timeout(STALL_TIMEOUT, response.chunk())
+            .await
+            .map_err(|_| FetchError::Network("Download stalled".to_string()))?
+            .map_err(|e| FetchError::Network(e.to_string()))?;
+        let chunk = match chunk {
+            Some(chunk) => chunk,
+            None => break,
+        };
+
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| FetchError::Local(e.to_string()))?;
+        received_bytes += chunk.len() as u64;
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            let _ = events::DOWNLOAD_PROGRESS.emit(
+                app,
+                &DownloadProgress {
+                    id: id.to_string(),
+                    received_bytes,
+                    total_bytes,
+                },
+            );
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| FetchError::Local(e.to_string()))?;
+
+    if let Some(total_bytes) = total_bytes {
+        if received_bytes < total_bytes {
+            return Err(FetchError::Network(
+                "Connection closed before the download finished".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn file_sha256(path: &Path) -> Result<String, String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let length = file.read(&mut buffer).await.map_err(|e| e.to_string())?;
+        if length == 0 {
+            return Ok(format!("{:x}", hasher.finalize()));
+        }
+        hasher.update(&buffer[..length]);
+    }
+}
+
+// Verify the finished partial file and move it into place. Failures here aren't
+// resumable, so the partial file is removed.
+async fn finish(job: &Job) -> Result<u64, String> {
+    if let Some(expected) = &job.sha256 {
+        let actual = file_sha256(&job.partial).await?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            discard_partial(job).await;
+            return Err(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected, actual
+            ));
+        }
+    }
+
+    tokio::fs::rename(&job.partial, &job.destination)
+        .await
+        .map_err(|e| e.to_string())?;
+    let _ = tokio::fs::remove_file(&job.validator_path).await;
+    tokio::fs::metadata(&job.destination)
+        .await
+        .map(|metadata| metadata.len())
+        .map_err(|e| e.to_string())
+}
+
+async fn run(app: AppHandle, id: String, job: Arc<Job>) {
+    let mut attempt = 0;
+    let fetched = loop {
+        match fetch(&app, &id, &job).await {
+            Ok(()) => break Ok(()),
+            Err(FetchError::Network(e)) if attempt < MAX_RETRIES => {
+                attempt += 1;
+                eprintln!(
+                    "Download {} interrupted ({}), retrying ({}/{})",
+                    id, e, attempt, MAX_RETRIES
+                );
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    // A rejected request isn't resumable, so its partial file is of no use
+    let result = match fetched {
+        Ok(()) => finish(&job).await.map_err(|e| (e, false)),
+        Err(FetchError::Rejected(e)) => {
+            discard_partial(&job).await;
+            Err((e, false))
+        }
+        Err(FetchError::Network(e) | FetchError::Local(e)) => Err((e, true)),
+    };
+
+    let state = app.state::<DownloadState>();
+    let mut downloads = state.downloads.lock().unwrap();
+    match result {
+        Ok(bytes) => {
+            downloads.remove(&id);
+            println!("Downloaded {} ({} bytes)", job.url, bytes);
+            let _ = events::DOWNLOAD_COMPLETED.emit(
+                &app,
+                &DownloadCompleted {
+                    id,
+                    path: job.destination.to_string_lossy().into_owned(),
+                    bytes,
+                },
+            );
+        }
+        Err((error, resumable)) => {
+            if resumable {
+                if let Some(download) = downloads.get_mut(&id) {
+                    download.task = None;
+                }
+            } else {
+                downloads.remove(&id);
+            }
+            eprintln!("Download {} failed: {}", id, error);
+            let _ = events::DOWNLOAD_FAILED.emit(
+                &app,
+                &DownloadFailed {
+                    id,
+                    error,
+                    resumable,
+                },
+            );
+        }
+    }
+}
+
+// Command to start downloading a file. Without a destination the file is saved to
+// `downloads` in the app data directory. A destination outside the app data
+// directory needs the user's permission. Returns the download id.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_download(
+    app: AppHandle,
+    paths: State<'_, AppPaths>,
+    state: State<'_, DownloadState>,
+    url: String,
+    destination: Option<String>,
+    sha256: Option<String>,
+) -> Result<String, String> {
+    reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+
+    let destination = match destination {
+        Some(destination) => {
+            let destination = PathBuf::from(destination);
+            let check_app = app.clone();
+            let check_path = destination.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                permissions::ensure_access(&check_app, &check_path, Access::Write)
+            })
+            .await
+            .map_err(|e| e.to_string())??;
+            destination
+        }
+        None => paths.downloads_dir().join(file_name_from_url(&url)),
+    };
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let mut downloads = state.downloads.lock().unwrap();
+    if downloads
+        .values()
+        .any(|download| download.job.destination == destination)
+    {
+        return Err(format!(
+            "{} is already being downloaded",
+            destination.display()
+        ));
+    }
+
+    let id = format!(
+        "download-{}",
+        state.next_id.fetch_add(1, Ordering::SeqCst) + 1
+    );
+    let partial = with_suffix(&destination, ".part");
+    let job = Arc::new(Job {
+        url,
+        validator_path: with_suffix(&partial, ".validator"),
+        partial,
+        destination,
+        sha256,
+        validator: Mutex::new(None),
+    });
+
+    // A partial file left by an earlier attempt is resumed, like any other
+    println!("Downloading {} to {:?}", job.url, job.destination);
+    let task = tauri::async_runtime::spawn(run(app.clone(), id.clone(), job.clone()));
+    downloads.insert(
+        id.clone(),
+        Download {
+            job,
+            task: Some(task),
+        },
+    );
+
+    Ok(id)
+}
+
+// Command to pause a download, keeping what has been downloaded so far
+#[tauri::command]
+#[specta::specta]
+pub fn pause_download(state: State<DownloadState>, id: String) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    let download = downloads.get_mut(&id).ok_or("Unknown download")?;
+    if let Some(task) = download.task.take() {
+        task.abort();
+    }
+    Ok(())
+}
+
+// Command to resume a paused or failed download
+#[tauri::command]
+#[specta::specta]
+pub fn resume_download(
+    app: AppHandle,
+    state: State<DownloadState>,
+    id: String,
+) -> Result<(), String> {
+    let mut downloads = state.downloads.lock().unwrap();
+    let download = downloads.get_mut(&id).ok_or("Unknown download")?;
+    if download.task.is_none() {
+        download.task = Some(tauri::async_runtime::spawn(run(
+            app.clone(),
+            id.clone(),
+            download.job.clone(),
+        )));
+    }
+    Ok(())
+}
+
+// Command to cancel a download and delete what was downloaded
+#[tauri::command]
+#[specta::specta]
+pub fn cancel_download(state: State<DownloadState>, id: String) -> Result<(), String> {
+    let download = state
+        .downloads
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or("Unknown download")?;
+    if let Some(task) = download.task {
+        task.abort();
+    }
+    let _ = std::fs::remove_file(&download.job.partial);
+    let _ = std::fs::remove_file(&download.job.validator_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(entries: &[(reqwest::header::HeaderName, &'static str)]) -> HeaderMap {
+        entries
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn reads_the_total_from_content_range() {
+        assert_eq!(
+            range_total(&headers(&[(CONTENT_RANGE, "bytes 100-199/1000")])),
+            Some(1000)
+        );
+        assert_eq!(
+            range_total(&headers(&[(CONTENT_RANGE, "bytes */2048")])),
+            Some(2048)
+        );
+        assert_eq!(
+            range_total(&headers(&[(CONTENT_RANGE, "bytes 0-99/*")])),
+            None
+        );
+        assert_eq!(range_total(&headers(&[])), None);
+    }
+
+    #[test]
+    fn prefers_a_strong_etag_as_validator() {
+        let modified = "Wed, 21 Oct 2026 07:28:00 GMT";
+        assert_eq!(
+            validator(&headers(&[(ETAG, "\"v2\""), (LAST_MODIFIED, modified)])),
+            Some("\"v2\"".to_string())
+        );
+        assert_eq!(
+            validator(&headers(&[(ETAG, "W/\"v2\""), (LAST_MODIFIED, modified)])),
+            Some(modified.to_string())
+        );
+        assert_eq!(validator(&headers(&[(ETAG, "W/\"v2\"")])), None);
+        assert_eq!(validator(&headers(&[])), None);
+    }
+
+    #[test]
+    fn names_files_after_the_last_path_segment() {
+        assert_eq!(
+            file_name_from_url("https://example.com/firmware/em-1.bin?token=x"),
+            "em-1.bin"
+        );
+        assert_eq!(
+            file_name_from_url("https://example.com/files/data%20set.csv"),
+            "data%20set.csv"
+        );
+        assert_eq!(file_name_from_url("https://example.com/"), "download");
+        assert_eq!(file_name_from_url("https://example.com"), "download");
+        assert_eq!(file_name_from_url("not a url"), "download");
+    }
+
+    #[test]
+    fn retries_only_server_errors() {
+        assert!(matches!(
+            FetchError::status(StatusCode::SERVICE_UNAVAILABLE),
+            FetchError::Network(_)
+        ));
+        assert!(matches!(
+            FetchError::status(StatusCode::INTERNAL_SERVER_ERROR),
+            FetchError::Network(_)
+        ));
+        assert!(matches!(
+            FetchError::status(StatusCode::NOT_FOUND),
+            FetchError::Rejected(_)
+        ));
+        assert!(matches!(
+            FetchError::status(StatusCode::FORBIDDEN),
+            FetchError::Rejected(_)
+        ));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+// Errors from starting the backend and other sidecars and from the log commands,
+// thrown to the frontend as `{ kind, code, message, retryable, context }` so it
+// can branch on `code` (a missing backend binary, a port in use, a health check
+// timeout) rather than on the message. `kind` groups codes for callers that only
+// care roughly what went wrong, `retryable` says whether trying the same thing
+// again may succeed and `context` carries details such as the port or binary.
+// Callers returning `Result<_, String>` can still use `?` on these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    // Something the backend needs isn't there
+    NotFound,
+    // Refused by a check, such as the sidecar's signature
+    Rejected,
+    // Something else holds what was asked for
+    Conflict,
+    Timeout,
+    // The backend isn't running or can't be reached
+    Unavailable,
+    // The backend answered, but with an error
+    Backend,
+    Io,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // No sidecar, Node script or build for this platform
+    BackendMissing,
+    // The sidecar failed its integrity check
+    SidecarRejected,
+    SpawnFailed,
+    // Another process is listening on the backend's port
+    PortInUse,
+    HealthCheckTimeout,
+    // The backend exited before reporting healthy
+    BackendExited,
+    // The backend listens beyond loopback
+    BackendExposed,
+    // The backend belongs to a headless shell
+    BackendNotOwned,
+    BackendUnavailable,
+    // No sidecar is declared with that name
+    UnknownSidecar,
+    // The backend answered with an error status
+    BackendStatus,
+    InvalidResponse,
+    Io,
+}
+
+impl ErrorCode {
+    pub fn kind(self) -> ErrorKind {
+        match self {
+            ErrorCode::BackendMissing | ErrorCode::UnknownSidecar => ErrorKind::NotFound,
+            ErrorCode::SidecarRejected | ErrorCode::BackendExposed => ErrorKind::Rejected,
+            ErrorCode::PortInUse | ErrorCode::BackendNotOwned => ErrorKind::Conflict,
+            ErrorCode::HealthCheckTimeout => ErrorKind::Timeout,
+            ErrorCode::BackendExited | ErrorCode::BackendUnavailable => ErrorKind::Unavailable,
+            ErrorCode::BackendStatus | ErrorCode::InvalidResponse => ErrorKind::Backend,
+            ErrorCode::SpawnFailed | ErrorCode::Io => ErrorKind::Io,
+        }
+    }
+
+    // Whether the same call may succeed later without anything being changed
+    fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::HealthCheckTimeout | ErrorCode::BackendUnavailable
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct ShellError {
+    pub kind: ErrorKind,
+    pub code: ErrorCode,
+    pub message: String,
+    pub retryable: bool,
+    pub context: BTreeMap<String, String>,
+}
+
+impl ShellError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            kind: code.kind(),
+            code,
+            message: message.into(),
+            retryable: code.retryable(),
+            context: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    // An error status from the backend, retryable when the backend is
+    // overloaded or failing rather than refusing the request
+    pub fn status(status: reqwest::StatusCode, message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::BackendStatus, message)
+            .with("status", status.as_u16())
+            .retryable(status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ShellError {}
+
+impl From<ShellError> for String {
+    fn from(error: ShellError) -> Self {
+        error.message
+    }
+}
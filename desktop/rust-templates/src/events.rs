@@ -0,0 +1,258 @@
+use std::marker::PhantomData;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::announcements::DeviceAnnouncement;
+use crate::backup::{BackupFailed, BackupInfo};
+use crate::battery::PowerStatus;
+use crate::ble::BleAdvertisement;
+use crate::certificates::CertificateExpiring;
+use crate::connectivity::NetworkStatus;
+use crate::data_dir::MigrationProgress;
+use crate::deep_link::DeepLink;
+use crate::dev::BackendRestarted;
+use crate::error::ShellError;
+use crate::discovery::Gateway;
+use crate::disk_space::VolumeStatus;
+use crate::downloads::{DownloadCompleted, DownloadFailed, DownloadProgress};
+use crate::export::ExportProgress;
+use crate::fs_watch::FsChange;
+use crate::import::{ImportCompleted, ImportFailed, ImportProgress};
+use crate::integrity::IntegrityFailure;
+use crate::lan::LanStatus;
+use crate::locale::LocaleInfo;
+use crate::offline_update::OfflineUpdate;
+use crate::open_file::OpenedFile;
+use crate::power::SessionResumed;
+use crate::rollback::RollbackEvent;
+use crate::serial::{SerialClosed, SerialData};
+use crate::server::BackendReady;
+use crate::session_lock::{SessionLocked, SessionUnlocked};
+use crate::sidecars::SidecarStatus;
+use crate::sse::SseStatus;
+use crate::time_sync::TimeSyncStatus;
+use crate::updater::SidecarUpdate;
+use crate::usb::UsbDevice;
+use crate::workspace::WorkspaceInfo;
+use crate::ws_bridge::BridgeStatus;
+
+// Every event the shell emits, with its payload type, in one place. Modules emit
+// through the constants here (`events::BACKUP_CREATED.emit(&app, &info)`), so an
+// event can't be sent with the wrong payload, and the same list is exported as
+// `ShellEvents` in the bindings and returned by `list_event_schema`.
+//
+// Naming: shell events are kebab-case and start with what they concern, followed
+// by what happened in the past tense (`backup-created`, `usb-device-detached`),
+// or `-progress`, `-status` or `-changed` for ongoing state. Each belongs to a
+// namespace grouping related events for the schema. Names containing `:` are
+// reserved for events relayed from elsewhere under a prefix: `backend:<name>`
+// from the WebSocket bridge and `sse:<name>` from SSE subscriptions, whose
+// payloads are the backend's own.
+//
+// `SCHEMA_VERSION` goes up whenever an event is renamed or removed or a payload
+// changes incompatibly, so apps can check they were built against the schema
+// the shell emits. Adding events or optional fields doesn't change it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// An event name tied to the type of its payload
+pub struct Event<T> {
+    pub name: &'static str,
+    payload: PhantomData<fn(&T)>,
+}
+
+impl<T: Serialize> Event<T> {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            payload: PhantomData,
+        }
+    }
+
+    // Emit to every window
+    pub fn emit(&self, app: &AppHandle, payload: &T) -> tauri::Result<()> {
+        app.emit(self.name, payload)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct EventInfo {
+    pub name: &'static str,
+    pub namespace: &'static str,
+    // The payload's type, as named in the bindings
+    pub payload: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize, specta::Type)]
+pub struct EventSchema {
+    pub version: u32,
+    pub events: Vec<EventInfo>,
+}
+
+// Declares the constants, `ShellEvents` and the schema from one list
+macro_rules! events {
+    ($($namespace:literal {
+        $($constant:ident: $payload:ty = $name:literal, $description:literal;)*
+    })*) => {
+        $($(pub const $constant: Event<$payload> = Event::new($name);)*)*
+
+        // Payloads of the shell's events by name, exported as `ShellEvents`:
+        //
+        //     listen<ShellEvents["backup-created"]>("backup-created", ...)
+        #[derive(Serialize, specta::Type)]
+        #[allow(non_snake_case)]
+        pub struct ShellEvents {
+            $($(#[serde(rename = $name)] $constant: $payload,)*)*
+        }
+
+        fn events() -> Vec<EventInfo> {
+            vec![$($(EventInfo {
+                name: $name,
+                namespace: $namespace,
+                payload: stringify!($payload),
+                description: $description,
+            },)*)*]
+        }
+    };
+}
+
+events! {
+    "backend" {
+        BACKEND_READY: BackendReady = "backend-ready",
+            "The backend started at launch is healthy and ready for requests";
+        BACKEND_FAILED: ShellError = "backend-failed",
+            "The backend couldn't be started at launch";
+        BACKEND_RESTARTED: BackendRestarted = "backend-restarted",
+            "The backend was restarted after its sources changed, in dev mode";
+        SIDECAR_INTEGRITY_FAILED: IntegrityFailure = "sidecar-integrity-failed",
+            "The sidecar failed its integrity check and wasn't started";
+        WS_BRIDGE_STATUS: BridgeStatus = "ws-bridge-status",
+            "The WebSocket bridge to the backend connected or disconnected";
+        SSE_STATUS: SseStatus = "sse-status",
+            "An SSE subscription connected or disconnected";
+        SIDECAR_STATUS: SidecarStatus = "sidecar-status",
+            "One of the app's other sidecars started, failed or exited";
+    }
+    "update" {
+        SIDECAR_UPDATED: SidecarUpdate = "sidecar-updated",
+            "A sidecar-only update was installed";
+        OFFLINE_UPDATE_INSTALLED: OfflineUpdate = "offline-update-installed",
+            "An update was installed from a file";
+        UPDATE_ROLLED_BACK: RollbackEvent = "update-rolled-back",
+            "A sidecar update failed to start and the previous sidecar was restored";
+    }
+    "data" {
+        DATA_MIGRATION_PROGRESS: MigrationProgress = "data-migration-progress",
+            "Progress moving the backend's data directory";
+        WORKSPACE_CHANGED: WorkspaceInfo = "workspace-changed",
+            "The current workspace was switched";
+        BACKUP_CREATED: BackupInfo = "backup-created",
+            "A backup was created";
+        BACKUP_FAILED: BackupFailed = "backup-failed",
+            "A backup, possibly a scheduled one, failed";
+        BACKUP_RESTORED: BackupInfo = "backup-restored",
+            "A backup was restored";
+        EXPORT_PROGRESS: ExportProgress = "export-progress",
+            "Progress writing an export to disk";
+        IMPORT_PROGRESS: ImportProgress = "import-progress",
+            "Progress uploading a dropped file to the backend";
+        IMPORT_COMPLETED: ImportCompleted = "import-completed",
+            "A dropped file was imported";
+        IMPORT_FAILED: ImportFailed = "import-failed",
+            "A dropped file couldn't be imported";
+    }
+    "download" {
+        DOWNLOAD_PROGRESS: DownloadProgress = "download-progress",
+            "Progress of a download";
+        DOWNLOAD_COMPLETED: DownloadCompleted = "download-completed",
+            "A download finished";
+        DOWNLOAD_FAILED: DownloadFailed = "download-failed",
+            "A download failed, possibly resumably";
+    }
+    "device" {
+        DEVICE_ANNOUNCED: DeviceAnnouncement = "device-announced",
+            "A device announced itself on the local network";
+        GATEWAY_DISCOVERED: Gateway = "gateway-discovered",
+            "Streaming discovery found a gateway";
+        GATEWAY_DISCOVERY_FINISHED: Option<String> = "gateway-discovery-finished",
+            "Streaming discovery finished, with the error if it failed";
+        SERIAL_DATA: SerialData = "serial-data",
+            "Data was read from an open serial port";
+        SERIAL_CLOSED: SerialClosed = "serial-closed",
+            "A serial port was closed, with the error if it failed";
+        USB_DEVICE_ATTACHED: UsbDevice = "usb-device-attached",
+            "A USB device was plugged in";
+        USB_DEVICE_DETACHED: UsbDevice = "usb-device-detached",
+            "A USB device was removed";
+        BLE_ADVERTISEMENT: BleAdvertisement = "ble-advertisement",
+            "A Bluetooth LE advertisement was received while scanning";
+    }
+    "network" {
+        NETWORK_STATUS: NetworkStatus = "network-status",
+            "The machine went online or offline";
+        LAN_EXPOSURE_CHANGED: LanStatus = "lan-exposure-changed",
+            "The backend was exposed to or hidden from the LAN";
+    }
+    "system" {
+        POWER_STATUS_CHANGED: PowerStatus = "power-status-changed",
+            "The battery level or power source changed";
+        BATTERY_LOW: PowerStatus = "battery-low",
+            "The battery fell below the low threshold";
+        SESSION_RESUMED: SessionResumed = "session-resumed",
+            "The machine woke from sleep";
+        DISK_SPACE_LOW: VolumeStatus = "disk-space-low",
+            "A watched volume is running out of space";
+        TIME_DRIFT_WARNING: TimeSyncStatus = "time-drift-warning",
+            "The clock drifted from the time server beyond the threshold";
+        CERTIFICATE_EXPIRING: CertificateExpiring = "certificate-expiring",
+            "A client certificate expires soon";
+    }
+    "session" {
+        SESSION_LOCKED: SessionLocked = "session-locked",
+            "The session was locked";
+        SESSION_UNLOCKED: SessionUnlocked = "session-unlocked",
+            "The session was unlocked";
+    }
+    "app" {
+        DEEP_LINK: DeepLink = "deep-link",
+            "The app was opened with a deep link";
+        OPEN_FILE: OpenedFile = "open-file",
+            "The app was asked to open a file";
+        FS_CHANGED: FsChange = "fs-changed",
+            "A watched path changed";
+        LOCALE_CHANGED: LocaleInfo = "locale-changed",
+            "The shell's menus and dialogs switched language";
+    }
+}
+
+// Events relayed under a prefix, listed in the schema with a `*` for the name
+fn relayed_events() -> Vec<EventInfo> {
+    vec![
+        EventInfo {
+            name: "backend:*",
+            namespace: "backend",
+            payload: "unknown",
+            description:
+                "An event from the backend's Socket.IO server, relayed by the WebSocket bridge",
+        },
+        EventInfo {
+            name: "sse:*",
+            namespace: "backend",
+            payload: "SseEvent",
+            description: "An event from an SSE subscription, named after the subscription",
+        },
+    ]
+}
+
+// Command to list the events the shell emits, with the schema version
+#[tauri::command]
+#[specta::specta]
+pub fn list_event_schema() -> EventSchema {
+    let mut events = events();
+    events.extend(relayed_events());
+    EventSchema {
+        version: SCHEMA_VERSION,
+        events,
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::backend;
+use crate::events;
+use crate::locale;
+use crate::server::ServerState;
+
+// Exports are streamed from the backend straight to a file the user picks, rather
+// than downloaded through the webview, which copes badly with multi-hundred-MB
+// CSV/XLSX files. Progress is emitted as `export-progress`.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct ExportProgress {
+    pub path: String,
+    pub received_bytes: u64,
+    // Null when the backend doesn't send a Content-Length
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct ExportResult {
+    pub path: String,
+    pub bytes: u64,
+}
+
+async fn pick_destination(
+    app: &AppHandle,
+    file_name: Option<String>,
+) -> Result<Option<PathBuf>, String> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut dialog = app
+            .dialog()
+            .file()
+            .set_title(locale::text(&app, "dialog.export.title", &[]));
+        if let Some(file_name) = &file_name {
+            if let Some(extension) = Path::new(file_name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+            {
+                dialog = dialog.add_filter(extension.to_uppercase(), &[extension]);
+            }
+            dialog = dialog.set_file_name(file_name);
+        }
+
+        dialog
+            .blocking_save_file()
+            .map(|path| path.into_path().map_err(|e| e.to_string()))
+            .transpose()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn download(
+    app: &AppHandle,
+    state: &ServerState,
+    path: &str,
+    body: Option<serde_json::Value>,
+    destination: &Path,
+    partial: &Path,
+) -> Result<u64, String> {
+    let display_path = destination.to_string_lossy().into_owned();
+
+    // A body means the backend builds the export from posted filters
+    let request = match &body {
+        Some(body) => backend::stream_request(state, Method::POST, path)?.json(body),
+        None => backend::stream_request(state, Method::GET, path)?,
+    };
+
+    let mut response = backend::send(state, request).await?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Export failed: backend responded with {}",
+            response.status()
+        ));
+    }
+    let total_bytes = response.content_length();
+
+    let mut file = tokio::fs::File::create(partial)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", display_path, e))?;
+    let mut received_bytes = 0;
+    let mut last_progress = Instant::now();
+
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        received_bytes += chunk.len() as u64;
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            let _ = events::EXPORT_PROGRESS.emit(
+                app,
+                &ExportProgress {
+                    path: display_path.clone(),
+                    received_bytes,
+                    total_bytes,
+                },
+            );
+        }
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+
+    let _ = events::EXPORT_PROGRESS.emit(
+        app,
+        &ExportProgress {
+            path: display_path,
+            received_bytes,
+            total_bytes: Some(received_bytes),
+        },
+    );
+    Ok(received_bytes)
+}
+
+// Command to export data from a backend endpoint to a file chosen with a save
+// dialog. Returns null if the user cancels the dialog.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_data(
+    app: AppHandle,
+    state: State<'_, ServerState>,
+    path: String,
+    body: Option<serde_json::Value>,
+    file_name: Option<String>,
+) -> Result<Option<ExportResult>, String> {
+    let destination = match pick_destination(&app, file_name).await? {
+        Some(destination) => destination,
+        None => return Ok(None),
+    };
+
+    // Written under a temporary name so a failed export never leaves a partial
+    // file looking like a complete one
+    let mut partial = destination.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let bytes = match download(&app, &state, &path, body, &destination, &partial).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+    };
+    tokio::fs::rename(&partial, &destination)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let path = destination.to_string_lossy().into_owned();
+    println!("Exported {} bytes to {}", bytes, path);
+    Ok(Some(ExportResult { path, bytes }))
+}
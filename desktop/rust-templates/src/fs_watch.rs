@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify_debouncer_full::notify::event::ModifyKind;
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::config::ConfigState;
+use crate::events;
+
+// Native directory watching, e.g. for a folder that meter CSV exports are dropped
+// into. Changes are debounced and emitted as `fs-changed`. Only directories under
+// `fileWatch.directories` in `desktop.json` can be watched.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FileWatchConfig {
+    // Directories that may be watched, along with anything inside them
+    pub directories: Vec<String>,
+    pub max_watches: usize,
+}
+
+impl Default for FileWatchConfig {
+    fn default() -> Self {
+        Self {
+            directories: Vec::new(),
+            max_watches: 8,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FsWatchState {
+    watchers: Mutex<HashMap<PathBuf, Debouncer<RecommendedWatcher, RecommendedCache>>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct FsChange {
+    // The watched directory the change happened under
+    pub root: String,
+    // "create", "modify", "rename", "remove" or "other"
+    pub kind: &'static str,
+    pub paths: Vec<String>,
+}
+
+fn change_kind(kind: &EventKind) -> Option<&'static str> {
+    match kind {
+        EventKind::Access(_) => None,
+        EventKind::Create(_) => Some("create"),
+        EventKind::Modify(ModifyKind::Name(_)) => Some("rename"),
+        EventKind::Modify(_) => Some("modify"),
+        EventKind::Remove(_) => Some("remove"),
+        EventKind::Any | EventKind::Other => Some("other"),
+    }
+}
+
+// Resolve a requested directory, refusing anything outside the configured ones.
+// Both sides are canonicalized so `..` and symlinks can't escape them.
+fn resolve(config: &FileWatchConfig, path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot watch {}: {}", path, e))?;
+
+    let allowed = config
+        .directories
+        .iter()
+        .filter_map(|directory| Path::new(directory).canonicalize().ok())
+        .any(|directory| path.starts_with(directory));
+    if !allowed {
+        return Err(format!(
+            "{} is not under a directory in fileWatch.directories",
+            path.display()
+        ));
+    }
+
+    Ok(path)
+}
+
+// Command to watch a directory (recursively unless `recursive` is false) and emit
+// `fs-changed` for changes in it. Returns the resolved path to unwatch with.
+#[tauri::command]
+#[specta::specta]
+pub fn watch_path(
+    app: AppHandle,
+    config: State<ConfigState>,
+    state: State<FsWatchState>,
+    path: String,
+    recursive: Option<bool>,
+) -> Result<String, String> {
+    let config = config.get().file_watch;
+    let path = resolve(&config, &path)?;
+    let root = path.to_string_lossy().into_owned();
+
+    let mut watchers = state.watchers.lock().unwrap();
+    if watchers.contains_key(&path) {
+        return Ok(root);
+    }
+    if watchers.len() >= config.max_watches {
+        return Err(format!(
+            "Already watching {} directories, the most allowed",
+            watchers.len()
+        ));
+    }
+
+    let event_root = root.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE, None, move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("Error watching {}: {}", event_root, error);
+                }
+                return;
+            }
+        };
+
+        for event in events {
+            if let Some(kind) = change_kind(&event.kind) {
+                let _ = events::FS_CHANGED.emit(
+                    &app,
+                    &FsChange {
+                        root: event_root.clone(),
+                        kind,
+                        paths: event
+                            .paths
+                            .iter()
+                            .map(|path| path.to_string_lossy().into_owned())
+                            .collect(),
+                    },
+                );
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mode = if recursive.unwrap_or(true) {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    debouncer
+        .watch(&path, mode)
+        .map_err(|e| format!("Cannot watch {}: {}", root, e))?;
+
+    println!("Watching {}", root);
+    watchers.insert(path, debouncer);
+    Ok(root)
+}
+
+// Command to stop watching a directory
+#[tauri::command]
+#[specta::specta]
+pub fn unwatch_path(state: State<FsWatchState>, path: String) {
+    let path = Path::new(&path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(&path));
+
+    // Dropping the debouncer stops the watcher
+    state.watchers.lock().unwrap().remove(&path);
+}
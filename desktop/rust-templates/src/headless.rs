@@ -0,0 +1,176 @@
+use std::fs;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tauri::{AppHandle, Manager};
+
+use crate::backup;
+use crate::data_dir;
+use crate::paths::AppPaths;
+use crate::server::{self, ServerState};
+use crate::telemetry::{self, UsageEvent};
+use crate::updater;
+
+// Headless mode, started with `--headless`: the shell runs the backend with the
+// usual supervision and sidecar updates but never creates a window, e.g. for data
+// collection on a server or a kiosk's boot sequence. It publishes the backend's
+// token in the data directory, so a shell launched later with a window attaches
+// to the same backend instead of starting its own.
+pub const FLAG: &str = "--headless";
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(15);
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct BackendLease {
+    pid: u32,
+    token: String,
+}
+
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == FLAG)
+}
+
+// Release builds on Windows have no console of their own, so write to the one
+// the shell was started from
+#[cfg(target_os = "windows")]
+pub fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn attach_console() {}
+
+fn is_running(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    system.process(pid).is_some()
+}
+
+// Token of the backend run by a headless shell, if one is still running
+pub fn running_backend(paths: &AppPaths) -> Option<String> {
+    let contents = fs::read_to_string(paths.backend_lease()).ok()?;
+    let lease: BackendLease = serde_json::from_str(&contents).ok()?;
+    if lease.pid == std::process::id() || !is_running(lease.pid) {
+        return None;
+    }
+    println!("Attaching to the backend of headless shell {}", lease.pid);
+    Some(lease.token)
+}
+
+// Let shells launched later find the backend. The token grants full access to
+// the backend, so the file is only readable by the current user.
+pub fn publish(paths: &AppPaths, state: &ServerState) {
+    let lease = BackendLease {
+        pid: std::process::id(),
+        token: state.token.clone(),
+    };
+    let path = paths.backend_lease();
+    let result = serde_json::to_vec(&lease)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            options
+                .open(&path)
+                .and_then(|mut file| file.write_all(&contents))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        eprintln!("Failed to publish backend for other shells: {}", e);
+    }
+}
+
+pub fn withdraw(paths: &AppPaths) {
+    let _ = fs::remove_file(paths.backend_lease());
+}
+
+// Restart the backend if it exits, unless it was stopped on purpose
+fn supervise(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(SUPERVISE_INTERVAL);
+
+        if backup::is_busy(&app) || data_dir::is_migrating(&app) {
+            continue;
+        }
+
+        let state = app.state::<ServerState>();
+        if state.has_exited() {
+            eprintln!("Backend exited, restarting");
+            telemetry::record(&app, UsageEvent::BackendRestarted, Some("exited"));
+            server::stop_backend_server(&state);
+            if let Err(e) = server::start_backend_server(&app.state::<AppPaths>(), &state) {
+                eprintln!("Failed to restart backend: {}", e);
+            }
+        }
+    });
+}
+
+// There is no frontend to offer sidecar updates, so install them as they appear
+fn install_updates(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match updater::check_sidecar_update(app.clone()).await {
+                Ok(Some(update)) => {
+                    println!("Installing sidecar {}", update.version);
+                    if let Err(e) = updater::install_sidecar_update(app.clone()).await {
+                        eprintln!("Failed to install sidecar update: {}", e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Sidecar update check failed: {}", e),
+            }
+            tokio::time::sleep(UPDATE_CHECK_INTERVAL).await;
+        }
+    });
+}
+
+// Exit cleanly on Ctrl+C or SIGTERM. The backend runs in its own process group,
+// so it doesn't receive the signal itself and has to be stopped by the shell.
+fn exit_on_signal(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminate) => {
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => {}
+                        _ = terminate.recv() => {}
+                    }
+                }
+                Err(_) => {
+                    let _ = tokio::signal::ctrl_c().await;
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+
+        println!("Shutting down");
+        app.exit(0);
+    });
+}
+
+pub fn init(app: &AppHandle) {
+    #[cfg(target_os = "macos")]
+    let _ = app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+
+    supervise(app.clone());
+    install_updates(app.clone());
+    exit_on_signal(app.clone());
+    println!("Running headless");
+}
@@ -0,0 +1,293 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures_util::StreamExt;
+use rand::Rng;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Method};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, DragDropEvent, Manager, Window, WindowEvent};
+use tokio_util::io::ReaderStream;
+
+use crate::backend;
+use crate::config::ConfigState;
+use crate::events;
+use crate::paths::AppPaths;
+use crate::server::ServerState;
+use crate::session_lock;
+
+// Files dropped on the window are imported without any frontend code: each one
+// is checked against `import` in `desktop.json`, copied into a staging directory
+// (so the original can be removed, e.g. on a USB stick) and uploaded to the
+// backend's import endpoint as multipart form data. Progress is reported with
+// `import-progress`, then `import-completed` or `import-failed`.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ImportConfig {
+    pub enabled: bool,
+    // Backend path the file is POSTed to, as the `file` form field
+    pub endpoint: String,
+    // Lowercase, without the dot
+    pub allowed_extensions: Vec<String>,
+    pub max_size_mb: u64,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            endpoint: "/api/import".to_string(),
+            allowed_extensions: vec!["csv".to_string()],
+            max_size_mb: 200,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct ImportProgress {
+    pub id: String,
+    pub file_name: String,
+    pub sent_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct ImportCompleted {
+    pub id: String,
+    pub file_name: String,
+    // The backend's response, parsed as JSON when possible
+    pub response: serde_json::Value,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct ImportFailed {
+    // Null when the file was rejected before it was staged
+    pub id: Option<String>,
+    pub file_name: String,
+    pub error: String,
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn validate(config: &ImportConfig, path: &Path) -> Result<u64, String> {
+    let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+    if !metadata.is_file() {
+        return Err("Only files can be imported".to_string());
+    }
+
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !config
+        .allowed_extensions
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(&extension))
+    {
+        return Err(format!(
+            "Unsupported file type, expected {}",
+            config.allowed_extensions.join(", ")
+        ));
+    }
+
+    if metadata.len() > config.max_size_mb * 1024 * 1024 {
+        return Err(format!("File is larger than {}MB", config.max_size_mb));
+    }
+
+    Ok(metadata.len())
+}
+
+// Copy the file into the staging directory under a unique name, used as the
+// import id. The random part keeps two drops of the same file within a
+// millisecond from overwriting each other.
+fn stage(paths: &AppPaths, path: &Path) -> Result<(String, PathBuf), String> {
+    let dir = paths.import_staging_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let id = format!(
+        "{}-{:08x}-{}",
+        millis,
+        rand::thread_rng().gen::<u32>(),
+        file_name(path)
+    );
+    let staged = dir.join(&id);
+    fs::copy(path, &staged).map_err(|e| format!("Failed to stage file: {}", e))?;
+
+    Ok((id, staged))
+}
+
+async fn upload(
+    app: &AppHandle,
+    endpoint: &str,
+    id: &str,
+    name: &str,
+    staged: &Path,
+    total_bytes: u64,
+) -> Result<serde_json::Value, String> {
+    let file = tokio::fs::File::open(staged)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let progress_app = app.clone();
+    let progress_id = id.to_string();
+    let progress_name = name.to_string();
+    let mut sent_bytes = 0;
+    let mut last_progress = Instant::now();
+    let stream = ReaderStream::new(file).inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            sent_bytes += chunk.len() as u64;
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                let _ = events::IMPORT_PROGRESS.emit(
+                    &progress_app,
+                    &ImportProgress {
+                        id: progress_id.clone(),
+                        file_name: progress_name.clone(),
+                        sent_bytes,
+                        total_bytes,
+                    },
+                );
+            }
+        }
+    });
+
+    let part = Part::stream_with_length(Body::wrap_stream(stream), total_bytes)
+        .file_name(name.to_string());
+    let form = Form::new().part("file", part);
+
+    // Uploads can take longer than the usual request timeout
+    let state = app.state::<ServerState>();
+    let request = backend::stream_request(&state, Method::POST, endpoint)?.multipart(form);
+    let response = backend::send(&state, request).await?;
+
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!(
+            "Backend rejected the import ({}): {}",
+            status, text
+        ));
+    }
+
+    let _ = events::IMPORT_PROGRESS.emit(
+        app,
+        &ImportProgress {
+            id: id.to_string(),
+            file_name: name.to_string(),
+            sent_bytes: total_bytes,
+            total_bytes,
+        },
+    );
+    Ok(serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)))
+}
+
+async fn import_file(app: AppHandle, config: ImportConfig, path: PathBuf) {
+    let name = file_name(&path);
+    let fail = |id: Option<String>, error: String| {
+        eprintln!("Import of {} failed: {}", name, error);
+        let _ = events::IMPORT_FAILED.emit(
+            &app,
+            &ImportFailed {
+                id,
+                file_name: name.clone(),
+                error,
+            },
+        );
+    };
+
+    let total_bytes = match validate(&config, &path) {
+        Ok(size) => size,
+        Err(e) => return fail(None, e),
+    };
+    let (id, staged) = match stage(&app.state::<AppPaths>(), &path) {
+        Ok(staged) => staged,
+        Err(e) => return fail(None, e),
+    };
+
+    println!("Importing {}", name);
+    let result = upload(&app, &config.endpoint, &id, &name, &staged, total_bytes).await;
+    let _ = fs::remove_file(&staged);
+
+    match result {
+        Ok(response) => {
+            let _ = events::IMPORT_COMPLETED.emit(
+                &app,
+                &ImportCompleted {
+                    id,
+                    file_name: name,
+                    response,
+                },
+            );
+        }
+        Err(e) => fail(Some(id), e),
+    }
+}
+
+// Remove files left behind by imports interrupted when the app last exited
+pub fn init(app: &AppHandle) {
+    let dir = app.state::<AppPaths>().import_staging_dir();
+    let _ = fs::remove_dir_all(dir);
+}
+
+// Window event hook: import every file dropped on the window
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    let paths = match event {
+        WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) => paths,
+        _ => return,
+    };
+
+    let app = window.app_handle();
+    let config = app.state::<ConfigState>().get().import;
+    if !config.enabled {
+        return;
+    }
+    if session_lock::is_locked(app) {
+        eprintln!("Dropped {} file(s) while the session is locked", paths.len());
+        return;
+    }
+
+    for path in paths {
+        tauri::async_runtime::spawn(import_file(app.clone(), config.clone(), path.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_allowed_files_within_the_size_limit() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = ImportConfig {
+            max_size_mb: 1,
+            ..ImportConfig::default()
+        };
+
+        let csv = dir.join("readings.CSV");
+        fs::write(&csv, "a,b\n1,2\n").unwrap();
+        assert_eq!(validate(&config, &csv), Ok(8));
+
+        let large = dir.join("large.csv");
+        fs::write(&large, vec![b'x'; 1024 * 1024 + 1]).unwrap();
+        assert!(validate(&config, &large).is_err());
+
+        let script = dir.join("readings.csv.exe");
+        fs::write(&script, "").unwrap();
+        assert!(validate(&config, &script).is_err());
+
+        assert!(validate(&config, dir).is_err());
+        assert!(validate(&config, &dir.join("missing.csv")).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+use crate::paths::AppPaths;
+use crate::server::ServerState;
+use crate::updater;
+
+// Integrity checks run before the backend sidecar is launched. The bundled
+// sidecar must match the SHA-256 recorded in `server-manifest.json`, which
+// `build-sidecar` writes next to the binaries. When the app has an updater key,
+// the manifest must carry a `.sig` made with it, or it could simply be rewritten
+// along with the binary. A sidecar installed by a
+// delta update keeps the signature it was installed with and is checked against
+// the updater key directly. A binary that fails either check is never started.
+//
+// The version of a delta update comes from the signed manifest it was installed
+// from, kept beside it as `update-manifest.json` and `update-manifest.json.sig`.
+// The manifest must list the installed binary's signature for this platform, so
+// neither the version nor the binary can be swapped on its own.
+
+const MANIFEST_NAME: &str = "server-manifest.json";
+pub const UPDATE_MANIFEST_NAME: &str = "update-manifest.json";
+
+#[derive(Deserialize)]
+struct SidecarManifest {
+    files: HashMap<String, String>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct IntegrityFailure {
+    pub binary: String,
+    pub error: String,
+}
+
+// Signature stored alongside a binary, e.g. `server-x86_64-...exe.sig`
+pub fn signature_path(binary: &Path) -> PathBuf {
+    let mut name = binary.file_name().unwrap_or_default().to_os_string();
+    name.push(".sig");
+    binary.with_file_name(name)
+}
+
+fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let length = file.read(&mut buffer)?;
+        if length == 0 {
+            return Ok(format!("{:x}", hasher.finalize()));
+        }
+        hasher.update(&buffer[..length]);
+    }
+}
+
+fn verify_bundled(binary: &Path, pubkey: Option<&str>) -> Result<(), String> {
+    let manifest_path = binary.with_file_name(MANIFEST_NAME);
+    let manifest_bytes = fs::read(&manifest_path)
+        .map_err(|e| format!("Sidecar manifest could not be read: {}", e))?;
+
+    if let Some(pubkey) = pubkey {
+        let signature = fs::read_to_string(signature_path(&manifest_path))
+            .map_err(|e| format!("Sidecar manifest signature could not be read: {}", e))?;
+        updater::verify_signature(&manifest_bytes, signature.trim(), pubkey)
+            .map_err(|e| format!("Sidecar manifest: {}", e))?;
+    }
+
+    let manifest: SidecarManifest = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| format!("Invalid sidecar manifest: {}", e))?;
+    let name = binary.file_name().unwrap_or_default().to_string_lossy();
+    let expected = manifest
+        .files
+        .get(name.as_ref())
+        .ok_or_else(|| format!("Sidecar manifest has no entry for {}", name))?;
+
+    let actual = file_sha256(binary).map_err(|e| format!("Failed to read sidecar: {}", e))?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "Sidecar checksum mismatch (expected {}, found {})",
+            expected, actual
+        ));
+    }
+    Ok(())
+}
+
+fn verify_installed(binary: &Path, pubkey: Option<&str>) -> Result<(), String> {
+    let pubkey = pubkey.ok_or("Updater public key is not configured (plugins.updater.pubkey)")?;
+    let signature = fs::read_to_string(signature_path(binary))
+        .map_err(|e| format!("Sidecar signature could not be read: {}", e))?;
+    let data = fs::read(binary).map_err(|e| format!("Failed to read sidecar: {}", e))?;
+    updater::verify_signature(&data, signature.trim(), pubkey)
+}
+
+// Version in an update manifest whose entry for `platform` carries
+// `binary_signature`. Sidecar update manifests give the signature in the
+// platform entry, offline bundles under its `sidecar`.
+fn manifest_version(data: &[u8], platform: &str, binary_signature: &str) -> Result<String, String> {
+    let manifest: serde_json::Value =
+        serde_json::from_slice(data).map_err(|e| format!("Invalid update manifest: {}", e))?;
+    let entry = &manifest["platforms"][platform];
+    let listed = [&entry["signature"], &entry["sidecar"]["signature"]]
+        .iter()
+        .any(|signature| signature.as_str().map(str::trim) == Some(binary_signature.trim()));
+    if !listed {
+        return Err(format!(
+            "Update manifest doesn't list this sidecar for {}",
+            platform
+        ));
+    }
+    manifest["version"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Update manifest has no version".to_string())
+}
+
+// Check that a signed update manifest vouches for a sidecar binary's signature,
+// and return the version it gives the sidecar
+pub fn signed_version(
+    manifest: &[u8],
+    manifest_signature: &str,
+    binary_signature: &str,
+    pubkey: &str,
+) -> Result<String, String> {
+    updater::verify_signature(manifest, manifest_signature.trim(), pubkey)
+        .map_err(|e| format!("Update manifest: {}", e))?;
+    manifest_version(manifest, &updater::platform_key(), binary_signature)
+}
+
+// Version of the sidecar installed by a delta update, vouched for by the update
+// manifest kept with it
+pub fn installed_version(binary: &Path, pubkey: &str) -> Result<String, String> {
+    let manifest_path = binary.with_file_name(UPDATE_MANIFEST_NAME);
+    let manifest = fs::read(&manifest_path)
+        .map_err(|e| format!("Update manifest could not be read: {}", e))?;
+    let manifest_signature = fs::read_to_string(signature_path(&manifest_path))
+        .map_err(|e| format!("Update manifest signature could not be read: {}", e))?;
+    let binary_signature = fs::read_to_string(signature_path(binary))
+        .map_err(|e| format!("Sidecar signature could not be read: {}", e))?;
+    signed_version(&manifest, &manifest_signature, &binary_signature, pubkey)
+}
+
+// Why a sidecar installed by a delta update no longer takes precedence over the
+// bundled one: a full update has caught up with it, or it was signed before the
+// bundled manifest, as an old signed binary put back with an edited `version`
+// file would be. Signing times are only compared when both are known.
+fn stale_reason(
+    delta_version: Option<&str>,
+    app_version: &str,
+    delta_signed: Option<u64>,
+    bundled_signed: Option<u64>,
+) -> Option<String> {
+    let Some(delta_version) = delta_version else {
+        return Some("it has no signed version".to_string());
+    };
+    match updater::is_newer(delta_version, app_version) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Some(format!(
+                "{} is not newer than the bundled {}",
+                delta_version, app_version
+            ))
+        }
+        Err(e) => return Some(format!("invalid version {}: {}", delta_version, e)),
+    }
+    match (delta_signed, bundled_signed) {
+        (Some(delta), Some(bundled)) if delta < bundled => {
+            Some("it was signed before the bundled sidecar".to_string())
+        }
+        _ => None,
+    }
+}
+
+// Remove a stale delta update before the sidecar is resolved, so the bundled
+// sidecar runs instead. The signing times are read before the signatures are
+// checked, which is safe: a delta whose comment was tampered with fails its
+// check at launch, and a bundled manifest claiming to be newer only makes the
+// bundled sidecar win.
+pub fn prune_stale_sidecar(
+    paths: &AppPaths,
+    binary_names: &[String],
+    app_version: &str,
+    pubkey: Option<&str>,
+) {
+    let dir = paths.sidecar_dir();
+    let Some(binary) = binary_names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|binary| binary.exists())
+    else {
+        return;
+    };
+    let delta_version = pubkey
+        .ok_or_else(|| "Updater public key is not configured".to_string())
+        .and_then(|pubkey| installed_version(&binary, pubkey))
+        .inspect_err(|e| eprintln!("Sidecar from an earlier delta update has no version: {}", e))
+        .ok();
+    let delta_signed = fs::read_to_string(signature_path(&binary))
+        .ok()
+        .and_then(|signature| updater::signed_at(signature.trim()));
+    let bundled_manifest = paths.resource_dir.join("server").join(MANIFEST_NAME);
+    let bundled_signed = fs::read_to_string(signature_path(&bundled_manifest))
+        .ok()
+        .and_then(|signature| updater::signed_at(signature.trim()));
+
+    if let Some(reason) = stale_reason(
+        delta_version.as_deref(),
+        app_version,
+        delta_signed,
+        bundled_signed,
+    ) {
+        println!("Removing the sidecar from an earlier delta update: {}", reason);
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            eprintln!("Failed to remove {:?}: {}", dir, e);
+        }
+    }
+}
+
+// Check a sidecar binary before it is launched
+pub fn verify_sidecar(paths: &AppPaths, binary: &Path, pubkey: Option<&str>) -> Result<(), String> {
+    if binary.starts_with(paths.sidecar_dir()) {
+        verify_installed(binary, pubkey)
+    } else {
+        verify_bundled(binary, pubkey)
+    }
+}
+
+// Report a failure from the launch at startup, once it has been attempted
+pub fn init(app: &AppHandle) {
+    if let Some(failure) = app.state::<ServerState>().integrity_failure() {
+        let _ = events::SIDECAR_INTEGRITY_FAILED.emit(app, &failure);
+    }
+}
+
+// Command to get the integrity failure that stopped the sidecar from launching,
+// if any
+#[tauri::command]
+#[specta::specta]
+pub fn get_sidecar_integrity(state: State<'_, ServerState>) -> Option<IntegrityFailure> {
+    state.integrity_failure()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_a_newer_delta() {
+        assert_eq!(stale_reason(Some("1.5.1"), "1.5.0", None, None), None);
+        assert_eq!(stale_reason(Some("1.5.1"), "1.5.0", Some(200), Some(100)), None);
+    }
+
+    #[test]
+    fn drops_a_delta_caught_up_by_a_full_update() {
+        assert!(stale_reason(Some("1.5.0"), "1.5.0", None, None).is_some());
+        assert!(stale_reason(Some("1.4.2"), "1.5.0", None, None).is_some());
+        assert!(stale_reason(None, "1.5.0", None, None).is_some());
+        assert!(stale_reason(Some("next"), "1.5.0", None, None).is_some());
+    }
+
+    #[test]
+    fn drops_a_delta_signed_before_the_bundled_sidecar() {
+        assert!(stale_reason(Some("9.0.0"), "1.5.0", Some(100), Some(200)).is_some());
+    }
+
+    #[test]
+    fn reads_the_version_the_manifest_gives_this_sidecar() {
+        let online = br#"{"version": "1.5.1", "platforms": {"windows-x86_64": {"url": "https://x", "signature": "sig-a"}}}"#;
+        assert_eq!(
+            manifest_version(online, "windows-x86_64", "sig-a\n"),
+            Ok("1.5.1".to_string())
+        );
+        let bundle = br#"{"version": "1.6.0", "platforms": {"windows-x86_64": {"sidecar": {"file": "s.exe", "signature": "sig-b"}}}}"#;
+        assert_eq!(
+            manifest_version(bundle, "windows-x86_64", "sig-b"),
+            Ok("1.6.0".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_a_manifest_for_another_binary() {
+        let manifest =
+            br#"{"version": "9.0.0", "platforms": {"windows-x86_64": {"signature": "sig-a"}}}"#;
+        assert!(manifest_version(manifest, "windows-x86_64", "sig-old").is_err());
+        assert!(manifest_version(manifest, "linux-x86_64", "sig-a").is_err());
+        let unversioned = br#"{"platforms": {"windows-x86_64": {"signature": "sig-a"}}}"#;
+        assert!(manifest_version(unversioned, "windows-x86_64", "sig-a").is_err());
+        assert!(manifest_version(b"9.0.0", "windows-x86_64", "sig-a").is_err());
+    }
+
+    #[test]
+    fn names_signatures_after_the_binary() {
+        assert_eq!(
+            signature_path(Path::new("/data/server/server-x86_64-pc-windows-msvc.exe")),
+            Path::new("/data/server/server-x86_64-pc-windows-msvc.exe.sig")
+        );
+    }
+}
@@ -0,0 +1,604 @@
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::{Request, State as AxumState};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use hyper_util::rt::TokioIo;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::auth;
+use crate::backend;
+use crate::config::ConfigState;
+use crate::events;
+use crate::locale;
+use crate::server::ServerState;
+use crate::tls;
+
+// The backend only ever listens on 127.0.0.1. Some customers need to open the
+// dashboard from a tablet on the same network, so LAN exposure is an explicit,
+// opt-in mode where the shell runs an authenticated reverse proxy on a fixed port
+// in front of the loopback-only backend. The proxy serves HTTPS with the
+// install's self-signed certificate, whose fingerprint the user can compare on
+// the other device, and passes WebSocket upgrades through to the backend.
+const MAX_BODY_SIZE: usize = 50 * 1024 * 1024;
+
+// Headers that only apply to one connection, so they are never forwarded
+// (RFC 9110 section 7.6.1), along with any the Connection header names
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LanExposureConfig {
+    pub enabled: bool,
+    // Fixed so firewall rules can be set up once
+    pub port: u16,
+    // Password for HTTP basic auth on the proxy (any username is accepted)
+    pub access_code: Option<String>,
+}
+
+impl Default for LanExposureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8090,
+            access_code: None,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct LanStatus {
+    pub enabled: bool,
+    pub port: u16,
+    pub addresses: Vec<String>,
+    pub access_code: Option<String>,
+    // SHA-256 fingerprint of the certificate the proxy presents
+    pub fingerprint: Option<String>,
+}
+
+// Stopping the proxy closes the listener and every connection, WebSockets
+// included, so nothing stays exposed once the user turns it off
+#[derive(Default)]
+pub struct LanState {
+    shutdown: Mutex<Option<watch::Sender<bool>>>,
+}
+
+#[derive(Clone)]
+struct ProxyContext {
+    app: AppHandle,
+    access_code: String,
+    shutdown: watch::Receiver<bool>,
+}
+
+fn non_loopback_addresses() -> Vec<IpAddr> {
+    if_addrs::get_if_addrs()
+        .map(|interfaces| {
+            interfaces
+                .into_iter()
+                .filter(|interface| !interface.is_loopback())
+                .map(|interface| interface.ip())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Refuse to run a backend that can be reached from other machines: connect to the
+// backend port on every non-loopback interface address
+pub fn verify_loopback_only(port: u16) -> Result<(), String> {
+    for ip in non_loopback_addresses() {
+        let address = SocketAddr::new(ip, port);
+        if TcpStream::connect_timeout(&address, Duration::from_millis(300)).is_ok() {
+            return Err(format!(
+                "Backend port {} is reachable on {}; it must only listen on 127.0.0.1",
+                port, ip
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn generate_access_code() -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut rng = rand::thread_rng();
+    (0..10)
+        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+        .collect()
+}
+
+fn lan_status(app: &AppHandle, config: &LanExposureConfig) -> LanStatus {
+    let addresses = if config.enabled {
+        non_loopback_addresses()
+            .into_iter()
+            .filter(|ip| ip.is_ipv4())
+            .map(|ip| format!("https://{}:{}", ip, config.port))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    LanStatus {
+        enabled: config.enabled,
+        port: config.port,
+        addresses,
+        access_code: config.access_code.clone(),
+        fingerprint: app.state::<ServerState>().tls.as_ref().and_then(tls::fingerprint),
+    }
+}
+
+fn is_authorized(request: &Request, access_code: &str) -> bool {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|credentials| {
+            base64::engine::general_purpose::STANDARD
+                .decode(credentials)
+                .ok()
+        })
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .and_then(|credentials| {
+            credentials
+                .split_once(':')
+                .map(|(_, password)| password.as_bytes().ct_eq(access_code.as_bytes()).into())
+        })
+        .unwrap_or(false)
+}
+
+async fn proxy_handler(AxumState(context): AxumState<ProxyContext>, request: Request) -> Response {
+    if !is_authorized(&request, &context.access_code) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"EpiSensor\"")],
+        )
+            .into_response();
+    }
+
+    let result = if is_websocket_upgrade(request.headers()) {
+        forward_websocket(&context, request).await
+    } else {
+        forward(&context.app, request).await
+    };
+    match result {
+        Ok(response) => response,
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+fn is_hop_by_hop(name: &str, headers: &HeaderMap) -> bool {
+    HOP_BY_HOP.iter().any(|hop| name.eq_ignore_ascii_case(hop))
+        || headers
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| name.eq_ignore_ascii_case(token.trim()))
+}
+
+// A page served through the proxy sends its own origin, which the backend
+// doesn't know; the proxy has authenticated the request, so that Origin is
+// dropped. Any other origin is passed on for the backend to refuse.
+fn is_same_origin(headers: &HeaderMap) -> bool {
+    let origin = headers.get(header::ORIGIN).and_then(|value| value.to_str().ok());
+    let host = headers.get(header::HOST).and_then(|value| value.to_str().ok());
+    match (origin, host) {
+        (Some(origin), Some(host)) => origin
+            .strip_prefix("https://")
+            .is_some_and(|origin| origin.eq_ignore_ascii_case(host)),
+        _ => false,
+    }
+}
+
+// Request headers passed to the backend: end-to-end ones only, without the
+// client's credentials for the proxy or a token of its own
+fn forwarded_request_headers(headers: &HeaderMap) -> Vec<(HeaderName, HeaderValue)> {
+    let same_origin = is_same_origin(headers);
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            !(is_hop_by_hop(name.as_str(), headers)
+                || *name == header::HOST
+                || *name == header::AUTHORIZATION
+                || name.as_str().eq_ignore_ascii_case(auth::TOKEN_HEADER)
+                || (same_origin && *name == header::ORIGIN))
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+// Forward a LAN request to the loopback backend with the shell's token attached.
+// reqwest and axum use different `http` versions, so everything crosses as strings.
+async fn forward(app: &AppHandle, request: Request) -> Result<Response, String> {
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "/".to_string());
+    let method = reqwest::Method::from_bytes(request.method().as_str().as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let state = app.state::<ServerState>();
+    let mut upstream = backend::request(&state, method, &path)?;
+    for (name, value) in forwarded_request_headers(request.headers()) {
+        upstream = upstream.header(name.as_str(), value.as_bytes());
+    }
+
+    let body = axum::body::to_bytes(request.into_body(), MAX_BODY_SIZE)
+        .await
+        .map_err(|e| e.to_string())?;
+    let upstream = backend::send(&state, upstream.body(body.to_vec())).await?;
+
+    let mut response = Response::builder().status(upstream.status().as_u16());
+    let mut headers = HeaderMap::new();
+    for (name, value) in upstream.headers() {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_str().as_bytes()),
+            HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    for (name, value) in &headers {
+        if !is_hop_by_hop(name.as_str(), &headers) {
+            response = response.header(name, value);
+        }
+    }
+    let bytes = upstream.bytes().await.map_err(|e| e.to_string())?;
+
+    response
+        .body(Body::from(bytes.to_vec()))
+        .map_err(|e| e.to_string())
+}
+
+// Pass a WebSocket upgrade through: connect to the backend with the shell's
+// token first, then complete the client's handshake and relay messages both ways
+async fn forward_websocket(context: &ProxyContext, mut request: Request) -> Result<Response, String> {
+    let key = request
+        .headers()
+        .get(header::SEC_WEBSOCKET_KEY)
+        .ok_or("Missing Sec-WebSocket-Key")?
+        .clone();
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "/".to_string());
+
+    let state = context.app.state::<ServerState>();
+    let port = state.port().ok_or("Backend server is not running")?;
+    let (scheme, connector) = match &state.tls {
+        Some(identity) => ("wss", Some(tls::ws_connector(identity)?)),
+        None => ("ws", None),
+    };
+    let mut upstream = format!("{}://127.0.0.1:{}{}", scheme, port, path)
+        .into_client_request()
+        .map_err(|e| e.to_string())?;
+    for (name, value) in forwarded_request_headers(request.headers()) {
+        // The backend handshake has its own key, version and extensions
+        if !name.as_str().starts_with("sec-websocket-") || name == header::SEC_WEBSOCKET_PROTOCOL {
+            upstream.headers_mut().append(name, value);
+        }
+    }
+    upstream.headers_mut().insert(
+        auth::TOKEN_HEADER,
+        HeaderValue::from_str(&state.token).map_err(|e| e.to_string())?,
+    );
+
+    let (backend, backend_response) =
+        tokio_tungstenite::connect_async_tls_with_config(upstream, None, false, connector)
+            .await
+            .map_err(|e| e.to_string())?;
+
+    let on_upgrade = hyper::upgrade::on(&mut request);
+    let mut shutdown = context.shutdown.clone();
+    tauri::async_runtime::spawn(async move {
+        let upgraded = match on_upgrade.await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                eprintln!("LAN WebSocket upgrade failed: {}", e);
+                return;
+            }
+        };
+        let client = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+        let (mut client_write, mut client_read) = client.split();
+        let (mut backend_write, mut backend_read) = backend.split();
+        let to_backend = async {
+            while let Some(Ok(message)) = client_read.next().await {
+                if backend_write.send(message).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let to_client = async {
+            while let Some(Ok(message)) = backend_read.next().await {
+                if client_write.send(message).await.is_err() {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            _ = to_backend => {}
+            _ = to_client => {}
+            _ = shutdown.changed() => {}
+        }
+    });
+
+    let mut response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, derive_accept_key(key.as_bytes()));
+    if let Some(protocol) = backend_response.headers().get(header::SEC_WEBSOCKET_PROTOCOL) {
+        response = response.header(header::SEC_WEBSOCKET_PROTOCOL, protocol.clone());
+    }
+    response.body(Body::empty()).map_err(|e| e.to_string())
+}
+
+async fn start_proxy(app: &AppHandle, config: &LanExposureConfig) -> Result<(), String> {
+    stop_proxy(app);
+
+    let access_code = config
+        .access_code
+        .clone()
+        .ok_or("LAN exposure requires an access code")?;
+    let identity = app.state::<ServerState>().tls.as_ref().map(tls::server_config);
+    let acceptor = match identity {
+        Some(config) => tokio_rustls::TlsAcceptor::from(config?),
+        None => return Err("LAN exposure needs the backend TLS certificate".to_string()),
+    };
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", config.port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {}: {}", config.port, e))?;
+
+    let (shutdown, mut shutdown_signal) = watch::channel(false);
+    let router = axum::Router::new()
+        .fallback(proxy_handler)
+        .with_state(ProxyContext {
+            app: app.clone(),
+            access_code,
+            shutdown: shutdown_signal.clone(),
+        });
+    *app.state::<LanState>().shutdown.lock().unwrap() = Some(shutdown);
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        eprintln!("LAN proxy failed to accept a connection: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown_signal.changed() => break,
+            };
+            let acceptor = acceptor.clone();
+            let router = router.clone();
+            let mut shutdown = shutdown_signal.clone();
+            tauri::async_runtime::spawn(async move {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let connection = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(
+                        TokioIo::new(stream),
+                        hyper_util::service::TowerToHyperService::new(router),
+                    )
+                    .with_upgrades();
+                tokio::select! {
+                    _ = connection => {}
+                    _ = shutdown.changed() => {}
+                }
+            });
+        }
+    });
+
+    println!("Backend exposed on the LAN on port {}", config.port);
+    Ok(())
+}
+
+fn stop_proxy(app: &AppHandle) {
+    if let Some(shutdown) = app.state::<LanState>().shutdown.lock().unwrap().take() {
+        let _ = shutdown.send(true);
+        println!("LAN exposure disabled");
+    }
+}
+
+// The window's configured title, with a warning while the backend is exposed
+fn set_title(app: &AppHandle, exposed_port: Option<u16>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let title = app
+            .config()
+            .app
+            .windows
+            .first()
+            .map(|window| window.title.clone())
+            .unwrap_or_else(|| app.package_info().name.clone());
+        let title = match exposed_port {
+            Some(port) => locale::text(
+                app,
+                "window.lanWarning",
+                &[("title", &title), ("port", &port.to_string())],
+            ),
+            None => title,
+        };
+        let _ = window.set_title(&title);
+    }
+}
+
+// Make the exposed state impossible to miss: the window title changes and the
+// frontend gets an event to show its own warning
+fn announce(app: &AppHandle, status: &LanStatus) {
+    set_title(app, status.enabled.then_some(status.port));
+    let _ = events::LAN_EXPOSURE_CHANGED.emit(app, status);
+}
+
+// Set the window title again, e.g. after the locale changes
+pub fn refresh_title(app: &AppHandle) {
+    let running = app.state::<LanState>().shutdown.lock().unwrap().is_some();
+    let port = app.state::<ConfigState>().get().lan_exposure.port;
+    set_title(app, running.then_some(port));
+}
+
+// Start the proxy at launch if the user left LAN exposure enabled
+pub fn init(app: &AppHandle) {
+    let config = app.state::<ConfigState>().get().lan_exposure;
+    if !config.enabled {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match start_proxy(&app, &config).await {
+            Ok(()) => announce(&app, &lan_status(&app, &config)),
+            Err(e) => eprintln!("Failed to expose backend on the LAN: {}", e),
+        }
+    });
+}
+
+// Command to get whether the backend is exposed on the LAN, and where
+#[tauri::command]
+#[specta::specta]
+pub fn get_lan_exposure(app: AppHandle, config: State<ConfigState>) -> LanStatus {
+    lan_status(&app, &config.get().lan_exposure)
+}
+
+// Command to enable or disable LAN exposure, optionally on a different port
+#[tauri::command]
+#[specta::specta]
+pub async fn set_lan_exposure(
+    app: AppHandle,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<LanStatus, String> {
+    let config = app
+        .state::<ConfigState>()
+        .update(|config| {
+            let lan = &mut config.lan_exposure;
+            lan.enabled = enabled;
+            if let Some(port) = port {
+                lan.port = port;
+            }
+            if lan.access_code.is_none() {
+                lan.access_code = Some(generate_access_code());
+            }
+        })?
+        .lan_exposure;
+
+    if enabled {
+        if let Err(e) = start_proxy(&app, &config).await {
+            app.state::<ConfigState>()
+                .update(|config| config.lan_exposure.enabled = false)?;
+            return Err(e);
+        }
+    } else {
+        stop_proxy(&app);
+    }
+
+    let status = lan_status(&app, &config);
+    announce(&app, &status);
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn authorization(credentials: &str) -> Request {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        Request::builder()
+            .header(header::AUTHORIZATION, format!("Basic {}", encoded))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn checks_the_access_code() {
+        assert!(is_authorized(&authorization("any:ABCD234567"), "ABCD234567"));
+        assert!(is_authorized(&authorization(":ABCD234567"), "ABCD234567"));
+        assert!(!is_authorized(&authorization("any:ABCD23456"), "ABCD234567"));
+        assert!(!is_authorized(&authorization("ABCD234567"), "ABCD234567"));
+        assert!(!is_authorized(
+            &Request::builder().body(Body::empty()).unwrap(),
+            "ABCD234567"
+        ));
+    }
+
+    #[test]
+    fn drops_hop_by_hop_and_credential_headers() {
+        let headers = headers(&[
+            ("host", "192.168.1.20:8090"),
+            ("connection", "keep-alive, x-private"),
+            ("keep-alive", "timeout=5"),
+            ("x-private", "1"),
+            ("transfer-encoding", "chunked"),
+            ("authorization", "Basic eDp5"),
+            ("x-desktop-token", "forged"),
+            ("accept", "application/json"),
+        ]);
+        let forwarded: Vec<_> = forwarded_request_headers(&headers)
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        assert_eq!(forwarded, vec!["accept"]);
+    }
+
+    #[test]
+    fn drops_only_the_proxy_origin() {
+        let same = headers(&[("host", "192.168.1.20:8090"), ("origin", "https://192.168.1.20:8090")]);
+        assert!(is_same_origin(&same));
+        assert!(forwarded_request_headers(&same).is_empty());
+
+        let other = headers(&[("host", "192.168.1.20:8090"), ("origin", "https://evil.example")]);
+        assert!(!is_same_origin(&other));
+        assert_eq!(forwarded_request_headers(&other).len(), 1);
+    }
+
+    #[test]
+    fn recognizes_websocket_upgrades() {
+        assert!(is_websocket_upgrade(&headers(&[("upgrade", "WebSocket")])));
+        assert!(!is_websocket_upgrade(&headers(&[("upgrade", "h2c")])));
+        assert!(!is_websocket_upgrade(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::config::ConfigState;
+use crate::events;
+use crate::lan;
+use crate::menu;
+
+// Language of the shell's own UI (the tray and app menus, native dialogs and the
+// window title), which the webview can't translate. Strings come from the
+// message catalogs in `src/locales`, bundled into the binary; missing keys fall
+// back to English. The locale follows the system unless one is chosen with
+// `set_locale`, which is stored as `locale` in `desktop.json` and emitted as
+// `locale-changed` so the frontend can follow. Placeholders are written
+// `{name}`.
+const FALLBACK: &str = "en";
+const CATALOGS: [(&str, &str); 3] = [
+    ("en", include_str!("locales/en.json")),
+    ("de", include_str!("locales/de.json")),
+    ("fr", include_str!("locales/fr.json")),
+];
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct LocaleInfo {
+    // The catalog in use
+    pub locale: String,
+    // Chosen with `set_locale`; null follows the system
+    pub preferred: Option<String>,
+    pub system: Option<String>,
+    pub available: Vec<String>,
+}
+
+pub struct LocaleState {
+    catalogs: HashMap<&'static str, HashMap<String, String>>,
+    locale: Mutex<&'static str>,
+}
+
+fn system_locale() -> Option<String> {
+    sys_locale::get_locale().map(|locale| locale.replace('_', "-"))
+}
+
+// The catalog for a locale such as `de-AT`: an exact match, or else one for its
+// language
+fn find(requested: &str) -> Option<&'static str> {
+    let language = requested.split('-').next().unwrap_or(requested);
+    [requested, language].into_iter().find_map(|candidate| {
+        CATALOGS
+            .iter()
+            .map(|(locale, _)| *locale)
+            .find(|locale| locale.eq_ignore_ascii_case(candidate))
+    })
+}
+
+impl LocaleState {
+    pub fn load(preferred: Option<&str>) -> Self {
+        let catalogs = CATALOGS
+            .iter()
+            .map(|(locale, contents)| {
+                let messages = serde_json::from_str(contents).unwrap_or_else(|e| {
+                    eprintln!("Invalid message catalog for {}: {}", locale, e);
+                    HashMap::new()
+                });
+                (*locale, messages)
+            })
+            .collect();
+        let locale = preferred
+            .and_then(find)
+            .or_else(|| system_locale().and_then(|system| find(&system)));
+        Self {
+            catalogs,
+            locale: Mutex::new(locale.unwrap_or(FALLBACK)),
+        }
+    }
+
+    pub fn locale(&self) -> &'static str {
+        *self.locale.lock().unwrap()
+    }
+
+    // The message for `key`, with each `{name}` replaced from `args`
+    pub fn text(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let locale = self.locale();
+        let message = [locale, FALLBACK]
+            .iter()
+            .find_map(|locale| self.catalogs.get(locale)?.get(key))
+            .map(String::as_str)
+            .unwrap_or(key);
+        args.iter()
+            .fold(message.to_string(), |message, (name, value)| {
+                message.replace(&format!("{{{}}}", name), value)
+            })
+    }
+}
+
+// Shorthand for `LocaleState::text`, with `{app}` filled in with the app's name
+pub fn text(app: &AppHandle, key: &str, args: &[(&str, &str)]) -> String {
+    let name = app.package_info().name.clone();
+    let mut args = args.to_vec();
+    args.push(("app", &name));
+    app.state::<LocaleState>().text(key, &args)
+}
+
+fn info(app: &AppHandle) -> LocaleInfo {
+    LocaleInfo {
+        locale: app.state::<LocaleState>().locale().to_string(),
+        preferred: app.state::<ConfigState>().get().locale,
+        system: system_locale(),
+        available: CATALOGS
+            .iter()
+            .map(|(locale, _)| locale.to_string())
+            .collect(),
+    }
+}
+
+// Command to get the shell's locale and the ones it has translations for
+#[tauri::command]
+#[specta::specta]
+pub fn get_locale(app: AppHandle) -> LocaleInfo {
+    info(&app)
+}
+
+// Command to choose the shell's locale, or to follow the system again with null
+#[tauri::command]
+#[specta::specta]
+pub fn set_locale(
+    app: AppHandle,
+    state: State<LocaleState>,
+    locale: Option<String>,
+) -> Result<LocaleInfo, String> {
+    let resolved = match &locale {
+        Some(requested) => {
+            find(requested).ok_or_else(|| format!("No translations for {}", requested))?
+        }
+        None => system_locale()
+            .and_then(|system| find(&system))
+            .unwrap_or(FALLBACK),
+    };
+
+    app.state::<ConfigState>()
+        .update(|config| config.locale = locale.clone())?;
+    *state.locale.lock().unwrap() = resolved;
+    println!("Shell locale set to {}", resolved);
+
+    // Rebuild everything already showing text
+    menu::refresh(&app);
+    lan::refresh_title(&app);
+
+    let info = info(&app);
+    let _ = events::LOCALE_CHANGED.emit(&app, &info);
+    Ok(info)
+}
@@ -1,169 +1,320 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::process::Command;
-use std::thread;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tauri::{Manager, AppHandle};
-use std::path::PathBuf;
+mod announcements;
+mod auth;
+mod backend;
+mod backup;
+mod battery;
+mod bindings;
+mod ble;
+mod certificates;
+mod clipboard;
+mod config;
+mod connectivity;
+mod data_dir;
+mod deep_link;
+mod dev;
+mod discovery;
+mod disk_space;
+mod downloads;
+mod error;
+mod events;
+mod export;
+mod fs_watch;
+mod headless;
+mod import;
+mod integrity;
+mod lan;
+mod locale;
+mod menu;
+mod mock_backend;
+mod modbus;
+mod offline_update;
+mod open_file;
+mod paths;
+mod permissions;
+mod power;
+mod proxy;
+mod rollback;
+mod serial;
+mod server;
+mod service;
+mod session_lock;
+mod sidecars;
+mod sse;
+mod startup;
+mod storage;
+mod system_info;
+mod telemetry;
+mod time_sync;
+mod tls;
+mod updater;
+mod usb;
+mod version;
+mod workspace;
+mod ws_bridge;
+
+use std::path::Path;
+use std::time::Instant;
+
+use reqwest::Method;
+use tauri::{Manager, RunEvent, State, WebviewWindowBuilder};
+
+use announcements::AnnouncementState;
+use backup::BackupState;
+use battery::BatteryState;
+use ble::BleState;
+use certificates::CertificateState;
+use config::ConfigState;
+use connectivity::NetworkState;
+use data_dir::DataDirState;
+use deep_link::DeepLinkState;
+use dev::DevState;
+use disk_space::DiskSpaceState;
+use downloads::DownloadState;
+use error::{ErrorCode, ShellError};
+use fs_watch::FsWatchState;
+use lan::LanState;
+use locale::LocaleState;
+use modbus::ModbusState;
+use open_file::OpenFileState;
+use paths::AppPaths;
+use permissions::PermissionState;
+use power::PowerState;
+use serial::SerialState;
+use server::ServerState;
+use session_lock::{guard_plugin, SessionLockState};
+use sidecars::SidecarState;
+use sse::SseState;
+use storage::StorageState;
+use telemetry::TelemetryState;
+use usb::UsbState;
+use ws_bridge::BridgeState;
 
 fn main() {
-    // Flag to track if backend is ready
-    let backend_ready = Arc::new(AtomicBool::new(false));
-    let backend_ready_clone = backend_ready.clone();
-
-    // Start the Node.js backend server
-    thread::spawn(move || {
-        println!("Starting backend server...");
-        
-        // Use resource directory for backend path
-        let resource_dir = std::env::current_exe()
-            .ok()
-            .and_then(|path| path.parent().map(|p| p.to_path_buf()))
-            .unwrap_or_else(|| PathBuf::from("."));
-        
-        let backend_path = resource_dir.join("backend").join("index.js");
-        let backend_exists = backend_path.exists();
-        
-        if !backend_exists {
-            println!("Backend not found at: {:?}", backend_path);
-            println!("Building backend...");
-            // Build the backend first if needed
-            let build_output = Command::new("npm")
-                .args(&["run", "build"])
-                .current_dir(resource_dir.parent().unwrap_or(&resource_dir))
-                .output();
-                
-            match build_output {
-                Ok(output) => {
-                    if !output.status.success() {
-                        eprintln!("Backend build failed: {}", String::from_utf8_lossy(&output.stderr));
-                    } else {
-                        println!("Backend built successfully");
-                    }
-                }
-                Err(e) => eprintln!("Failed to build backend: {}", e),
-            }
+    let launched = Instant::now();
+    let headless = headless::requested();
+    let dev = dev::requested();
+    if headless {
+        headless::attach_console();
+    }
+    if service::requested() {
+        service::start_dispatcher();
+    }
+
+    let mut context = tauri::generate_context!();
+    let paths = AppPaths::resolve(&context);
+    // Headless, the backend is all that runs, so no window is ever created. In
+    // portable mode windows are created in setup instead, where the webview can be
+    // given a data directory beside the executable.
+    let windows = if headless || paths.portable {
+        std::mem::take(&mut context.config_mut().app.windows)
+    } else {
+        Vec::new()
+    };
+    let config = ConfigState::load(&paths);
+    let storage = StorageState::open(&paths, &config.get().workspaces.active);
+    let shell_config = config.get();
+    let telemetry = TelemetryState::load(&paths, &shell_config.telemetry);
+    let locale = LocaleState::load(shell_config.locale.as_deref());
+    let sidecars = SidecarState::load(&paths, context.config());
+    let commands = bindings::builder();
+    if dev && !headless {
+        dev::use_dev_server(context.config_mut(), &shell_config.dev);
+        bindings::export(&commands, &shell_config.dev);
+    }
+    if !dev {
+        integrity::prune_stale_sidecar(
+            &paths,
+            &server::sidecar_binary_names(),
+            &context.package_info().version.to_string(),
+            updater::configured_pubkey(context.config()).as_deref(),
+        );
+    }
+    let mut server_state = ServerState::new(
+        &paths,
+        shell_config.backend_client,
+        shell_config.backend_process,
+        dev.then_some(shell_config.dev),
+        mock_backend::requested().then_some(shell_config.mock_backend),
+        server::configured_port(context.config()),
+        updater::configured_pubkey(context.config()),
+    );
+    if !headless {
+        if let Some(token) = headless::running_backend(&paths) {
+            server_state.attach(token);
         }
+    }
+
+    if headless {
+        headless::publish(&paths, &server_state);
+    }
+
+    let mut builder = tauri::Builder::default();
+    if !headless {
+        // Launching the app again focuses the running window and hands it any
+        // deep link or file. A headless shell runs alongside the windowed one, so
+        // it doesn't take part.
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            deep_link::focus_window(app);
+            open_file::open_args(app, &args, Path::new(&cwd));
+        }));
+    }
+
+    let app = builder
+        .plugin(guard_plugin(tauri_plugin_shell::init()))
+        .plugin(guard_plugin(tauri_plugin_dialog::init()))
+        .plugin(guard_plugin(tauri_plugin_clipboard_manager::init()))
+        .plugin(guard_plugin(tauri_plugin_updater::Builder::new().build()))
+        .plugin(guard_plugin(tauri_plugin_deep_link::init()))
+        .manage(paths)
+        .manage(config)
+        .manage(server_state)
+        .manage(BridgeState::default())
+        .manage(LanState::default())
+        .manage(NetworkState::default())
+        .manage(SseState::default())
+        .manage(AnnouncementState::default())
+        .manage(SerialState::default())
+        .manage(UsbState::default())
+        .manage(BleState::default())
+        .manage(ModbusState::default())
+        .manage(PowerState::default())
+        .manage(FsWatchState::default())
+        .manage(BatteryState::default())
+        .manage(storage)
+        .manage(telemetry)
+        .manage(locale)
+        .manage(sidecars)
+        .manage(DataDirState::default())
+        .manage(BackupState::default())
+        .manage(DownloadState::default())
+        .manage(DiskSpaceState::default())
+        .manage(CertificateState::default())
+        .manage(PermissionState::default())
+        .manage(SessionLockState::default())
+        .manage(DeepLinkState::default())
+        .manage(OpenFileState::default())
+        .manage(DevState::default())
+        .setup(move |app| {
+            // The backend starts alongside the window rather than before it
+            startup::start_backend(app.handle(), launched, headless);
+            telemetry::init(app.handle());
 
-        // Start the backend server
-        let mut child = Command::new("node")
-            .arg(backend_path.to_str().unwrap_or("backend/index.js"))
-            .current_dir(&resource_dir)
-            .env("NODE_ENV", "production")
-            .env("DESKTOP", "true")
-            .spawn()
-            .expect("Failed to start backend server");
-        
-        // Poll backend health endpoint instead of hardcoded sleep
-        println!("Waiting for backend to be ready...");
-        let start_time = std::time::Instant::now();
-        let timeout = std::time::Duration::from_secs(30);
-        
-        loop {
-            // Try common API ports
-            let ports = vec![8080, 7500, 5000, 3000];
-            let mut backend_found = false;
-            for port in &ports {
-                if let Ok(response) = reqwest::blocking::Client::new()
-                    .get(&format!("http://localhost:{}/api/health", port))
-                    .timeout(std::time::Duration::from_secs(1))
-                    .send() 
-                {
-                    if response.status().is_success() {
-                        backend_ready_clone.store(true, Ordering::Relaxed);
-                        println!("Backend server is ready on port {}!", port);
-                        backend_found = true;
-                        break;
+            if !headless {
+                let webview_dir = app.state::<AppPaths>().webview_dir();
+                for window in windows.iter().filter(|window| window.create) {
+                    let mut builder = WebviewWindowBuilder::from_config(app, window)?;
+                    if let Some(dir) = &webview_dir {
+                        builder = builder.data_directory(dir.clone());
                     }
+                    builder.build()?;
                 }
+                menu::init(app.handle());
+                println!(
+                    "Window created {} ms after launch",
+                    launched.elapsed().as_millis()
+                );
             }
-            
-            if backend_found {
-                break;
-            }
-            
-            if start_time.elapsed() > timeout {
-                eprintln!("Backend failed to start within timeout");
-                break;
-            }
-            
-            thread::sleep(std::time::Duration::from_millis(500));
-        }
-        
-        let _ = child.wait();
-    });
 
-    // Wait for backend to be ready
-    println!("Waiting for backend to start...");
-    while !backend_ready.load(Ordering::Relaxed) {
-        thread::sleep(std::time::Duration::from_millis(100));
-    }
-
-    tauri::Builder::default()
-        .plugin(tauri_plugin_shell::init())
-        .setup(|app| {
-            #[cfg(debug_assertions)]
-            {
+            if dev {
+                // DevTools are only compiled into debug and `dev-mode` builds
+                #[cfg(any(debug_assertions, feature = "dev-mode"))]
                 if let Some(window) = app.get_webview_window("main") {
                     window.open_devtools();
                 }
+                dev::watch_backend(app.handle());
             }
-            
+
             // Log app data directory for debugging
-            if let Ok(app_dir) = app.path().app_data_dir() {
-                println!("App data directory: {:?}", app_dir);
+            let paths = app.state::<AppPaths>();
+            if paths.portable {
+                println!("Running portable");
             }
-            
+            println!("App data directory: {:?}", paths.data_dir);
+
             // Log app log directory
-            if let Ok(log_dir) = app.path().app_log_dir() {
-                println!("App log directory: {:?}", log_dir);
+            println!("App log directory: {:?}", paths.log_dir);
+
+            ws_bridge::start_bridge(app.handle().clone());
+            lan::init(app.handle());
+            connectivity::start_monitor(app.handle().clone());
+            usb::start_monitor(app.handle().clone());
+            power::start_monitor(app.handle().clone());
+            battery::start_monitor(app.handle().clone());
+            import::init(app.handle());
+            backup::start_scheduler(app.handle().clone());
+            disk_space::start_monitor(app.handle().clone());
+            certificates::start_monitor(app.handle().clone());
+            session_lock::start_monitor(app.handle().clone());
+            if headless {
+                headless::init(app.handle());
+                service::init(app.handle());
+            } else {
+                deep_link::init(app.handle());
+                open_file::init(app.handle());
             }
-            
+
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            get_logs,
-            clear_logs
-        ])
-        .run(tauri::generate_context!())
+        .on_window_event(import::handle_window_event)
+        .on_page_load(session_lock::handle_page_load)
+        .invoke_handler(session_lock::guard_commands(commands.invoke_handler()))
+        .build(context)
         .expect("error while running tauri application");
+
+    app.run(move |app_handle, event| {
+        // Don't leave the backend running after the shell exits
+        if let RunEvent::Exit = event {
+            server::shut_down(&app_handle.state::<ServerState>());
+            sidecars::stop_all(&app_handle.state::<SidecarState>());
+            if headless {
+                headless::withdraw(&app_handle.state::<AppPaths>());
+                service::report_stopped();
+            }
+        }
+        if !headless {
+            open_file::handle_run_event(app_handle, &event);
+        }
+    });
 }
 
 // Command to get logs from the backend API
 #[tauri::command]
-async fn get_logs() -> Result<serde_json::Value, String> {
+#[specta::specta]
+async fn get_logs(state: State<'_, ServerState>) -> Result<serde_json::Value, ShellError> {
     // Call the Node.js backend API instead of direct file access
-    let client = reqwest::Client::new();
-    let response = client
-        .get("http://localhost:8080/api/logs/entries?limit=1000")
-        .send()
+    let request = backend::request(&state, Method::GET, "/api/logs/entries?limit=1000")
+        .map_err(|e| ShellError::new(ErrorCode::BackendUnavailable, e))?;
+    let response = backend::send(&state, request)
         .await
-        .map_err(|e| e.to_string())?;
-    
-    if response.status().is_success() {
+        .map_err(|e| ShellError::new(ErrorCode::BackendUnavailable, e))?;
+
+    let status = response.status();
+    if status.is_success() {
         response.json::<serde_json::Value>()
             .await
-            .map_err(|e| e.to_string())
+            .map_err(|e| ShellError::new(ErrorCode::InvalidResponse, e.to_string()))
     } else {
-        Err(format!("Failed to fetch logs: {}", response.status()))
+        Err(ShellError::status(status, format!("Failed to fetch logs: {}", status)))
     }
 }
 
 // Command to clear logs via backend API
 #[tauri::command]
-async fn clear_logs() -> Result<(), String> {
-    let client = reqwest::Client::new();
-    let response = client
-        .post("http://localhost:8080/api/logs/clear")
-        .send()
+#[specta::specta]
+async fn clear_logs(state: State<'_, ServerState>) -> Result<(), ShellError> {
+    let request = backend::request(&state, Method::POST, "/api/logs/clear")
+        .map_err(|e| ShellError::new(ErrorCode::BackendUnavailable, e))?;
+    let response = backend::send(&state, request)
         .await
-        .map_err(|e| e.to_string())?;
-    
-    if response.status().is_success() {
+        .map_err(|e| ShellError::new(ErrorCode::BackendUnavailable, e))?;
+
+    let status = response.status();
+    if status.is_success() {
         Ok(())
     } else {
-        Err(format!("Failed to clear logs: {}", response.status()))
+        Err(ShellError::status(status, format!("Failed to clear logs: {}", status)))
     }
 }
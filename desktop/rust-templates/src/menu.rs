@@ -0,0 +1,110 @@
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::AppHandle;
+
+use crate::deep_link;
+use crate::locale;
+
+// The shell's menus: the tray icon's (configured as `app.trayIcon` in
+// `tauri.conf.json`) and, on macOS, the app menu, which Tauri would otherwise
+// create in English. Both are built from the locale's message catalog and
+// rebuilt when it changes. Windows and Linux get no menu bar.
+const TRAY_ID: &str = "main";
+const SHOW: &str = "show";
+const QUIT: &str = "quit";
+
+fn tray_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(
+                app,
+                SHOW,
+                locale::text(app, "tray.show", &[]),
+                true,
+                None::<&str>,
+            )?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(
+                app,
+                QUIT,
+                locale::text(app, "tray.quit", &[]),
+                true,
+                None::<&str>,
+            )?,
+        ],
+    )
+}
+
+fn app_menu(app: &AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let text = |key| locale::text(app, key, &[]);
+    let name = app.package_info().name.clone();
+    let app_submenu = Submenu::with_items(
+        app,
+        &name,
+        true,
+        &[
+            &PredefinedMenuItem::about(app, Some(&text("menu.about")), None)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::hide(app, Some(&text("menu.hide")))?,
+            &PredefinedMenuItem::hide_others(app, Some(&text("menu.hideOthers")))?,
+            &PredefinedMenuItem::show_all(app, Some(&text("menu.showAll")))?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::quit(app, Some(&text("menu.quit")))?,
+        ],
+    )?;
+    let edit = Submenu::with_items(
+        app,
+        text("menu.edit"),
+        true,
+        &[
+            &PredefinedMenuItem::undo(app, Some(&text("menu.undo")))?,
+            &PredefinedMenuItem::redo(app, Some(&text("menu.redo")))?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::cut(app, Some(&text("menu.cut")))?,
+            &PredefinedMenuItem::copy(app, Some(&text("menu.copy")))?,
+            &PredefinedMenuItem::paste(app, Some(&text("menu.paste")))?,
+            &PredefinedMenuItem::select_all(app, Some(&text("menu.selectAll")))?,
+        ],
+    )?;
+    let window = Submenu::with_items(
+        app,
+        text("menu.window"),
+        true,
+        &[
+            &PredefinedMenuItem::minimize(app, Some(&text("menu.minimize")))?,
+            &PredefinedMenuItem::fullscreen(app, Some(&text("menu.fullscreen")))?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::close_window(app, Some(&text("menu.closeWindow")))?,
+        ],
+    )?;
+    Menu::with_items(app, &[&app_submenu, &edit, &window])
+}
+
+fn handle_tray_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        SHOW => deep_link::focus_window(app),
+        QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+// Build the menus in the current locale
+pub fn refresh(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(e) = tray_menu(app).and_then(|menu| tray.set_menu(Some(menu))) {
+            eprintln!("Failed to set the tray menu: {}", e);
+        }
+    }
+    if cfg!(target_os = "macos") {
+        if let Err(e) = app_menu(app).and_then(|menu| app.set_menu(menu)) {
+            eprintln!("Failed to set the app menu: {}", e);
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        tray.on_menu_event(handle_tray_event);
+    }
+    refresh(app);
+}
@@ -0,0 +1,283 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use axum::extract::{Request, State as AxumState};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::oneshot;
+
+use crate::auth;
+
+// Mock backend, started with `--mock-backend`: the shell serves canned responses
+// from an embedded HTTP server in place of the Node sidecar, so frontend work
+// doesn't need the packaged backend. Routes from `mockBackend.routes` come first,
+// then those in `mockBackend.routesFile` (re-read on every restart, so fixtures
+// can be edited without restarting the app), then the built-in health and log
+// endpoints. Like the real backend it listens on 127.0.0.1 and requires the
+// shell's token.
+pub const FLAG: &str = "--mock-backend";
+const BIND_ATTEMPTS: u32 = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MockRoute {
+    pub method: String,
+    // Exact, or a prefix when it ends in `*`. The query string is ignored.
+    pub path: String,
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl Default for MockRoute {
+    fn default() -> Self {
+        Self {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            status: 200,
+            body: serde_json::Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MockBackendConfig {
+    pub port: u16,
+    pub routes: Vec<MockRoute>,
+    // JSON array of routes, e.g. fixtures kept with the frontend
+    pub routes_file: Option<String>,
+    // Added to every response, to see how the frontend copes with a slow backend
+    pub delay_ms: u64,
+}
+
+impl Default for MockBackendConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            routes: Vec::new(),
+            routes_file: None,
+            delay_ms: 0,
+        }
+    }
+}
+
+struct MockContext {
+    token: String,
+    routes: Vec<MockRoute>,
+    delay: Duration,
+}
+
+pub struct MockBackend {
+    config: MockBackendConfig,
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == FLAG)
+}
+
+fn builtin_routes() -> Vec<MockRoute> {
+    vec![
+        MockRoute {
+            path: "/api/health".to_string(),
+            body: json!({ "status": "healthy", "mock": true }),
+            ..MockRoute::default()
+        },
+        MockRoute {
+            path: "/api/logs/entries".to_string(),
+            body: json!({
+                "success": true,
+                "logs": [{
+                    "timestamp": "2024-01-01T00:00:00.000Z",
+                    "level": "info",
+                    "message": "Mock backend started",
+                }],
+            }),
+            ..MockRoute::default()
+        },
+        MockRoute {
+            method: "POST".to_string(),
+            path: "/api/logs/clear".to_string(),
+            body: json!({ "success": true, "message": "Logs cleared successfully" }),
+            ..MockRoute::default()
+        },
+    ]
+}
+
+fn matches(route: &MockRoute, method: &str, path: &str) -> bool {
+    if !route.method.eq_ignore_ascii_case(method) {
+        return false;
+    }
+    match route.path.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => route.path == path,
+    }
+}
+
+async fn handler(AxumState(context): AxumState<Arc<MockContext>>, request: Request) -> Response {
+    let authorized = request
+        .headers()
+        .get(auth::TOKEN_HEADER)
+        .map(|token| token.as_bytes() == context.token.as_bytes())
+        .unwrap_or(false);
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    if !context.delay.is_zero() {
+        tokio::time::sleep(context.delay).await;
+    }
+
+    let method = request.method().as_str();
+    let path = request.uri().path();
+    match context
+        .routes
+        .iter()
+        .find(|route| matches(route, method, path))
+    {
+        Some(route) => {
+            let status = StatusCode::from_u16(route.status).unwrap_or(StatusCode::OK);
+            (status, Json(route.body.clone())).into_response()
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "error": format!("No mock response for {} {}", method, path),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+// Bound before serving so a taken port is reported to the caller. A restart may
+// come before the previous server has let go of the port, so binding is retried
+// briefly.
+fn bind(port: u16) -> Result<std::net::TcpListener, String> {
+    let mut attempts = 0;
+    loop {
+        let result = std::net::TcpListener::bind(("127.0.0.1", port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener));
+        match result {
+            Ok(listener) => return Ok(listener),
+            Err(e) if attempts >= BIND_ATTEMPTS => {
+                return Err(format!("Failed to listen on port {}: {}", port, e))
+            }
+            Err(_) => {
+                attempts += 1;
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+impl MockBackend {
+    pub fn new(config: MockBackendConfig) -> Self {
+        Self {
+            config,
+            shutdown: Mutex::new(None),
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.config.port
+    }
+
+    fn routes(&self) -> Vec<MockRoute> {
+        let mut routes = self.config.routes.clone();
+        if let Some(file) = &self.config.routes_file {
+            let loaded = fs::read_to_string(file)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| {
+                    serde_json::from_str::<Vec<MockRoute>>(&contents).map_err(|e| e.to_string())
+                });
+            match loaded {
+                Ok(loaded) => routes.extend(loaded),
+                Err(e) => eprintln!("Failed to load mock routes from {}: {}", file, e),
+            }
+        }
+        routes.extend(builtin_routes());
+        routes
+    }
+
+    // Start serving unless already running, returning whether it was started
+    pub fn start(&self, token: &str) -> Result<bool, String> {
+        let mut shutdown = self.shutdown.lock().unwrap();
+        if shutdown.is_some() {
+            return Ok(false);
+        }
+
+        let listener = bind(self.config.port)?;
+        let context = Arc::new(MockContext {
+            token: token.to_string(),
+            routes: self.routes(),
+            delay: Duration::from_millis(self.config.delay_ms),
+        });
+        let router = axum::Router::new().fallback(handler).with_state(context);
+
+        let (sender, shutdown_signal) = oneshot::channel::<()>();
+        *shutdown = Some(sender);
+
+        tauri::async_runtime::spawn(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Mock backend failed to start: {}", e);
+                    return;
+                }
+            };
+            let server = axum::serve(listener, router).with_graceful_shutdown(async {
+                let _ = shutdown_signal.await;
+            });
+            if let Err(e) = server.await {
+                eprintln!("Mock backend stopped: {}", e);
+            }
+        });
+
+        println!("Starting mock backend on port {}", self.config.port);
+        Ok(true)
+    }
+
+    pub fn stop(&self) {
+        if let Some(shutdown) = self.shutdown.lock().unwrap().take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: &str, path: &str) -> MockRoute {
+        MockRoute {
+            method: method.to_string(),
+            path: path.to_string(),
+            ..MockRoute::default()
+        }
+    }
+
+    #[test]
+    fn matches_exact_paths() {
+        let route = route("GET", "/api/devices");
+        assert!(matches(&route, "GET", "/api/devices"));
+        assert!(matches(&route, "get", "/api/devices"));
+        assert!(!matches(&route, "POST", "/api/devices"));
+        assert!(!matches(&route, "GET", "/api/devices/1"));
+        assert!(!matches(&route, "GET", "/api/device"));
+    }
+
+    #[test]
+    fn matches_prefixes_ending_in_a_wildcard() {
+        let route = route("GET", "/api/devices/*");
+        assert!(matches(&route, "GET", "/api/devices/1"));
+        assert!(matches(&route, "GET", "/api/devices/"));
+        assert!(!matches(&route, "GET", "/api/devices"));
+        assert!(!matches(&route, "GET", "/api/other"));
+    }
+}
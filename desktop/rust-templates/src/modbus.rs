@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tauri::State;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Minimal Modbus TCP client for diagnostic reads against meters, which has to
+// work even when the backend is down or not configured yet. Connections are kept
+// open per device and reused across commands.
+const DEFAULT_PORT: u16 = 502;
+const DEFAULT_UNIT_ID: u8 = 1;
+const TIMEOUT: Duration = Duration::from_secs(3);
+// Protocol limit for a single read
+const MAX_READ_COUNT: u16 = 125;
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+
+struct Connection {
+    stream: TcpStream,
+    transaction_id: u16,
+}
+
+type SharedConnection = Arc<tokio::sync::Mutex<Connection>>;
+
+#[derive(Default)]
+pub struct ModbusState {
+    connections: Mutex<HashMap<String, SharedConnection>>,
+}
+
+fn exception_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "illegal function",
+        0x02 => "illegal data address",
+        0x03 => "illegal data value",
+        0x04 => "server device failure",
+        0x06 => "server device busy",
+        0x0A => "gateway path unavailable",
+        0x0B => "gateway target device failed to respond",
+        _ => "unknown exception",
+    }
+}
+
+async fn connection(state: &ModbusState, address: &str) -> Result<SharedConnection, String> {
+    if let Some(connection) = state.connections.lock().unwrap().get(address) {
+        return Ok(connection.clone());
+    }
+
+    let stream = tokio::time::timeout(TIMEOUT, TcpStream::connect(address))
+        .await
+        .map_err(|_| format!("Timed out connecting to {}", address))?
+        .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+    let _ = stream.set_nodelay(true);
+
+    let connection = Arc::new(tokio::sync::Mutex::new(Connection {
+        stream,
+        transaction_id: 0,
+    }));
+    state
+        .connections
+        .lock()
+        .unwrap()
+        .insert(address.to_string(), connection.clone());
+    Ok(connection)
+}
+
+// Send one request PDU framed with an MBAP header and return the response PDU
+async fn exchange(
+    connection: &mut Connection,
+    unit_id: u8,
+    pdu: &[u8],
+) -> std::io::Result<Vec<u8>> {
+    connection.transaction_id = connection.transaction_id.wrapping_add(1);
+    let transaction_id = connection.transaction_id;
+
+    let mut frame = Vec::with_capacity(7 + pdu.len());
+    frame.extend_from_slice(&transaction_id.to_be_bytes());
+    frame.extend_from_slice(&0u16.to_be_bytes());
+    frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(unit_id);
+    frame.extend_from_slice(pdu);
+    connection.stream.write_all(&frame).await?;
+
+    loop {
+        let mut header = [0u8; 7];
+        connection.stream.read_exact(&mut header).await?;
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if length < 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid Modbus frame length",
+            ));
+        }
+
+        let mut response = vec![0u8; length - 1];
+        connection.stream.read_exact(&mut response).await?;
+
+        // Skip late responses to earlier requests that timed out
+        if u16::from_be_bytes([header[0], header[1]]) == transaction_id {
+            return Ok(response);
+        }
+    }
+}
+
+// `host:port`, with IPv6 literals in brackets so the port can be told apart
+fn socket_address(host: &str, port: u16) -> String {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<Ipv6Addr>() {
+        Ok(ip) => SocketAddr::from((ip, port)).to_string(),
+        Err(_) => format!("{}:{}", host, port),
+    }
+}
+
+// Run a request against a device, reconnecting once if the pooled connection has
+// gone stale
+async fn request(
+    state: &ModbusState,
+    host: &str,
+    port: Option<u16>,
+    unit_id: Option<u8>,
+    pdu: &[u8],
+) -> Result<Vec<u8>, String> {
+    let address = socket_address(host, port.unwrap_or(DEFAULT_PORT));
+    let unit_id = unit_id.unwrap_or(DEFAULT_UNIT_ID);
+    let mut attempt = 0;
+
+    let response = loop {
+        let connection = connection(state, &address).await?;
+        let result = {
+            let mut connection = connection.lock().await;
+            tokio::time::timeout(TIMEOUT, exchange(&mut connection, unit_id, pdu)).await
+        };
+
+        match result {
+            Ok(Ok(response)) => break response,
+            failure => {
+                state.connections.lock().unwrap().remove(&address);
+                let error = match failure {
+                    Ok(Err(e)) => e.to_string(),
+                    _ => "timed out".to_string(),
+                };
+                attempt += 1;
+                if attempt > 1 {
+                    return Err(format!("Modbus request to {} failed: {}", address, error));
+                }
+            }
+        }
+    };
+
+    match response.first() {
+        Some(function) if function & 0x80 != 0 => {
+            let code = response.get(1).copied().unwrap_or(0);
+            Err(format!(
+                "Modbus exception {}: {}",
+                code,
+                exception_message(code)
+            ))
+        }
+        Some(function) if *function == pdu[0] => Ok(response),
+        _ => Err("Unexpected Modbus response".to_string()),
+    }
+}
+
+// Command to read holding registers (or input registers when `input` is set)
+#[tauri::command]
+#[specta::specta]
+pub async fn modbus_read_registers(
+    state: State<'_, ModbusState>,
+    host: String,
+    port: Option<u16>,
+    unit_id: Option<u8>,
+    address: u16,
+    count: u16,
+    input: Option<bool>,
+) -> Result<Vec<u16>, String> {
+    if count == 0 || count > MAX_READ_COUNT {
+        return Err(format!(
+            "Register count must be between 1 and {}",
+            MAX_READ_COUNT
+        ));
+    }
+
+    let function = if input.unwrap_or(false) {
+        READ_INPUT_REGISTERS
+    } else {
+        READ_HOLDING_REGISTERS
+    };
+    let mut pdu = vec![function];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&count.to_be_bytes());
+
+    let response = request(&state, &host, port, unit_id, &pdu).await?;
+    let data = response.get(2..).unwrap_or_default();
+    if data.len() != count as usize * 2 {
+        return Err("Modbus response has the wrong number of registers".to_string());
+    }
+
+    Ok(data
+        .chunks_exact(2)
+        .map(|register| u16::from_be_bytes([register[0], register[1]]))
+        .collect())
+}
+
+// Command to write a single holding register
+#[tauri::command]
+#[specta::specta]
+pub async fn modbus_write_register(
+    state: State<'_, ModbusState>,
+    host: String,
+    port: Option<u16>,
+    unit_id: Option<u8>,
+    address: u16,
+    value: u16,
+) -> Result<(), String> {
+    let mut pdu = vec![WRITE_SINGLE_REGISTER];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&value.to_be_bytes());
+
+    request(&state, &host, port, unit_id, &pdu).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brackets_ipv6_literals() {
+        assert_eq!(socket_address("192.168.1.10", 502), "192.168.1.10:502");
+        assert_eq!(socket_address("meter.local", 1502), "meter.local:1502");
+        assert_eq!(socket_address("fe80::1", 502), "[fe80::1]:502");
+        assert_eq!(socket_address("[::1]", 502), "[::1]:502");
+    }
+}
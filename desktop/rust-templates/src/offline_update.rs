@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::events;
+use crate::locale;
+use crate::paths::AppPaths;
+use crate::permissions::{self, Access};
+use crate::telemetry::{self, UsageEvent};
+use crate::updater;
+
+// Offline updates for sites without internet access. An update bundle is a zip
+// containing a `manifest.json` and the signed artifacts it references:
+//
+// {
+//   "version": "1.5.0",
+//   "platforms": {
+//     "windows-x86_64": {
+//       "app": { "file": "MyApp_1.5.0_x64-setup.exe", "signature": "..." },
+//       "sidecar": { "file": "server-x86_64-pc-windows-msvc.exe", "signature": "..." }
+//     }
+//   }
+// }
+//
+// Signatures are produced with `tauri signer sign`, using the same key as online
+// updates. Either artifact may be omitted. The manifest itself is signed too, as
+// `manifest.json.sig`, so its version can't be altered to replay an older bundle.
+
+// Larger entries are refused rather than trusting the size in the zip header
+const MAX_ENTRY_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct BundleManifest {
+    version: String,
+    platforms: HashMap<String, BundlePlatform>,
+}
+
+#[derive(Deserialize)]
+struct BundlePlatform {
+    app: Option<BundleArtifact>,
+    sidecar: Option<BundleArtifact>,
+}
+
+#[derive(Deserialize)]
+struct BundleArtifact {
+    file: String,
+    signature: String,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct OfflineUpdate {
+    pub version: String,
+    pub app_updated: bool,
+    pub sidecar_updated: bool,
+}
+
+struct VerifiedArtifact {
+    file: String,
+    data: Vec<u8>,
+    signature: String,
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|_| format!("Update bundle is missing {}", name))?;
+    let mut data = Vec::new();
+    entry
+        .take(MAX_ENTRY_SIZE + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    if data.len() as u64 > MAX_ENTRY_SIZE {
+        return Err(format!("{} in the update bundle is too large", name));
+    }
+    Ok(data)
+}
+
+// Artifacts are written next to each other in the staging directory, so a name
+// has to be a single file name, not a path
+fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !name.contains(['/', '\\'])
+}
+
+fn read_artifact(
+    archive: &mut zip::ZipArchive<fs::File>,
+    artifact: &BundleArtifact,
+    pubkey: &str,
+) -> Result<VerifiedArtifact, String> {
+    if !is_file_name(&artifact.file) {
+        return Err(format!("Invalid artifact name in update bundle: {}", artifact.file));
+    }
+    let data = read_entry(archive, &artifact.file)?;
+    updater::verify_signature(&data, &artifact.signature, pubkey)
+        .map_err(|e| format!("{}: {}", artifact.file, e))?;
+
+    Ok(VerifiedArtifact {
+        file: artifact.file.clone(),
+        data,
+        signature: artifact.signature.clone(),
+    })
+}
+
+fn pick_bundle(app: &AppHandle) -> Result<PathBuf, String> {
+    app.dialog()
+        .file()
+        .set_title(locale::text(app, "dialog.updateBundle.title", &[]))
+        .add_filter(locale::text(app, "dialog.updateBundle.filter", &[]), &["zip"])
+        .blocking_pick_file()
+        .ok_or("No update bundle selected")?
+        .into_path()
+        .map_err(|e| e.to_string())
+}
+
+fn install_from_bundle(app: &AppHandle, path: &Path) -> Result<OfflineUpdate, String> {
+    println!("Installing update bundle: {:?}", path);
+    let file = fs::File::open(path).map_err(|e| format!("Failed to open update bundle: {}", e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Invalid update bundle: {}", e))?;
+
+    // Verify everything before touching the installation
+    let pubkey = updater::updater_pubkey(app)?;
+    let manifest_data = read_entry(&mut archive, "manifest.json")?;
+    let manifest_signature = String::from_utf8(read_entry(&mut archive, "manifest.json.sig")?)
+        .map_err(|_| "Invalid update bundle manifest signature".to_string())?;
+    updater::verify_signature(&manifest_data, manifest_signature.trim(), &pubkey)
+        .map_err(|e| format!("manifest.json: {}", e))?;
+    let manifest: BundleManifest = serde_json::from_slice(&manifest_data)
+        .map_err(|e| format!("Invalid update bundle manifest: {}", e))?;
+
+    // Even a sidecar-only bundle may not go back behind the installed app
+    let current_version = app.package_info().version.to_string();
+    if updater::is_newer(&current_version, &manifest.version)? {
+        return Err(format!(
+            "Update bundle {} is older than the installed version {}",
+            manifest.version, current_version
+        ));
+    }
+
+    let platform = manifest
+        .platforms
+        .get(&updater::platform_key())
+        .ok_or_else(|| format!("Update bundle has no artifacts for {}", updater::platform_key()))?;
+
+    let sidecar = match &platform.sidecar {
+        Some(artifact)
+            if updater::is_newer(&manifest.version, &updater::installed_sidecar_version(app))? =>
+        {
+            Some(read_artifact(&mut archive, artifact, &pubkey)?)
+        }
+        _ => None,
+    };
+
+    let app_artifact = match &platform.app {
+        Some(artifact) if updater::is_newer(&manifest.version, &current_version)? => {
+            Some(read_artifact(&mut archive, artifact, &pubkey)?)
+        }
+        _ => None,
+    };
+
+    if sidecar.is_none() && app_artifact.is_none() {
+        return Err(format!(
+            "Update bundle {} is not newer than the installed version",
+            manifest.version
+        ));
+    }
+
+    if let Some(sidecar) = &sidecar {
+        updater::apply_sidecar_update(
+            app,
+            &sidecar.data,
+            &sidecar.signature,
+            &manifest_data,
+            &manifest_signature,
+        )?;
+    }
+
+    let update = OfflineUpdate {
+        version: manifest.version,
+        app_updated: app_artifact.is_some(),
+        sidecar_updated: sidecar.is_some(),
+    };
+    let _ = events::OFFLINE_UPDATE_INSTALLED.emit(app, &update);
+    telemetry::record(app, UsageEvent::UpdateApplied, Some("offline"));
+
+    // Installing the app restarts or exits the shell, so it has to come last
+    if let Some(artifact) = app_artifact {
+        install_app_artifact(app, &artifact)?;
+    }
+
+    Ok(update)
+}
+
+// A fresh directory under the app's own data, private to this user, for an app
+// artifact that is then installed with privileges. A shared temp directory won't
+// do: another user could create it first, or swap the file once it is verified.
+fn staging_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let parent = app.state::<AppPaths>().update_staging_dir();
+    let _ = fs::remove_dir_all(&parent);
+    fs::create_dir_all(&parent).map_err(|e| e.to_string())?;
+
+    let dir = parent.join(format!("{:016x}", rand::thread_rng().gen::<u64>()));
+    // Fails if the directory already exists, rather than using one made by someone
+    // else
+    #[cfg(unix)]
+    let created = {
+        use std::os::unix::fs::DirBuilderExt;
+        fs::DirBuilder::new().mode(0o700).create(&dir)
+    };
+    #[cfg(not(unix))]
+    let created = fs::create_dir(&dir);
+    created.map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    Ok(dir)
+}
+
+// Write a verified artifact into a private staging directory. The file is new,
+// so what is installed is exactly the data that was verified.
+fn stage_artifact(dir: &Path, artifact: &VerifiedArtifact) -> Result<PathBuf, String> {
+    let path = dir.join(&artifact.file);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(&artifact.data))
+        .map_err(|e| format!("Failed to stage {}: {}", artifact.file, e))?;
+    Ok(path)
+}
+
+// Windows artifacts are the NSIS or MSI installers, which take over from here
+#[cfg(target_os = "windows")]
+fn install_app_artifact(app: &AppHandle, artifact: &VerifiedArtifact) -> Result<(), String> {
+    use std::process::Command;
+
+    let installer = stage_artifact(&staging_dir(app)?, artifact)?;
+
+    let mut command = if artifact.file.to_lowercase().ends_with(".msi") {
+        let mut command = Command::new("msiexec");
+        command.arg("/i").arg(&installer).arg("/passive");
+        command
+    } else {
+        let mut command = Command::new(&installer);
+        command.args(["/P", "/UPDATE"]);
+        command
+    };
+    command
+        .spawn()
+        .map_err(|e| format!("Failed to launch installer: {}", e))?;
+
+    app.exit(0);
+    Ok(())
+}
+
+// macOS artifacts are `.app.tar.gz` archives that replace the running bundle
+#[cfg(target_os = "macos")]
+fn install_app_artifact(app: &AppHandle, artifact: &VerifiedArtifact) -> Result<(), String> {
+    use std::process::Command;
+
+    let staging = staging_dir(app)?;
+    let archive = stage_artifact(&staging, artifact)?;
+
+    let extract_dir = staging.join("extracted");
+    fs::create_dir(&extract_dir).map_err(|e| e.to_string())?;
+
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir)
+        .status()
+        .map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err("Failed to extract app update".to_string());
+    }
+
+    let new_bundle = fs::read_dir(&extract_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().map(|ext| ext == "app").unwrap_or(false))
+        .ok_or("App update does not contain an .app bundle")?;
+
+    // <bundle>.app/Contents/MacOS/<exe>
+    let current_bundle = std::env::current_exe()
+        .map_err(|e| e.to_string())?
+        .ancestors()
+        .nth(3)
+        .map(|path| path.to_path_buf())
+        .ok_or("Could not locate the running app bundle")?;
+
+    let backup = staging.join("previous.app");
+    let _ = fs::remove_dir_all(&backup);
+    fs::rename(&current_bundle, &backup).map_err(|e| e.to_string())?;
+    if let Err(e) = fs::rename(&new_bundle, &current_bundle) {
+        let _ = fs::rename(&backup, &current_bundle);
+        return Err(format!("Failed to install app update: {}", e));
+    }
+
+    app.restart();
+}
+
+// Linux artifacts are either an AppImage replacing the running one, or a .deb
+#[cfg(target_os = "linux")]
+fn install_app_artifact(app: &AppHandle, artifact: &VerifiedArtifact) -> Result<(), String> {
+    use std::process::Command;
+
+    if artifact.file.ends_with(".deb") {
+        let package = stage_artifact(&staging_dir(app)?, artifact)?;
+
+        let status = Command::new("pkexec")
+            .arg("dpkg")
+            .arg("-i")
+            .arg(&package)
+            .status()
+            .map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err("Failed to install app update package".to_string());
+        }
+    } else {
+        let appimage = std::env::var_os("APPIMAGE")
+            .map(PathBuf::from)
+            .ok_or("App updates require running from an AppImage or a .deb artifact")?;
+
+        let staged = appimage.with_extension("new");
+        fs::write(&staged, &artifact.data).map_err(|e| e.to_string())?;
+        updater::make_executable(&staged)?;
+        fs::rename(&staged, &appimage).map_err(|e| e.to_string())?;
+    }
+
+    app.restart();
+}
+
+// Command to install a signed update bundle from disk. Without a path the user
+// is asked to pick the bundle with a file dialog; a path given by the caller
+// needs the user's permission.
+#[tauri::command]
+#[specta::specta]
+pub async fn install_update_from_file(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<OfflineUpdate, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = match path {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                permissions::ensure_access(&app, &path, Access::Read)?;
+                path
+            }
+            None => pick_bundle(&app)?,
+        };
+        install_from_bundle(&app, &path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_file_names() {
+        assert!(is_file_name("MyApp_1.5.0_x64-setup.exe"));
+        assert!(is_file_name("server-x86_64-pc-windows-msvc.exe"));
+    }
+
+    #[test]
+    fn rejects_paths() {
+        for name in [
+            "",
+            ".",
+            "..",
+            "../evil.exe",
+            "dir/evil.exe",
+            "dir\\evil.exe",
+            "/etc/evil",
+            "C:\\evil.exe",
+        ] {
+            assert!(!is_file_name(name), "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, RunEvent, State};
+
+use crate::events;
+use crate::session_lock;
+
+// Files opened with the app from the OS, e.g. by double-clicking a `.epx` project
+// export. The installers associate the extensions in `bundle.fileAssociations`
+// with the app. Windows and Linux pass the file as an argument, to the new
+// instance or forwarded to the running one; macOS sends an open event instead.
+// Each file is emitted as `open-file`. As with deep links, files are also kept
+// until `take_pending_open_files` is first called, since the file that launched
+// the app arrives before the frontend is listening. The backend runs on the same
+// machine, so the frontend can hand it the path to read.
+
+// Must match `bundle.fileAssociations` in `tauri.conf.json`
+pub const EXTENSIONS: &[&str] = &["epx"];
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct OpenedFile {
+    pub path: String,
+    pub name: String,
+    // Lowercase, without the dot
+    pub extension: String,
+}
+
+#[derive(Default)]
+pub struct OpenFileState {
+    pending: Mutex<Vec<OpenedFile>>,
+    collected: AtomicBool,
+}
+
+fn describe(path: &Path) -> Option<OpenedFile> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    if !EXTENSIONS.contains(&extension.as_str()) || !path.is_file() {
+        return None;
+    }
+
+    let path = path.canonicalize().ok()?;
+    Some(OpenedFile {
+        name: path.file_name()?.to_string_lossy().into_owned(),
+        path: path.to_string_lossy().into_owned(),
+        extension,
+    })
+}
+
+fn open(app: &AppHandle, paths: impl IntoIterator<Item = PathBuf>) {
+    if session_lock::is_locked(app) {
+        eprintln!("Dropped opened files while the session is locked");
+        return;
+    }
+
+    let state = app.state::<OpenFileState>();
+    for file in paths.into_iter().filter_map(|path| describe(&path)) {
+        println!("Opening {}", file.path);
+        if !state.collected.load(Ordering::SeqCst) {
+            state.pending.lock().unwrap().push(file.clone());
+        }
+        let _ = events::OPEN_FILE.emit(app, &file);
+    }
+}
+
+// Files in a command line, which may be relative to the directory it was run in.
+// The first argument is the executable and flags are skipped.
+pub fn open_args(app: &AppHandle, args: &[String], cwd: &Path) {
+    let paths = args
+        .iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg));
+    open(app, paths);
+}
+
+// Run event hook: on macOS, files opened with the app arrive as file URLs
+pub fn handle_run_event(app: &AppHandle, event: &RunEvent) {
+    #[cfg(target_os = "macos")]
+    if let RunEvent::Opened { urls } = event {
+        open(app, urls.iter().filter_map(|url| url.to_file_path().ok()));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    let _ = (app, event);
+}
+
+pub fn init(app: &AppHandle) {
+    let args: Vec<String> = std::env::args().collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    open_args(app, &args, &cwd);
+}
+
+// Command to collect the files opened before the frontend was listening
+#[tauri::command]
+#[specta::specta]
+pub fn take_pending_open_files(state: State<OpenFileState>) -> Vec<OpenedFile> {
+    let mut pending = state.pending.lock().unwrap();
+    state.collected.store(true, Ordering::SeqCst);
+    std::mem::take(&mut *pending)
+}
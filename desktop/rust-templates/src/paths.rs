@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use tauri::{Context, Runtime};
+
+// Portable mode, started with `--portable` or by a `portable` file beside the
+// executable: config, data and logs live in `portable-data` beside it instead of
+// the OS app-data directories, so the app can run off a USB stick without leaving
+// anything behind on the machine.
+pub const PORTABLE_FLAG: &str = "--portable";
+const PORTABLE_MARKER: &str = "portable";
+const PORTABLE_DIR: &str = "portable-data";
+
+// Directories used by the shell and the backend. These are resolved from the Tauri
+// context rather than an AppHandle because the backend is started before the
+// builder runs, and must agree with what `app.path()` reports later on.
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    pub resource_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+    pub log_dir: PathBuf,
+    pub portable: bool,
+}
+
+impl AppPaths {
+    pub fn resolve<R: Runtime>(context: &Context<R>) -> Self {
+        let identifier = &context.config().identifier;
+
+        // Fall back to the executable directory, which is where the backend
+        // lives in development builds
+        let resource_dir =
+            tauri::utils::platform::resource_dir(context.package_info(), &tauri::Env::default())
+                .unwrap_or_else(|_| exe_dir());
+
+        if let Some(root) = portable_root() {
+            return Self {
+                resource_dir,
+                data_dir: root.join("data"),
+                config_dir: root.join("config"),
+                log_dir: root.join("logs"),
+                portable: true,
+            };
+        }
+
+        let data_dir = dirs::data_dir()
+            .map(|dir| dir.join(identifier))
+            .unwrap_or_else(|| resource_dir.join("data"));
+
+        let config_dir = dirs::config_dir()
+            .map(|dir| dir.join(identifier))
+            .unwrap_or_else(|| data_dir.clone());
+
+        // Same as `app.path().app_log_dir()`
+        #[cfg(target_os = "macos")]
+        let log_dir = dirs::home_dir().map(|dir| dir.join("Library/Logs").join(identifier));
+        #[cfg(not(target_os = "macos"))]
+        let log_dir = dirs::data_local_dir().map(|dir| dir.join(identifier).join("logs"));
+        let log_dir = log_dir.unwrap_or_else(|| data_dir.join("logs"));
+
+        // A Windows service runs as LocalSystem, and is pointed at its own
+        // directory under ProgramData
+        let data_dir = arg_value("--data-dir").unwrap_or(data_dir);
+        let config_dir = arg_value("--config-dir").unwrap_or(config_dir);
+
+        Self {
+            resource_dir,
+            data_dir,
+            config_dir,
+            log_dir,
+            portable: false,
+        }
+    }
+
+    // Webview profile (cookies, local storage, caches). Only set in portable mode;
+    // otherwise the webview keeps its default location. WKWebView ignores a data
+    // directory, so on macOS the profile stays in `~/Library/WebKit` even then.
+    pub fn webview_dir(&self) -> Option<PathBuf> {
+        (self.portable && cfg!(not(target_os = "macos"))).then(|| self.data_dir.join("webview"))
+    }
+
+    // Wrapping keys for client certificates in portable mode, where the OS
+    // keystore would leave them behind on the machine
+    pub fn portable_keystore_dir(&self) -> PathBuf {
+        self.config_dir.join("keystore")
+    }
+
+    // Sidecar binaries installed by delta updates, which take precedence over the
+    // binary bundled with the app
+    pub fn sidecar_dir(&self) -> PathBuf {
+        self.data_dir.join("server")
+    }
+
+    // Per-install certificate for HTTPS between the shell and the backend
+    pub fn tls_dir(&self) -> PathBuf {
+        self.data_dir.join("tls")
+    }
+
+    // SQLite cache the frontend can use while the backend is down, one per
+    // workspace
+    pub fn cache_db(&self, workspace: &str) -> PathBuf {
+        if workspace == crate::workspace::DEFAULT_ID {
+            self.data_dir.join("cache.db")
+        } else {
+            self.data_dir.join(format!("cache-{}.db", workspace))
+        }
+    }
+
+    // Copies of dropped files while they are uploaded to the backend
+    pub fn import_staging_dir(&self) -> PathBuf {
+        self.data_dir.join("imports")
+    }
+
+    // App installers from offline update bundles, written just before they run
+    pub fn update_staging_dir(&self) -> PathBuf {
+        self.data_dir.join("updates")
+    }
+
+    // Client certificates for mutual TLS, with their encrypted private keys
+    pub fn client_certs_dir(&self) -> PathBuf {
+        self.data_dir.join("client-certs")
+    }
+
+    // Files fetched by the download manager when no destination is given
+    pub fn downloads_dir(&self) -> PathBuf {
+        self.data_dir.join("downloads")
+    }
+
+    // Token of the backend run by a headless shell, for shells launched later
+    pub fn backend_lease(&self) -> PathBuf {
+        self.data_dir.join("headless.json")
+    }
+
+    // Backend data of workspaces other than the default one
+    pub fn workspaces_dir(&self) -> PathBuf {
+        self.data_dir.join("workspaces")
+    }
+
+    // Backups of the backend data directory, unless configured elsewhere
+    pub fn backups_dir(&self) -> PathBuf {
+        self.data_dir.join("backups")
+    }
+
+    // Usage telemetry events not yet sent
+    pub fn telemetry_queue(&self) -> PathBuf {
+        self.data_dir.join("telemetry-queue.json")
+    }
+}
+
+fn arg_value(name: &str) -> Option<PathBuf> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next().map(PathBuf::from)
+}
+
+// Directory the portable data lives in, if portable mode is on. On macOS the
+// executable is inside the app bundle, so this is beside the bundle instead. An
+// AppImage runs from a temporary mount, so it is beside the AppImage file, which
+// the runtime gives as `$APPIMAGE`.
+fn portable_root() -> Option<PathBuf> {
+    let exe_dir = exe_dir();
+    let appimage = std::env::var_os("APPIMAGE")
+        .map(PathBuf::from)
+        .filter(|path| cfg!(target_os = "linux") && path.is_absolute())
+        .and_then(|path| path.parent().map(Path::to_path_buf));
+    let base = appimage.unwrap_or_else(|| {
+        exe_dir
+            .ancestors()
+            .find(|dir| dir.extension().map(|ext| ext == "app").unwrap_or(false))
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or(exe_dir)
+    });
+
+    let requested =
+        std::env::args().any(|arg| arg == PORTABLE_FLAG) || base.join(PORTABLE_MARKER).is_file();
+    requested.then(|| base.join(PORTABLE_DIR))
+}
+
+fn exe_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| path.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
@@ -0,0 +1,250 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+use crate::config::ConfigState;
+use crate::data_dir;
+use crate::locale;
+use crate::paths::AppPaths;
+use crate::server::ServerState;
+
+// Access to files outside the app data directory. The first time a command is
+// given such a path, the user is asked whether to allow it. Granted paths are
+// stored as `grantedPaths` in `desktop.json` and cover everything beneath them,
+// so the user isn't asked again; denials are not remembered. A write grant also
+// allows reading.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "read and write",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "camelCase")]
+pub struct PathGrant {
+    pub path: String,
+    pub access: Access,
+    pub granted_at: u64,
+}
+
+// Only one prompt is shown at a time, so concurrent requests for the same path
+// don't ask twice
+#[derive(Default)]
+pub struct PermissionState {
+    prompt: Mutex<()>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// Make a path absolute and resolve `..` and symlinks, so a grant can't be
+// escaped. The path itself may not exist yet (e.g. a download destination), in
+// which case its nearest existing ancestor is resolved instead.
+fn normalize(path: &Path) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path", path.display()));
+    }
+    if path
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(format!("{} must not contain `..`", path.display()));
+    }
+
+    let mut existing = path;
+    let mut remainder = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return Ok(remainder
+                .iter()
+                .rev()
+                .fold(resolved, |resolved, name| resolved.join(name)));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                remainder.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return Err(format!("{} could not be resolved", path.display())),
+        }
+    }
+}
+
+// Shell state in the app data directory that always needs a grant: its config
+// with the grants themselves (`config_dir` is the data directory on Windows and
+// macOS), key material, the sidecars it runs and the backend's token
+fn protected_paths(paths: &AppPaths) -> Vec<PathBuf> {
+    vec![
+        paths.config_dir.join("desktop.json"),
+        paths.portable_keystore_dir(),
+        paths.sidecar_dir(),
+        paths.tls_dir(),
+        paths.client_certs_dir(),
+        paths.backend_lease(),
+    ]
+}
+
+fn is_exempt(path: &Path, exempt: &[PathBuf], protected: &[PathBuf]) -> bool {
+    exempt.iter().any(|dir| path.starts_with(dir))
+        && !protected.iter().any(|protected| path.starts_with(protected))
+}
+
+// The app data directory and the backend's data directory never need a grant,
+// apart from the shell's own protected state
+fn is_app_data(app: &AppHandle, path: &Path) -> bool {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+    let exempt: Vec<PathBuf> = [
+        paths.data_dir.clone(),
+        data_dir::current_dir(&paths, &state),
+    ]
+    .iter()
+    .filter_map(|dir| normalize(dir).ok())
+    .collect();
+    let protected: Vec<PathBuf> = protected_paths(&paths)
+        .iter()
+        .filter_map(|path| normalize(path).ok())
+        .collect();
+    is_exempt(path, &exempt, &protected)
+}
+
+fn is_granted(app: &AppHandle, path: &Path, access: Access) -> bool {
+    app.state::<ConfigState>()
+        .get()
+        .granted_paths
+        .iter()
+        .any(|grant| grant.access >= access && path.starts_with(&grant.path))
+}
+
+fn prompt(app: &AppHandle, path: &Path, access: Access) -> bool {
+    let message = match access {
+        Access::Read => "dialog.fileAccess.read",
+        Access::Write => "dialog.fileAccess.write",
+    };
+    let path = path.display().to_string();
+    app.dialog()
+        .message(locale::text(app, message, &[("path", &path)]))
+        .title(locale::text(app, "dialog.fileAccess.title", &[]))
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            locale::text(app, "dialog.fileAccess.allow", &[]),
+            locale::text(app, "dialog.fileAccess.deny", &[]),
+        ))
+        .blocking_show()
+}
+
+// Check that the shell may access a path, asking the user if it hasn't been
+// granted yet. Blocks while the prompt is shown, so it must not be called on the
+// main thread.
+pub fn ensure_access(app: &AppHandle, path: &Path, access: Access) -> Result<(), String> {
+    let path = normalize(path)?;
+    if is_app_data(app, &path) || is_granted(app, &path, access) {
+        return Ok(());
+    }
+
+    let state = app.state::<PermissionState>();
+    let _prompt = state.prompt.lock().unwrap();
+    if is_granted(app, &path, access) {
+        return Ok(());
+    }
+
+    if !prompt(app, &path, access) {
+        return Err(format!("Access to {} was denied", path.display()));
+    }
+
+    let granted = path.to_string_lossy().into_owned();
+    app.state::<ConfigState>().update(|config| {
+        config.granted_paths.retain(|grant| grant.path != granted);
+        config.granted_paths.push(PathGrant {
+            path: granted.clone(),
+            access,
+            granted_at: now_ms(),
+        });
+    })?;
+    println!("Granted {} access to {}", access.as_str(), granted);
+    Ok(())
+}
+
+// Command to ask for access to a path before handing it to the backend
+#[tauri::command]
+#[specta::specta]
+pub async fn request_path_access(app: AppHandle, path: String, write: bool) -> Result<(), String> {
+    let access = if write { Access::Write } else { Access::Read };
+    tauri::async_runtime::spawn_blocking(move || ensure_access(&app, Path::new(&path), access))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Command to list the paths the user has granted access to
+#[tauri::command]
+#[specta::specta]
+pub fn list_granted_paths(config: State<ConfigState>) -> Vec<PathGrant> {
+    config.get().granted_paths
+}
+
+// Command to revoke a grant. The path must match a granted path exactly.
+#[tauri::command]
+#[specta::specta]
+pub fn revoke_path(config: State<ConfigState>, path: String) -> Result<Vec<PathGrant>, String> {
+    if !config
+        .get()
+        .granted_paths
+        .iter()
+        .any(|grant| grant.path == path)
+    {
+        return Err(format!("{} has not been granted", path));
+    }
+
+    config
+        .update(|config| config.granted_paths.retain(|grant| grant.path != path))
+        .map(|config| config.granted_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exempts_app_data_but_not_shell_state() {
+        let exempt = [PathBuf::from("/data/app"), PathBuf::from("/srv/backend")];
+        let protected = [
+            PathBuf::from("/data/app/desktop.json"),
+            PathBuf::from("/data/app/server"),
+        ];
+        assert!(is_exempt(Path::new("/data/app/exports/a.csv"), &exempt, &protected));
+        assert!(is_exempt(Path::new("/srv/backend/db.sqlite"), &exempt, &protected));
+        assert!(is_exempt(Path::new("/data/app/desktop.json.old"), &exempt, &protected));
+        assert!(!is_exempt(Path::new("/data/app/desktop.json"), &exempt, &protected));
+        assert!(!is_exempt(Path::new("/data/app/server/server.exe"), &exempt, &protected));
+        assert!(!is_exempt(Path::new("/data/application"), &exempt, &protected));
+        assert!(!is_exempt(Path::new("/home/user/a.csv"), &exempt, &protected));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn normalizes_paths() {
+        assert!(normalize(Path::new("relative/path")).is_err());
+        assert!(normalize(Path::new("/tmp/../etc/passwd")).is_err());
+        let missing = normalize(Path::new("/tmp/not-yet-created/file.txt")).unwrap();
+        assert!(missing.ends_with("not-yet-created/file.txt"));
+    }
+}
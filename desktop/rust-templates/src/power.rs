@@ -0,0 +1,196 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::events;
+use crate::paths::AppPaths;
+use crate::server::{self, ServerState};
+use crate::sse;
+use crate::telemetry::{self, UsageEvent};
+use crate::ws_bridge::{self, BridgeState};
+
+// Recovery after the machine sleeps. A resume is detected by comparing two
+// clocks counting from boot, one of which stops while the machine is suspended
+// (see `clocks`): the gap that opens between them is the time spent asleep.
+// Unlike the wall clock, neither can be set, so a clock change or NTP step isn't
+// taken for a sleep. Until recovery finishes, health checks are paused so the
+// time the network takes to come back isn't counted as backend failures.
+const TICK: Duration = Duration::from_secs(2);
+// Suspends shorter than this are ignored
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(5);
+// Health checks stay paused this long after recovery
+const RESUME_GRACE: Duration = Duration::from_secs(30);
+const BRIDGE_RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+pub struct PowerState {
+    // When the last resume was detected, cleared once the grace period passes
+    resumed_at: Mutex<Option<Instant>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SessionResumed {
+    pub slept_secs: u64,
+    pub backend_restarted: bool,
+}
+
+// Whether health checks should be skipped because the machine has just woken up
+pub fn is_resuming(app: &AppHandle) -> bool {
+    let state = app.state::<PowerState>();
+    let mut resumed_at = state.resumed_at.lock().unwrap();
+    match *resumed_at {
+        Some(at) if at.elapsed() < RESUME_GRACE => true,
+        Some(_) => {
+            *resumed_at = None;
+            false
+        }
+        None => false,
+    }
+}
+
+fn recover(app: &AppHandle, slept: Duration) {
+    println!("Resumed after sleeping for {}s", slept.as_secs());
+    *app.state::<PowerState>().resumed_at.lock().unwrap() = Some(Instant::now());
+
+    // Re-verify the backend, restarting it if it didn't survive the sleep
+    let state = app.state::<ServerState>();
+    let backend_restarted = if server::probe_health(&state).is_none() {
+        eprintln!("Backend unhealthy after resume, restarting");
+        telemetry::record(app, UsageEvent::BackendRestarted, Some("resume"));
+        server::stop_backend_server(&state);
+        if let Err(e) = server::start_backend_server(&app.state::<AppPaths>(), &state) {
+            eprintln!("Failed to restart backend after resume: {}", e);
+        }
+        true
+    } else {
+        false
+    };
+
+    // Connections that were open across the sleep are usually dead without
+    // having noticed yet
+    ws_bridge::reconnect(app);
+    sse::reconnect_all(app);
+
+    let event = SessionResumed {
+        slept_secs: slept.as_secs(),
+        backend_restarted,
+    };
+
+    // Tell the backend once the bridge is back up
+    let bridge = app.state::<BridgeState>();
+    let payload = serde_json::to_value(&event).unwrap_or_default();
+    let started = Instant::now();
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        if ws_bridge::is_connected(&bridge)
+            && ws_bridge::send_event(&bridge, "session-resumed", payload.clone()).is_ok()
+        {
+            break;
+        }
+        if started.elapsed() > BRIDGE_RECONNECT_TIMEOUT {
+            eprintln!("Backend WebSocket didn't reconnect after resume");
+            break;
+        }
+    }
+
+    let _ = events::SESSION_RESUMED.emit(app, &event);
+}
+
+// Time since boot excluding and including suspends: CLOCK_MONOTONIC and
+// CLOCK_BOOTTIME on Linux, CLOCK_UPTIME_RAW and CLOCK_MONOTONIC on macOS, the
+// unbiased and plain interrupt time on Windows
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn clocks() -> Option<(Duration, Duration)> {
+    fn read(clock: libc::clockid_t) -> Option<Duration> {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        (unsafe { libc::clock_gettime(clock, &mut time) } == 0)
+            .then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+    }
+
+    #[cfg(target_os = "linux")]
+    let (awake, total) = (libc::CLOCK_MONOTONIC, libc::CLOCK_BOOTTIME);
+    #[cfg(target_os = "macos")]
+    let (awake, total) = (libc::CLOCK_UPTIME_RAW, libc::CLOCK_MONOTONIC);
+    Some((read(awake)?, read(total)?))
+}
+
+#[cfg(target_os = "windows")]
+fn clocks() -> Option<(Duration, Duration)> {
+    use windows::Win32::System::WindowsProgramming::{
+        QueryInterruptTime, QueryUnbiasedInterruptTimePrecise,
+    };
+
+    // Both count in 100 ns units
+    let (awake, total) = unsafe { (QueryUnbiasedInterruptTimePrecise(), QueryInterruptTime()) };
+    Some((
+        Duration::from_nanos(awake.saturating_mul(100)),
+        Duration::from_nanos(total.saturating_mul(100)),
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn clocks() -> Option<(Duration, Duration)> {
+    None
+}
+
+// Time spent suspended between two readings of `clocks`
+fn slept_between(before: (Duration, Duration), after: (Duration, Duration)) -> Duration {
+    let awake = after.0.saturating_sub(before.0);
+    let total = after.1.saturating_sub(before.1);
+    total.saturating_sub(awake)
+}
+
+pub fn start_monitor(app: AppHandle) {
+    let Some(mut last) = clocks() else {
+        eprintln!("Sleep detection is not supported on this platform");
+        return;
+    };
+    thread::spawn(move || loop {
+        thread::sleep(TICK);
+
+        let Some(now) = clocks() else {
+            continue;
+        };
+        let slept = slept_between(last, now);
+        if slept > SLEEP_THRESHOLD {
+            recover(&app, slept);
+            // Recovery takes a while, which isn't a sleep
+            last = clocks().unwrap_or(now);
+        } else {
+            last = now;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(awake: u64, total: u64) -> (Duration, Duration) {
+        (Duration::from_secs(awake), Duration::from_secs(total))
+    }
+
+    #[test]
+    fn measures_the_time_suspended() {
+        assert_eq!(slept_between(secs(100, 150), secs(102, 152)), Duration::ZERO);
+        assert_eq!(
+            slept_between(secs(100, 150), secs(102, 3752)),
+            Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn clocks_advance_together_while_awake() {
+        let before = clocks().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let after = clocks().unwrap();
+        assert!(after.0 > before.0);
+        assert!(slept_between(before, after) < SLEEP_THRESHOLD);
+    }
+}
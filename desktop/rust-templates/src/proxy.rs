@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use serde::Serialize;
+use tauri::State;
+
+use crate::auth;
+use crate::backend;
+use crate::server::ServerState;
+
+// Frontend API calls are routed through the shell, so the webview never talks to
+// the backend directly: no CORS configuration and no ports hardcoded in JS.
+
+#[derive(Serialize, specta::Type)]
+pub struct BackendResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    // Parsed JSON when the backend returns JSON, the raw text otherwise
+    pub body: serde_json::Value,
+}
+
+// Headers supplied by the frontend, minus the auth token which only the shell sets
+fn request_headers(headers: HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case(auth::TOKEN_HEADER) {
+            continue;
+        }
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+        let value = HeaderValue::from_str(&value).map_err(|e| e.to_string())?;
+        header_map.insert(name, value);
+    }
+    Ok(header_map)
+}
+
+// Command to forward a request to the backend and return its response
+#[tauri::command]
+#[specta::specta]
+pub async fn backend_request(
+    state: State<'_, ServerState>,
+    method: String,
+    path: String,
+    headers: Option<HashMap<String, String>>,
+    body: Option<serde_json::Value>,
+) -> Result<BackendResponse, String> {
+    let method = Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut request = backend::request(&state, method, &path)?
+        .headers(request_headers(headers.unwrap_or_default())?);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+
+    let response = backend::send(&state, request).await?;
+
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (name.to_string(), value.to_string()))
+        })
+        .collect::<HashMap<_, _>>();
+    let is_json = headers
+        .get(CONTENT_TYPE.as_str())
+        .map(|content_type| content_type.contains("json"))
+        .unwrap_or(false);
+
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    let body = if is_json {
+        serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text))
+    } else {
+        serde_json::Value::String(text)
+    };
+
+    Ok(BackendResponse {
+        status,
+        headers,
+        body,
+    })
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::config::ConfigState;
+use crate::events;
+use crate::integrity;
+use crate::paths::AppPaths;
+use crate::power;
+use crate::server::{self, ServerState};
+use crate::updater;
+
+// After a sidecar update the new backend is on probation: if it fails its health
+// check too many times in a row, the previous binary (kept as `.bak`) is restored
+// so a bad release can't leave users without a working backend.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const PROBATION_PERIOD: Duration = Duration::from_secs(120);
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct RollbackEvent {
+    pub failed_version: String,
+    pub restored_version: String,
+    pub reason: String,
+}
+
+pub fn watch_sidecar_update(app: AppHandle, version: String) {
+    thread::spawn(move || {
+        let max_failures = app
+            .state::<ConfigState>()
+            .get()
+            .rollback_after_failures
+            .max(1);
+        let started = Instant::now();
+        let mut failures = 0;
+
+        while started.elapsed() < PROBATION_PERIOD {
+            thread::sleep(HEALTH_CHECK_INTERVAL);
+
+            // A newer update (or a rollback) has replaced the binary under watch
+            if updater::installed_sidecar_version(&app) != version {
+                return;
+            }
+
+            // The backend is being re-verified after the machine slept
+            if power::is_resuming(&app) {
+                continue;
+            }
+
+            if server::probe_health(&app.state::<ServerState>()).is_some() {
+                failures = 0;
+                continue;
+            }
+
+            failures += 1;
+            eprintln!(
+                "Sidecar {} failed health check ({}/{})",
+                version, failures, max_failures
+            );
+
+            if failures >= max_failures {
+                let reason = format!(
+                    "Backend failed {} consecutive health checks after updating to {}",
+                    failures, version
+                );
+                if let Err(e) = rollback_sidecar_update(&app, &reason) {
+                    eprintln!("Failed to roll back sidecar update: {}", e);
+                }
+                return;
+            }
+        }
+
+        println!("Sidecar {} passed post-update health checks", version);
+    });
+}
+
+// Restore the previous sidecar (or the bundled one, if the failed update was the
+// first), restart the backend and let the frontend know why
+pub fn rollback_sidecar_update(app: &AppHandle, reason: &str) -> Result<RollbackEvent, String> {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+
+    let name = state
+        .shell
+        .sidecar()
+        .binary_names()
+        .into_iter()
+        .next()
+        .ok_or("Sidecar updates are not supported on this platform")?;
+
+    let sidecar_dir = paths.sidecar_dir();
+    let target = sidecar_dir.join(&name);
+    let backup = sidecar_dir.join(format!("{}.bak", name));
+    let manifest_file = sidecar_dir.join(integrity::UPDATE_MANIFEST_NAME);
+    let manifest_backup = sidecar_dir.join(format!("{}.bak", integrity::UPDATE_MANIFEST_NAME));
+
+    let failed_version = updater::installed_sidecar_version(app);
+    eprintln!("Rolling back sidecar {}: {}", failed_version, reason);
+
+    server::stop_backend_server(&state);
+
+    let signature_file = integrity::signature_path(&target);
+    let signature_backup = integrity::signature_path(&backup);
+    if backup.exists() {
+        fs::rename(&backup, &target).map_err(|e| format!("Failed to restore sidecar: {}", e))?;
+        let _ = fs::rename(&signature_backup, &signature_file);
+    } else {
+        fs::remove_file(&target).map_err(|e| format!("Failed to remove sidecar: {}", e))?;
+        let _ = fs::remove_file(&signature_file);
+    }
+
+    let manifest_signature = integrity::signature_path(&manifest_file);
+    if manifest_backup.exists() {
+        fs::rename(&manifest_backup, &manifest_file).map_err(|e| e.to_string())?;
+        let _ = fs::rename(
+            integrity::signature_path(&manifest_backup),
+            &manifest_signature,
+        );
+    } else {
+        let _ = fs::remove_file(&manifest_file);
+        let _ = fs::remove_file(&manifest_signature);
+    }
+
+    let event = RollbackEvent {
+        failed_version,
+        restored_version: updater::installed_sidecar_version(app),
+        reason: reason.to_string(),
+    };
+
+    if let Err(e) = server::start_backend_server(&paths, &state) {
+        eprintln!("Restored sidecar failed to start: {}", e);
+    }
+
+    let _ = events::UPDATE_ROLLED_BACK.emit(app, &event);
+
+    Ok(event)
+}
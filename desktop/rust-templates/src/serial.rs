@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serialport::{DataBits, Parity, SerialPort, SerialPortType, StopBits};
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+
+// Serial ports for commissioning sensors over RS-485/USB adapters. Open ports are
+// owned by the shell; data read from a port is emitted as `serial-data`, and
+// `serial-closed` is emitted when a port closes or fails.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+struct OpenPort {
+    writer: Box<dyn SerialPort>,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct SerialState {
+    ports: Mutex<HashMap<String, OpenPort>>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct SerialPortDescription {
+    pub path: String,
+    // "usb", "pci", "bluetooth" or "unknown"
+    pub port_type: &'static str,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialParity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Deserialize, specta::Type)]
+#[serde(default)]
+pub struct SerialOptions {
+    pub data_bits: u8,
+    pub parity: SerialParity,
+    pub stop_bits: u8,
+}
+
+impl Default for SerialOptions {
+    fn default() -> Self {
+        Self {
+            data_bits: 8,
+            parity: SerialParity::None,
+            stop_bits: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SerialData {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SerialClosed {
+    pub path: String,
+    pub error: Option<String>,
+}
+
+impl SerialPortDescription {
+    fn from_info(info: serialport::SerialPortInfo) -> Self {
+        let mut description = Self {
+            path: info.port_name,
+            port_type: "unknown",
+            vid: None,
+            pid: None,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+        };
+
+        match info.port_type {
+            SerialPortType::UsbPort(usb) => {
+                description.port_type = "usb";
+                description.vid = Some(usb.vid);
+                description.pid = Some(usb.pid);
+                description.serial_number = usb.serial_number;
+                description.manufacturer = usb.manufacturer;
+                description.product = usb.product;
+            }
+            SerialPortType::PciPort => description.port_type = "pci",
+            SerialPortType::BluetoothPort => description.port_type = "bluetooth",
+            SerialPortType::Unknown => {}
+        }
+
+        description
+    }
+}
+
+fn open_port(path: &str, baud_rate: u32, options: &SerialOptions) -> Result<Box<dyn SerialPort>, String> {
+    let data_bits = match options.data_bits {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        8 => DataBits::Eight,
+        bits => return Err(format!("Unsupported data bits: {}", bits)),
+    };
+    let stop_bits = match options.stop_bits {
+        1 => StopBits::One,
+        2 => StopBits::Two,
+        bits => return Err(format!("Unsupported stop bits: {}", bits)),
+    };
+    let parity = match options.parity {
+        SerialParity::None => Parity::None,
+        SerialParity::Odd => Parity::Odd,
+        SerialParity::Even => Parity::Even,
+    };
+
+    serialport::new(path, baud_rate)
+        .data_bits(data_bits)
+        .stop_bits(stop_bits)
+        .parity(parity)
+        .timeout(READ_TIMEOUT)
+        .open()
+        .map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+// Read until the port is closed or fails, forwarding everything received. A
+// failed port, e.g. an adapter that was unplugged, is forgotten so it can be
+// opened again.
+fn read_loop(app: AppHandle, path: String, mut reader: Box<dyn SerialPort>, stop: Arc<AtomicBool>) {
+    let mut buffer = [0u8; 1024];
+
+    let error = loop {
+        if stop.load(Ordering::SeqCst) {
+            break None;
+        }
+
+        match reader.read(&mut buffer) {
+            Ok(0) => {}
+            Ok(length) => {
+                let _ = events::SERIAL_DATA.emit(
+                    &app,
+                    &SerialData {
+                        path: path.clone(),
+                        data: buffer[..length].to_vec(),
+                    },
+                );
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => break Some(e.to_string()),
+        }
+    };
+
+    // Unless it was closed, or closed and opened again, in the meantime
+    {
+        let state = app.state::<SerialState>();
+        let mut ports = state.ports.lock().unwrap();
+        if ports
+            .get(&path)
+            .is_some_and(|port| Arc::ptr_eq(&port.stop, &stop))
+        {
+            ports.remove(&path);
+        }
+    }
+
+    let _ = events::SERIAL_CLOSED.emit(&app, &SerialClosed { path, error });
+}
+
+// Command to list the serial ports on this machine
+#[tauri::command]
+#[specta::specta]
+pub fn list_serial_ports() -> Result<Vec<SerialPortDescription>, String> {
+    let ports = serialport::available_ports().map_err(|e| e.to_string())?;
+    Ok(ports
+        .into_iter()
+        .map(SerialPortDescription::from_info)
+        .collect())
+}
+
+// Command to open a serial port and start emitting `serial-data` for it
+#[tauri::command]
+#[specta::specta]
+pub fn open_serial(
+    app: AppHandle,
+    state: State<SerialState>,
+    path: String,
+    baud_rate: u32,
+    options: Option<SerialOptions>,
+) -> Result<(), String> {
+    let mut ports = state.ports.lock().unwrap();
+    if ports.contains_key(&path) {
+        return Err(format!("{} is already open", path));
+    }
+
+    let writer = open_port(&path, baud_rate, &options.unwrap_or_default())?;
+    let reader = writer.try_clone().map_err(|e| e.to_string())?;
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let thread_stop = stop.clone();
+    let thread_path = path.clone();
+    thread::spawn(move || read_loop(app, thread_path, reader, thread_stop));
+
+    ports.insert(path, OpenPort { writer, stop });
+    Ok(())
+}
+
+// Command to write bytes to an open serial port
+#[tauri::command]
+#[specta::specta]
+pub fn write_serial(state: State<SerialState>, path: String, data: Vec<u8>) -> Result<(), String> {
+    let mut ports = state.ports.lock().unwrap();
+    let port = ports
+        .get_mut(&path)
+        .ok_or_else(|| format!("{} is not open", path))?;
+
+    port.writer
+        .write_all(&data)
+        .and_then(|_| port.writer.flush())
+        .map_err(|e| e.to_string())
+}
+
+// Command to close a serial port
+#[tauri::command]
+#[specta::specta]
+pub fn close_serial(state: State<SerialState>, path: String) {
+    if let Some(port) = state.ports.lock().unwrap().remove(&path) {
+        // The reader thread notices within one read timeout and emits `serial-closed`
+        port.stop.store(true, Ordering::SeqCst);
+    }
+}
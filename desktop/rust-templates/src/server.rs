@@ -0,0 +1,571 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use episensor_app_framework::{
+    AppShell, HealthCheck, Launch, Logging, SandboxOptions, SandboxStatus, Sidecar, StartError,
+};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+use crate::auth;
+use crate::backend::{BackendClient, BackendClientConfig};
+use crate::data_dir;
+use crate::dev::{self, DevConfig};
+use crate::error::{ErrorCode, ShellError};
+use crate::integrity::{self, IntegrityFailure};
+use crate::lan;
+use crate::locale;
+use crate::mock_backend::{MockBackend, MockBackendConfig};
+use crate::paths::AppPaths;
+use crate::tls::{self, TlsIdentity};
+
+// Ports the backend is probed on until one of them reports healthy, after the
+// one set in `plugins.backend.port` in `tauri.conf.json`
+const BACKEND_PORTS: [u16; 4] = [8080, 7500, 5000, 3000];
+// Sidecar binaries are named `server-<target>` by `build-sidecar`
+const SIDECAR_NAME: &str = "server";
+
+// Limits for the backend process. The defaults depend on the platform: ARM Linux
+// boards such as the Raspberry Pi have little memory and start Node slowly, so
+// the heap is capped and the backend gets longer to come up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BackendProcessConfig {
+    // Passed to Node as `--max-old-space-size`; unset leaves Node's default
+    pub max_heap_mb: Option<u32>,
+    pub startup_timeout_secs: u64,
+    // Passed to the backend as DATA_DIR; unset leaves it at `data` under the
+    // resource directory
+    pub data_dir: Option<String>,
+    // Only pass the backend the variables Node needs, plus `inherit_env`
+    pub restrict_env: bool,
+    pub inherit_env: Vec<String>,
+    // Run the backend from its data directory instead of the resource directory
+    pub run_from_data_dir: bool,
+}
+
+impl Default for BackendProcessConfig {
+    fn default() -> Self {
+        if is_low_power_platform() {
+            Self {
+                max_heap_mb: Some(256),
+                startup_timeout_secs: 90,
+                data_dir: None,
+                restrict_env: true,
+                inherit_env: Vec::new(),
+                run_from_data_dir: false,
+            }
+        } else {
+            Self {
+                max_heap_mb: None,
+                startup_timeout_secs: 30,
+                data_dir: None,
+                restrict_env: true,
+                inherit_env: Vec::new(),
+                run_from_data_dir: false,
+            }
+        }
+    }
+}
+
+fn is_low_power_platform() -> bool {
+    cfg!(all(target_os = "linux", target_arch = "aarch64"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, specta::Type)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendPhase {
+    Starting,
+    Ready,
+    Failed,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BackendStatus {
+    pub phase: BackendPhase,
+    pub port: Option<u16>,
+    // Why the backend last failed to start
+    pub error: Option<ShellError>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BackendReady {
+    pub port: u16,
+}
+
+// Backend process owned by the shell (managed by the framework crate's
+// `AppShell`), the port it was found listening on, the token it requires on every
+// request and the certificate it serves HTTPS with. The updater public key is
+// kept to verify the sidecar before each launch. In dev mode the backend runs
+// from source instead, and with `--mock-backend` the shell serves canned
+// responses itself.
+pub struct ServerState {
+    pub shell: AppShell,
+    pub port: Mutex<Option<u16>>,
+    pub token: String,
+    pub tls: Option<TlsIdentity>,
+    pub client: BackendClient,
+    process_config: Mutex<BackendProcessConfig>,
+    pubkey: Option<String>,
+    integrity_failure: Mutex<Option<IntegrityFailure>>,
+    last_error: Mutex<Option<ShellError>>,
+    // Set once the shell is exiting, so a start still under way is refused
+    exiting: AtomicBool,
+    // Set when the backend belongs to a headless shell rather than this one
+    attached: bool,
+    dev: Option<DevConfig>,
+    mock: Option<MockBackend>,
+    // Passed to the backend as PORT
+    configured_port: Option<u16>,
+}
+
+impl ServerState {
+    pub fn new(
+        paths: &AppPaths,
+        client_config: BackendClientConfig,
+        process_config: BackendProcessConfig,
+        dev: Option<DevConfig>,
+        mock: Option<MockBackendConfig>,
+        configured_port: Option<u16>,
+        pubkey: Option<String>,
+    ) -> Self {
+        // Falling back to plain HTTP keeps the app usable if the data directory
+        // can't be written. The mock backend only speaks plain HTTP.
+        let tls = match mock {
+            Some(_) => None,
+            None => tls::ensure_certificate(paths)
+                .map_err(|e| eprintln!("Failed to set up backend TLS, using plain HTTP: {}", e))
+                .ok(),
+        };
+        let mock = mock.map(MockBackend::new);
+        let ports = match &mock {
+            Some(mock) => vec![mock.port()],
+            None => {
+                let mut ports: Vec<u16> = configured_port.into_iter().collect();
+                ports.extend(BACKEND_PORTS.iter().filter(|port| Some(**port) != configured_port));
+                ports
+            }
+        };
+
+        // Sidecars installed by delta updates take precedence over the bundled
+        // one while they are newer, see `integrity::prune_stale_sidecar`.
+        // Development builds fall back to the plain Node backend, and dev mode
+        // always runs the sources.
+        let sidecar = match &dev {
+            Some(config) => {
+                Sidecar::new(SIDECAR_NAME).node_script(dev::project_dir(config).join(&config.entry))
+            }
+            None => Sidecar::new(SIDECAR_NAME)
+                .search_dir(paths.sidecar_dir())
+                .search_dir(paths.resource_dir.join("server"))
+                .node_script(paths.resource_dir.join("backend").join("index.js"))
+                .build_dir(paths.resource_dir.parent().unwrap_or(&paths.resource_dir)),
+        };
+        let shell = AppShell::builder()
+            .sidecar(sidecar)
+            .health_check(HealthCheck::new("/api/health").ports(&ports))
+            .logging(Logging::to_file(paths.log_dir.join("backend.log")))
+            .build();
+
+        Self {
+            shell,
+            port: Mutex::new(None),
+            token: auth::generate_token(),
+            client: BackendClient::new(client_config, tls.as_ref()),
+            tls,
+            process_config: Mutex::new(process_config),
+            pubkey,
+            integrity_failure: Mutex::new(None),
+            last_error: Mutex::new(None),
+            exiting: AtomicBool::new(false),
+            attached: false,
+            dev,
+            mock,
+            configured_port,
+        }
+    }
+
+    // Use the backend of a headless shell instead of starting one
+    pub fn attach(&mut self, token: String) {
+        self.token = token;
+        self.attached = true;
+    }
+
+    // Whether the backend was started and has since exited
+    pub fn has_exited(&self) -> bool {
+        self.shell.has_exited()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        *self.port.lock().unwrap()
+    }
+
+    pub fn process_config(&self) -> BackendProcessConfig {
+        self.process_config.lock().unwrap().clone()
+    }
+
+    // Takes effect the next time the backend starts
+    pub fn set_data_dir(&self, data_dir: Option<String>) {
+        self.process_config.lock().unwrap().data_dir = data_dir;
+    }
+
+    // Why the sidecar was last refused, cleared once it passes verification
+    pub fn integrity_failure(&self) -> Option<IntegrityFailure> {
+        self.integrity_failure.lock().unwrap().clone()
+    }
+
+    pub fn status(&self) -> BackendStatus {
+        let port = self.port();
+        let error = self.last_error.lock().unwrap().clone();
+        let phase = match (port, &error) {
+            (Some(_), _) => BackendPhase::Ready,
+            (None, Some(_)) => BackendPhase::Failed,
+            (None, None) => BackendPhase::Starting,
+        };
+        BackendStatus { phase, port, error }
+    }
+
+    pub fn is_exiting(&self) -> bool {
+        self.exiting.load(Ordering::SeqCst)
+    }
+
+    // Set in dev mode
+    pub fn dev_config(&self) -> Option<&DevConfig> {
+        self.dev.as_ref()
+    }
+
+    pub fn sandbox_status(&self) -> Option<SandboxStatus> {
+        self.shell.sandbox_status()
+    }
+
+    pub fn scheme(&self) -> &'static str {
+        if self.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+}
+
+// Names of the backend sidecar binary on this platform, preferred first
+pub fn sidecar_binary_names() -> Vec<String> {
+    Sidecar::new(SIDECAR_NAME).binary_names()
+}
+
+// The backend's port from `plugins.backend.port` in `tauri.conf.json`, set when
+// the app is generated
+pub fn configured_port(config: &tauri::Config) -> Option<u16> {
+    config
+        .plugins
+        .0
+        .get("backend")
+        .and_then(|config| config.get("port"))
+        .and_then(|port| port.as_u64())
+        .and_then(|port| u16::try_from(port).ok())
+}
+
+// Refuse a sidecar that fails its integrity check, recording why
+fn verify_sidecar(paths: &AppPaths, state: &ServerState, binary: &Path) -> Result<(), ShellError> {
+    let result = integrity::verify_sidecar(paths, binary, state.pubkey.as_deref());
+    let failure = result.as_ref().err().map(|error| IntegrityFailure {
+        binary: binary.to_string_lossy().to_string(),
+        error: error.clone(),
+    });
+    *state.integrity_failure.lock().unwrap() = failure;
+    result.map_err(|e| {
+        ShellError::new(
+            ErrorCode::SidecarRejected,
+            format!("Refusing to start backend sidecar {:?}: {}", binary, e),
+        )
+        .with("binary", binary.display())
+    })
+}
+
+// Set up the backend's environment for `AppShell::start`
+fn prepare_launch(
+    paths: &AppPaths,
+    state: &ServerState,
+    process_config: &BackendProcessConfig,
+    run_from_data_dir: bool,
+    working_dir: &Path,
+    launch: &Launch,
+    command: &mut Command,
+) -> Result<(), ShellError> {
+    if state.is_exiting() {
+        return Err(ShellError::new(
+            ErrorCode::BackendUnavailable,
+            "The shell is exiting",
+        ));
+    }
+    match launch {
+        Launch::Sidecar(binary) => {
+            verify_sidecar(paths, state, binary)?;
+            println!("Starting backend sidecar: {:?}", binary);
+        }
+        Launch::Node(script) => println!("Starting backend server: {:?}", script),
+        Launch::Script {
+            interpreter,
+            script,
+        } => println!("Starting backend server: {} {:?}", interpreter, script),
+    }
+
+    let node_env = if state.dev.is_some() {
+        "development"
+    } else {
+        "production"
+    };
+    command
+        .env("NODE_ENV", node_env)
+        .env("DESKTOP", "true")
+        .env("HOST", "127.0.0.1")
+        .env(auth::TOKEN_ENV, &state.token);
+    if let Some(port) = state.configured_port {
+        command.env("PORT", port.to_string());
+    }
+
+    if let Some(identity) = &state.tls {
+        command
+            .env(tls::CERT_ENV, &identity.cert_path)
+            .env(tls::KEY_ENV, &identity.key_path);
+    }
+
+    let mut node_options = Vec::new();
+    if let Some(config) = &state.dev {
+        node_options.push(format!("--import={}", config.loader));
+    }
+    if let Some(max_heap_mb) = process_config.max_heap_mb {
+        node_options.push(format!("--max-old-space-size={}", max_heap_mb));
+    }
+    if !node_options.is_empty() {
+        command.env("NODE_OPTIONS", node_options.join(" "));
+    }
+    if let Some(data_dir) = &process_config.data_dir {
+        command.env("DATA_DIR", data_dir);
+    } else if run_from_data_dir {
+        // The default data directory is relative to the working directory
+        command.env("DATA_DIR", working_dir);
+    } else if paths.portable {
+        let dir = data_dir::current_dir(paths, state);
+        create_dir(&dir, "Failed to create backend data directory")?;
+        command.env("DATA_DIR", dir);
+    }
+    Ok(())
+}
+
+fn create_dir(dir: &Path, message: &str) -> Result<(), ShellError> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| ShellError::new(ErrorCode::Io, format!("{}: {}", message, e)).with("path", dir.display()))
+}
+
+// Start the backend unless it is running, returning whether it was started
+fn spawn_backend(paths: &AppPaths, state: &ServerState) -> Result<bool, ShellError> {
+    if let Some(mock) = &state.mock {
+        // Binding is all that can fail
+        return mock.start(&state.token).map_err(|e| {
+            ShellError::new(ErrorCode::PortInUse, e).with("port", mock.port())
+        });
+    }
+
+    let process_config = state.process_config();
+    let run_from_data_dir = process_config.run_from_data_dir && state.dev.is_none();
+    // Sources run from the project, as with `npm run dev`, so the loader resolves
+    let working_dir = if let Some(config) = &state.dev {
+        dev::project_dir(config)
+    } else if run_from_data_dir {
+        let dir = data_dir::current_dir(paths, state);
+        create_dir(&dir, "Failed to create backend working directory")?;
+        dir
+    } else {
+        paths.resource_dir.clone()
+    };
+    let options = SandboxOptions {
+        restrict_env: process_config.restrict_env,
+        inherit_env: process_config.inherit_env.clone(),
+    };
+
+    // `prepare` can only fail with a message, so what refused the launch is
+    // kept to be returned in its place
+    let mut refused = None;
+    let started = state.shell.start(&options, &working_dir, |launch, command| {
+        prepare_launch(
+            paths,
+            state,
+            &process_config,
+            run_from_data_dir,
+            &working_dir,
+            launch,
+            command,
+        )
+        .map_err(|e| refused.insert(e).message.clone())
+    });
+    started.map_err(|e| launch_error(e, refused))
+}
+
+// Why `AppShell::start` failed. `refused` is what `prepare` refused the launch
+// with, which can only be passed back as a message.
+pub fn launch_error(error: StartError, refused: Option<ShellError>) -> ShellError {
+    match error {
+        StartError::NotFound => ShellError::new(ErrorCode::BackendMissing, error.to_string()),
+        StartError::Refused(message) => {
+            refused.unwrap_or_else(|| ShellError::new(ErrorCode::Io, message))
+        }
+        // The interpreter, for a backend run as a script
+        StartError::Spawn(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            ShellError::new(ErrorCode::BackendMissing, error.to_string())
+        }
+        StartError::Spawn(_) => ShellError::new(ErrorCode::SpawnFailed, error.to_string()),
+    }
+}
+
+// In dev mode any answer short of a server error counts
+fn is_healthy(state: &ServerState, url: &str) -> bool {
+    state
+        .client
+        .blocking()
+        .get(url)
+        .header(auth::TOKEN_HEADER, &state.token)
+        .timeout(Duration::from_secs(1))
+        .send()
+        .map(|response| match state.dev {
+            Some(_) => !response.status().is_server_error(),
+            None => response.status().is_success(),
+        })
+        .unwrap_or(false)
+}
+
+// Returns the first port whose health endpoint answers successfully
+pub fn probe_health(state: &ServerState) -> Option<u16> {
+    state
+        .shell
+        .health_check()
+        .probe(state.scheme(), |url| is_healthy(state, url))
+}
+
+// Refuse operations that stop the backend when it belongs to a headless shell
+pub fn ensure_owned(state: &ServerState) -> Result<(), ShellError> {
+    if state.attached {
+        return Err(ShellError::new(
+            ErrorCode::BackendNotOwned,
+            "The backend is managed by the headless shell",
+        ));
+    }
+    Ok(())
+}
+
+// Whether something is accepting connections on a loopback port
+fn is_listening(port: u16) -> bool {
+    TcpStream::connect_timeout(&SocketAddr::from(([127, 0, 0, 1], port)), Duration::from_millis(500))
+        .is_ok()
+}
+
+// Why the backend never reported healthy. A backend that exits while its
+// configured port is taken has most likely failed to bind it.
+fn startup_failure(state: &ServerState, timeout: Duration) -> ShellError {
+    if !state.has_exited() {
+        return ShellError::new(
+            ErrorCode::HealthCheckTimeout,
+            "Backend failed to start within timeout",
+        )
+        .with("timeout_secs", timeout.as_secs());
+    }
+    match state.configured_port.filter(|port| is_listening(*port)) {
+        Some(port) => ShellError::new(
+            ErrorCode::PortInUse,
+            format!("Backend port {} is in use by another process", port),
+        )
+        .with("port", port),
+        None => ShellError::new(ErrorCode::BackendExited, "Backend exited during startup"),
+    }
+}
+
+// Start the backend (if it is not already running) and block until it is healthy.
+// An attached backend is only waited for.
+pub fn start_backend_server(paths: &AppPaths, state: &ServerState) -> Result<u16, ShellError> {
+    let result = start(paths, state);
+    *state.last_error.lock().unwrap() = result.as_ref().err().cloned();
+    result
+}
+
+fn start(paths: &AppPaths, state: &ServerState) -> Result<u16, ShellError> {
+    if !state.attached && !spawn_backend(paths, state)? {
+        if let Some(port) = state.port() {
+            return Ok(port);
+        }
+    }
+
+    println!("Waiting for backend to be ready...");
+    let mut timeout = Duration::from_secs(state.process_config().startup_timeout_secs);
+    if state.dev.is_some() {
+        timeout = timeout.max(dev::STARTUP_TIMEOUT);
+    }
+    let port = state
+        .shell
+        .wait_for_health(state.scheme(), timeout, |url| is_healthy(state, url))
+        .ok_or_else(|| startup_failure(state, timeout))?;
+
+    // LAN access goes through the shell's authenticated proxy, never directly
+    if let Err(e) = lan::verify_loopback_only(port) {
+        stop_backend_server(state);
+        return Err(ShellError::new(ErrorCode::BackendExposed, e).with("port", port));
+    }
+
+    *state.port.lock().unwrap() = Some(port);
+    state.client.reset();
+    println!("Backend server is ready on port {}!", port);
+
+    Ok(port)
+}
+
+// Tell the user why the backend didn't start, in their language. The window
+// still opens, so the frontend can show its own state and offer a retry.
+pub fn show_startup_error(app: &AppHandle, error: &ShellError) {
+    let port = error.context.get("port").cloned().unwrap_or_default();
+    let message = match error.code {
+        ErrorCode::BackendMissing => locale::text(app, "error.backend.missing", &[]),
+        ErrorCode::SidecarRejected => locale::text(app, "error.backend.rejected", &[]),
+        ErrorCode::PortInUse => locale::text(app, "error.backend.portInUse", &[("port", &port)]),
+        ErrorCode::HealthCheckTimeout => locale::text(app, "error.backend.timeout", &[]),
+        _ => locale::text(app, "error.backend.other", &[("message", &error.message)]),
+    };
+    app.dialog()
+        .message(message)
+        .title(locale::text(app, "error.backend.title", &[]))
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
+}
+
+pub fn stop_backend_server(state: &ServerState) {
+    state.shell.stop();
+    if let Some(mock) = &state.mock {
+        mock.stop();
+    }
+    *state.port.lock().unwrap() = None;
+}
+
+// Stop the backend for good as the shell exits. A start that hasn't spawned the
+// backend yet is refused rather than leaving it running.
+pub fn shut_down(state: &ServerState) {
+    state.exiting.store(true, Ordering::SeqCst);
+    stop_backend_server(state);
+}
+
+// Command to get whether the backend is still starting, ready or failed to
+// start, for a webview that loaded after `backend-ready` or `backend-failed`
+#[tauri::command]
+#[specta::specta]
+pub fn get_backend_status(state: State<ServerState>) -> BackendStatus {
+    state.status()
+}
+
+// Command to report how the backend was sandboxed when it was last started
+#[tauri::command]
+#[specta::specta]
+pub fn get_sandbox_status(state: State<ServerState>) -> Option<SandboxStatus> {
+    state.sandbox_status()
+}
@@ -0,0 +1,555 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::paths::AppPaths;
+
+// Runs the headless shell at boot, before anyone logs in: a Windows service, a
+// launchd daemon on macOS or a systemd unit on Linux. Installing needs
+// administrator rights, which the user is asked for. On macOS and Linux the
+// service runs as the installing user, so it shares their data directory. A
+// Windows service runs as LocalSystem, so it only runs an app installed per
+// machine under Program Files, and keeps its data in a directory under
+// ProgramData that only administrators can write, passed with `--data-dir` and
+// `--config-dir`. Anything it executes or reads settings from is then out of
+// reach of an unprivileged user.
+pub const FLAG: &str = "--service";
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct ServiceStatus {
+    pub name: String,
+    // "windows-service", "launchd" or "systemd"
+    pub manager: &'static str,
+    pub installed: bool,
+    pub running: bool,
+}
+
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == FLAG)
+}
+
+fn service_name(app: &AppHandle) -> String {
+    app.config().identifier.clone()
+}
+
+// The executable to start, which for an AppImage is the image rather than its
+// mounted contents
+fn executable() -> Result<PathBuf, String> {
+    if let Some(appimage) = std::env::var_os("APPIMAGE") {
+        return Ok(PathBuf::from(appimage));
+    }
+    std::env::current_exe().map_err(|e| e.to_string())
+}
+
+fn run(command: &mut Command) -> Result<String, String> {
+    let output = command.output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(if stderr.is_empty() {
+            format!("Command failed with {}", output.status)
+        } else {
+            stderr
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::fs;
+    use std::os::windows::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+    use std::thread;
+
+    use base64::Engine;
+    use tauri::AppHandle;
+    use windows::core::{PCWSTR, PWSTR};
+    use windows::Win32::System::Services::{
+        RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+        SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE,
+        SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS,
+        SERVICE_STATUS_CURRENT_STATE, SERVICE_STATUS_HANDLE, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+        SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+    };
+
+    use super::{executable, run, service_name, ServiceStatus};
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+    static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    fn set_status(state: SERVICE_STATUS_CURRENT_STATE) {
+        let handle = STATUS_HANDLE.load(Ordering::SeqCst);
+        if handle == 0 {
+            return;
+        }
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            dwWaitHint: if state == SERVICE_STOP_PENDING {
+                30_000
+            } else {
+                0
+            },
+            ..Default::default()
+        };
+        let handle = SERVICE_STATUS_HANDLE(handle as *mut _);
+        let _ = unsafe { SetServiceStatus(handle, &status) };
+    }
+
+    unsafe extern "system" fn handle_control(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut std::ffi::c_void,
+        _context: *mut std::ffi::c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                set_status(SERVICE_STOP_PENDING);
+                STOP_REQUESTED.store(true, Ordering::SeqCst);
+                if let Some(app) = APP.get() {
+                    app.exit(0);
+                }
+                0
+            }
+            SERVICE_CONTROL_INTERROGATE => 0,
+            // ERROR_CALL_NOT_IMPLEMENTED
+            _ => 120,
+        }
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+        let Ok(handle) =
+            (unsafe { RegisterServiceCtrlHandlerExW(PCWSTR::null(), Some(handle_control), None) })
+        else {
+            eprintln!("Failed to register the service control handler");
+            return;
+        };
+        STATUS_HANDLE.store(handle.0 as usize, Ordering::SeqCst);
+        set_status(SERVICE_RUNNING);
+    }
+
+    // Connect to the service control manager. The dispatcher blocks until the
+    // service stops, so it gets its own thread and the app keeps the main one.
+    pub fn start_dispatcher() {
+        thread::spawn(|| {
+            let table = [
+                SERVICE_TABLE_ENTRYW {
+                    lpServiceName: PWSTR::null(),
+                    lpServiceProc: Some(service_main),
+                },
+                SERVICE_TABLE_ENTRYW::default(),
+            ];
+            if let Err(e) = unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } {
+                eprintln!("Not started by the service control manager: {}", e);
+            }
+        });
+    }
+
+    pub fn init(app: &AppHandle) {
+        let _ = APP.set(app.clone());
+        if STOP_REQUESTED.load(Ordering::SeqCst) {
+            app.exit(0);
+        }
+    }
+
+    pub fn report_stopped() {
+        set_status(SERVICE_STOPPED);
+    }
+
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    // Run a PowerShell script elevated, which shows the UAC prompt
+    fn run_elevated(script: &str) -> Result<(), String> {
+        let utf16: Vec<u8> = script
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(utf16);
+        let launcher = format!(
+            "$p = Start-Process powershell -Verb RunAs -Wait -PassThru -WindowStyle Hidden \
+             -ArgumentList '-NoProfile','-EncodedCommand','{}'; exit $p.ExitCode",
+            encoded
+        );
+        run(Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &launcher])
+            .creation_flags(CREATE_NO_WINDOW))
+        .map(|_| ())
+    }
+
+    // Whether the executable is under Program Files, where only administrators
+    // can replace it
+    fn installed_per_machine(exe: &Path) -> bool {
+        let Ok(exe) = fs::canonicalize(exe) else {
+            return false;
+        };
+        let exe = PathBuf::from(exe.to_string_lossy().to_lowercase());
+        ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432"]
+            .into_iter()
+            .filter_map(std::env::var_os)
+            .filter_map(|dir| fs::canonicalize(dir).ok())
+            .any(|dir| exe.starts_with(dir.to_string_lossy().to_lowercase()))
+    }
+
+    pub fn install(app: &AppHandle) -> Result<(), String> {
+        let exe = executable()?;
+        if !installed_per_machine(&exe) {
+            return Err(
+                "Running as a service needs the app installed for all users, under Program Files"
+                    .to_string(),
+            );
+        }
+        let program_data =
+            std::env::var_os("ProgramData").ok_or("Could not find the ProgramData directory")?;
+        let data_dir = PathBuf::from(program_data).join(service_name(app));
+        let binary_path = format!(
+            "\"{exe}\" --headless --service --data-dir \"{dir}\" --config-dir \"{dir}\"",
+            exe = exe.display(),
+            dir = data_dir.display(),
+        );
+        let name = service_name(app);
+        // Users may create directories under ProgramData, so one left there
+        // beforehand is taken over: owned by administrators, with only SYSTEM
+        // and administrators allowed to write
+        let script = format!(
+            "$ErrorActionPreference = 'Stop'\n\
+             New-Item -ItemType Directory -Force -Path {dir} | Out-Null\n\
+             & icacls.exe {dir} /setowner '*S-1-5-32-544' /T /C /Q | Out-Null\n\
+             & icacls.exe {dir} /reset /T /C /Q | Out-Null\n\
+             & icacls.exe {dir} /inheritance:r /grant:r '*S-1-5-18:(OI)(CI)F' \
+             '*S-1-5-32-544:(OI)(CI)F' '*S-1-5-32-545:(OI)(CI)RX' /Q | Out-Null\n\
+             if ($LASTEXITCODE -ne 0) {{ exit $LASTEXITCODE }}\n\
+             New-Service -Name {name} -BinaryPathName {binary} -DisplayName {display} \
+             -Description {description} -StartupType Automatic | Out-Null\n\
+             & sc.exe failure {name} reset= 86400 actions= restart/60000 | Out-Null\n\
+             Start-Service -Name {name}",
+            dir = quote(&data_dir.to_string_lossy()),
+            name = quote(&name),
+            binary = quote(&binary_path),
+            display = quote(&app.package_info().name),
+            description = quote(&format!(
+                "Runs {} in the background",
+                app.package_info().name
+            )),
+        );
+        run_elevated(&script)
+    }
+
+    pub fn uninstall(app: &AppHandle) -> Result<(), String> {
+        let name = quote(&service_name(app));
+        let script = format!(
+            "Stop-Service -Name {name} -ErrorAction SilentlyContinue\n\
+             & sc.exe delete {name} | Out-Null\n\
+             exit $LASTEXITCODE",
+            name = name
+        );
+        run_elevated(&script)
+    }
+
+    pub fn status(app: &AppHandle) -> ServiceStatus {
+        let name = service_name(app);
+        let output = Command::new("sc.exe")
+            .args(["query", &name])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output();
+        // `sc query` fails with 1060 when the service doesn't exist
+        let (installed, running) = match output {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                (true, stdout.contains("RUNNING"))
+            }
+            _ => (false, false),
+        };
+        ServiceStatus {
+            name,
+            manager: "windows-service",
+            installed,
+            running,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use base64::Engine;
+    use tauri::{AppHandle, Manager};
+
+    use super::{executable, run, service_name, ServiceStatus};
+
+    fn plist_path(app: &AppHandle) -> PathBuf {
+        PathBuf::from("/Library/LaunchDaemons").join(format!("{}.plist", service_name(app)))
+    }
+
+    fn escape_xml(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+
+    // Run a shell script as root, which asks for an administrator password
+    fn run_elevated(script: &str) -> Result<(), String> {
+        let escaped = script.replace('\\', "\\\\").replace('"', "\\\"");
+        run(Command::new("osascript").args([
+            "-e",
+            &format!(
+                "do shell script \"{}\" with administrator privileges",
+                escaped
+            ),
+        ]))
+        .map(|_| ())
+    }
+
+    pub fn start_dispatcher() {}
+
+    pub fn init(_app: &AppHandle) {}
+
+    pub fn report_stopped() {}
+
+    pub fn install(app: &AppHandle) -> Result<(), String> {
+        let user = std::env::var("USER").map_err(|_| "Could not determine the current user")?;
+        let log_dir = app.path().app_log_dir().map_err(|e| e.to_string())?;
+        fs::create_dir_all(&log_dir).map_err(|e| e.to_string())?;
+        let log_file = log_dir.join("service.log");
+
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{label}</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>{program}</string>
+    <string>--headless</string>
+  </array>
+  <key>UserName</key>
+  <string>{user}</string>
+  <key>RunAtLoad</key>
+  <true/>
+  <key>KeepAlive</key>
+  <dict>
+    <key>SuccessfulExit</key>
+    <false/>
+  </dict>
+  <key>StandardOutPath</key>
+  <string>{log}</string>
+  <key>StandardErrorPath</key>
+  <string>{log}</string>
+</dict>
+</plist>
+"#,
+            label = escape_xml(&service_name(app)),
+            program = escape_xml(&executable()?.to_string_lossy()),
+            user = escape_xml(&user),
+            log = escape_xml(&log_file.to_string_lossy()),
+        );
+
+        // Written by root straight from the script, as a file staged in a shared
+        // temp directory could be swapped before it is copied
+        let target = plist_path(app);
+        run_elevated(&format!(
+            "umask 022 && printf %s {contents} | base64 -D > {target} && \
+             chown root:wheel {target} && chmod 644 {target} && \
+             launchctl bootstrap system {target}",
+            contents = quote(&base64::engine::general_purpose::STANDARD.encode(plist)),
+            target = quote(&target.to_string_lossy()),
+        ))
+    }
+
+    pub fn uninstall(app: &AppHandle) -> Result<(), String> {
+        run_elevated(&format!(
+            "launchctl bootout system/{label}; rm -f {target}",
+            label = quote(&service_name(app)),
+            target = quote(&plist_path(app).to_string_lossy()),
+        ))
+    }
+
+    pub fn status(app: &AppHandle) -> ServiceStatus {
+        let name = service_name(app);
+        let running = Command::new("launchctl")
+            .args(["print", &format!("system/{}", name)])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains("state = running"))
+            .unwrap_or(false);
+        ServiceStatus {
+            installed: plist_path(app).exists(),
+            name,
+            manager: "launchd",
+            running,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use base64::Engine;
+    use tauri::AppHandle;
+
+    use super::{executable, run, service_name, ServiceStatus};
+
+    fn unit_name(app: &AppHandle) -> String {
+        format!("{}.service", service_name(app))
+    }
+
+    fn unit_path(app: &AppHandle) -> PathBuf {
+        PathBuf::from("/etc/systemd/system").join(unit_name(app))
+    }
+
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+
+    fn find_program(name: &str) -> Option<PathBuf> {
+        std::env::var_os("PATH").and_then(|path| {
+            std::env::split_paths(&path)
+                .map(|dir| dir.join(name))
+                .find(|candidate| candidate.is_file())
+        })
+    }
+
+    // Run a shell script as root, which asks for an administrator password
+    fn run_elevated(script: &str) -> Result<(), String> {
+        run(Command::new("pkexec").args(["sh", "-c", script])).map(|_| ())
+    }
+
+    pub fn start_dispatcher() {}
+
+    pub fn init(_app: &AppHandle) {}
+
+    pub fn report_stopped() {}
+
+    pub fn install(app: &AppHandle) -> Result<(), String> {
+        let user = std::env::var("USER").map_err(|_| "Could not determine the current user")?;
+        // GTK needs a display even though headless mode never opens a window
+        let xvfb_run = find_program("xvfb-run").ok_or(
+            "Running at boot needs xvfb-run (the xvfb package), as there is no display yet",
+        )?;
+
+        let unit = format!(
+            "[Unit]\n\
+             Description={description}\n\
+             After=network-online.target\n\
+             Wants=network-online.target\n\
+             \n\
+             [Service]\n\
+             User={user}\n\
+             ExecStart=\"{xvfb_run}\" -a \"{program}\" --headless\n\
+             Restart=on-failure\n\
+             RestartSec=10\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            description = app.package_info().name,
+            user = user,
+            xvfb_run = xvfb_run.display(),
+            program = executable()?.display(),
+        );
+
+        // Written by root straight from the script, as a file staged in a shared
+        // temp directory could be swapped before it is installed
+        run_elevated(&format!(
+            "umask 022 && printf %s {contents} | base64 -d > {target} && \
+             chmod 644 {target} && systemctl daemon-reload && systemctl enable --now {unit}",
+            contents = quote(&base64::engine::general_purpose::STANDARD.encode(unit)),
+            target = quote(&unit_path(app).to_string_lossy()),
+            unit = quote(&unit_name(app)),
+        ))
+    }
+
+    pub fn uninstall(app: &AppHandle) -> Result<(), String> {
+        run_elevated(&format!(
+            "systemctl disable --now {unit}; rm -f {target} && systemctl daemon-reload",
+            unit = quote(&unit_name(app)),
+            target = quote(&unit_path(app).to_string_lossy()),
+        ))
+    }
+
+    pub fn status(app: &AppHandle) -> ServiceStatus {
+        let running = Command::new("systemctl")
+            .args(["is-active", "--quiet", &unit_name(app)])
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        ServiceStatus {
+            installed: unit_path(app).exists(),
+            name: unit_name(app),
+            manager: "systemd",
+            running,
+        }
+    }
+}
+
+pub use platform::{init, report_stopped, start_dispatcher};
+
+// Command to install the headless shell as a service that starts at boot
+#[tauri::command]
+#[specta::specta]
+pub async fn install_service(app: AppHandle) -> Result<ServiceStatus, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        // A service outlives the USB stick a portable install runs from
+        if app.state::<AppPaths>().portable {
+            return Err("A portable install can't be run as a service".to_string());
+        }
+        if platform::status(&app).installed {
+            return Err("The service is already installed".to_string());
+        }
+        platform::install(&app)?;
+        println!("Installed service {}", service_name(&app));
+        Ok(platform::status(&app))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to stop and remove the service
+#[tauri::command]
+#[specta::specta]
+pub async fn uninstall_service(app: AppHandle) -> Result<ServiceStatus, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if !platform::status(&app).installed {
+            return Err("The service is not installed".to_string());
+        }
+        platform::uninstall(&app)?;
+        println!("Uninstalled service {}", service_name(&app));
+        Ok(platform::status(&app))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to report whether the service is installed and running
+#[tauri::command]
+#[specta::specta]
+pub async fn service_status(app: AppHandle) -> Result<ServiceStatus, String> {
+    tauri::async_runtime::spawn_blocking(move || platform::status(&app))
+        .await
+        .map_err(|e| e.to_string())
+}
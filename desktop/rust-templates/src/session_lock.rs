@@ -0,0 +1,617 @@
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Invoke;
+use tauri::plugin::Plugin;
+use tauri::webview::{PageLoadEvent, PageLoadPayload};
+use tauri::{AppHandle, Manager, RunEvent, State, Url, Webview, WebviewWindow, Window, Wry};
+
+use crate::config::ConfigState;
+use crate::events;
+use crate::locale;
+
+// Idle lock for shared PCs such as control rooms. Every page gets a small script
+// that reports keyboard and pointer activity; once nothing has been reported for
+// `sessionLock.idleMinutes`, every window is covered by an opaque overlay until
+// the user enters the PIN or, on Windows, passes Windows Hello. The overlay is
+// put back if a page reloads while locked, and what the page adds while locked is
+// made inert too. While locked the shell refuses every command but the ones the
+// overlay needs, including those of the plugins wrapped with `guard_plugin`, and
+// drops dropped files, deep links and opened files.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const PIN_ITERATIONS: u32 = 100_000;
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const LOCKOUT: Duration = Duration::from_secs(30);
+const OVERLAY_ID: &str = "__session-lock";
+// Commands still answered while the session is locked
+const ALLOWED_WHILE_LOCKED: &[&str] = &["unlock_session", "get_session_lock", "report_activity"];
+
+const ACTIVITY_SCRIPT: &str = r#"(() => {
+  if (window.__sessionActivity) return;
+  window.__sessionActivity = true;
+  let last = 0;
+  const report = () => {
+    const now = Date.now();
+    if (now - last < 10000) return;
+    last = now;
+    window.__TAURI_INTERNALS__.invoke('report_activity').catch(() => {});
+  };
+  for (const type of ['keydown', 'pointerdown', 'pointermove', 'wheel', 'touchstart']) {
+    window.addEventListener(type, report, { capture: true, passive: true });
+  }
+})();"#;
+
+const LOCK_SCRIPT: &str = r#"(() => {
+  if (document.getElementById('__ID__')) return;
+  const text = __TEXT__;
+  for (const child of document.body.children) child.inert = true;
+  // Whatever the page adds while locked is covered as well
+  window.__sessionLockObserver = new MutationObserver((mutations) => {
+    for (const mutation of mutations) {
+      for (const node of mutation.addedNodes) {
+        if (node instanceof HTMLElement && node.id !== '__ID__') node.inert = true;
+      }
+    }
+  });
+  window.__sessionLockObserver.observe(document.body, { childList: true });
+  const overlay = document.createElement('div');
+  overlay.id = '__ID__';
+  overlay.style.cssText = 'position:fixed;inset:0;z-index:2147483647;background:#111;color:#eee;' +
+    'display:flex;align-items:center;justify-content:center;font:16px system-ui,sans-serif';
+  overlay.innerHTML = '<form style="display:flex;flex-direction:column;gap:12px;width:260px">' +
+    '<div style="font-size:20px"></div>' +
+    '<input type="password" inputmode="numeric" autocomplete="off" style="padding:8px">' +
+    '<button type="submit" style="padding:8px"></button>' +
+    (text.osAuth ? '<button type="button" style="padding:8px"></button>' : '') +
+    '<div role="alert" style="color:#f66;min-height:1.2em"></div></form>';
+  const form = overlay.querySelector('form');
+  const input = overlay.querySelector('input');
+  const error = overlay.querySelector('[role=alert]');
+  form.firstChild.textContent = text.title;
+  input.placeholder = text.pin;
+  overlay.querySelector('button[type=submit]').textContent = text.unlock;
+  const unlock = (pin) => window.__TAURI_INTERNALS__.invoke('unlock_session', { pin })
+    .catch((e) => { error.textContent = String(e); input.value = ''; input.focus(); });
+  form.addEventListener('submit', (event) => { event.preventDefault(); unlock(input.value); });
+  const osButton = overlay.querySelector('button[type=button]');
+  if (osButton) {
+    osButton.textContent = text.osAuth;
+    osButton.addEventListener('click', () => unlock(null));
+  }
+  document.body.appendChild(overlay);
+  input.focus();
+})();"#;
+
+const UNLOCK_SCRIPT: &str = r#"(() => {
+  if (window.__sessionLockObserver) window.__sessionLockObserver.disconnect();
+  window.__sessionLockObserver = null;
+  const overlay = document.getElementById('__ID__');
+  if (overlay) overlay.remove();
+  for (const child of document.body.children) child.inert = false;
+})();"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SessionLockConfig {
+    pub enabled: bool,
+    pub idle_minutes: u64,
+    // PBKDF2 hash of the PIN as `<salt>$<hash>`, both base64
+    pub pin_hash: Option<String>,
+    // Offer Windows Hello as well as the PIN, where available
+    pub allow_os_auth: bool,
+}
+
+impl Default for SessionLockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 15,
+            pin_hash: None,
+            allow_os_auth: true,
+        }
+    }
+}
+
+pub struct SessionLockState {
+    locked: AtomicBool,
+    last_activity: Mutex<Instant>,
+    // Failed PIN attempts since the last success, and when the last one was
+    failed_attempts: Mutex<(u32, Option<Instant>)>,
+}
+
+impl Default for SessionLockState {
+    fn default() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            last_activity: Mutex::new(Instant::now()),
+            failed_attempts: Mutex::new((0, None)),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SessionLockStatus {
+    pub enabled: bool,
+    pub idle_minutes: u64,
+    pub locked: bool,
+    pub has_pin: bool,
+    pub os_auth_available: bool,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SessionLocked {
+    // "idle" or "manual"
+    pub reason: &'static str,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SessionUnlocked {
+    // "pin" or "os"
+    pub method: &'static str,
+}
+
+fn pin_iterations() -> NonZeroU32 {
+    NonZeroU32::new(PIN_ITERATIONS).unwrap()
+}
+
+fn hash_pin(pin: &str) -> Result<String, String> {
+    let mut salt = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| "Failed to generate a salt".to_string())?;
+    let mut hash = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        pin_iterations(),
+        &salt,
+        pin.as_bytes(),
+        &mut hash,
+    );
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    Ok(format!("{}${}", engine.encode(salt), engine.encode(hash)))
+}
+
+fn verify_pin(pin_hash: &str, pin: &str) -> bool {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let Some((salt, hash)) = pin_hash.split_once('$') else {
+        return false;
+    };
+    match (engine.decode(salt), engine.decode(hash)) {
+        (Ok(salt), Ok(hash)) => pbkdf2::verify(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            pin_iterations(),
+            &salt,
+            pin.as_bytes(),
+            &hash,
+        )
+        .is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn os_auth_available() -> bool {
+    use windows::Security::Credentials::UI::{
+        UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+
+    UserConsentVerifier::CheckAvailabilityAsync()
+        .and_then(|operation| operation.get())
+        .map(|availability| availability == UserConsentVerifierAvailability::Available)
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn os_auth_available() -> bool {
+    false
+}
+
+// Windows Hello, with its prompt owned by the window it was asked for from so it
+// shows in front of it rather than behind
+#[cfg(target_os = "windows")]
+fn os_authenticate(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    use windows::core::{factory, HSTRING};
+    use windows::Security::Credentials::UI::{UserConsentVerificationResult, UserConsentVerifier};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+    use windows_future::IAsyncOperation;
+
+    let hwnd = HWND(window.hwnd().map_err(|e| e.to_string())?.0);
+    let message = HSTRING::from(locale::text(app, "lock.osAuthPrompt", &[]));
+    let result = factory::<UserConsentVerifier, IUserConsentVerifierInterop>()
+        .and_then(|interop| unsafe {
+            interop.RequestVerificationForWindowAsync::<
+                IAsyncOperation<UserConsentVerificationResult>,
+            >(hwnd, &message)
+        })
+        .and_then(|operation| operation.get())
+        .map_err(|e| format!("Windows Hello failed: {}", e))?;
+    if result == UserConsentVerificationResult::Verified {
+        Ok(())
+    } else {
+        Err(locale::text(app, "lock.osAuthIncomplete", &[]))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn os_authenticate(_app: &AppHandle, _window: &WebviewWindow) -> Result<(), String> {
+    Err("OS authentication is not available on this platform".to_string())
+}
+
+fn lock_script(app: &AppHandle) -> String {
+    let config = app.state::<ConfigState>().get().session_lock;
+    let os_auth = (config.allow_os_auth && os_auth_available())
+        .then(|| locale::text(app, "lock.osAuth", &[]));
+    let text = serde_json::json!({
+        "title": locale::text(app, "lock.title", &[]),
+        "pin": locale::text(app, "lock.pin", &[]),
+        "unlock": locale::text(app, "lock.unlock", &[]),
+        "osAuth": os_auth,
+    });
+    LOCK_SCRIPT
+        .replace("__ID__", OVERLAY_ID)
+        .replace("__TEXT__", &text.to_string())
+}
+
+pub fn is_locked(app: &AppHandle) -> bool {
+    app.state::<SessionLockState>()
+        .locked
+        .load(Ordering::SeqCst)
+}
+
+fn lock(app: &AppHandle, reason: &'static str) {
+    if app
+        .state::<SessionLockState>()
+        .locked
+        .swap(true, Ordering::SeqCst)
+    {
+        return;
+    }
+
+    let script = lock_script(app);
+    for window in app.webview_windows().values() {
+        let _ = window.eval(&script);
+    }
+    println!("Session locked ({})", reason);
+    let _ = events::SESSION_LOCKED.emit(app, &SessionLocked { reason });
+}
+
+fn unlock(app: &AppHandle, method: &'static str) {
+    let state = app.state::<SessionLockState>();
+    *state.last_activity.lock().unwrap() = Instant::now();
+    *state.failed_attempts.lock().unwrap() = (0, None);
+    state.locked.store(false, Ordering::SeqCst);
+
+    let script = UNLOCK_SCRIPT.replace("__ID__", OVERLAY_ID);
+    for window in app.webview_windows().values() {
+        let _ = window.eval(&script);
+    }
+    println!("Session unlocked ({})", method);
+    let _ = events::SESSION_UNLOCKED.emit(app, &SessionUnlocked { method });
+}
+
+// Check a PIN, refusing further attempts for a while after too many failures
+fn check_pin(app: &AppHandle, pin: &str) -> Result<(), String> {
+    let pin_hash = app
+        .state::<ConfigState>()
+        .get()
+        .session_lock
+        .pin_hash
+        .ok_or("No PIN has been set")?;
+
+    let state = app.state::<SessionLockState>();
+    let mut failed_attempts = state.failed_attempts.lock().unwrap();
+    if let (count, Some(last)) = *failed_attempts {
+        if count >= MAX_FAILED_ATTEMPTS && last.elapsed() < LOCKOUT {
+            let seconds = ((LOCKOUT - last.elapsed()).as_secs() + 1).to_string();
+            return Err(locale::text(
+                app,
+                "lock.tooManyAttempts",
+                &[("seconds", &seconds)],
+            ));
+        }
+    }
+
+    if verify_pin(&pin_hash, pin) {
+        *failed_attempts = (0, None);
+        Ok(())
+    } else {
+        let count = if failed_attempts.0 >= MAX_FAILED_ATTEMPTS {
+            1
+        } else {
+            failed_attempts.0 + 1
+        };
+        *failed_attempts = (count, Some(Instant::now()));
+        Err(locale::text(app, "lock.incorrectPin", &[]))
+    }
+}
+
+fn status(app: &AppHandle) -> SessionLockStatus {
+    let config = app.state::<ConfigState>().get().session_lock;
+    SessionLockStatus {
+        enabled: config.enabled,
+        idle_minutes: config.idle_minutes,
+        locked: is_locked(app),
+        has_pin: config.pin_hash.is_some(),
+        os_auth_available: config.allow_os_auth && os_auth_available(),
+    }
+}
+
+// Page load hook: track activity on every page, and cover pages that load while
+// the session is locked
+pub fn handle_page_load(webview: &Webview, payload: &PageLoadPayload<'_>) {
+    if payload.event() != PageLoadEvent::Finished {
+        return;
+    }
+
+    let _ = webview.eval(ACTIVITY_SCRIPT);
+    let app = webview.app_handle();
+    if is_locked(app) {
+        let _ = webview.eval(lock_script(app));
+    }
+}
+
+// Wrap the command handler so that while the session is locked, commands other
+// than the ones the overlay needs are refused
+pub fn guard_commands(
+    handler: impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        if !ALLOWED_WHILE_LOCKED.contains(&command)
+            && is_locked(invoke.message.webview_ref().app_handle())
+        {
+            eprintln!("Refused {} while the session is locked", command);
+            invoke.resolver.reject("The session is locked");
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+// A plugin whose commands are refused while the session is locked. Plugin
+// commands don't pass through the shell's command handler, so `guard_commands`
+// doesn't see them.
+pub struct GuardedPlugin<P>(P);
+
+pub fn guard_plugin<P: Plugin<Wry>>(plugin: P) -> GuardedPlugin<P> {
+    GuardedPlugin(plugin)
+}
+
+impl<P: Plugin<Wry>> Plugin<Wry> for GuardedPlugin<P> {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn initialize(
+        &mut self,
+        app: &AppHandle,
+        config: serde_json::Value,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.initialize(app, config)
+    }
+
+    // Plugins' scripts are only added to the main frame, as before
+    fn initialization_script(&self) -> Option<String> {
+        self.0.initialization_script()
+    }
+
+    fn window_created(&mut self, window: Window) {
+        self.0.window_created(window)
+    }
+
+    fn webview_created(&mut self, webview: Webview) {
+        self.0.webview_created(webview)
+    }
+
+    fn on_navigation(&mut self, webview: &Webview, url: &Url) -> bool {
+        self.0.on_navigation(webview, url)
+    }
+
+    fn on_page_load(&mut self, webview: &Webview, payload: &PageLoadPayload<'_>) {
+        self.0.on_page_load(webview, payload)
+    }
+
+    fn on_event(&mut self, app: &AppHandle, event: &RunEvent) {
+        self.0.on_event(app, event)
+    }
+
+    fn extend_api(&mut self, invoke: Invoke<Wry>) -> bool {
+        if is_locked(invoke.message.webview_ref().app_handle()) {
+            eprintln!(
+                "Refused plugin:{}|{} while the session is locked",
+                self.0.name(),
+                invoke.message.command()
+            );
+            invoke.resolver.reject("The session is locked");
+            return true;
+        }
+        self.0.extend_api(invoke)
+    }
+}
+
+// Lock the session once no activity has been reported for the idle period
+pub fn start_monitor(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        let config = app.state::<ConfigState>().get().session_lock;
+        if !config.enabled || is_locked(&app) {
+            continue;
+        }
+
+        let idle = app
+            .state::<SessionLockState>()
+            .last_activity
+            .lock()
+            .unwrap()
+            .elapsed();
+        if idle >= Duration::from_secs(config.idle_minutes.max(1) * 60) {
+            lock(&app, "idle");
+        }
+    });
+}
+
+// Command called by the page script whenever the user is active
+#[tauri::command]
+#[specta::specta]
+pub fn report_activity(state: State<SessionLockState>) {
+    if !state.locked.load(Ordering::SeqCst) {
+        *state.last_activity.lock().unwrap() = Instant::now();
+    }
+}
+
+// Command to lock the session straight away
+#[tauri::command]
+#[specta::specta]
+pub fn lock_session(app: AppHandle) {
+    lock(&app, "manual");
+}
+
+// Command to unlock the session with the PIN, or with OS authentication when no
+// PIN is given
+#[tauri::command]
+#[specta::specta]
+pub async fn unlock_session(
+    app: AppHandle,
+    window: WebviewWindow,
+    pin: Option<String>,
+) -> Result<(), String> {
+    if !is_locked(&app) {
+        return Ok(());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let method = match pin {
+            Some(pin) => {
+                check_pin(&app, &pin)?;
+                "pin"
+            }
+            None => {
+                if !app.state::<ConfigState>().get().session_lock.allow_os_auth {
+                    return Err("OS authentication is disabled".to_string());
+                }
+                os_authenticate(&app, &window)?;
+                "os"
+            }
+        };
+        unlock(&app, method);
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to get the session lock settings and whether the session is locked
+#[tauri::command]
+#[specta::specta]
+pub async fn get_session_lock(app: AppHandle) -> Result<SessionLockStatus, String> {
+    tauri::async_runtime::spawn_blocking(move || status(&app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to turn the idle lock on or off. Turning it on needs a PIN or OS
+// authentication to unlock with.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_session_lock(
+    app: AppHandle,
+    enabled: bool,
+    idle_minutes: Option<u64>,
+) -> Result<SessionLockStatus, String> {
+    if is_locked(&app) {
+        return Err("The session is locked".to_string());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let current = status(&app);
+        if enabled && !current.has_pin && !current.os_auth_available {
+            return Err("Set a PIN before turning on the session lock".to_string());
+        }
+
+        app.state::<ConfigState>().update(|config| {
+            config.session_lock.enabled = enabled;
+            if let Some(idle_minutes) = idle_minutes {
+                config.session_lock.idle_minutes = idle_minutes.max(1);
+            }
+        })?;
+        *app.state::<SessionLockState>()
+            .last_activity
+            .lock()
+            .unwrap() = Instant::now();
+        Ok(status(&app))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to set or change the unlock PIN. Changing an existing PIN needs the
+// current one.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_lock_pin(
+    app: AppHandle,
+    current_pin: Option<String>,
+    pin: String,
+) -> Result<(), String> {
+    if is_locked(&app) {
+        return Err("The session is locked".to_string());
+    }
+    if pin.len() < 4 {
+        return Err("The PIN must be at least 4 characters".to_string());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        if app
+            .state::<ConfigState>()
+            .get()
+            .session_lock
+            .pin_hash
+            .is_some()
+        {
+            check_pin(&app, current_pin.as_deref().unwrap_or_default())?;
+        }
+
+        let pin_hash = hash_pin(&pin)?;
+        app.state::<ConfigState>()
+            .update(|config| config.session_lock.pin_hash = Some(pin_hash))?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_only_the_right_pin() {
+        let hash = hash_pin("2468").unwrap();
+        assert!(verify_pin(&hash, "2468"));
+        assert!(!verify_pin(&hash, "2469"));
+        assert!(!verify_pin(&hash, ""));
+    }
+
+    #[test]
+    fn salts_every_hash() {
+        assert_ne!(hash_pin("2468").unwrap(), hash_pin("2468").unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_hashes() {
+        assert!(!verify_pin("", "2468"));
+        assert!(!verify_pin("no-separator", "2468"));
+        assert!(!verify_pin("not base64$also not", "2468"));
+        assert!(!verify_pin("$", ""));
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use episensor_app_framework::{
+    AppShell, HealthCheck, Launch, Logging, SandboxOptions, SandboxStatus, Sidecar,
+};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::auth;
+use crate::error::{ErrorCode, ShellError};
+use crate::events;
+use crate::integrity;
+use crate::paths::AppPaths;
+use crate::server::{self, ServerState};
+use crate::tls;
+use crate::updater;
+
+// Processes an app runs alongside its backend, e.g. a Python analytics worker,
+// declared in `plugins.backend.sidecars` in `tauri.conf.json`. Each is launched
+// through the framework crate like the backend: sandboxed, its output in
+// `<name>.log` in the log directory, killed with the shell. A sidecar is a
+// binary (`<name>-<target>` in the `sidecars` resource directory, as built by
+// `build-sidecar`, or `path` as-is) or a script run with Node, Python or the
+// given `interpreter`. It gets its own data directory, `sidecars/<name>` under
+// the app data directory, and the backend's URL and token to call it with. The
+// supervisor restarts sidecars that exit according to their `restart` policy,
+// up to `maxRestarts` times; the commands start, stop and restart them by name.
+const SIDECARS_DIR: &str = "sidecars";
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarRuntime {
+    #[default]
+    Binary,
+    Node,
+    Python,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Never,
+    // Only after a non-zero exit
+    #[default]
+    OnFailure,
+    Always,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarHealthCheck {
+    pub path: String,
+    // Passed to the sidecar as PORT
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SidecarSpec {
+    pub name: String,
+    pub runtime: SidecarRuntime,
+    // Relative to the resource directory: the script, or a binary used when
+    // there is no `<name>-<target>` build
+    pub path: Option<String>,
+    // Replaces `node` or `python3` (`python` on Windows)
+    pub interpreter: Option<String>,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    // Variables passed through from the shell's environment
+    pub inherit_env: Vec<String>,
+    // Without one, a sidecar is up as soon as it is spawned
+    pub health_check: Option<SidecarHealthCheck>,
+    pub startup_timeout_secs: u64,
+    pub restart: RestartPolicy,
+    pub max_restarts: u32,
+    // Check the binary or script against the `server-manifest.json` beside it,
+    // like the backend
+    pub verify: bool,
+    pub auto_start: bool,
+}
+
+impl Default for SidecarSpec {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            runtime: SidecarRuntime::default(),
+            path: None,
+            interpreter: None,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            inherit_env: Vec::new(),
+            health_check: None,
+            startup_timeout_secs: 30,
+            restart: RestartPolicy::default(),
+            max_restarts: 5,
+            verify: true,
+            auto_start: true,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SidecarStatus {
+    pub name: String,
+    pub runtime: SidecarRuntime,
+    pub running: bool,
+    // The health check's port, once it has answered
+    pub port: Option<u16>,
+    // Restarts by the supervisor since the sidecar was last started by hand
+    pub restarts: u32,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub sandbox: Option<SandboxStatus>,
+}
+
+struct ManagedSidecar {
+    spec: SidecarSpec,
+    shell: AppShell,
+    data_dir: PathBuf,
+    running: AtomicBool,
+    port: Mutex<Option<u16>>,
+    restarts: AtomicU32,
+    exit_code: Mutex<Option<i32>>,
+    error: Mutex<Option<String>>,
+}
+
+pub struct SidecarState {
+    sidecars: Vec<ManagedSidecar>,
+    // Set once the shell is exiting, so a start still under way is refused
+    exiting: AtomicBool,
+}
+
+fn default_interpreter(runtime: SidecarRuntime) -> &'static str {
+    match runtime {
+        SidecarRuntime::Python if cfg!(target_os = "windows") => "python",
+        SidecarRuntime::Python => "python3",
+        _ => "node",
+    }
+}
+
+// Where the framework crate looks for the sidecar
+fn locate(paths: &AppPaths, spec: &SidecarSpec) -> Result<Sidecar, String> {
+    let sidecar =
+        Sidecar::new(spec.name.as_str()).search_dir(paths.resource_dir.join(SIDECARS_DIR));
+    let path = spec.path.as_ref().map(|path| paths.resource_dir.join(path));
+    Ok(match (spec.runtime, path) {
+        (SidecarRuntime::Binary, Some(path)) => sidecar.program(path),
+        (SidecarRuntime::Binary, None) => sidecar,
+        (SidecarRuntime::Node, Some(path)) if spec.interpreter.is_none() => {
+            sidecar.node_script(path)
+        }
+        (runtime, Some(path)) => {
+            let interpreter = spec
+                .interpreter
+                .clone()
+                .unwrap_or_else(|| default_interpreter(runtime).to_string());
+            sidecar.script(interpreter, path)
+        }
+        (_, None) => return Err("a script sidecar needs a path".to_string()),
+    })
+}
+
+impl SidecarState {
+    pub fn load(paths: &AppPaths, config: &tauri::Config) -> Self {
+        let specs = config
+            .plugins
+            .0
+            .get("backend")
+            .and_then(|config| config.get("sidecars"))
+            .cloned()
+            .map(serde_json::from_value::<Vec<SidecarSpec>>)
+            .transpose()
+            .unwrap_or_else(|e| {
+                eprintln!("Invalid plugins.backend.sidecars: {}", e);
+                None
+            })
+            .unwrap_or_default();
+
+        let mut sidecars: Vec<ManagedSidecar> = Vec::new();
+        for spec in specs {
+            if spec.name.is_empty() || spec.name == "server" {
+                eprintln!("Ignoring sidecar without a name, or named \"server\"");
+                continue;
+            }
+            if sidecars
+                .iter()
+                .any(|sidecar| sidecar.spec.name == spec.name)
+            {
+                eprintln!("Ignoring duplicate sidecar {}", spec.name);
+                continue;
+            }
+            let sidecar = match locate(paths, &spec) {
+                Ok(sidecar) => sidecar,
+                Err(e) => {
+                    eprintln!("Ignoring sidecar {}: {}", spec.name, e);
+                    continue;
+                }
+            };
+
+            let mut builder = AppShell::builder()
+                .sidecar(sidecar)
+                .logging(Logging::to_file(
+                    paths.log_dir.join(format!("{}.log", spec.name)),
+                ));
+            if let Some(health_check) = &spec.health_check {
+                builder = builder.health_check(
+                    HealthCheck::new(health_check.path.as_str()).ports(&[health_check.port]),
+                );
+            }
+            sidecars.push(ManagedSidecar {
+                data_dir: paths.data_dir.join(SIDECARS_DIR).join(&spec.name),
+                shell: builder.build(),
+                spec,
+                running: AtomicBool::new(false),
+                port: Mutex::new(None),
+                restarts: AtomicU32::new(0),
+                exit_code: Mutex::new(None),
+                error: Mutex::new(None),
+            });
+        }
+        Self {
+            sidecars,
+            exiting: AtomicBool::new(false),
+        }
+    }
+
+    fn find(&self, name: &str) -> Result<&ManagedSidecar, ShellError> {
+        self.sidecars
+            .iter()
+            .find(|sidecar| sidecar.spec.name == name)
+            .ok_or_else(|| {
+                ShellError::new(
+                    ErrorCode::UnknownSidecar,
+                    format!("No sidecar named {}", name),
+                )
+                .with("sidecar", name)
+            })
+    }
+}
+
+fn status(sidecar: &ManagedSidecar) -> SidecarStatus {
+    SidecarStatus {
+        name: sidecar.spec.name.clone(),
+        runtime: sidecar.spec.runtime,
+        running: sidecar.running.load(Ordering::Relaxed),
+        port: *sidecar.port.lock().unwrap(),
+        restarts: sidecar.restarts.load(Ordering::Relaxed),
+        exit_code: *sidecar.exit_code.lock().unwrap(),
+        error: sidecar.error.lock().unwrap().clone(),
+        sandbox: sidecar.shell.sandbox_status(),
+    }
+}
+
+fn announce(app: &AppHandle, sidecar: &ManagedSidecar) {
+    let _ = events::SIDECAR_STATUS.emit(app, &status(sidecar));
+}
+
+// Set up the sidecar's environment for `AppShell::start`
+fn prepare_launch(
+    app: &AppHandle,
+    sidecar: &ManagedSidecar,
+    launch: &Launch,
+    command: &mut Command,
+) -> Result<(), ShellError> {
+    let spec = &sidecar.spec;
+    if app.state::<SidecarState>().exiting.load(Ordering::SeqCst) {
+        return Err(ShellError::new(
+            ErrorCode::BackendUnavailable,
+            "The shell is exiting",
+        ));
+    }
+    let file = match launch {
+        Launch::Sidecar(binary) => binary,
+        Launch::Node(script) | Launch::Script { script, .. } => script,
+    };
+    if spec.verify {
+        let paths = app.state::<AppPaths>();
+        let pubkey = updater::configured_pubkey(app.config());
+        integrity::verify_sidecar(&paths, file, pubkey.as_deref()).map_err(|e| {
+            ShellError::new(
+                ErrorCode::SidecarRejected,
+                format!("Refusing to start sidecar {}: {}", spec.name, e),
+            )
+            .with("sidecar", &spec.name)
+            .with("binary", file.display())
+        })?;
+    }
+    match launch {
+        Launch::Sidecar(binary) => println!("Starting sidecar {}: {:?}", spec.name, binary),
+        Launch::Node(script) => println!("Starting sidecar {}: node {:?}", spec.name, script),
+        Launch::Script {
+            interpreter,
+            script,
+        } => println!(
+            "Starting sidecar {}: {} {:?}",
+            spec.name, interpreter, script
+        ),
+    }
+
+    command
+        .args(&spec.args)
+        .env("DESKTOP", "true")
+        .env("HOST", "127.0.0.1")
+        .env("DATA_DIR", &sidecar.data_dir);
+    if let Some(health_check) = &spec.health_check {
+        command.env("PORT", health_check.port.to_string());
+    }
+    if spec.runtime == SidecarRuntime::Node {
+        command.env("NODE_ENV", "production");
+    }
+
+    // Sidecars reach the backend like the shell does
+    let backend = app.state::<ServerState>();
+    if let Some(port) = backend.port() {
+        command
+            .env(
+                "BACKEND_URL",
+                format!("{}://127.0.0.1:{}", backend.scheme(), port),
+            )
+            .env(auth::TOKEN_ENV, &backend.token);
+        if let Some(identity) = &backend.tls {
+            command.env(tls::CERT_ENV, &identity.cert_path);
+        }
+    }
+    command.envs(&spec.env);
+    Ok(())
+}
+
+fn is_healthy(client: &reqwest::blocking::Client, url: &str) -> bool {
+    client
+        .get(url)
+        .timeout(Duration::from_secs(1))
+        .send()
+        .map(|response| response.status().is_success())
+        .unwrap_or(false)
+}
+
+// Start a sidecar unless it is running, and wait for its health check
+fn start(app: &AppHandle, sidecar: &ManagedSidecar) -> Result<Option<u16>, ShellError> {
+    let spec = &sidecar.spec;
+    fs::create_dir_all(&sidecar.data_dir).map_err(|e| {
+        ShellError::new(
+            ErrorCode::Io,
+            format!(
+                "Failed to create data directory for sidecar {}: {}",
+                spec.name, e
+            ),
+        )
+        .with("path", sidecar.data_dir.display())
+    })?;
+
+    let paths = app.state::<AppPaths>();
+    let options = SandboxOptions {
+        restrict_env: true,
+        inherit_env: spec.inherit_env.clone(),
+    };
+    let mut refused = None;
+    let started = sidecar
+        .shell
+        .start(&options, &paths.resource_dir, |launch, command| {
+            prepare_launch(app, sidecar, launch, command)
+                .map_err(|e| refused.insert(e).message.clone())
+        })
+        .map_err(|e| server::launch_error(e, refused).with("sidecar", &spec.name))?;
+    if !started {
+        return Ok(*sidecar.port.lock().unwrap());
+    }
+
+    let Some(health_check) = &spec.health_check else {
+        return Ok(None);
+    };
+    let client = reqwest::blocking::Client::new();
+    let timeout = Duration::from_secs(spec.startup_timeout_secs);
+    match sidecar
+        .shell
+        .wait_for_health("http", timeout, |url| is_healthy(&client, url))
+    {
+        Some(port) => Ok(Some(port)),
+        None if sidecar.shell.has_exited() => Err(ShellError::new(
+            ErrorCode::BackendExited,
+            format!("Sidecar {} exited during startup", spec.name),
+        )
+        .with("sidecar", &spec.name)),
+        None => Err(ShellError::new(
+            ErrorCode::HealthCheckTimeout,
+            format!("Sidecar {} failed to start within timeout", spec.name),
+        )
+        .with("sidecar", &spec.name)
+        .with("port", health_check.port)),
+    }
+}
+
+// Start a sidecar, recording the outcome in its status
+fn launch(app: &AppHandle, sidecar: &ManagedSidecar) -> Result<(), ShellError> {
+    let result = start(app, sidecar);
+    match &result {
+        Ok(port) => {
+            sidecar.running.store(true, Ordering::Relaxed);
+            *sidecar.port.lock().unwrap() = *port;
+            *sidecar.exit_code.lock().unwrap() = None;
+            *sidecar.error.lock().unwrap() = None;
+            println!("Sidecar {} is running", sidecar.spec.name);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            // A sidecar that timed out is left running for the supervisor
+            let running = e.code == ErrorCode::HealthCheckTimeout;
+            sidecar.running.store(running, Ordering::Relaxed);
+            *sidecar.error.lock().unwrap() = Some(e.message.clone());
+        }
+    }
+    announce(app, sidecar);
+    result.map(|_| ())
+}
+
+fn stop(sidecar: &ManagedSidecar) {
+    sidecar.shell.stop();
+    sidecar.running.store(false, Ordering::Relaxed);
+    *sidecar.port.lock().unwrap() = None;
+}
+
+// Deal with a sidecar that has exited, restarting it if its policy allows
+fn recover(app: &AppHandle, sidecar: &ManagedSidecar) {
+    let Some(exit) = sidecar.shell.exit_status() else {
+        return;
+    };
+    let spec = &sidecar.spec;
+    // Reaps the process, so the exit is only handled once
+    stop(sidecar);
+    *sidecar.exit_code.lock().unwrap() = exit.code();
+
+    let restarts = sidecar.restarts.load(Ordering::Relaxed);
+    let restart = match spec.restart {
+        RestartPolicy::Never => false,
+        RestartPolicy::OnFailure => !exit.success(),
+        RestartPolicy::Always => true,
+    };
+    if !restart || restarts >= spec.max_restarts {
+        eprintln!("Sidecar {} exited ({})", spec.name, exit);
+        if restart {
+            *sidecar.error.lock().unwrap() = Some(format!("Gave up after {} restarts", restarts));
+        }
+        announce(app, sidecar);
+        return;
+    }
+
+    eprintln!("Sidecar {} exited ({}), restarting", spec.name, exit);
+    sidecar.restarts.store(restarts + 1, Ordering::Relaxed);
+    let _ = launch(app, sidecar);
+}
+
+// Start the sidecars marked `autoStart`, then supervise them all. Called once
+// the backend is up, so they get its URL.
+pub fn init(app: &AppHandle) {
+    if app.state::<SidecarState>().sidecars.is_empty() {
+        return;
+    }
+
+    let app = app.clone();
+    thread::spawn(move || {
+        let state = app.state::<SidecarState>();
+        for sidecar in state
+            .sidecars
+            .iter()
+            .filter(|sidecar| sidecar.spec.auto_start)
+        {
+            let _ = launch(&app, sidecar);
+        }
+        loop {
+            thread::sleep(SUPERVISE_INTERVAL);
+            for sidecar in &state.sidecars {
+                recover(&app, sidecar);
+            }
+        }
+    });
+}
+
+pub fn stop_all(state: &SidecarState) {
+    state.exiting.store(true, Ordering::SeqCst);
+    for sidecar in &state.sidecars {
+        stop(sidecar);
+    }
+}
+
+// Runs a start on a blocking thread, since it waits for the health check
+async fn run_blocking(
+    app: AppHandle,
+    name: String,
+    restart: bool,
+) -> Result<SidecarStatus, ShellError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<SidecarState>();
+        let sidecar = state.find(&name)?;
+        if restart {
+            stop(sidecar);
+        }
+        sidecar.restarts.store(0, Ordering::Relaxed);
+        launch(&app, sidecar)?;
+        Ok(status(sidecar))
+    })
+    .await
+    .map_err(|e| ShellError::new(ErrorCode::Io, e.to_string()))?
+}
+
+// Command to list the app's sidecars and whether they are running
+#[tauri::command]
+#[specta::specta]
+pub fn list_sidecars(state: State<SidecarState>) -> Vec<SidecarStatus> {
+    state.sidecars.iter().map(status).collect()
+}
+
+// Command to start a sidecar, e.g. one without `autoStart`
+#[tauri::command]
+#[specta::specta]
+pub async fn start_sidecar(app: AppHandle, name: String) -> Result<SidecarStatus, ShellError> {
+    run_blocking(app, name, false).await
+}
+
+// Command to stop a sidecar. The supervisor leaves it stopped until it is
+// started again.
+#[tauri::command]
+#[specta::specta]
+pub fn stop_sidecar(
+    app: AppHandle,
+    state: State<SidecarState>,
+    name: String,
+) -> Result<SidecarStatus, ShellError> {
+    let sidecar = state.find(&name)?;
+    stop(sidecar);
+    println!("Sidecar {} stopped", name);
+    announce(&app, sidecar);
+    Ok(status(sidecar))
+}
+
+// Command to restart a sidecar, resetting its restart count
+#[tauri::command]
+#[specta::specta]
+pub async fn restart_sidecar(app: AppHandle, name: String) -> Result<SidecarStatus, ShellError> {
+    run_blocking(app, name, true).await
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::backend;
+use crate::events;
+use crate::server::ServerState;
+use crate::ws_bridge;
+
+// Server-sent event streams from the backend, re-emitted to the webview as
+// `sse:<name>` events. Each subscription reconnects on its own, resuming from the
+// last event id the way a browser EventSource does.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+struct Subscription {
+    path: String,
+    handle: JoinHandle<()>,
+}
+
+#[derive(Default)]
+pub struct SseState {
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SseEvent {
+    pub event: String,
+    // Parsed JSON when the data is JSON, the raw text otherwise
+    pub data: serde_json::Value,
+    pub id: Option<String>,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SseStatus {
+    pub name: String,
+    pub connected: bool,
+}
+
+// Incremental parser for the `text/event-stream` format
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                events.extend(self.dispatch());
+                continue;
+            }
+
+            // Lines starting with ':' are comments, used by servers as keep-alives
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+                "retry" => {
+                    if let Ok(ms) = value.parse() {
+                        self.retry = Some(Duration::from_millis(ms));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    // Discard a partially received event on disconnect, keeping the last event id
+    // and retry delay for the next connection
+    fn disconnect(&mut self) {
+        self.buffer.clear();
+        self.event = None;
+        self.data.clear();
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let data = self.data.drain(..).collect::<Vec<_>>().join("\n");
+        Some(SseEvent {
+            event: event.unwrap_or_else(|| "message".to_string()),
+            data: serde_json::from_str(&data).unwrap_or(serde_json::Value::String(data)),
+            id: self.last_event_id.clone(),
+        })
+    }
+}
+
+// Headers for (re)connecting, resuming after the last event received
+fn event_stream_request(
+    request: reqwest::RequestBuilder,
+    parser: &SseParser,
+) -> reqwest::RequestBuilder {
+    let request = request
+        .header("Accept", "text/event-stream")
+        .header("Cache-Control", "no-cache");
+    match &parser.last_event_id {
+        Some(id) => request.header("Last-Event-ID", id),
+        None => request,
+    }
+}
+
+fn set_status(app: &AppHandle, name: &str, connected: bool) {
+    let _ = events::SSE_STATUS.emit(
+        app,
+        &SseStatus {
+            name: name.to_string(),
+            connected,
+        },
+    );
+}
+
+// Returns false when the server asked the client to stop reconnecting (204)
+async fn run_connection(
+    app: &AppHandle,
+    name: &str,
+    path: &str,
+    parser: &mut SseParser,
+) -> Result<bool, String> {
+    let state = app.state::<ServerState>();
+    let request = backend::stream_request(&state, Method::GET, path)?;
+
+    let mut response = event_stream_request(request, parser)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status() == StatusCode::NO_CONTENT {
+        return Ok(false);
+    }
+    if !response.status().is_success() {
+        return Err(format!("Event stream responded with {}", response.status()));
+    }
+
+    set_status(app, name, true);
+    let event_name = format!("sse:{}", ws_bridge::sanitize_event_name(name));
+
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        for event in parser.feed(&chunk) {
+            let _ = app.emit(&event_name, event);
+        }
+    }
+
+    Ok(true)
+}
+
+fn start_subscription(app: AppHandle, name: String, path: String) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut parser = SseParser::default();
+
+        loop {
+            parser.disconnect();
+
+            match run_connection(&app, &name, &path, &mut parser).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    set_status(&app, &name, false);
+                    return;
+                }
+                Err(e) => eprintln!("Event stream '{}' error: {}", name, e),
+            }
+            set_status(&app, &name, false);
+
+            tokio::time::sleep(parser.retry.unwrap_or(DEFAULT_RETRY)).await;
+        }
+    })
+}
+
+// Command to subscribe to a backend event stream under a name, replacing any
+// existing subscription with the same name
+#[tauri::command]
+#[specta::specta]
+pub fn subscribe_sse(
+    app: AppHandle,
+    state: State<SseState>,
+    name: String,
+    path: String,
+) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err(format!("Backend path must start with '/': {}", path));
+    }
+
+    let handle = start_subscription(app, name.clone(), path.clone());
+    let subscription = Subscription { path, handle };
+    if let Some(previous) = state.subscriptions.lock().unwrap().insert(name, subscription) {
+        previous.handle.abort();
+    }
+    Ok(())
+}
+
+// Restart every subscription, for when the streams are likely dead without having
+// noticed, such as after the machine slept. Events sent in between are lost.
+pub fn reconnect_all(app: &AppHandle) {
+    let state = app.state::<SseState>();
+    let mut subscriptions = state.subscriptions.lock().unwrap();
+    for (name, subscription) in subscriptions.iter_mut() {
+        subscription.handle.abort();
+        subscription.handle = start_subscription(app.clone(), name.clone(), subscription.path.clone());
+    }
+}
+
+// Command to close a backend event stream
+#[tauri::command]
+#[specta::specta]
+pub fn unsubscribe_sse(app: AppHandle, state: State<SseState>, name: String) {
+    if let Some(subscription) = state.subscriptions.lock().unwrap().remove(&name) {
+        subscription.handle.abort();
+        set_status(&app, &name, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> (SseParser, Vec<SseEvent>) {
+        let mut parser = SseParser::default();
+        let events = parser.feed(input.as_bytes());
+        (parser, events)
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let (_, events) = parse("data: first\ndata: second\ndata:third\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].data, "first\nsecond\nthird");
+    }
+
+    #[test]
+    fn parses_json_data_and_event_names() {
+        let (_, events) = parse("event: reading\r\ndata: {\"value\": 4}\r\n\r\ndata: plain\n\n");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "reading");
+        assert_eq!(events[0].data, serde_json::json!({ "value": 4 }));
+        assert_eq!(events[1].event, "message");
+        assert_eq!(events[1].data, "plain");
+    }
+
+    #[test]
+    fn handles_lines_split_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b"data: hel").is_empty());
+        assert!(parser.feed(b"lo\n").is_empty());
+        let events = parser.feed(b"\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "hello");
+    }
+
+    #[test]
+    fn keeps_the_last_event_id() {
+        let (parser, events) = parse("id: 1\ndata: a\n\ndata: b\n\nid: 3\n\nid: bad\0id\n\n");
+        assert_eq!(events[0].id.as_deref(), Some("1"));
+        assert_eq!(events[1].id.as_deref(), Some("1"));
+        assert_eq!(events.len(), 2);
+        assert_eq!(parser.last_event_id.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn takes_the_retry_delay_from_the_stream() {
+        let (parser, _) = parse("retry: 5000\n\n");
+        assert_eq!(parser.retry, Some(Duration::from_secs(5)));
+
+        let (parser, _) = parse("retry: soon\n\n");
+        assert_eq!(parser.retry, None);
+    }
+
+    #[test]
+    fn ignores_comments_and_unknown_fields() {
+        let (_, events) = parse(": keep-alive\n\n:\nfoo: bar\ndata\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "");
+    }
+
+    #[test]
+    fn discards_a_partial_event_on_disconnect() {
+        let (mut parser, _) = parse("id: 7\nretry: 100\nevent: reading\ndata: partial\n");
+        parser.disconnect();
+        let events = parser.feed(b"data: next\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].data, "next");
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+        assert_eq!(parser.retry, Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn resends_the_last_event_id_on_reconnect() {
+        let client = reqwest::Client::new();
+        let (mut parser, _) = parse("data: first\n\n");
+        let request = event_stream_request(client.get("http://127.0.0.1/events"), &parser)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["Accept"], "text/event-stream");
+        assert!(request.headers().get("Last-Event-ID").is_none());
+
+        parser.feed(b"id: 42\ndata: second\n\n");
+        parser.disconnect();
+        let request = event_stream_request(client.get("http://127.0.0.1/events"), &parser)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["Last-Event-ID"], "42");
+    }
+}
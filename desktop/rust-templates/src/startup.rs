@@ -0,0 +1,52 @@
+use std::thread;
+use std::time::Instant;
+
+use tauri::{AppHandle, Manager};
+
+use crate::events;
+use crate::integrity;
+use crate::paths::AppPaths;
+use crate::server::{self, BackendReady, ServerState};
+use crate::sidecars;
+use crate::ws_bridge;
+
+// Startup work that doesn't have to finish before the window appears. The window
+// is created as soon as Tauri is ready, while the backend is started on a thread
+// of its own: verifying the sidecar, spawning it and waiting for its health check
+// take seconds, during which the webview shows its own loading state. The
+// frontend learns the outcome from `backend-ready` or `backend-failed`, or from
+// `get_backend_status` if it loaded after them. What needs the backend (the
+// WebSocket bridge, other sidecars) follows once it is up.
+
+// Start the backend in the background, then everything waiting on it
+pub fn start_backend(app: &AppHandle, launched: Instant, headless: bool) {
+    let app = app.clone();
+    thread::spawn(move || {
+        println!("Starting backend server...");
+        let state = app.state::<ServerState>();
+        let result = server::start_backend_server(&app.state::<AppPaths>(), &state);
+        if state.is_exiting() {
+            return;
+        }
+        integrity::init(&app);
+
+        match result {
+            Ok(port) => {
+                println!(
+                    "Backend ready {} ms after launch",
+                    launched.elapsed().as_millis()
+                );
+                let _ = events::BACKEND_READY.emit(&app, &BackendReady { port });
+                ws_bridge::reconnect(&app);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                let _ = events::BACKEND_FAILED.emit(&app, &e);
+                if !headless {
+                    server::show_startup_error(&app, &e);
+                }
+            }
+        }
+        sidecars::init(&app);
+    });
+}
@@ -0,0 +1,199 @@
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::State;
+
+use crate::paths::AppPaths;
+
+// Key-value cache in SQLite, owned by the shell so it stays available while the
+// backend is restarting or being updated. The frontend uses it for last-known
+// readings and to queue user actions until the backend is back. Each workspace
+// has a database of its own, so one site's readings never show up in another.
+const DEFAULT_QUERY_LIMIT: u32 = 100;
+const MAX_QUERY_LIMIT: u32 = 1000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS cache (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        expires_at INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS cache_updated_at ON cache (updated_at);
+";
+
+pub struct StorageState {
+    // None when the database couldn't be opened; the app still runs without it
+    db: Mutex<Option<Connection>>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct CacheEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    // Milliseconds since the Unix epoch
+    pub updated_at: i64,
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn open(paths: &AppPaths, workspace: &str) -> rusqlite::Result<Connection> {
+    let _ = fs::create_dir_all(&paths.data_dir);
+    let db = Connection::open(paths.cache_db(workspace))?;
+
+    // WAL keeps readers from blocking on a write in progress
+    db.pragma_update(None, "journal_mode", "WAL")?;
+    db.execute_batch(SCHEMA)?;
+    db.execute(
+        "DELETE FROM cache WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+        params![now_ms()],
+    )?;
+    Ok(db)
+}
+
+impl StorageState {
+    pub fn open(paths: &AppPaths, workspace: &str) -> Self {
+        let state = Self {
+            db: Mutex::new(None),
+        };
+        state.switch(paths, workspace);
+        state
+    }
+
+    // Close the current database and open the one of `workspace`
+    pub fn switch(&self, paths: &AppPaths, workspace: &str) {
+        let mut db = self.db.lock().unwrap();
+        *db = None;
+        *db = open(paths, workspace)
+            .map_err(|e| eprintln!("Failed to open local cache, continuing without it: {}", e))
+            .ok();
+    }
+
+    fn with_db<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+        let db = self.db.lock().unwrap();
+        let db = db.as_ref().ok_or("Local cache is unavailable")?;
+        f(db).map_err(|e| e.to_string())
+    }
+}
+
+// A TTL too long to represent means the entry never expires in practice
+fn expiry(now: i64, ttl_secs: u64) -> i64 {
+    i64::try_from(ttl_secs)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(1000)
+        .saturating_add(now)
+}
+
+fn parse_value(value: String) -> serde_json::Value {
+    serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value))
+}
+
+// Command to store a JSON value under a key, replacing any previous value. With
+// `ttl_secs` the entry expires after that long.
+#[tauri::command]
+#[specta::specta]
+pub fn cache_put(
+    state: State<StorageState>,
+    key: String,
+    value: serde_json::Value,
+    ttl_secs: Option<u64>,
+) -> Result<(), String> {
+    let now = now_ms();
+    let expires_at = ttl_secs.map(|ttl| expiry(now, ttl));
+    let value = value.to_string();
+
+    state.with_db(|db| {
+        db.execute(
+            "INSERT INTO cache (key, value, updated_at, expires_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (key) DO UPDATE SET
+                value = excluded.value,
+                updated_at = excluded.updated_at,
+                expires_at = excluded.expires_at",
+            params![key, value, now, expires_at],
+        )
+        .map(|_| ())
+    })
+}
+
+// Command to read the value stored under a key, or null if there is none
+#[tauri::command]
+#[specta::specta]
+pub fn cache_get(state: State<StorageState>, key: String) -> Result<Option<CacheEntry>, String> {
+    state.with_db(|db| {
+        db.query_row(
+            "SELECT key, value, updated_at FROM cache
+             WHERE key = ?1 AND (expires_at IS NULL OR expires_at > ?2)",
+            params![key, now_ms()],
+            |row| {
+                Ok(CacheEntry {
+                    key: row.get(0)?,
+                    value: parse_value(row.get(1)?),
+                    updated_at: row.get(2)?,
+                })
+            },
+        )
+        .optional()
+    })
+}
+
+// Command to list entries whose key starts with `prefix` (all entries without
+// one), oldest first so queued actions replay in order
+#[tauri::command]
+#[specta::specta]
+pub fn cache_query(
+    state: State<StorageState>,
+    prefix: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<CacheEntry>, String> {
+    let prefix = prefix.unwrap_or_default();
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT);
+
+    state.with_db(|db| {
+        // substr rather than LIKE, so `%` and `_` in the prefix aren't wildcards
+        let mut statement = db.prepare(
+            "SELECT key, value, updated_at FROM cache
+             WHERE substr(key, 1, length(?1)) = ?1
+               AND (expires_at IS NULL OR expires_at > ?2)
+             ORDER BY updated_at, key
+             LIMIT ?3",
+        )?;
+        let entries = statement.query_map(params![prefix, now_ms(), limit], |row| {
+            Ok(CacheEntry {
+                key: row.get(0)?,
+                value: parse_value(row.get(1)?),
+                updated_at: row.get(2)?,
+            })
+        })?;
+        entries.collect()
+    })
+}
+
+// Command to remove an entry, e.g. a queued action once the backend has accepted it
+#[tauri::command]
+#[specta::specta]
+pub fn cache_delete(state: State<StorageState>, key: String) -> Result<bool, String> {
+    state.with_db(|db| {
+        db.execute("DELETE FROM cache WHERE key = ?1", params![key])
+            .map(|deleted| deleted > 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_saturates_instead_of_overflowing() {
+        assert_eq!(expiry(1_000, 60), 61_000);
+        assert_eq!(expiry(1_000, u64::MAX), i64::MAX);
+        assert_eq!(expiry(1_000, i64::MAX as u64 / 1000), i64::MAX);
+    }
+}
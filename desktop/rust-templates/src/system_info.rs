@@ -0,0 +1,118 @@
+use serde::Serialize;
+use sysinfo::{Disks, Networks, System};
+
+// Machine details for the diagnostics bundle and for node-locked licensing, which
+// keys licences on the MAC addresses reported here.
+
+#[derive(Serialize, specta::Type)]
+pub struct SystemInfo {
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    // e.g. "Windows 11 Pro" or "macOS 14.5 Sonoma"
+    pub os_long_version: Option<String>,
+    pub kernel_version: Option<String>,
+    pub arch: String,
+    pub hostname: Option<String>,
+    pub cpu: CpuInfo,
+    pub memory_total_bytes: u64,
+    pub memory_available_bytes: u64,
+    pub disks: Vec<DiskInfo>,
+    pub network_interfaces: Vec<NetworkInterface>,
+    // BCP 47 tag such as "en-IE"
+    pub locale: Option<String>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct CpuInfo {
+    pub brand: String,
+    pub logical_cores: usize,
+    pub physical_cores: Option<usize>,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct DiskInfo {
+    pub mount_point: String,
+    pub file_system: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    pub removable: bool,
+}
+
+#[derive(Serialize, specta::Type)]
+pub struct NetworkInterface {
+    pub name: String,
+    // Null for interfaces without a hardware address, such as loopback
+    pub mac_address: Option<String>,
+    // CIDR notation, e.g. "192.168.1.20/24"
+    pub addresses: Vec<String>,
+}
+
+fn collect() -> SystemInfo {
+    let mut system = System::new();
+    system.refresh_cpu_all();
+    system.refresh_memory();
+
+    let disks = Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .map(|disk| DiskInfo {
+            mount_point: disk.mount_point().to_string_lossy().into_owned(),
+            file_system: disk.file_system().to_string_lossy().into_owned(),
+            total_bytes: disk.total_space(),
+            available_bytes: disk.available_space(),
+            removable: disk.is_removable(),
+        })
+        .collect();
+
+    let mut network_interfaces: Vec<NetworkInterface> = Networks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .map(|(name, network)| {
+            let mac_address = network.mac_address();
+            NetworkInterface {
+                name: name.clone(),
+                mac_address: (!mac_address.is_unspecified()).then(|| mac_address.to_string()),
+                addresses: network
+                    .ip_networks()
+                    .iter()
+                    .map(|network| format!("{}/{}", network.addr, network.prefix))
+                    .collect(),
+            }
+        })
+        .collect();
+    // Keep the order stable between calls, which licensing relies on
+    network_interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    SystemInfo {
+        os_name: System::name(),
+        os_version: System::os_version(),
+        os_long_version: System::long_os_version(),
+        kernel_version: System::kernel_version(),
+        arch: System::cpu_arch(),
+        hostname: System::host_name(),
+        cpu: CpuInfo {
+            brand: system
+                .cpus()
+                .first()
+                .map(|cpu| cpu.brand().trim().to_string())
+                .unwrap_or_default(),
+            logical_cores: system.cpus().len(),
+            physical_cores: system.physical_core_count(),
+        },
+        memory_total_bytes: system.total_memory(),
+        memory_available_bytes: system.available_memory(),
+        disks,
+        network_interfaces,
+        locale: sys_locale::get_locale(),
+    }
+}
+
+// Command to describe this machine. Enumerating disks can be slow when network
+// drives are mounted, so it runs off the main thread.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_system_info() -> Result<SystemInfo, String> {
+    tauri::async_runtime::spawn_blocking(collect)
+        .await
+        .map_err(|e| e.to_string())
+}
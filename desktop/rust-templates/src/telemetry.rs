@@ -0,0 +1,309 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::certificates;
+use crate::config::ConfigState;
+use crate::connectivity::NetworkState;
+use crate::paths::AppPaths;
+use crate::version;
+
+// Anonymous usage telemetry, off until the user opts in with
+// `set_telemetry_enabled`. Only coarse events are recorded (the app launching,
+// the backend being restarted, an update being applied, a crash), with the app
+// version and platform and a random install ID that isn't derived from the
+// machine or user. Events are queued in the data directory and sent to
+// `plugins.telemetry.endpoint` in `tauri.conf.json` when online, so nothing is
+// lost while offline; apps without an endpoint can't enable telemetry at all.
+// Opting out drops the install ID and anything still queued.
+const MAX_QUEUED: usize = 500;
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    // Created when telemetry is enabled, so reports from before opting out
+    // can't be linked to those after opting back in
+    pub install_id: Option<String>,
+    pub send_interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            install_id: None,
+            send_interval_secs: 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageEvent {
+    AppLaunched,
+    BackendRestarted,
+    UpdateApplied,
+    Crash,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueuedEvent {
+    event: UsageEvent,
+    // e.g. which kind of update was applied
+    detail: Option<String>,
+    timestamp: u64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    install_id: &'a str,
+    app_version: String,
+    framework_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    events: &'a [QueuedEvent],
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    // Whether the app has an endpoint to send to
+    pub available: bool,
+    pub install_id: Option<String>,
+    pub queued: u32,
+}
+
+pub struct TelemetryState {
+    path: PathBuf,
+    queue: Mutex<Vec<QueuedEvent>>,
+    // Mirrors `telemetry.enabled`, so the panic hook doesn't need the config lock
+    enabled: AtomicBool,
+}
+
+impl TelemetryState {
+    pub fn load(paths: &AppPaths, config: &TelemetryConfig) -> Self {
+        let path = paths.telemetry_queue();
+        let queue = match fs::read_to_string(&path) {
+            Ok(contents) if config.enabled => serde_json::from_str(&contents).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        Self {
+            path,
+            queue: Mutex::new(queue),
+            enabled: AtomicBool::new(config.enabled),
+        }
+    }
+
+    fn push(&self, queue: &mut Vec<QueuedEvent>, event: UsageEvent, detail: Option<String>) {
+        queue.push(QueuedEvent {
+            event,
+            detail,
+            timestamp: now(),
+        });
+        let excess = queue.len().saturating_sub(MAX_QUEUED);
+        queue.drain(..excess);
+        self.save(queue);
+    }
+
+    fn save(&self, queue: &[QueuedEvent]) {
+        if queue.is_empty() {
+            let _ = fs::remove_file(&self.path);
+            return;
+        }
+        let result = serde_json::to_string(queue)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| fs::write(&self.path, contents).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("Failed to save telemetry queue: {}", e);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn generate_install_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// `plugins.telemetry.endpoint` in tauri.conf.json
+fn endpoint(app: &AppHandle) -> Option<String> {
+    app.config()
+        .plugins
+        .0
+        .get("telemetry")
+        .and_then(|config| config.get("endpoint"))
+        .and_then(|endpoint| endpoint.as_str())
+        .filter(|endpoint| !endpoint.is_empty())
+        .map(|endpoint| endpoint.to_string())
+}
+
+// Queue an event if the user has opted in
+pub fn record(app: &AppHandle, event: UsageEvent, detail: Option<&str>) {
+    let state = app.state::<TelemetryState>();
+    if !state.enabled.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut queue = state.queue.lock().unwrap();
+    state.push(&mut queue, event, detail.map(str::to_string));
+}
+
+// Record panics as crashes before the previous hook runs (release builds abort
+// straight after). The queue may be locked by the panicking thread, in which
+// case the crash goes unrecorded rather than deadlocking.
+fn install_panic_hook(app: &AppHandle) {
+    let app = app.clone();
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let state = app.state::<TelemetryState>();
+        if state.enabled.load(Ordering::Relaxed) {
+            if let Ok(mut queue) = state.queue.try_lock() {
+                let location = info
+                    .location()
+                    .map(|location| format!("{}:{}", location.file(), location.line()));
+                state.push(&mut queue, UsageEvent::Crash, location);
+            }
+        }
+        previous(info);
+    }));
+}
+
+// Send the queued events, keeping them if the machine is offline or sending fails
+async fn send(app: &AppHandle) -> Result<(), String> {
+    let Some(endpoint) = endpoint(app) else {
+        return Ok(());
+    };
+    let Some(install_id) = app.state::<ConfigState>().get().telemetry.install_id else {
+        return Ok(());
+    };
+    if app.state::<NetworkState>().online() == Some(false) {
+        return Ok(());
+    }
+
+    let state = app.state::<TelemetryState>();
+    let events = state.queue.lock().unwrap().clone();
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let report = Report {
+        install_id: &install_id,
+        app_version: app.package_info().version.to_string(),
+        framework_version: version::FRAMEWORK_VERSION,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        events: &events,
+    };
+    // The shared client, which has a connect timeout and presents a client
+    // certificate if one is selected for the endpoint
+    let response = certificates::client_for(app, &endpoint)?
+        .post(&endpoint)
+        .json(&report)
+        .timeout(SEND_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Telemetry endpoint responded with {}",
+            response.status()
+        ));
+    }
+
+    // Events recorded while sending stay queued
+    let mut queue = state.queue.lock().unwrap();
+    let sent = events.len().min(queue.len());
+    queue.drain(..sent);
+    state.save(&queue);
+    Ok(())
+}
+
+pub fn init(app: &AppHandle) {
+    install_panic_hook(app);
+    record(app, UsageEvent::AppLaunched, None);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = send(&app).await {
+                eprintln!("Failed to send telemetry: {}", e);
+            }
+            let interval = app
+                .state::<ConfigState>()
+                .get()
+                .telemetry
+                .send_interval_secs;
+            tokio::time::sleep(Duration::from_secs(interval.max(60))).await;
+        }
+    });
+}
+
+fn status(app: &AppHandle) -> TelemetryStatus {
+    let config = app.state::<ConfigState>().get().telemetry;
+    TelemetryStatus {
+        enabled: config.enabled,
+        available: endpoint(app).is_some(),
+        install_id: config.install_id,
+        queued: app.state::<TelemetryState>().queue.lock().unwrap().len() as u32,
+    }
+}
+
+// Command to get whether telemetry is enabled, and what is waiting to be sent
+#[tauri::command]
+#[specta::specta]
+pub fn get_telemetry_status(app: AppHandle) -> TelemetryStatus {
+    status(&app)
+}
+
+// Command to opt in to or out of usage telemetry. Opting out discards the
+// install ID and any events not yet sent.
+#[tauri::command]
+#[specta::specta]
+pub fn set_telemetry_enabled(
+    app: AppHandle,
+    state: State<TelemetryState>,
+    enabled: bool,
+) -> Result<TelemetryStatus, String> {
+    if enabled && endpoint(&app).is_none() {
+        return Err("Telemetry is not configured (plugins.telemetry.endpoint)".to_string());
+    }
+
+    app.state::<ConfigState>().update(|config| {
+        config.telemetry.enabled = enabled;
+        config.telemetry.install_id = if enabled {
+            config
+                .telemetry
+                .install_id
+                .take()
+                .or_else(|| Some(generate_install_id()))
+        } else {
+            None
+        };
+    })?;
+    state.enabled.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        let mut queue = state.queue.lock().unwrap();
+        queue.clear();
+        state.save(&queue);
+    }
+    println!(
+        "Usage telemetry {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+
+    Ok(status(&app))
+}
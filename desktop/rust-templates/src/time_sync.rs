@@ -0,0 +1,189 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use tokio::net::{lookup_host, UdpSocket};
+
+use crate::config::ConfigState;
+use crate::events;
+
+// Clock drift check against an NTP server. Meters timestamp readings with the
+// host clock, so a machine whose clock is off silently corrupts timeseries data.
+// This is a single SNTP exchange, enough to measure drift; it never sets the clock.
+const NTP_PORT: u16 = 123;
+const TIMEOUT: Duration = Duration::from_secs(5);
+// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_EPOCH_OFFSET: f64 = 2_208_988_800.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TimeSyncConfig {
+    pub ntp_server: String,
+    // Drift beyond this emits `time-drift-warning`
+    pub warn_threshold_ms: u64,
+}
+
+impl Default for TimeSyncConfig {
+    fn default() -> Self {
+        Self {
+            ntp_server: "pool.ntp.org".to_string(),
+            warn_threshold_ms: 2000,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct TimeSyncStatus {
+    pub server: String,
+    // How far the system clock is behind the server (negative when ahead)
+    pub offset_ms: i64,
+    pub round_trip_ms: u64,
+    pub in_sync: bool,
+}
+
+fn now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+// Read a 64-bit NTP timestamp as seconds since the Unix epoch
+fn read_timestamp(bytes: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+    let fraction = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as f64;
+    seconds - NTP_EPOCH_OFFSET + fraction / 4_294_967_296.0
+}
+
+fn write_timestamp(bytes: &mut [u8], time: f64) {
+    let time = time + NTP_EPOCH_OFFSET;
+    let seconds = time.trunc() as u32;
+    let fraction = (time.fract() * 4_294_967_296.0) as u32;
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..8].copy_from_slice(&fraction.to_be_bytes());
+}
+
+// `server` is a host name or address, optionally with a port
+fn server_address(server: &str) -> String {
+    let host = server.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return SocketAddr::from((ip, NTP_PORT)).to_string();
+    }
+    if server.contains(':') {
+        server.to_string()
+    } else {
+        format!("{}:{}", server, NTP_PORT)
+    }
+}
+
+// A socket bound to match the server's address family, trying each address it
+// resolves to so that an IPv6 address on a host without IPv6 falls back to IPv4
+async fn connect(server: &str) -> Result<UdpSocket, String> {
+    let addresses = lookup_host(server_address(server))
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", server, e))?;
+
+    let mut last_error = format!("{} has no addresses", server);
+    for address in addresses {
+        let local = match address {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = match UdpSocket::bind(local).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                last_error = e.to_string();
+                continue;
+            }
+        };
+        match socket.connect(address).await {
+            Ok(()) => return Ok(socket),
+            Err(e) => last_error = format!("Failed to connect to {}: {}", address, e),
+        }
+    }
+    Err(last_error)
+}
+
+// Returns (offset, round trip) in seconds
+async fn query(server: &str) -> Result<(f64, f64), String> {
+    let socket = connect(server).await?;
+
+    // Version 3, client mode; the transmit timestamp comes back as the
+    // originate timestamp, which ties the response to this request
+    let mut request = [0u8; 48];
+    request[0] = 0x1B;
+    let sent = now_secs();
+    write_timestamp(&mut request[40..48], sent);
+    socket.send(&request).await.map_err(|e| e.to_string())?;
+
+    let mut response = [0u8; 48];
+    let length = tokio::time::timeout(TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| format!("Timed out waiting for {}", server))?
+        .map_err(|e| e.to_string())?;
+    let received = now_secs();
+
+    if length < 48 || response[24..32] != request[40..48] {
+        return Err(format!("Invalid NTP response from {}", server));
+    }
+    // Stratum 0 is a "kiss-o'-death", telling the client to back off
+    if response[1] == 0 {
+        return Err(format!("{} refused the request", server));
+    }
+
+    let server_received = read_timestamp(&response[32..40]);
+    let server_sent = read_timestamp(&response[40..48]);
+
+    let offset = ((server_received - sent) + (server_sent - received)) / 2.0;
+    let round_trip = (received - sent) - (server_sent - server_received);
+    Ok((offset, round_trip.max(0.0)))
+}
+
+// Command to measure the system clock's drift, against `server` or the
+// configured NTP server. Emits `time-drift-warning` when the drift is over the
+// configured threshold.
+#[tauri::command]
+#[specta::specta]
+pub async fn check_time_sync(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    server: Option<String>,
+) -> Result<TimeSyncStatus, String> {
+    let config = config.get().time_sync;
+    let server = server.unwrap_or(config.ntp_server);
+
+    let (offset, round_trip) = query(&server).await?;
+    let offset_ms = (offset * 1000.0).round() as i64;
+    let status = TimeSyncStatus {
+        server,
+        offset_ms,
+        round_trip_ms: (round_trip * 1000.0).round() as u64,
+        in_sync: offset_ms.unsigned_abs() <= config.warn_threshold_ms,
+    };
+
+    if !status.in_sync {
+        eprintln!(
+            "System clock is off by {}ms according to {}",
+            offset_ms, status.server
+        );
+        let _ = events::TIME_DRIFT_WARNING.emit(&app, &status);
+    }
+
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_the_ntp_port_when_there_is_none() {
+        assert_eq!(server_address("pool.ntp.org"), "pool.ntp.org:123");
+        assert_eq!(server_address("time.local:1123"), "time.local:1123");
+        assert_eq!(server_address("10.0.0.1"), "10.0.0.1:123");
+        assert_eq!(server_address("2001:db8::1"), "[2001:db8::1]:123");
+        assert_eq!(server_address("[2001:db8::1]"), "[2001:db8::1]:123");
+        assert_eq!(server_address("[2001:db8::1]:1123"), "[2001:db8::1]:1123");
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use tokio_tungstenite::Connector;
+
+use crate::paths::AppPaths;
+
+// Traffic between the shell and the backend is HTTPS with a certificate generated
+// once per install. The backend receives the certificate and key file paths through
+// these variables, and the shell trusts only that certificate for backend requests.
+pub const CERT_ENV: &str = "DESKTOP_TLS_CERT";
+pub const KEY_ENV: &str = "DESKTOP_TLS_KEY";
+
+pub struct TlsIdentity {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub cert_pem: String,
+}
+
+// Load the install's localhost certificate, generating it on first run
+pub fn ensure_certificate(paths: &AppPaths) -> Result<TlsIdentity, String> {
+    let dir = paths.tls_dir();
+    let cert_path = dir.join("localhost.crt");
+    let key_path = dir.join("localhost.key");
+
+    if let (Ok(cert_pem), true) = (fs::read_to_string(&cert_path), key_path.exists()) {
+        return Ok(TlsIdentity {
+            cert_path,
+            key_path,
+            cert_pem,
+        });
+    }
+
+    let certified =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .map_err(|e| e.to_string())?;
+    let cert_pem = certified.cert.pem();
+
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    write_private(&key_path, &certified.key_pair.serialize_pem())?;
+    fs::write(&cert_path, &cert_pem).map_err(|e| e.to_string())?;
+    println!("Generated backend TLS certificate: {:?}", cert_path);
+
+    Ok(TlsIdentity {
+        cert_path,
+        key_path,
+        cert_pem,
+    })
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    // The data directory is already private to the user on Windows
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+fn backend_certificate(identity: Option<&TlsIdentity>) -> Option<reqwest::Certificate> {
+    identity.and_then(|identity| reqwest::Certificate::from_pem(identity.cert_pem.as_bytes()).ok())
+}
+
+// HTTP client builders for backend requests, trusting only the backend certificate
+pub fn client_builder(identity: Option<&TlsIdentity>) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder();
+    match backend_certificate(identity) {
+        Some(certificate) => builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(certificate),
+        None => builder,
+    }
+}
+
+pub fn blocking_client_builder(identity: Option<&TlsIdentity>) -> reqwest::blocking::ClientBuilder {
+    let builder = reqwest::blocking::Client::builder();
+    match backend_certificate(identity) {
+        Some(certificate) => builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(certificate),
+        None => builder,
+    }
+}
+
+// The base64 body of a PEM block
+fn pem_to_der(pem: &str) -> Option<Vec<u8>> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    base64::engine::general_purpose::STANDARD.decode(body).ok()
+}
+
+// WebSocket TLS connector trusting only the backend certificate
+pub fn ws_connector(identity: &TlsIdentity) -> Result<Connector, String> {
+    let der = pem_to_der(&identity.cert_pem).ok_or("Invalid backend certificate")?;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(rustls::pki_types::CertificateDer::from(der))
+        .map_err(|e| e.to_string())?;
+
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| e.to_string())?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+    Ok(Connector::Rustls(Arc::new(config)))
+}
+
+// Server config presenting the install's certificate, for the LAN proxy
+pub fn server_config(identity: &TlsIdentity) -> Result<Arc<rustls::ServerConfig>, String> {
+    let cert = pem_to_der(&identity.cert_pem).ok_or("Invalid backend certificate")?;
+    let key_pem = fs::read_to_string(&identity.key_path).map_err(|e| e.to_string())?;
+    let key = pem_to_der(&key_pem).ok_or("Invalid backend certificate key")?;
+
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| e.to_string())?
+    .with_no_client_auth()
+    .with_single_cert(
+        vec![rustls::pki_types::CertificateDer::from(cert)],
+        rustls::pki_types::PrivatePkcs8KeyDer::from(key).into(),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(Arc::new(config))
+}
+
+// SHA-256 fingerprint of the certificate, as browsers show it (`AB:CD:...`), so
+// a user accepting the self-signed certificate on another device can compare it
+pub fn fingerprint(identity: &TlsIdentity) -> Option<String> {
+    let der = pem_to_der(&identity.cert_pem)?;
+    Some(
+        Sha256::digest(der)
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
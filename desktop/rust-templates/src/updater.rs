@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State, Url};
+use tauri_plugin_updater::UpdaterExt;
+
+use crate::certificates;
+use crate::config::{ConfigState, UpdateChannel};
+use crate::events;
+use crate::integrity;
+use crate::paths::AppPaths;
+use crate::rollback;
+use crate::server::{self, ServerState};
+use crate::telemetry::{self, UsageEvent};
+
+// Both the app updater and the sidecar updater honour the update channel stored in
+// the desktop config. Endpoints may contain a `{{channel}}` placeholder; endpoints
+// without one select non-stable channels with a `channel` query parameter.
+//
+// Sidecar-only updates: most releases only change the Node backend, so instead of
+// reinstalling the whole app we download a new sidecar binary, verify it against
+// the updater public key and swap it into the app data directory.
+//
+// The manifest mirrors the Tauri updater format, and is signed with the same key
+// as `<manifest>.sig` so that its version is covered by a signature too:
+// { "version": "1.2.3", "notes": "...", "platforms": { "darwin-aarch64": { "url": "...", "signature": "..." } } }
+
+#[derive(Deserialize)]
+struct SidecarManifest {
+    version: String,
+    notes: Option<String>,
+    platforms: HashMap<String, SidecarPlatform>,
+}
+
+#[derive(Deserialize)]
+struct SidecarPlatform {
+    url: String,
+    signature: String,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct AppUpdate {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    pub channel: UpdateChannel,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct SidecarUpdate {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+}
+
+struct SidecarUpdaterConfig {
+    endpoint: String,
+    pubkey: String,
+}
+
+impl SidecarUpdaterConfig {
+    // `plugins.sidecarUpdater.endpoint` in tauri.conf.json; the public key is
+    // shared with the regular updater plugin
+    fn from_app(app: &AppHandle) -> Result<Self, String> {
+        let plugins = &app.config().plugins.0;
+
+        let endpoint = plugins
+            .get("sidecarUpdater")
+            .and_then(|config| config.get("endpoint"))
+            .and_then(|endpoint| endpoint.as_str())
+            .ok_or("Sidecar updates are not configured (plugins.sidecarUpdater.endpoint)")?;
+
+        Ok(Self {
+            endpoint: channel_endpoint(endpoint, update_channel(app)),
+            pubkey: updater_pubkey(app)?,
+        })
+    }
+}
+
+// Public key the app, sidecar and offline bundles are all signed with. Takes the
+// config rather than an AppHandle so the key is available before the backend is
+// first started.
+pub fn configured_pubkey(config: &tauri::Config) -> Option<String> {
+    config
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|config| config.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .filter(|pubkey| !pubkey.is_empty())
+        .map(|pubkey| pubkey.to_string())
+}
+
+pub fn updater_pubkey(app: &AppHandle) -> Result<String, String> {
+    configured_pubkey(app.config())
+        .ok_or_else(|| "Updater public key is not configured (plugins.updater.pubkey)".to_string())
+}
+
+fn update_channel(app: &AppHandle) -> UpdateChannel {
+    app.state::<ConfigState>().get().update_channel
+}
+
+pub fn channel_endpoint(endpoint: &str, channel: UpdateChannel) -> String {
+    if endpoint.contains("{{channel}}") {
+        endpoint.replace("{{channel}}", channel.as_str())
+    } else if channel == UpdateChannel::Stable {
+        endpoint.to_string()
+    } else {
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        format!("{}{}channel={}", endpoint, separator, channel.as_str())
+    }
+}
+
+// App updater using the `plugins.updater.endpoints` from tauri.conf.json, rewritten
+// for the selected channel
+fn app_updater(app: &AppHandle) -> Result<tauri_plugin_updater::Updater, String> {
+    let channel = update_channel(app);
+
+    let endpoints = app
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|config| config.get("endpoints"))
+        .and_then(|endpoints| endpoints.as_array())
+        .map(|endpoints| {
+            endpoints
+                .iter()
+                .filter_map(|endpoint| endpoint.as_str())
+                .map(|endpoint| Url::parse(&channel_endpoint(endpoint, channel)))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|e| format!("Invalid updater endpoint: {}", e))?
+        .unwrap_or_default();
+
+    if endpoints.is_empty() {
+        return Err("App updates are not configured (plugins.updater.endpoints)".to_string());
+    }
+
+    app.updater_builder()
+        .endpoints(endpoints)
+        .and_then(|builder| builder.build())
+        .map_err(|e| e.to_string())
+}
+
+// Origin of the first app update endpoint, such as `https://updates.example.com/`,
+// which is what the connectivity monitor probes unless told otherwise
+pub fn update_origin(app: &AppHandle) -> Option<String> {
+    let endpoint = app
+        .config()
+        .plugins
+        .0
+        .get("updater")?
+        .get("endpoints")?
+        .as_array()?
+        .iter()
+        .find_map(|endpoint| endpoint.as_str())?;
+    let origin = Url::parse(endpoint).ok()?.origin();
+    origin
+        .is_tuple()
+        .then(|| format!("{}/", origin.ascii_serialization()))
+}
+
+// Platform key used in update manifests, e.g. `darwin-aarch64` or `windows-x86_64`
+pub fn platform_key() -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    format!("{}-{}", os, std::env::consts::ARCH)
+}
+
+// Version of the sidecar that will be launched: the last delta update, as its
+// signed manifest gives it, or the version bundled with the app
+pub fn installed_sidecar_version(app: &AppHandle) -> String {
+    let dir = app.state::<AppPaths>().sidecar_dir();
+    server::sidecar_binary_names()
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|binary| binary.exists())
+        .and_then(|binary| {
+            let pubkey = updater_pubkey(app).ok()?;
+            integrity::installed_version(&binary, &pubkey).ok()
+        })
+        .unwrap_or_else(|| app.package_info().version.to_string())
+}
+
+// The manifest's signature, from `tauri signer sign sidecar.json`, is served
+// beside it with `.sig` added to the path
+fn signature_url(endpoint: &str) -> Result<String, String> {
+    let mut url =
+        Url::parse(endpoint).map_err(|e| format!("Invalid sidecar manifest endpoint: {}", e))?;
+    let path = format!("{}.sig", url.path());
+    url.set_path(&path);
+    Ok(url.to_string())
+}
+
+async fn fetch(app: &AppHandle, url: &str, what: &str) -> Result<Vec<u8>, String> {
+    let response = certificates::client_for(app, url)?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch {}: {}", what, response.status()));
+    }
+    response
+        .bytes()
+        .await
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| e.to_string())
+}
+
+// The manifest, parsed only once its signature has been checked, along with the
+// signed bytes and the signature to keep with the sidecar
+async fn fetch_manifest(
+    app: &AppHandle,
+    config: &SidecarUpdaterConfig,
+) -> Result<(SidecarManifest, Vec<u8>, String), String> {
+    let data = fetch(app, &config.endpoint, "sidecar manifest").await?;
+    let signature_url = signature_url(&config.endpoint)?;
+    let signature = fetch(app, &signature_url, "sidecar manifest signature").await?;
+    let signature = String::from_utf8(signature)
+        .map_err(|_| "Invalid sidecar manifest signature".to_string())?;
+    verify_signature(&data, signature.trim(), &config.pubkey)
+        .map_err(|e| format!("Sidecar manifest: {}", e))?;
+
+    let manifest =
+        serde_json::from_slice(&data).map_err(|e| format!("Invalid sidecar manifest: {}", e))?;
+    Ok((manifest, data, signature))
+}
+
+pub fn is_newer(candidate: &str, current: &str) -> Result<bool, String> {
+    let candidate = semver::Version::parse(candidate).map_err(|e| e.to_string())?;
+    let current = semver::Version::parse(current).map_err(|e| e.to_string())?;
+    Ok(candidate > current)
+}
+
+fn decode_base64(value: &str) -> Result<String, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+// Verify a minisign signature as produced by `tauri signer sign`. Both the key and
+// the signature are base64 encoded minisign files, as in the Tauri updater.
+pub fn verify_signature(data: &[u8], signature: &str, pubkey: &str) -> Result<(), String> {
+    let public_key = PublicKey::decode(&decode_base64(pubkey)?)
+        .map_err(|e| format!("Invalid updater public key: {}", e))?;
+    let signature = Signature::decode(&decode_base64(signature)?)
+        .map_err(|e| format!("Invalid update signature: {}", e))?;
+
+    public_key
+        .verify(data, &signature, true)
+        .map_err(|e| format!("Update signature verification failed: {}", e))
+}
+
+// Time a signature was made, from the trusted comment `tauri signer sign` writes
+// (`timestamp:<secs>\tfile:<name>`). The comment is covered by the signature, so
+// the time can be relied on once the signature has been verified.
+pub fn signed_at(signature: &str) -> Option<u64> {
+    let signature = Signature::decode(&decode_base64(signature).ok()?).ok()?;
+    comment_timestamp(signature.trusted_comment())
+}
+
+fn comment_timestamp(comment: &str) -> Option<u64> {
+    comment
+        .split('\t')
+        .find_map(|field| field.strip_prefix("timestamp:"))
+        .and_then(|timestamp| timestamp.trim().parse().ok())
+}
+
+// Stop the backend, swap the new binary in (keeping the previous one as `.bak`)
+// and start it again. The new binary stays on probation until it has passed its
+// health checks, and is rolled back otherwise. Its signature and the signed
+// manifest it came from are kept alongside it, so the binary can be verified
+// each time it is launched and its version can't be changed. The manifest must
+// list the binary's signature, and gives the version installed.
+pub fn apply_sidecar_update(
+    app: &AppHandle,
+    binary: &[u8],
+    signature: &str,
+    manifest: &[u8],
+    manifest_signature: &str,
+) -> Result<u16, String> {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+    server::ensure_owned(&state)?;
+    let version = integrity::signed_version(
+        manifest,
+        manifest_signature,
+        signature,
+        &updater_pubkey(app)?,
+    )?;
+
+    let name = state
+        .shell
+        .sidecar()
+        .binary_names()
+        .into_iter()
+        .next()
+        .ok_or("Sidecar updates are not supported on this platform")?;
+
+    let sidecar_dir = paths.sidecar_dir();
+    fs::create_dir_all(&sidecar_dir).map_err(|e| e.to_string())?;
+
+    let target = sidecar_dir.join(&name);
+    let staged = sidecar_dir.join(format!("{}.new", name));
+    let backup = sidecar_dir.join(format!("{}.bak", name));
+
+    fs::write(&staged, binary).map_err(|e| format!("Failed to stage sidecar: {}", e))?;
+    make_executable(&staged)?;
+
+    server::stop_backend_server(&state);
+
+    // Without a previous delta update there is nothing to back up, and rolling
+    // back means falling back to the bundled sidecar
+    let manifest_file = sidecar_dir.join(integrity::UPDATE_MANIFEST_NAME);
+    let manifest_backup = sidecar_dir.join(format!("{}.bak", integrity::UPDATE_MANIFEST_NAME));
+    let backed_up = [
+        (target.clone(), backup.clone()),
+        (
+            integrity::signature_path(&target),
+            integrity::signature_path(&backup),
+        ),
+        (manifest_file.clone(), manifest_backup.clone()),
+        (
+            integrity::signature_path(&manifest_file),
+            integrity::signature_path(&manifest_backup),
+        ),
+    ];
+    if target.exists() {
+        fs::rename(&target, &backup).map_err(|e| format!("Failed to back up sidecar: {}", e))?;
+        for (file, backup) in &backed_up[1..] {
+            let _ = fs::rename(file, backup);
+        }
+    } else {
+        for (_, backup) in &backed_up {
+            let _ = fs::remove_file(backup);
+        }
+    }
+    fs::write(integrity::signature_path(&target), signature).map_err(|e| e.to_string())?;
+    fs::write(&manifest_file, manifest).map_err(|e| e.to_string())?;
+    fs::write(
+        integrity::signature_path(&manifest_file),
+        manifest_signature,
+    )
+    .map_err(|e| e.to_string())?;
+    fs::rename(&staged, &target).map_err(|e| format!("Failed to install sidecar: {}", e))?;
+    // Written by earlier versions of the shell, which didn't keep the manifest
+    let _ = fs::remove_file(sidecar_dir.join("version"));
+
+    match server::start_backend_server(&paths, &state) {
+        Ok(port) => {
+            rollback::watch_sidecar_update(app.clone(), version);
+            Ok(port)
+        }
+        Err(e) => {
+            rollback::rollback_sidecar_update(app, &e.message)?;
+            Err(format!("Sidecar {} failed to start and was rolled back: {}", version, e))
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn make_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+pub fn make_executable(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+// Command to get the update channel used by both updaters
+#[tauri::command]
+#[specta::specta]
+pub fn get_update_channel(config: State<ConfigState>) -> UpdateChannel {
+    config.get().update_channel
+}
+
+// Command to switch between stable, beta and nightly updates. Updates only ever
+// go forward, so moving to a more stable channel doesn't downgrade: a pre-release
+// stays installed until the new channel has a newer version.
+#[tauri::command]
+#[specta::specta]
+pub fn set_update_channel(
+    config: State<ConfigState>,
+    channel: UpdateChannel,
+) -> Result<UpdateChannel, String> {
+    println!("Switching update channel to {}", channel.as_str());
+    config
+        .update(|config| config.update_channel = channel)
+        .map(|config| config.update_channel)
+}
+
+// Command to check for a new version of the whole app on the selected channel
+#[tauri::command]
+#[specta::specta]
+pub async fn check_app_update(app: AppHandle) -> Result<Option<AppUpdate>, String> {
+    let update = app_updater(&app)?
+        .check()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(update.map(|update| AppUpdate {
+        version: update.version,
+        current_version: update.current_version,
+        notes: update.body,
+        channel: update_channel(&app),
+    }))
+}
+
+// Command to download and install a new version of the app, then restart it
+#[tauri::command]
+#[specta::specta]
+pub async fn install_app_update(app: AppHandle) -> Result<(), String> {
+    let update = app_updater(&app)?
+        .check()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("No app update available")?;
+
+    println!("Installing app update {}", update.version);
+    update
+        .download_and_install(|_, _| {}, || {})
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Queued, so it is sent after the restart
+    telemetry::record(&app, UsageEvent::UpdateApplied, Some("app"));
+    app.restart();
+}
+
+// Command to check whether a newer sidecar is available for this platform
+#[tauri::command]
+#[specta::specta]
+pub async fn check_sidecar_update(app: AppHandle) -> Result<Option<SidecarUpdate>, String> {
+    let config = SidecarUpdaterConfig::from_app(&app)?;
+    let (manifest, _, _) = fetch_manifest(&app, &config).await?;
+    let current_version = installed_sidecar_version(&app);
+
+    if !manifest.platforms.contains_key(&platform_key())
+        || !is_newer(&manifest.version, &current_version)?
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(SidecarUpdate {
+        version: manifest.version,
+        current_version,
+        notes: manifest.notes,
+    }))
+}
+
+// Command to download, verify and install the latest sidecar, then restart the backend
+#[tauri::command]
+#[specta::specta]
+pub async fn install_sidecar_update(app: AppHandle) -> Result<SidecarUpdate, String> {
+    let config = SidecarUpdaterConfig::from_app(&app)?;
+    let (manifest, manifest_data, manifest_signature) = fetch_manifest(&app, &config).await?;
+    let current_version = installed_sidecar_version(&app);
+
+    if !is_newer(&manifest.version, &current_version)? {
+        return Err(format!("Sidecar {} is already up to date", current_version));
+    }
+
+    let platform = manifest
+        .platforms
+        .get(&platform_key())
+        .ok_or_else(|| format!("No sidecar update for platform {}", platform_key()))?;
+
+    println!("Downloading sidecar {} from {}", manifest.version, platform.url);
+    let binary = fetch(&app, &platform.url, "sidecar").await?;
+
+    verify_signature(&binary, &platform.signature, &config.pubkey)?;
+
+    let app_handle = app.clone();
+    let signature = platform.signature.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        apply_sidecar_update(
+            &app_handle,
+            &binary,
+            &signature,
+            &manifest_data,
+            &manifest_signature,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let update = SidecarUpdate {
+        version: manifest.version,
+        current_version,
+        notes: manifest.notes,
+    };
+    let _ = events::SIDECAR_UPDATED.emit(&app, &update);
+    telemetry::record(&app, UsageEvent::UpdateApplied, Some("sidecar"));
+
+    Ok(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serves_the_manifest_signature_beside_it() {
+        assert_eq!(
+            signature_url("https://updates.example.com/app/sidecar.json").unwrap(),
+            "https://updates.example.com/app/sidecar.json.sig"
+        );
+        assert_eq!(
+            signature_url("https://updates.example.com/sidecar.json?channel=beta").unwrap(),
+            "https://updates.example.com/sidecar.json.sig?channel=beta"
+        );
+    }
+
+    #[test]
+    fn reads_the_signing_time() {
+        assert_eq!(
+            comment_timestamp("timestamp:1700000000\tfile:server-x86_64-pc-windows-msvc.exe"),
+            Some(1700000000)
+        );
+        assert_eq!(comment_timestamp("file:server\ttimestamp:42"), Some(42));
+        assert_eq!(comment_timestamp("file:server"), None);
+        assert_eq!(comment_timestamp("timestamp:soon"), None);
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(is_newer("1.5.0", "1.4.9"), Ok(true));
+        assert_eq!(is_newer("1.5.0", "1.5.0"), Ok(false));
+        assert_eq!(is_newer("1.5.0-beta.1", "1.5.0"), Ok(false));
+        assert!(is_newer("latest", "1.5.0").is_err());
+    }
+
+    #[test]
+    fn selects_the_channel() {
+        let endpoint = "https://updates.example.com/latest.json";
+        assert_eq!(channel_endpoint(endpoint, UpdateChannel::Stable), endpoint);
+        assert_eq!(
+            channel_endpoint(endpoint, UpdateChannel::Beta),
+            "https://updates.example.com/latest.json?channel=beta"
+        );
+        assert_eq!(
+            channel_endpoint("https://updates.example.com/{{channel}}.json", UpdateChannel::Beta),
+            "https://updates.example.com/beta.json"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::events;
+
+// USB hot-plug events, so the frontend can offer to start commissioning when a
+// configuration dongle is plugged in. libusb has no hot-plug callbacks on
+// Windows, so the device list is polled on every platform instead.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct UsbDevice {
+    pub vid: u16,
+    pub pid: u16,
+    pub bus: u8,
+    pub address: u8,
+    // Strings are only available when the OS lets us open the device
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+#[derive(Default)]
+pub struct UsbState {
+    devices: Mutex<HashMap<(u8, u8), UsbDevice>>,
+}
+
+fn describe(device: &rusb::Device<rusb::GlobalContext>) -> Option<UsbDevice> {
+    let descriptor = device.device_descriptor().ok()?;
+    let (manufacturer, product, serial_number) = match device.open() {
+        Ok(handle) => (
+            handle.read_manufacturer_string_ascii(&descriptor).ok(),
+            handle.read_product_string_ascii(&descriptor).ok(),
+            handle.read_serial_number_string_ascii(&descriptor).ok(),
+        ),
+        Err(_) => (None, None, None),
+    };
+
+    Some(UsbDevice {
+        vid: descriptor.vendor_id(),
+        pid: descriptor.product_id(),
+        bus: device.bus_number(),
+        address: device.address(),
+        manufacturer,
+        product,
+        serial_number,
+    })
+}
+
+// Diff the connected devices against the last poll and emit the changes.
+// Devices already connected at startup are recorded without events.
+fn poll(app: &AppHandle, emit: bool) -> Result<(), String> {
+    let connected = rusb::devices().map_err(|e| e.to_string())?;
+    let state = app.state::<UsbState>();
+    let mut known = state.devices.lock().unwrap();
+
+    let mut current = HashMap::new();
+    for device in connected.iter() {
+        let key = (device.bus_number(), device.address());
+        let description = match known.remove(&key) {
+            Some(description) => description,
+            None => match describe(&device) {
+                Some(description) => {
+                    if emit {
+                        let _ = events::USB_DEVICE_ATTACHED.emit(app, &description);
+                    }
+                    description
+                }
+                None => continue,
+            },
+        };
+        current.insert(key, description);
+    }
+
+    if emit {
+        for description in known.values() {
+            let _ = events::USB_DEVICE_DETACHED.emit(app, description);
+        }
+    }
+
+    *known = current;
+    Ok(())
+}
+
+pub fn start_monitor(app: AppHandle) {
+    thread::spawn(move || {
+        let mut emit = false;
+        loop {
+            if let Err(e) = poll(&app, emit) {
+                eprintln!("Failed to list USB devices: {}", e);
+            }
+            emit = true;
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+// Command to list the USB devices currently connected
+#[tauri::command]
+#[specta::specta]
+pub fn list_usb_devices(state: State<UsbState>) -> Vec<UsbDevice> {
+    state.devices.lock().unwrap().values().cloned().collect()
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::time::Duration;
+
+use reqwest::Method;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::backend;
+use crate::paths::AppPaths;
+use crate::server::ServerState;
+use crate::updater;
+
+// Version of @episensor/app-framework this shell was generated from
+pub const FRAMEWORK_VERSION: &str = "{{FRAMEWORK_VERSION}}";
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct VersionInfo {
+    pub app_version: String,
+    pub framework_version: &'static str,
+    pub tauri_version: &'static str,
+    pub backend_version: Option<String>,
+    pub sidecar_version: String,
+    pub git_commit: &'static str,
+    pub build_date: &'static str,
+    pub platform: String,
+}
+
+// Ask the running backend for its version: `/api/version` if the app provides
+// one, otherwise the version reported by the framework health endpoint
+async fn backend_api_version(state: &ServerState) -> Option<String> {
+    for path in ["/api/version", "/api/health"] {
+        let request = backend::request(state, Method::GET, path)
+            .ok()?
+            .timeout(Duration::from_secs(2));
+        let response = backend::send(state, request).await;
+
+        if let Ok(response) = response {
+            if !response.status().is_success() {
+                continue;
+            }
+            if let Ok(body) = response.json::<serde_json::Value>().await {
+                if let Some(version) = body.get("version").and_then(|v| v.as_str()) {
+                    return Some(version.to_string());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Fall back to the package manifest shipped next to the Node backend
+fn backend_manifest_version(paths: &AppPaths) -> Option<String> {
+    let manifest = fs::read_to_string(paths.resource_dir.join("backend").join("package.json")).ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).ok()?;
+    manifest
+        .get("version")
+        .and_then(|version| version.as_str())
+        .map(|version| version.to_string())
+}
+
+pub async fn version_info(app: &AppHandle) -> VersionInfo {
+    let backend_version = backend_api_version(&app.state::<ServerState>())
+        .await
+        .or_else(|| backend_manifest_version(&app.state::<AppPaths>()));
+
+    VersionInfo {
+        app_version: app.package_info().version.to_string(),
+        framework_version: FRAMEWORK_VERSION,
+        tauri_version: tauri::VERSION,
+        backend_version,
+        sidecar_version: updater::installed_sidecar_version(app),
+        git_commit: env!("GIT_COMMIT"),
+        build_date: env!("BUILD_DATE"),
+        platform: updater::platform_key(),
+    }
+}
+
+// Command to report every version involved in this build, for support requests
+#[tauri::command]
+#[specta::specta]
+pub async fn get_version_info(app: AppHandle) -> Result<VersionInfo, String> {
+    Ok(version_info(&app).await)
+}
@@ -0,0 +1,445 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::backup::BackupConfig;
+use crate::ble::BleConfig;
+use crate::certificates::ClientCertificateConfig;
+use crate::config::{ConfigState, ShellConfig};
+use crate::connectivity::ConnectivityConfig;
+use crate::data_dir;
+use crate::events;
+use crate::fs_watch::FileWatchConfig;
+use crate::import::ImportConfig;
+use crate::paths::AppPaths;
+use crate::permissions::PathGrant;
+use crate::server::{self, ServerState};
+use crate::storage::StorageState;
+use crate::time_sync::TimeSyncConfig;
+
+// Workspaces let one install manage several sites, e.g. a consultant's customers.
+// Each has its own backend data directory, so its own backend settings, devices
+// and history, and its own backups. It also has its own local cache and the shell
+// settings that describe a site (see `WorkspaceSettings`). One backend runs at a
+// time: switching stops it and starts it against the selected workspace's data,
+// then emits `workspace-changed`. If that fails, the previous workspace is
+// restored and its backend restarted. The `default` workspace is the data the app
+// has always used; the data of others is in `<data dir>/workspaces/<id>` unless
+// moved with `set_data_dir`.
+pub const DEFAULT_ID: &str = "default";
+const DEFAULT_NAME: &str = "Default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    // Where the data was moved to, if it was. Not used for the active workspace,
+    // whose data directory is `backendProcess.dataDir`.
+    #[serde(default)]
+    pub data_dir: Option<String>,
+    pub created_at: u64,
+    // Its shell settings while another workspace is active. None for the active
+    // workspace, and for one never switched away from, which starts from defaults.
+    #[serde(default)]
+    pub settings: Option<WorkspaceSettings>,
+}
+
+// Shell settings that belong to a site rather than to the install. The active
+// workspace's are the ones at the top level of the config, where the rest of the
+// shell reads them; switching stores them with the workspace being left and puts
+// the selected workspace's in their place.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkspaceSettings {
+    pub connectivity: ConnectivityConfig,
+    pub ble: BleConfig,
+    pub file_watch: FileWatchConfig,
+    pub time_sync: TimeSyncConfig,
+    pub import: ImportConfig,
+    pub backup: BackupConfig,
+    pub client_certificates: ClientCertificateConfig,
+    pub granted_paths: Vec<PathGrant>,
+}
+
+impl WorkspaceSettings {
+    fn of(config: &ShellConfig) -> Self {
+        Self {
+            connectivity: config.connectivity.clone(),
+            ble: config.ble.clone(),
+            file_watch: config.file_watch.clone(),
+            time_sync: config.time_sync.clone(),
+            import: config.import.clone(),
+            backup: config.backup.clone(),
+            client_certificates: config.client_certificates.clone(),
+            granted_paths: config.granted_paths.clone(),
+        }
+    }
+
+    fn apply(self, config: &mut ShellConfig) {
+        config.connectivity = self.connectivity;
+        config.ble = self.ble;
+        config.file_watch = self.file_watch;
+        config.time_sync = self.time_sync;
+        config.import = self.import;
+        config.backup = self.backup;
+        config.client_certificates = self.client_certificates;
+        config.granted_paths = self.granted_paths;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkspaceConfig {
+    pub active: String,
+    // Empty until a second workspace is created
+    pub workspaces: Vec<Workspace>,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_ID.to_string(),
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct WorkspaceInfo {
+    pub id: String,
+    pub name: String,
+    pub data_dir: String,
+    pub active: bool,
+    pub created_at: u64,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// Every workspace, including the default one
+fn all(config: &WorkspaceConfig) -> Vec<Workspace> {
+    let mut workspaces = config.workspaces.clone();
+    if !workspaces
+        .iter()
+        .any(|workspace| workspace.id == DEFAULT_ID)
+    {
+        workspaces.insert(
+            0,
+            Workspace {
+                id: DEFAULT_ID.to_string(),
+                name: DEFAULT_NAME.to_string(),
+                data_dir: None,
+                created_at: 0,
+                settings: None,
+            },
+        );
+    }
+    workspaces
+}
+
+fn managed_dir(paths: &AppPaths, id: &str) -> Option<PathBuf> {
+    (id != DEFAULT_ID).then(|| paths.workspaces_dir().join(id))
+}
+
+// The backend's data directory setting for the active workspace when its data
+// hasn't been moved. None for the default workspace, whose data is wherever the
+// backend keeps it by default.
+pub fn default_data_dir(app: &AppHandle) -> Option<PathBuf> {
+    let active = app.state::<ConfigState>().get().workspaces.active;
+    managed_dir(&app.state::<AppPaths>(), &active)
+}
+
+fn data_dir_of(paths: &AppPaths, workspace: &Workspace) -> Option<String> {
+    workspace
+        .data_dir
+        .clone()
+        .or_else(|| managed_dir(paths, &workspace.id).map(|dir| dir.to_string_lossy().into_owned()))
+}
+
+fn info(app: &AppHandle, workspace: &Workspace) -> WorkspaceInfo {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+    let active = app.state::<ConfigState>().get().workspaces.active == workspace.id;
+    let data_dir = if active {
+        data_dir::current_dir(&paths, &state)
+    } else {
+        data_dir_of(&paths, workspace)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| data_dir::default_dir(&paths))
+    };
+
+    WorkspaceInfo {
+        id: workspace.id.clone(),
+        name: workspace.name.clone(),
+        data_dir: data_dir.to_string_lossy().into_owned(),
+        active,
+        created_at: workspace.created_at,
+    }
+}
+
+fn list(app: &AppHandle) -> Vec<WorkspaceInfo> {
+    all(&app.state::<ConfigState>().get().workspaces)
+        .iter()
+        .map(|workspace| info(app, workspace))
+        .collect()
+}
+
+// Id for a new workspace, derived from its name
+fn new_id(name: &str, existing: &[Workspace]) -> String {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug = if slug.is_empty() {
+        "workspace".to_string()
+    } else {
+        slug
+    };
+
+    let taken = |id: &str| id == DEFAULT_ID || existing.iter().any(|workspace| workspace.id == id);
+    let mut id = slug.clone();
+    let mut suffix = 2;
+    while taken(&id) {
+        id = format!("{}-{}", slug, suffix);
+        suffix += 1;
+    }
+    id
+}
+
+fn switch(app: &AppHandle, id: &str) -> Result<WorkspaceInfo, String> {
+    let paths = app.state::<AppPaths>();
+    let state = app.state::<ServerState>();
+    let config = app.state::<ConfigState>();
+    server::ensure_owned(&state)?;
+
+    let current = config.get();
+    let target = all(&current.workspaces)
+        .into_iter()
+        .find(|workspace| workspace.id == id)
+        .ok_or_else(|| format!("No workspace {}", id))?;
+    if current.workspaces.active == target.id {
+        return Ok(info(app, &target));
+    }
+
+    let data_dir = data_dir_of(&paths, &target);
+    if let Some(dir) = &data_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir, e))?;
+    }
+
+    println!("Switching to workspace {}", target.id);
+    server::stop_backend_server(&state);
+
+    let storage = app.state::<StorageState>();
+    let result = config
+        .update(|config| {
+            let mut workspaces = all(&config.workspaces);
+            let active = config.workspaces.active.clone();
+            // Remember where the workspace being left keeps its data, in case it
+            // moved, and its settings
+            let moved = config
+                .backend_process
+                .data_dir
+                .clone()
+                .filter(|dir| Some(PathBuf::from(dir)) != managed_dir(&paths, &active));
+            let settings = WorkspaceSettings::of(config);
+            for workspace in workspaces.iter_mut() {
+                if workspace.id == active {
+                    workspace.data_dir = moved.clone();
+                    workspace.settings = Some(settings.clone());
+                } else if workspace.id == target.id {
+                    workspace.settings.take().unwrap_or_default().apply(config);
+                }
+            }
+
+            config.workspaces.workspaces = workspaces;
+            config.workspaces.active = target.id.clone();
+            config.backend_process.data_dir = data_dir.clone();
+        })
+        .and_then(|_| {
+            storage.switch(&paths, &target.id);
+            state.set_data_dir(data_dir);
+            server::start_backend_server(&paths, &state).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        // Back to the workspace being left, with its backend running again
+        eprintln!(
+            "Failed to switch to workspace {}, restoring {}: {}",
+            target.id, current.workspaces.active, e
+        );
+        server::stop_backend_server(&state);
+        if let Err(config_error) = config.update(|config| {
+            config.workspaces = current.workspaces.clone();
+            config.backend_process.data_dir = current.backend_process.data_dir.clone();
+            WorkspaceSettings::of(&current).apply(config);
+        }) {
+            eprintln!("Failed to restore the workspace setting: {}", config_error);
+        }
+        storage.switch(&paths, &current.workspaces.active);
+        state.set_data_dir(current.backend_process.data_dir.clone());
+        if let Err(restart_error) = server::start_backend_server(&paths, &state) {
+            eprintln!("Failed to restart backend: {}", restart_error);
+        }
+        return Err(e);
+    }
+
+    let workspace = info(app, &target);
+    let _ = events::WORKSPACE_CHANGED.emit(app, &workspace);
+    Ok(workspace)
+}
+
+// Command to list the workspaces, including the default one
+#[tauri::command]
+#[specta::specta]
+pub fn list_workspaces(app: AppHandle) -> Vec<WorkspaceInfo> {
+    list(&app)
+}
+
+// Command to create a workspace. Its data directory is created when it is first
+// switched to.
+#[tauri::command]
+#[specta::specta]
+pub fn create_workspace(app: AppHandle, name: String) -> Result<WorkspaceInfo, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("The workspace needs a name".to_string());
+    }
+
+    let config = app.state::<ConfigState>();
+    let mut workspaces = all(&config.get().workspaces);
+    let workspace = Workspace {
+        id: new_id(&name, &workspaces),
+        name,
+        data_dir: None,
+        created_at: now_ms(),
+        settings: None,
+    };
+    workspaces.push(workspace.clone());
+    config.update(|config| config.workspaces.workspaces = workspaces)?;
+    println!("Created workspace {}", workspace.id);
+    Ok(info(&app, &workspace))
+}
+
+// Command to rename a workspace
+#[tauri::command]
+#[specta::specta]
+pub fn rename_workspace(
+    app: AppHandle,
+    config: State<ConfigState>,
+    id: String,
+    name: String,
+) -> Result<WorkspaceInfo, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("The workspace needs a name".to_string());
+    }
+
+    let mut workspaces = all(&config.get().workspaces);
+    let workspace = workspaces
+        .iter_mut()
+        .find(|workspace| workspace.id == id)
+        .ok_or_else(|| format!("No workspace {}", id))?;
+    workspace.name = name;
+    let workspace = workspace.clone();
+    config.update(|config| config.workspaces.workspaces = workspaces)?;
+    Ok(info(&app, &workspace))
+}
+
+// Command to delete a workspace along with its data. The active workspace and the
+// default one can't be deleted.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_workspace(app: AppHandle, id: String) -> Result<Vec<WorkspaceInfo>, String> {
+    data_dir::begin_change(&app)?;
+
+    let delete_app = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let app = delete_app;
+        let paths = app.state::<AppPaths>();
+        let config = app.state::<ConfigState>();
+        let current = config.get().workspaces;
+        if id == DEFAULT_ID || id == current.active {
+            return Err("The default and active workspaces can't be deleted".to_string());
+        }
+        let workspace = all(&current)
+            .into_iter()
+            .find(|workspace| workspace.id == id)
+            .ok_or_else(|| format!("No workspace {}", id))?;
+
+        config.update(|config| config.workspaces.workspaces.retain(|w| w.id != id))?;
+        if let Some(dir) = data_dir_of(&paths, &workspace) {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                eprintln!("Failed to remove data of workspace {}: {}", id, e);
+            }
+        }
+        // The cache database, with SQLite's journal files beside it
+        let cache = paths.cache_db(&id).to_string_lossy().into_owned();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", cache, suffix));
+        }
+        println!("Deleted workspace {}", id);
+        Ok(list(&app))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+
+    data_dir::end_change(&app);
+    result
+}
+
+// Command to switch to another workspace, restarting the backend against its data
+#[tauri::command]
+#[specta::specta]
+pub async fn switch_workspace(app: AppHandle, id: String) -> Result<WorkspaceInfo, String> {
+    data_dir::begin_change(&app)?;
+
+    let switch_app = app.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || switch(&switch_app, &id))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    data_dir::end_change(&app);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(id: &str) -> Workspace {
+        Workspace {
+            id: id.to_string(),
+            name: id.to_string(),
+            data_dir: None,
+            created_at: 0,
+            settings: None,
+        }
+    }
+
+    #[test]
+    fn derives_ids_from_names() {
+        assert_eq!(new_id("Site Survey 2024", &[]), "site-survey-2024");
+        assert_eq!(new_id("  Ünïcode / ../Name ", &[]), "n-code-name");
+        assert_eq!(new_id("../..", &[]), "workspace");
+        assert_eq!(new_id("", &[]), "workspace");
+    }
+
+    #[test]
+    fn never_reuses_an_id() {
+        let existing = [workspace("survey"), workspace("survey-2")];
+        assert_eq!(new_id("Survey", &existing), "survey-3");
+        assert_eq!(new_id("Default", &[]), "default-2");
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{mpsc, Notify};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::battery;
+use crate::connectivity;
+use crate::events;
+use crate::server::ServerState;
+use crate::tls;
+
+// Bridge between the backend's Socket.IO server and Tauri events. Messages from the
+// backend are re-emitted as `backend:<event>`, and `ws_send` goes the other way, so
+// frontend code doesn't have to manage its own connection.
+//
+// Only the small part of the Engine.IO v4 / Socket.IO v5 protocol needed over a
+// plain WebSocket transport is implemented: open, ping/pong, connect and events.
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub struct BridgeState {
+    sender: Mutex<Option<mpsc::UnboundedSender<String>>>,
+    connected: AtomicBool,
+    reconnect: Notify,
+}
+
+#[derive(Serialize, Clone, specta::Type)]
+pub struct BridgeStatus {
+    pub connected: bool,
+}
+
+// Keep the bridge connected for the lifetime of the app, reconnecting with
+// exponential backoff whenever the backend goes away
+pub fn start_bridge(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<BridgeState>();
+        let mut backoff = MIN_BACKOFF;
+
+        loop {
+            if let Some(port) = app.state::<ServerState>().port() {
+                match run_connection(&app, port).await {
+                    Ok(true) => backoff = MIN_BACKOFF,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Backend WebSocket error: {}", e),
+                }
+                set_connected(&app, false);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => backoff = (backoff * 2).min(MAX_BACKOFF),
+                _ = state.reconnect.notified() => backoff = MIN_BACKOFF,
+            }
+        }
+    });
+}
+
+// Drop the current connection (if any) and connect again straight away, for when
+// it is likely dead without having noticed, such as after the machine slept
+pub fn reconnect(app: &AppHandle) {
+    app.state::<BridgeState>().reconnect.notify_waiters();
+}
+
+pub fn is_connected(state: &BridgeState) -> bool {
+    state.connected.load(Ordering::SeqCst)
+}
+
+fn set_connected(app: &AppHandle, connected: bool) {
+    let state = app.state::<BridgeState>();
+    if !connected {
+        *state.sender.lock().unwrap() = None;
+    }
+    if state.connected.swap(connected, Ordering::SeqCst) != connected {
+        let _ = events::WS_BRIDGE_STATUS.emit(app, &BridgeStatus { connected });
+    }
+}
+
+// Tauri event names only allow alphanumerics and `-/:_`
+pub fn sanitize_event_name(event: &str) -> String {
+    event
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-/:_".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Handle a Socket.IO event packet: `42["name", payload...]`
+fn forward_event(app: &AppHandle, packet: &str) {
+    // Skip an optional namespace prefix (`42/ns,[...]`)
+    let data = match packet.find('[') {
+        Some(start) => &packet[start..],
+        None => return,
+    };
+
+    let mut args = match serde_json::from_str::<Vec<serde_json::Value>>(data) {
+        Ok(args) if !args.is_empty() => args,
+        _ => return,
+    };
+
+    let name = match args.remove(0) {
+        serde_json::Value::String(name) => name,
+        _ => return,
+    };
+
+    let payload = match args.len() {
+        0 => serde_json::Value::Null,
+        1 => args.remove(0),
+        _ => serde_json::Value::Array(args),
+    };
+
+    let _ = app.emit(&format!("backend:{}", sanitize_event_name(&name)), payload);
+}
+
+// Returns whether the Socket.IO connection was established before it closed
+async fn run_connection(app: &AppHandle, port: u16) -> Result<bool, String> {
+    let server_state = app.state::<ServerState>();
+    let (scheme, connector) = match &server_state.tls {
+        Some(identity) => ("wss", Some(tls::ws_connector(identity)?)),
+        None => ("ws", None),
+    };
+    let url = format!(
+        "{}://127.0.0.1:{}/socket.io/?EIO=4&transport=websocket",
+        scheme, port
+    );
+    let (stream, _) =
+        tokio_tungstenite::connect_async_tls_with_config(url.as_str(), None, false, connector)
+            .await
+            .map_err(|e| e.to_string())?;
+    let (mut write, mut read) = stream.split();
+
+    let (sender, mut outgoing) = mpsc::unbounded_channel::<String>();
+    let mut connected = false;
+    let bridge_state = app.state::<BridgeState>();
+
+    loop {
+        tokio::select! {
+            message = read.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return Ok(connected),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.to_string()),
+                };
+
+                match text.as_bytes().first() {
+                    // Engine.IO open: connect to the default namespace, passing the
+                    // shell's token as the Socket.IO auth payload
+                    Some(b'0') => {
+                        let auth = serde_json::json!({ "token": app.state::<ServerState>().token });
+                        write.send(Message::Text(format!("40{}", auth))).await.map_err(|e| e.to_string())?;
+                    }
+                    // Engine.IO ping
+                    Some(b'2') => {
+                        write.send(Message::Text("3".into())).await.map_err(|e| e.to_string())?;
+                    }
+                    Some(b'4') => match text.as_bytes().get(1) {
+                        Some(b'0') => {
+                            connected = true;
+                            *app.state::<BridgeState>().sender.lock().unwrap() = Some(sender.clone());
+                            set_connected(app, true);
+                            connectivity::sync_backend(app);
+                            battery::sync_backend(app);
+                        }
+                        Some(b'1') => return Ok(connected),
+                        Some(b'2') => forward_event(app, &text[2..]),
+                        Some(b'4') => return Err(format!("Socket.IO connect error: {}", &text[2..])),
+                        _ => {}
+                    },
+                    // Engine.IO close
+                    Some(b'1') => return Ok(connected),
+                    _ => {}
+                }
+            }
+            Some(packet) = outgoing.recv() => {
+                write.send(Message::Text(packet)).await.map_err(|e| e.to_string())?;
+            }
+            _ = bridge_state.reconnect.notified() => return Ok(connected),
+        }
+    }
+}
+
+// Send a Socket.IO event to the backend, if the bridge is connected
+pub fn send_event(state: &BridgeState, event: &str, payload: serde_json::Value) -> Result<(), String> {
+    let packet = serde_json::to_string(&serde_json::json!([event, payload]))
+        .map_err(|e| e.to_string())?;
+
+    state
+        .sender
+        .lock()
+        .unwrap()
+        .as_ref()
+        .ok_or("Backend WebSocket is not connected")?
+        .send(format!("42{}", packet))
+        .map_err(|e| e.to_string())
+}
+
+// Command to send an event to the backend over the bridge
+#[tauri::command]
+#[specta::specta]
+pub fn ws_send(
+    state: State<BridgeState>,
+    event: String,
+    payload: serde_json::Value,
+) -> Result<(), String> {
+    send_event(&state, &event, payload)
+}
+
+// Command to check whether the bridge is currently connected
+#[tauri::command]
+#[specta::specta]
+pub fn ws_status(state: State<BridgeState>) -> BridgeStatus {
+    BridgeStatus {
+        connected: is_connected(&state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_valid_event_names() {
+        assert_eq!(
+            sanitize_event_name("device:reading/1_a-b"),
+            "device:reading/1_a-b"
+        );
+    }
+
+    #[test]
+    fn replaces_characters_tauri_rejects() {
+        assert_eq!(sanitize_event_name("alarm raised!"), "alarm_raised_");
+        assert_eq!(sanitize_event_name("a.b*c\u{e9}"), "a_b_c_");
+        assert_eq!(sanitize_event_name("../../etc"), "__/__/etc");
+    }
+}
@@ -45,6 +45,7 @@ const tauriPath = path.join(projectPath, 'src-tauri');
 const tauriDirs = [
   tauriPath,
   path.join(tauriPath, 'src'),
+  path.join(tauriPath, 'src', 'locales'),
   path.join(tauriPath, 'icons'),
   path.join(tauriPath, 'capabilities')
 ];
@@ -146,10 +147,26 @@ const tauriConfig = {
       "icons/icon.ico"
     ],
     "resources": [],
+    // Must match EXTENSIONS in src/open_file.rs
+    "fileAssociations": [
+      {
+        "ext": ["epx"],
+        "name": "EpiSensor Project",
+        "description": "EpiSensor project export",
+        "role": "Editor",
+        "mimeType": "application/x-episensor-project"
+      }
+    ],
     "copyright": `© ${new Date().getFullYear()} EpiSensor`,
     "category": "DeveloperTool",
     "shortDescription": appDescription.substring(0, 100),
     "longDescription": appDescription,
+    "linux": {
+      "deb": {
+        // WebKitGTK for the webview; Raspberry Pi OS Lite doesn't ship it
+        "depends": ["libwebkit2gtk-4.1-0", "libgtk-3-0"]
+      }
+    },
     "macOS": {
       "minimumSystemVersion": "10.15",
       "exceptionDomain": "",
@@ -160,7 +177,14 @@ const tauriConfig = {
   },
   "plugins": {
     "updater": {
-      "active": false
+      "active": false,
+      "endpoints": [],
+      "pubkey": ""
+    },
+    "deep-link": {
+      "desktop": {
+        "schemes": ["episensor"]
+      }
     }
   }
 };
@@ -174,10 +198,74 @@ console.log('  ✓ Created tauri.conf.json');
 // Copy Rust source files from framework
 console.log('🦀 Setting up Rust source files...');
 const rustTemplates = path.join(frameworkRoot, 'desktop/rust-templates');
+const frameworkVersion = JSON.parse(
+  fs.readFileSync(path.join(frameworkRoot, 'package.json'), 'utf8')
+).version;
+// The shell depends on the framework's crate where the package is installed
+const frameworkCratePath = path
+  .relative(tauriPath, path.join(frameworkRoot, 'desktop/crates/app-framework'))
+  .split(path.sep)
+  .join('/');
 const rustFiles = [
   'Cargo.toml',
   'build.rs',
-  'src/main.rs'
+  'src/main.rs',
+  'src/announcements.rs',
+  'src/auth.rs',
+  'src/backend.rs',
+  'src/backup.rs',
+  'src/battery.rs',
+  'src/bindings.rs',
+  'src/ble.rs',
+  'src/certificates.rs',
+  'src/clipboard.rs',
+  'src/config.rs',
+  'src/connectivity.rs',
+  'src/data_dir.rs',
+  'src/deep_link.rs',
+  'src/dev.rs',
+  'src/discovery.rs',
+  'src/disk_space.rs',
+  'src/downloads.rs',
+  'src/error.rs',
+  'src/events.rs',
+  'src/export.rs',
+  'src/fs_watch.rs',
+  'src/headless.rs',
+  'src/import.rs',
+  'src/integrity.rs',
+  'src/lan.rs',
+  'src/locale.rs',
+  'src/locales/en.json',
+  'src/locales/de.json',
+  'src/locales/fr.json',
+  'src/menu.rs',
+  'src/mock_backend.rs',
+  'src/modbus.rs',
+  'src/offline_update.rs',
+  'src/open_file.rs',
+  'src/paths.rs',
+  'src/permissions.rs',
+  'src/power.rs',
+  'src/proxy.rs',
+  'src/rollback.rs',
+  'src/serial.rs',
+  'src/server.rs',
+  'src/service.rs',
+  'src/session_lock.rs',
+  'src/sidecars.rs',
+  'src/sse.rs',
+  'src/startup.rs',
+  'src/storage.rs',
+  'src/system_info.rs',
+  'src/telemetry.rs',
+  'src/time_sync.rs',
+  'src/tls.rs',
+  'src/updater.rs',
+  'src/usb.rs',
+  'src/version.rs',
+  'src/workspace.rs',
+  'src/ws_bridge.rs'
 ];
 
 rustFiles.forEach(file => {
@@ -190,7 +278,9 @@ rustFiles.forEach(file => {
     content = content
       .replace(/{{APP_NAME}}/g, appName)
       .replace(/{{APP_VERSION}}/g, appVersion)
-      .replace(/{{APP_DESCRIPTION}}/g, appDescription);
+      .replace(/{{APP_DESCRIPTION}}/g, appDescription)
+      .replace(/{{FRAMEWORK_VERSION}}/g, frameworkVersion)
+      .replace(/{{FRAMEWORK_CRATE_PATH}}/g, frameworkCratePath);
     
     fs.writeFileSync(destPath, content);
     console.log(`  ✓ Created ${file}`);
@@ -227,7 +317,8 @@ const tauriScripts = {
   "tauri:build": "tauri build",
   "tauri:icon": "tauri icon src-tauri/icons/icon.png",
   "desktop:setup": "node node_modules/@episensor/app-framework/desktop/scripts/setup-desktop.js",
-  "desktop:build": "npm run build && npm run tauri:build"
+  "desktop:build": "npm run build && npm run tauri:build",
+  "desktop:build:linux-arm64": "npm run build && npm run tauri:build -- --target aarch64-unknown-linux-gnu --bundles deb"
 };
 
 Object.assign(packageJson.scripts, tauriScripts);
@@ -8,6 +8,7 @@ import { getLogger } from "../core/logger.js";
 import path from "path";
 import fs from "fs/promises";
 import { existsSync, createReadStream } from "fs";
+import { getDataDir } from "../utils/appPaths.js";
 
 const router = express.Router();
 
@@ -58,7 +59,7 @@ router.get("/entries", async (req: Request, res: Response) => {
  */
 router.get("/files", async (_req: Request, res: Response) => {
   try {
-    const logsDir = path.join(process.cwd(), "data", "logs");
+    const logsDir = path.join(getDataDir(), "logs");
 
     if (!existsSync(logsDir)) {
       res.json({
@@ -111,7 +112,7 @@ router.get("/files", async (_req: Request, res: Response) => {
 router.get("/download/:filename", async (req: Request, res: Response) => {
   try {
     const { filename } = req.params;
-    const logsDir = path.join(process.cwd(), "data", "logs");
+    const logsDir = path.join(getDataDir(), "logs");
     const filePath = path.join(logsDir, filename);
 
     // Security check - prevent directory traversal
@@ -148,7 +149,7 @@ router.get("/download/:filename", async (req: Request, res: Response) => {
 router.get("/stream/:filename", async (req: Request, res: Response) => {
   try {
     const { filename } = req.params;
-    const logsDir = path.join(process.cwd(), "data", "logs");
+    const logsDir = path.join(getDataDir(), "logs");
     const filePath = path.join(logsDir, filename);
 
     // Security check
@@ -208,7 +209,7 @@ router.post("/clear", async (_req: Request, res: Response) => {
  */
 router.get("/archives", async (_req: Request, res: Response) => {
   try {
-    const logsDir = path.join(process.cwd(), "data", "logs");
+    const logsDir = path.join(getDataDir(), "logs");
 
     if (!existsSync(logsDir)) {
       return res.json({
@@ -258,7 +259,7 @@ router.get("/archives", async (_req: Request, res: Response) => {
 router.delete("/archive/:filename", async (req: Request, res: Response) => {
   try {
     const { filename } = req.params;
-    const logsDir = path.join(process.cwd(), "data", "logs");
+    const logsDir = path.join(getDataDir(), "logs");
     const filePath = path.join(logsDir, filename);
 
     // Security check
@@ -338,7 +339,7 @@ router.get("/export", async (req: Request, res: Response) => {
  */
 router.get("/stats", async (_req: Request, res: Response) => {
   try {
-    const logsDir = path.join(process.cwd(), "data", "logs");
+    const logsDir = path.join(getDataDir(), "logs");
 
     if (!existsSync(logsDir)) {
       res.json({
@@ -511,7 +512,7 @@ router.get(
       const safeName = path.basename(filename);
 
       // Check in logs directory first
-      const logsDir = path.join(process.cwd(), "data", "logs");
+      const logsDir = path.join(getDataDir(), "logs");
       let filePath = path.join(logsDir, safeName);
 
       // If not found, check in archive directory
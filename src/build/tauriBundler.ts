@@ -7,6 +7,7 @@ import { execSync } from "child_process";
 import path from "path";
 import { ensureDir, pathExists, move } from "../utils/fs-utils.js";
 import { createLogger } from "../core/logger.js";
+import { writeSidecarManifest } from "../desktop/sidecarManifest.js";
 const logger = createLogger('tauriBundler');
 
 export interface TauriBundleOptions {
@@ -19,7 +20,7 @@ export interface TauriBundleOptions {
   /** Additional external modules to exclude from bundling */
   externals?: string[];
   /** Platforms to build for */
-  platforms?: ("macos-arm64" | "win-x64" | "linux-x64")[];
+  platforms?: ("macos-arm64" | "win-x64" | "linux-x64" | "linux-arm64")[];
   /** Output directory for binaries */
   binaryOutput?: string;
   /** Whether to use compression */
@@ -54,6 +55,7 @@ const PLATFORM_BINARY_NAMES = {
   "macos-arm64": "server-aarch64-apple-darwin",
   "win-x64": "server-x86_64-pc-windows-msvc.exe",
   "linux-x64": "server-x86_64-unknown-linux-gnu",
+  "linux-arm64": "server-aarch64-unknown-linux-gnu",
 };
 
 /**
@@ -75,6 +77,9 @@ export async function buildTauriSidecar(
   // Step 3: Rename binaries for Tauri
   await renameBinariesForTauri(config);
 
+  // Step 4: Record checksums the shell verifies before launching a binary
+  await writeSidecarManifest(config.binaryOutput);
+
   logger.info("✅ Tauri sidecar build complete!");
 }
 
@@ -175,6 +180,8 @@ function getPkgOutputName(bundlePath: string, platform: string): string {
       return `${baseName}-win-x64.exe`;
     case "linux-x64":
       return `${baseName}-linux-x64`;
+    case "linux-arm64":
+      return `${baseName}-linux-arm64`;
     default:
       throw new Error(`Unknown platform: ${platform}`);
   }
@@ -223,5 +230,6 @@ export function generateBuildSidecarScript(
     `npx pkg ${config.bundleOutput} --targets ${targets} --out-path ${config.binaryOutput} ${compressFlag}`,
     `cd ${config.binaryOutput}`,
     ...renameCommands,
+    "npx build-sidecar --manifest-only --output .",
   ].join(" && ");
 }
@@ -23,7 +23,7 @@ export const buildTauriCommand = new Command("build-tauri")
   .option("--node-version <version>", "Node.js version to target", "18")
   .option(
     "--platforms <platforms>",
-    "Comma-separated list of platforms",
+    "Comma-separated list of platforms (macos-arm64, win-x64, linux-x64, linux-arm64)",
     "macos-arm64,win-x64,linux-x64",
   )
   .option("--no-compress", "Disable GZip compression")
@@ -6,7 +6,8 @@
 
 import express, { Express } from "express";
 import { createServer, Server as HttpServer } from "http";
-import { Server as HttpsServer } from "https";
+import { createServer as createHttpsServer, Server as HttpsServer } from "https";
+import { readFileSync } from "fs";
 import type { Socket as NetSocket } from "net";
 import cors from "cors";
 import { createWebSocketServer } from "../services/websocketServer.js";
@@ -16,6 +17,10 @@ import { createLogger, getLogger } from "./index.js";
 import { aiErrorHandler } from "../middleware/aiErrorHandler.js";
 import { apiErrorHandler } from "./apiResponse.js";
 import { createRequestLoggingMiddleware } from "../middleware/requestLogging.js";
+import {
+  createDesktopTokenMiddleware,
+  DESKTOP_TOKEN_ENV,
+} from "../middleware/desktopToken.js";
 import {
   getAppDataPath,
   getLogsPath,
@@ -24,6 +29,13 @@ import {
 
 let logger: any; // Will be initialized when needed
 
+/**
+ * Certificate and key files for HTTPS, set by the desktop shell which generates
+ * a per-install localhost certificate
+ */
+export const DESKTOP_TLS_CERT_ENV = "DESKTOP_TLS_CERT";
+export const DESKTOP_TLS_KEY_ENV = "DESKTOP_TLS_KEY";
+
 function ensureLogger() {
   if (!logger) {
     logger = createLogger("Server");
@@ -136,7 +148,15 @@ export class StandardServer {
     };
 
     this.app = express();
-    this.httpServer = createServer(this.app);
+    const tlsCert = process.env[DESKTOP_TLS_CERT_ENV];
+    const tlsKey = process.env[DESKTOP_TLS_KEY_ENV];
+    this.httpServer =
+      tlsCert && tlsKey
+        ? createHttpsServer(
+            { cert: readFileSync(tlsCert), key: readFileSync(tlsKey) },
+            this.app,
+          )
+        : createServer(this.app);
     this.httpServer.on("connection", (socket: NetSocket) => {
       this.connections.add(socket);
       socket.on("close", () => this.connections.delete(socket));
@@ -303,6 +323,11 @@ export class StandardServer {
       }
     }
 
+    // When launched by the desktop shell, only accept API calls carrying its token
+    if (process.env[DESKTOP_TOKEN_ENV]) {
+      this.app.use("/api", createDesktopTokenMiddleware());
+    }
+
     // Request logging (can be disabled)
     if (this.config.enableRequestLogging) {
       this.app.use(
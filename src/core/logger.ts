@@ -16,9 +16,10 @@ import {
 import { createGzip } from "zlib";
 import { pipeline } from "stream/promises";
 import type { Request, Response } from "express";
+import { getDataDir } from "../utils/appPaths.js";
 
-// Use process.cwd() based paths for better compatibility
-const getLogsDir = () => path.join(process.cwd(), "data", "logs");
+// Resolved on each call so DATA_DIR set after import is still honoured
+const getLogsDir = () => path.join(getDataDir(), "logs");
 
 interface LogLevels {
   levels: {
@@ -9,6 +9,7 @@ import * as fsUtils from "../utils/fs-utils.js";
 import { createWriteStream } from "fs";
 import { createLogger } from "./index.js";
 import crypto from "crypto";
+import { getDataDir } from "../utils/appPaths.js";
 
 let logger: any; // Will be initialized when needed
 
@@ -20,7 +21,7 @@ function ensureLogger() {
 }
 
 // Define safe base directories
-const DATA_DIR = path.join(process.cwd(), "data");
+const DATA_DIR = getDataDir();
 const BASE_DIRS = {
   attachments: path.join(DATA_DIR, "uploads", "attachments"),
   data: DATA_DIR,
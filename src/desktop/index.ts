@@ -7,18 +7,21 @@ export * from "./bundler.js";
 export * from "./tauri.js";
 export * from "./native-modules.js";
 export * from "./sidecar.js";
+export * from "./sidecarManifest.js";
 
 // Re-export as namespace for convenience
 import * as bundler from "./bundler.js";
 import * as tauri from "./tauri.js";
 import * as nativeModules from "./native-modules.js";
 import * as sidecar from "./sidecar.js";
+import * as sidecarManifest from "./sidecarManifest.js";
 
 export const Desktop = {
   ...bundler,
   ...tauri,
   ...nativeModules,
   ...sidecar,
+  ...sidecarManifest,
 };
 
 export default Desktop;
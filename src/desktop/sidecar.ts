@@ -10,6 +10,11 @@ import { promisify } from "util";
 import { ensureDir, readJson, writeFile } from "../utils/fs-utils.js";
 import path from "path";
 import { createLogger } from "../core/logger.js";
+import {
+  includeAllFiles,
+  isBackendBinary,
+  writeSidecarManifest,
+} from "./sidecarManifest.js";
 const logger = createLogger('sidecar');
 
 const execAsync = promisify(exec);
@@ -54,6 +59,7 @@ export const DEFAULT_TARGETS: SidecarTarget[] = [
   { platform: "macos", arch: "x64" },
   { platform: "windows", arch: "x64" },
   { platform: "linux", arch: "x64" },
+  { platform: "linux", arch: "arm64" },
 ];
 
 /**
@@ -64,6 +70,7 @@ const TARGET_MAP: Record<string, string> = {
   "macos-x64": "node20-macos-x64",
   "windows-x64": "node20-win-x64",
   "linux-x64": "node20-linux-x64",
+  "linux-arm64": "node20-linux-arm64",
 };
 
 /**
@@ -74,6 +81,7 @@ const BINARY_NAME_MAP: Record<string, string> = {
   "macos-x64": "server-x86_64-apple-darwin",
   "windows-x64": "server-x86_64-pc-windows-gnu.exe",
   "linux-x64": "server-x86_64-unknown-linux-gnu",
+  "linux-arm64": "server-aarch64-unknown-linux-gnu",
 };
 
 /**
@@ -144,6 +152,8 @@ export async function buildSidecar(
       logger.error(`❌ Failed to build ${targetKey}: ${_error.message}`);
     }
   }
+
+  await writeSidecarManifest(outputDir);
 }
 
 /**
@@ -198,7 +208,11 @@ pub fn start_backend_server(app: AppHandle, state: State<ServerState>) -> Result
     let server_binary = server_path.join("server-x86_64-pc-windows-gnu.exe");
     
     #[cfg(target_os = "linux")]
-    let server_binary = server_path.join("server-x86_64-unknown-linux-gnu");
+    let server_binary = if cfg!(target_arch = "aarch64") {
+        server_path.join("server-aarch64-unknown-linux-gnu")
+    } else {
+        server_path.join("server-x86_64-unknown-linux-gnu")
+    };
     
     // Start the sidecar server
     let child = std::process::Command::new(&server_binary)
@@ -317,9 +331,18 @@ export async function buildSidecarCLI(args: string[]): Promise<void> {
     Object.assign(config, packageJson.sidecar);
   }
 
+  let manifestOnly = false;
+  let allFiles = false;
+
   // Parse CLI arguments
   for (let i = 0; i < args.length; i++) {
     switch (args[i]) {
+      case "--manifest-only":
+        manifestOnly = true;
+        break;
+      case "--all-files":
+        allFiles = true;
+        break;
       case "--entry":
         config.entryFile = args[++i];
         break;
@@ -342,6 +365,14 @@ export async function buildSidecarCLI(args: string[]): Promise<void> {
     }
   }
 
+  if (manifestOnly) {
+    await writeSidecarManifest(
+      config.outputDir ?? "src-tauri/binaries",
+      allFiles ? includeAllFiles : isBackendBinary,
+    );
+    return;
+  }
+
   logger.info("Building Tauri sidecar with configuration:");
   logger.info(JSON.stringify(config, null, 2));
 
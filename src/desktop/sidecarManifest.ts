@@ -0,0 +1,106 @@
+/**
+ * Sidecar Integrity Manifest
+ *
+ * Records the SHA-256 of every sidecar binary in the binaries directory. The
+ * manifest is bundled with the app next to the binaries, and the desktop shell
+ * refuses to launch a sidecar that doesn't match it. Additional sidecars, and
+ * the scripts of script sidecars, are checked against a manifest in their own
+ * directory, written with `includeAllFiles`.
+ */
+
+import { exec } from "child_process";
+import { createHash } from "crypto";
+import path from "path";
+import { promisify } from "util";
+import {
+  createReadStream,
+  fs,
+  remove,
+  writeFile,
+} from "../utils/fs-utils.js";
+import { createLogger } from "../core/logger.js";
+const logger = createLogger('sidecarManifest');
+
+const execAsync = promisify(exec);
+
+/**
+ * File name of the manifest. It matches the `binaries/server-*` resource glob,
+ * so it is bundled into the same directory as the binaries.
+ */
+export const SIDECAR_MANIFEST_NAME = "server-manifest.json";
+
+export interface SidecarManifest {
+  /** SHA-256 (hex) of each binary, by file name */
+  files: Record<string, string>;
+}
+
+/** Backend sidecar binaries, the files hashed by default */
+export function isBackendBinary(name: string): boolean {
+  return name.startsWith("server-");
+}
+
+/** Every file, for a directory of additional sidecars or sidecar scripts */
+export function includeAllFiles(_name: string): boolean {
+  return true;
+}
+
+async function sha256File(filePath: string): Promise<string> {
+  const hash = createHash("sha256");
+  for await (const chunk of createReadStream(filePath)) {
+    hash.update(chunk);
+  }
+  return hash.digest("hex");
+}
+
+/**
+ * Hash every sidecar binary in a directory, or every file `include` accepts.
+ * The manifest and its signature are never included.
+ */
+export async function createSidecarManifest(
+  binaryDir: string,
+  include: (name: string) => boolean = isBackendBinary,
+): Promise<SidecarManifest> {
+  const entries = await fs.readdir(binaryDir, { withFileTypes: true });
+  const names = entries
+    .filter((entry) => entry.isFile())
+    .map((entry) => entry.name)
+    .filter((name) => include(name) && !name.startsWith(SIDECAR_MANIFEST_NAME))
+    .sort();
+
+  const files: Record<string, string> = {};
+  for (const name of names) {
+    files[name] = await sha256File(path.join(binaryDir, name));
+  }
+  return { files };
+}
+
+/**
+ * Write the manifest for the binaries in a directory. When
+ * `TAURI_SIGNING_PRIVATE_KEY` is set, the manifest is also signed with the
+ * updater key, so the shell can tell it hasn't been replaced along with a binary.
+ */
+export async function writeSidecarManifest(
+  binaryDir: string,
+  include: (name: string) => boolean = isBackendBinary,
+): Promise<string> {
+  const manifest = await createSidecarManifest(binaryDir, include);
+  const manifestPath = path.join(binaryDir, SIDECAR_MANIFEST_NAME);
+  await writeFile(manifestPath, JSON.stringify(manifest, null, 2) + "\n");
+  logger.info(
+    `Wrote sidecar manifest for ${Object.keys(manifest.files).length} files`,
+  );
+
+  if (process.env.TAURI_SIGNING_PRIVATE_KEY) {
+    try {
+      await execAsync(`npx tauri signer sign "${manifestPath}"`);
+      logger.info("Signed sidecar manifest");
+    } catch (_error: any) {
+      throw new Error(`Failed to sign sidecar manifest: ${_error.message}`);
+    }
+  } else {
+    // A signature left from an earlier build would no longer match
+    await remove(`${manifestPath}.sig`);
+  }
+
+  return manifestPath;
+}
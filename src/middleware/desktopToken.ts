@@ -0,0 +1,113 @@
+/**
+ * Desktop Token Middleware
+ * Requires the per-launch token generated by the Tauri shell on every API request
+ * and Socket.IO connection, so other processes on the machine can't call the
+ * backend directly
+ */
+
+import { Request, Response, NextFunction } from "express";
+import { timingSafeEqual } from "crypto";
+
+export const DESKTOP_TOKEN_HEADER = "x-desktop-token";
+export const DESKTOP_TOKEN_ENV = "DESKTOP_AUTH_TOKEN";
+
+/**
+ * Origins of the shell's webview: `tauri://localhost` on macOS and Linux,
+ * `http(s)://tauri.localhost` on Windows
+ */
+export const DESKTOP_ORIGINS = [
+  "tauri://localhost",
+  "http://tauri.localhost",
+  "https://tauri.localhost",
+];
+
+export interface DesktopTokenOptions {
+  /**
+   * Expected token (default: DESKTOP_AUTH_TOKEN environment variable)
+   */
+  token?: string;
+}
+
+function tokensMatch(expected: string, provided: string): boolean {
+  const a = Buffer.from(expected);
+  const b = Buffer.from(provided);
+  return a.length === b.length && timingSafeEqual(a, b);
+}
+
+/**
+ * Whether a browser origin belongs to the shell. Clients without an origin
+ * (the shell's own WebSocket bridge) are allowed, and still need the token. In
+ * development the webview loads the Vite dev server, so localhost is allowed too.
+ */
+export function isDesktopOrigin(origin: string | undefined): boolean {
+  if (!origin || DESKTOP_ORIGINS.includes(origin)) {
+    return true;
+  }
+  if (process.env.NODE_ENV !== "development") {
+    return false;
+  }
+  try {
+    const { protocol, hostname } = new URL(origin);
+    return (
+      (protocol === "http:" || protocol === "https:") &&
+      (hostname === "localhost" || hostname === "127.0.0.1")
+    );
+  } catch {
+    return false;
+  }
+}
+
+/**
+ * Creates middleware rejecting requests without the shell's token.
+ * When no token is configured (not launched by the shell) all requests pass.
+ */
+export function createDesktopTokenMiddleware(options: DesktopTokenOptions = {}) {
+  const token = options.token ?? process.env[DESKTOP_TOKEN_ENV];
+
+  return (req: Request, res: Response, next: NextFunction) => {
+    if (!token) {
+      return next();
+    }
+
+    const provided = req.headers[DESKTOP_TOKEN_HEADER];
+    if (typeof provided === "string" && tokensMatch(token, provided)) {
+      return next();
+    }
+
+    res.status(401).json({
+      success: false,
+      error: "Missing or invalid desktop token",
+    });
+  };
+}
+
+interface SocketHandshake {
+  handshake: {
+    auth?: Record<string, unknown>;
+    headers?: Record<string, string | string[] | undefined>;
+  };
+}
+
+/**
+ * Creates Socket.IO middleware (`io.use`) rejecting connections that don't
+ * carry the shell's token, either in the auth payload (`{ token }` from the
+ * shell's WebSocket bridge) or in the token header (added by the shell's LAN
+ * proxy; browsers can't set it). When no token is configured all connections pass.
+ */
+export function createDesktopTokenSocketMiddleware(options: DesktopTokenOptions = {}) {
+  const token = options.token ?? process.env[DESKTOP_TOKEN_ENV];
+
+  return (socket: SocketHandshake, next: (err?: Error) => void) => {
+    if (!token) {
+      return next();
+    }
+
+    const provided =
+      socket.handshake.auth?.token ?? socket.handshake.headers?.[DESKTOP_TOKEN_HEADER];
+    if (typeof provided === "string" && tokensMatch(token, provided)) {
+      return next();
+    }
+
+    next(new Error("Missing or invalid desktop token"));
+  };
+}
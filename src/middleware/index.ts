@@ -56,6 +56,17 @@ export type { AuthConfig } from "./auth.js";
 export { configureSession } from "./session.js";
 export type { SessionConfig as SessionMiddlewareConfig } from "./session.js";
 
+// Desktop shell token middleware exports
+export {
+  createDesktopTokenMiddleware,
+  createDesktopTokenSocketMiddleware,
+  isDesktopOrigin,
+  DESKTOP_ORIGINS,
+  DESKTOP_TOKEN_HEADER,
+  DESKTOP_TOKEN_ENV,
+} from "./desktopToken.js";
+export type { DesktopTokenOptions } from "./desktopToken.js";
+
 // CORS middleware exports
 export {
   createDynamicCors,
@@ -8,6 +8,7 @@ import { JSONFile } from "lowdb/node";
 import path from "path";
 import { ensureDir } from "fs-extra";
 import { createLogger } from "../core/index.js";
+import { getDataDir } from "../utils/appPaths.js";
 let logger: any; // Will be initialized when needed
 
 function ensureLogger() {
@@ -49,7 +50,7 @@ class ConversationStorage {
 
     try {
       // Ensure data directory exists
-      const dataDir = path.join(process.cwd(), "data");
+      const dataDir = getDataDir();
       await ensureDir(dataDir);
 
       // Initialize lowdb with JSON file adapter
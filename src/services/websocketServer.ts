@@ -6,6 +6,11 @@
 import { Server, Socket } from "socket.io";
 import { Server as HTTPServer } from "http";
 import { createLogger } from "../core/index.js";
+import {
+  createDesktopTokenSocketMiddleware,
+  DESKTOP_TOKEN_ENV,
+  isDesktopOrigin,
+} from "../middleware/desktopToken.js";
 import {
   WebSocketMessage,
   SimulatorUpdateMessage,
@@ -56,13 +61,24 @@ class WebSocketServer {
       return;
     }
 
+    // Launched by the desktop shell: only its webview (and its own bridge,
+    // which sends no Origin) may connect, and every connection needs the token
+    const desktop = Boolean(process.env[DESKTOP_TOKEN_ENV]);
+
     this.io = new Server(this.httpServer, {
       cors: {
-        origin: "*", // Allow all origins for local development
+        origin: desktop
+          ? (origin, callback) => callback(null, isDesktopOrigin(origin))
+          : "*", // Allow all origins for local development
         methods: ["GET", "POST"],
       },
+      ...(desktop && {
+        allowRequest: (req, callback) =>
+          callback(null, isDesktopOrigin(req.headers.origin)),
+      }),
       transports: ["websocket", "polling"],
     });
+    this.io.use(createDesktopTokenSocketMiddleware());
 
     if (opts?.broadcastHook) {
       this.broadcastHook = opts.broadcastHook;
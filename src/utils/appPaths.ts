@@ -2,9 +2,25 @@ import os from "os";
 import path from "path";
 import { mkdirSync } from "fs";
 
+/**
+ * Get the directory the backend stores its data in.
+ *
+ * The desktop shell passes `DATA_DIR` when the user has moved the data
+ * directory; otherwise this is `./data` under the working directory.
+ *
+ * @returns The absolute path to the backend data directory
+ */
+export function getDataDir(): string {
+  return process.env.DATA_DIR
+    ? path.resolve(process.env.DATA_DIR)
+    : path.join(process.cwd(), "data");
+}
+
 /**
  * Get the appropriate data directory for the application.
  *
+ * `DATA_DIR` takes precedence when set.
+ *
  * In desktop app mode (Tauri/Electron):
  * - macOS: ~/Library/Application Support/{appId}
  * - Windows: %APPDATA%/{appName}
@@ -18,6 +34,12 @@ import { mkdirSync } from "fs";
  * @returns The absolute path to the application data directory
  */
 export function getAppDataPath(appId: string, appName: string): string {
+  if (process.env.DATA_DIR) {
+    const dataDir = getDataDir();
+    mkdirSync(dataDir, { recursive: true });
+    return dataDir;
+  }
+
   // Check if running in a desktop app environment
   const isTauriProduction = process.env.TAURI === "1";
   const isElectron = process.versions?.electron;
@@ -61,7 +83,7 @@ export function getAppDataPath(appId: string, appName: string): string {
   }
 
   // In development or web mode, use the project root ./data directory
-  const dataDir = path.join(process.cwd(), "data");
+  const dataDir = getDataDir();
   mkdirSync(dataDir, { recursive: true });
   return dataDir;
 }
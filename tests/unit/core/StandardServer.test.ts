@@ -28,6 +28,7 @@ jest.mock('express', () => {
   return expressMock;
 });
 jest.mock('http');
+jest.mock('https');
 jest.mock('../../../src/services/websocketServer');
 jest.mock('../../../src/utils/startupBanner', () => ({
   displayStartupBanner: jest.fn()
@@ -46,6 +47,10 @@ jest.mock('../../../src/core', () => ({
 import { StandardServer, StandardServerConfig } from '../../../src/core/StandardServer';
 import * as express from 'express';
 import { createServer } from 'http';
+import { createServer as createHttpsServer } from 'https';
+import fs from 'fs';
+import os from 'os';
+import path from 'path';
 import { createWebSocketServer } from '../../../src/services/websocketServer';
 import { displayStartupBanner } from '../../../src/utils/startupBanner';
 import { getProcessOnPort } from '../../../src/core/portUtils';
@@ -157,6 +162,32 @@ describe('StandardServer', () => {
       expect(server['config'].environment).toBe('production');
       expect(server['config'].enableWebSocket).toBe(false);
     });
+
+    test('serves HTTPS when the desktop shell provides a certificate', () => {
+      const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'standard-server-tls-'));
+      const certPath = path.join(dir, 'localhost.crt');
+      const keyPath = path.join(dir, 'localhost.key');
+      fs.writeFileSync(certPath, 'cert');
+      fs.writeFileSync(keyPath, 'key');
+      (createHttpsServer as jest.Mock).mockReturnValue(mockHttpServer);
+      process.env.DESKTOP_TLS_CERT = certPath;
+      process.env.DESKTOP_TLS_KEY = keyPath;
+
+      try {
+        const server = new StandardServer({ appName: 'TestApp', appVersion: '1.0.0' });
+        servers.push(server);
+
+        expect(createServer).not.toHaveBeenCalled();
+        expect(createHttpsServer).toHaveBeenCalledWith(
+          { cert: Buffer.from('cert'), key: Buffer.from('key') },
+          mockApp
+        );
+      } finally {
+        delete process.env.DESKTOP_TLS_CERT;
+        delete process.env.DESKTOP_TLS_KEY;
+        fs.rmSync(dir, { recursive: true, force: true });
+      }
+    });
   });
 
   describe('initialize', () => {
@@ -0,0 +1,65 @@
+/**
+ * Unit tests for the sidecar integrity manifest
+ */
+
+import { createHash } from 'crypto';
+import { mkdirSync, mkdtempSync, rmSync, writeFileSync } from 'fs';
+import os from 'os';
+import path from 'path';
+import {
+  createSidecarManifest,
+  includeAllFiles,
+  SIDECAR_MANIFEST_NAME,
+} from '../../../src/desktop/sidecarManifest';
+
+const sha256 = (data: string) => createHash('sha256').update(data).digest('hex');
+
+describe('createSidecarManifest', () => {
+  let binaryDir: string;
+
+  beforeEach(() => {
+    binaryDir = mkdtempSync(path.join(os.tmpdir(), 'sidecar-manifest-'));
+  });
+
+  afterEach(() => {
+    rmSync(binaryDir, { recursive: true, force: true });
+  });
+
+  it('hashes every sidecar binary', async () => {
+    writeFileSync(path.join(binaryDir, 'server-x86_64-unknown-linux-gnu'), 'linux');
+    writeFileSync(path.join(binaryDir, 'server-x86_64-pc-windows-msvc.exe'), 'windows');
+
+    const manifest = await createSidecarManifest(binaryDir);
+
+    expect(manifest.files).toEqual({
+      'server-x86_64-pc-windows-msvc.exe': sha256('windows'),
+      'server-x86_64-unknown-linux-gnu': sha256('linux'),
+    });
+  });
+
+  it('skips other files and the manifest itself', async () => {
+    writeFileSync(path.join(binaryDir, 'server-aarch64-apple-darwin'), 'mac');
+    writeFileSync(path.join(binaryDir, SIDECAR_MANIFEST_NAME), '{}');
+    writeFileSync(path.join(binaryDir, `${SIDECAR_MANIFEST_NAME}.sig`), 'sig');
+    writeFileSync(path.join(binaryDir, 'README.md'), 'notes');
+
+    const manifest = await createSidecarManifest(binaryDir);
+
+    expect(Object.keys(manifest.files)).toEqual(['server-aarch64-apple-darwin']);
+  });
+
+  it('hashes every file but the manifest with includeAllFiles', async () => {
+    writeFileSync(path.join(binaryDir, 'main.py'), 'print()');
+    writeFileSync(path.join(binaryDir, 'worker-x86_64-unknown-linux-gnu'), 'worker');
+    writeFileSync(path.join(binaryDir, SIDECAR_MANIFEST_NAME), '{}');
+    writeFileSync(path.join(binaryDir, `${SIDECAR_MANIFEST_NAME}.sig`), 'sig');
+    mkdirSync(path.join(binaryDir, 'lib'));
+
+    const manifest = await createSidecarManifest(binaryDir, includeAllFiles);
+
+    expect(manifest.files).toEqual({
+      'main.py': sha256('print()'),
+      'worker-x86_64-unknown-linux-gnu': sha256('worker'),
+    });
+  });
+});
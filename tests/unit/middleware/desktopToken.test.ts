@@ -0,0 +1,142 @@
+/**
+ * Unit tests for desktop token middleware
+ */
+
+import { Request, Response, NextFunction } from 'express';
+import {
+  createDesktopTokenMiddleware,
+  createDesktopTokenSocketMiddleware,
+  isDesktopOrigin,
+} from '../../../src/middleware/desktopToken';
+
+describe('Desktop Token Middleware', () => {
+  let mockRes: Partial<Response>;
+  let mockNext: NextFunction;
+  let statusMock: jest.Mock;
+  let jsonMock: jest.Mock;
+
+  const request = (headers: Record<string, string> = {}) =>
+    ({ headers } as unknown as Request);
+
+  beforeEach(() => {
+    jsonMock = jest.fn();
+    statusMock = jest.fn(() => ({ json: jsonMock }));
+    mockRes = { status: statusMock } as Partial<Response>;
+    mockNext = jest.fn();
+    delete process.env.DESKTOP_AUTH_TOKEN;
+  });
+
+  it('should allow all requests when no token is configured', () => {
+    const middleware = createDesktopTokenMiddleware();
+
+    middleware(request(), mockRes as Response, mockNext);
+
+    expect(mockNext).toHaveBeenCalled();
+    expect(statusMock).not.toHaveBeenCalled();
+  });
+
+  it('should allow requests with the matching token', () => {
+    const middleware = createDesktopTokenMiddleware({ token: 'secret' });
+
+    middleware(request({ 'x-desktop-token': 'secret' }), mockRes as Response, mockNext);
+
+    expect(mockNext).toHaveBeenCalled();
+  });
+
+  it('should reject requests without a token', () => {
+    const middleware = createDesktopTokenMiddleware({ token: 'secret' });
+
+    middleware(request(), mockRes as Response, mockNext);
+
+    expect(mockNext).not.toHaveBeenCalled();
+    expect(statusMock).toHaveBeenCalledWith(401);
+  });
+
+  it('should reject requests with the wrong token', () => {
+    const middleware = createDesktopTokenMiddleware({ token: 'secret' });
+
+    middleware(request({ 'x-desktop-token': 'other' }), mockRes as Response, mockNext);
+
+    expect(mockNext).not.toHaveBeenCalled();
+    expect(statusMock).toHaveBeenCalledWith(401);
+  });
+
+  it('should read the token from DESKTOP_AUTH_TOKEN', () => {
+    process.env.DESKTOP_AUTH_TOKEN = 'from-env';
+    const middleware = createDesktopTokenMiddleware();
+
+    middleware(request({ 'x-desktop-token': 'from-env' }), mockRes as Response, mockNext);
+
+    expect(mockNext).toHaveBeenCalled();
+  });
+
+  describe('socket middleware', () => {
+    const socket = (auth?: Record<string, unknown>) => ({ handshake: { auth } });
+
+    it('should allow connections with the matching token', () => {
+      const middleware = createDesktopTokenSocketMiddleware({ token: 'secret' });
+      const next = jest.fn();
+
+      middleware(socket({ token: 'secret' }), next);
+
+      expect(next).toHaveBeenCalledWith();
+    });
+
+    it('should reject connections without or with the wrong token', () => {
+      const middleware = createDesktopTokenSocketMiddleware({ token: 'secret' });
+      const next = jest.fn();
+
+      middleware(socket(), next);
+      middleware(socket({ token: 'other' }), next);
+      middleware(socket({ token: 42 }), next);
+
+      expect(next).toHaveBeenCalledTimes(3);
+      next.mock.calls.forEach(([err]) => expect(err).toBeInstanceOf(Error));
+    });
+
+    it('should accept the token header added by the LAN proxy', () => {
+      const middleware = createDesktopTokenSocketMiddleware({ token: 'secret' });
+      const next = jest.fn();
+
+      middleware({ handshake: { auth: {}, headers: { 'x-desktop-token': 'secret' } } }, next);
+
+      expect(next).toHaveBeenCalledWith();
+    });
+
+    it('should allow all connections when no token is configured', () => {
+      const middleware = createDesktopTokenSocketMiddleware();
+      const next = jest.fn();
+
+      middleware(socket(), next);
+
+      expect(next).toHaveBeenCalledWith();
+    });
+  });
+
+  describe('isDesktopOrigin', () => {
+    const env = process.env.NODE_ENV;
+
+    afterEach(() => {
+      process.env.NODE_ENV = env;
+    });
+
+    it('should allow the shell origins and clients without an origin', () => {
+      expect(isDesktopOrigin('tauri://localhost')).toBe(true);
+      expect(isDesktopOrigin('https://tauri.localhost')).toBe(true);
+      expect(isDesktopOrigin(undefined)).toBe(true);
+    });
+
+    it('should reject other origins', () => {
+      process.env.NODE_ENV = 'production';
+      expect(isDesktopOrigin('https://evil.example')).toBe(false);
+      expect(isDesktopOrigin('http://localhost:5173')).toBe(false);
+      expect(isDesktopOrigin('http://tauri.localhost.evil.example')).toBe(false);
+    });
+
+    it('should allow localhost in development', () => {
+      process.env.NODE_ENV = 'development';
+      expect(isDesktopOrigin('http://localhost:5173')).toBe(true);
+      expect(isDesktopOrigin('http://127.0.0.1:5173')).toBe(true);
+    });
+  });
+});
@@ -25,6 +25,7 @@ describe('WebSocket Server', () => {
     mockIO = {
       emit: jest.fn(),
       on: jest.fn(),
+      use: jest.fn(),
       to: jest.fn().mockReturnThis(),
       sockets: {
         sockets: new Map()
@@ -62,6 +63,27 @@ describe('WebSocket Server', () => {
       );
     });
     
+    test('requires the desktop token and shell origins when launched by the shell', () => {
+      process.env.DESKTOP_AUTH_TOKEN = 'secret';
+      try {
+        wsServer.initialize();
+      } finally {
+        delete process.env.DESKTOP_AUTH_TOKEN;
+      }
+
+      const options = (SocketIOServer as unknown as jest.Mock).mock.calls[0][1];
+      const allowed = (origin?: string) => {
+        const callback = jest.fn();
+        options.allowRequest({ headers: { origin } }, callback);
+        return callback.mock.calls[0][1];
+      };
+      expect(options.cors.origin).not.toBe("*");
+      expect(allowed('tauri://localhost')).toBe(true);
+      expect(allowed(undefined)).toBe(true);
+      expect(allowed('https://evil.example')).toBe(false);
+      expect(mockIO.use).toHaveBeenCalledWith(expect.any(Function));
+    });
+
     test('sets up connection handlers', () => {
       wsServer.initialize();
       
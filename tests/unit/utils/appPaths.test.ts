@@ -0,0 +1,33 @@
+/**
+ * Unit tests for App Paths utility
+ */
+
+import path from 'path';
+import { getDataDir } from '../../../src/utils/appPaths';
+
+describe('getDataDir', () => {
+  const originalDataDir = process.env.DATA_DIR;
+
+  afterEach(() => {
+    if (originalDataDir === undefined) {
+      delete process.env.DATA_DIR;
+    } else {
+      process.env.DATA_DIR = originalDataDir;
+    }
+  });
+
+  it('defaults to ./data under the working directory', () => {
+    delete process.env.DATA_DIR;
+    expect(getDataDir()).toBe(path.join(process.cwd(), 'data'));
+  });
+
+  it('uses DATA_DIR when set', () => {
+    process.env.DATA_DIR = path.join(path.sep, 'mnt', 'storage', 'app');
+    expect(getDataDir()).toBe(path.resolve(process.env.DATA_DIR));
+  });
+
+  it('resolves a relative DATA_DIR against the working directory', () => {
+    process.env.DATA_DIR = 'relocated';
+    expect(getDataDir()).toBe(path.join(process.cwd(), 'relocated'));
+  });
+});